@@ -0,0 +1,62 @@
+//! Benchmark for scanning Chromium's `Cache_Data` for servings responses
+//!
+//! Compares `api_cache_reader::read_api_cache_sequential` against the
+//! rayon-backed `read_api_cache` on a 300-entry cache directory, the size
+//! a Brain.fm install reaches after a few weeks of use.
+
+use brainfm_presence::api_cache_reader::{read_api_cache, read_api_cache_sequential};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::fs;
+
+const ENTRIES: usize = 300;
+
+/// Build an app support dir whose cache holds `ENTRIES` entries, one in ten a servings response
+fn cache_fixture() -> tempfile::TempDir {
+    let root = tempfile::tempdir().unwrap();
+    let cache_data = root.path().join("Cache").join("Cache_Data");
+    fs::create_dir_all(&cache_data).unwrap();
+
+    // 32KB of binary noise stands in for the rest of a cached response
+    let noise: Vec<u8> = (0..32 * 1024u32)
+        .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+        .filter(|&b| b != b'{')
+        .collect();
+
+    for i in 0..ENTRIES {
+        let mut data = Vec::new();
+        if i % 10 == 0 {
+            data.extend_from_slice(
+                b"\x30\x5c\x72\xa8https://api.brain.fm/v3/users/abc/servings/recent\0",
+            );
+            data.extend_from_slice(&noise);
+            data.extend_from_slice(
+                format!(
+                    r#"{{"result":[{{"track":{{"name":"Track {i}"}},"trackVariation":{{"url":"Track_{i}_Focus_VBR5.mp3"}}}}]}}"#
+                )
+                .as_bytes(),
+            );
+        } else {
+            data.extend_from_slice(b"\x30\x5c\x72\xa8https://images.unsplash.com/photo\0");
+            data.extend_from_slice(&noise);
+        }
+        fs::write(cache_data.join(format!("{i:016x}_0")), data).unwrap();
+    }
+    root
+}
+
+fn bench_api_cache_scan(c: &mut Criterion) {
+    let fixture = cache_fixture();
+    let root = fixture.path();
+
+    let mut group = c.benchmark_group("api_cache_scan");
+    group.bench_function("sequential", |b| {
+        b.iter(|| read_api_cache_sequential(black_box(root)).unwrap());
+    });
+    group.bench_function("rayon", |b| {
+        b.iter(|| read_api_cache(black_box(root)).unwrap());
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_api_cache_scan);
+criterion_main!(benches);
@@ -0,0 +1,46 @@
+//! Benchmark for audio URL extraction from raw cache file bytes
+//!
+//! Compares `cache_reader::find_audio_url` against the previous approach of
+//! running `AUDIO_URL_RE` over a lossy UTF-8 copy of the searched prefix.
+
+use brainfm_presence::cache_reader::find_audio_url;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use regex::Regex;
+
+const AUDIO_URL: &str =
+    "https://audio2.brain.fm/Tied_In_Strings_Focus_Deep_Work_Electronic_30_120bpm_HighNEL_Nrmlzd2_VBR5.mp3";
+
+/// 128KB synthetic cache entry: binary noise with the URL near the end of the searched 32KB
+fn synthetic_cache_file() -> Vec<u8> {
+    let mut data: Vec<u8> = (0..128 * 1024u32)
+        .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+        .collect();
+    let offset = 30 * 1024;
+    data[offset..offset + AUDIO_URL.len()].copy_from_slice(AUDIO_URL.as_bytes());
+    data
+}
+
+fn bench_audio_url_search(c: &mut Criterion) {
+    let data = synthetic_cache_file();
+    let regex = Regex::new(r#"(https?://audio\d*\.brain\.fm/[^\s\x00"'<>]+\.mp3)"#).unwrap();
+
+    let mut group = c.benchmark_group("audio_url_search");
+    group.bench_function("regex_over_lossy_utf8", |b| {
+        b.iter(|| {
+            let data = black_box(&data);
+            let search_size = data.len().min(32768);
+            let content = String::from_utf8_lossy(&data[..search_size]);
+            regex
+                .captures(&content)
+                .and_then(|caps| caps.get(1))
+                .map(|m| m.as_str().to_string())
+        });
+    });
+    group.bench_function("memmem_window", |b| {
+        b.iter(|| find_audio_url(black_box(&data)));
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_audio_url_search);
+criterion_main!(benches);
@@ -0,0 +1,104 @@
+//! Benchmark for finding cache entries through Chromium's Simple Cache index
+//!
+//! Compares `api_cache_reader::parse_chromium_cache_index` against listing
+//! `Cache_Data`, and `read_api_cache_sequential` on the same 500-entry cache
+//! with and without `index-dir/the-real-index`.
+
+use brainfm_presence::api_cache_reader::{parse_chromium_cache_index, read_api_cache_sequential};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::fs;
+use std::path::Path;
+
+const ENTRIES: u64 = 500;
+
+/// Magic number and format version of the Simple Cache index
+const INDEX_MAGIC: u64 = 0x656e_7465_7220_796f;
+const INDEX_VERSION: u32 = 9;
+
+/// Fill `app_support` with a cache of `ENTRIES` entries (each a `_0` file
+/// and its `_s` stream file), one in ten a servings response, and an index
+/// listing them when `with_index` is set
+fn cache_fixture(app_support: &Path, with_index: bool) {
+    let cache_data = app_support.join("Cache").join("Cache_Data");
+    fs::create_dir_all(&cache_data).unwrap();
+
+    // 8KB of binary noise stands in for the rest of a cached response
+    let noise: Vec<u8> = (0..8 * 1024u32)
+        .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+        .filter(|&b| b != b'{')
+        .collect();
+
+    let mut index = Vec::new();
+    index.extend_from_slice(&0u32.to_le_bytes()); // payload size (unchecked)
+    index.extend_from_slice(&0u32.to_le_bytes()); // crc (unchecked)
+    index.extend_from_slice(&INDEX_MAGIC.to_le_bytes());
+    index.extend_from_slice(&INDEX_VERSION.to_le_bytes());
+    index.extend_from_slice(&ENTRIES.to_le_bytes());
+    index.extend_from_slice(&0u64.to_le_bytes()); // cache size
+    index.extend_from_slice(&0u32.to_le_bytes()); // reason
+
+    for i in 0..ENTRIES {
+        let hash = i.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        let mut data = Vec::new();
+        if i % 10 == 0 {
+            data.extend_from_slice(
+                b"\x30\x5c\x72\xa8https://api.brain.fm/v3/users/abc/servings/recent\0",
+            );
+            data.extend_from_slice(&noise);
+            data.extend_from_slice(
+                format!(
+                    r#"{{"result":[{{"track":{{"name":"Track {i}"}},"trackVariation":{{"url":"Track_{i}_Focus_VBR5.mp3"}}}}]}}"#
+                )
+                .as_bytes(),
+            );
+        } else {
+            data.extend_from_slice(b"\x30\x5c\x72\xa8https://images.unsplash.com/photo\0");
+            data.extend_from_slice(&noise);
+        }
+        fs::write(cache_data.join(format!("{hash:016x}_0")), data).unwrap();
+        fs::write(cache_data.join(format!("{hash:016x}_s")), &noise[..1024]).unwrap();
+
+        index.extend_from_slice(&hash.to_le_bytes());
+        index.extend_from_slice(&i64::try_from(i).unwrap().to_le_bytes());
+        index.extend_from_slice(&0u64.to_le_bytes()); // packed size
+    }
+
+    if with_index {
+        let index_dir = cache_data.join("index-dir");
+        fs::create_dir_all(&index_dir).unwrap();
+        fs::write(index_dir.join("the-real-index"), index).unwrap();
+    }
+}
+
+fn bench_cache_index(c: &mut Criterion) {
+    let indexed = tempfile::tempdir().unwrap();
+    cache_fixture(indexed.path(), true);
+    let unindexed = tempfile::tempdir().unwrap();
+    cache_fixture(unindexed.path(), false);
+    let cache_data = indexed.path().join("Cache").join("Cache_Data");
+
+    let mut group = c.benchmark_group("cache_index");
+    group.bench_function("enumerate_index", |b| {
+        b.iter(|| parse_chromium_cache_index(black_box(&cache_data)).unwrap());
+    });
+    group.bench_function("enumerate_read_dir", |b| {
+        b.iter(|| {
+            fs::read_dir(black_box(&cache_data))
+                .unwrap()
+                .flatten()
+                .filter(|entry| entry.file_name().to_string_lossy().ends_with("_0"))
+                .map(|entry| entry.path())
+                .collect::<Vec<_>>()
+        });
+    });
+    group.bench_function("read_api_cache_with_index", |b| {
+        b.iter(|| read_api_cache_sequential(black_box(indexed.path())).unwrap());
+    });
+    group.bench_function("read_api_cache_without_index", |b| {
+        b.iter(|| read_api_cache_sequential(black_box(unindexed.path())).unwrap());
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_cache_index);
+criterion_main!(benches);
@@ -0,0 +1,38 @@
+//! Benchmark for detecting a running Brain.fm on Linux
+//!
+//! Compares scanning `/proc/<pid>/cmdline` (`platform::linux::is_brainfm_running`)
+//! against spawning `pgrep`, as the macOS implementation does.
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use brainfm_presence::platform::linux::brainfm_process_in;
+    use criterion::{black_box, Criterion};
+    use std::path::Path;
+    use std::process::Command;
+
+    pub fn bench_process_detection(c: &mut Criterion) {
+        let mut group = c.benchmark_group("process_detection");
+        group.bench_function("proc_cmdline_scan", |b| {
+            b.iter(|| brainfm_process_in(black_box(Path::new("/proc"))));
+        });
+        if Command::new("pgrep").arg("--version").output().is_ok() {
+            group.bench_function("pgrep_subprocess", |b| {
+                b.iter(|| {
+                    Command::new("pgrep")
+                        .args(["-i", "brain.?fm"])
+                        .output()
+                        .is_ok_and(|output| output.status.success())
+                });
+            });
+        }
+        group.finish();
+    }
+
+    criterion::criterion_group!(benches, bench_process_detection);
+}
+
+#[cfg(target_os = "linux")]
+criterion::criterion_main!(linux::benches);
+
+#[cfg(not(target_os = "linux"))]
+fn main() {}
@@ -0,0 +1,30 @@
+//! Print the Brain.fm presence each time it changes, from async code.
+//!
+//! ```sh
+//! cargo run --example async_stream --features tokio-async
+//! ```
+
+use brainfm_presence::async_reader::{AsyncBrainFmReader, BrainFmStream};
+use brainfm_presence::{BrainFmReader, BrainFmState};
+use futures_util::StreamExt;
+use std::time::Duration;
+
+/// Stand-in for an async Discord client call
+async fn update_discord(state: BrainFmState) {
+    if state.is_playing {
+        println!("🎵 {}", state.to_presence_string());
+    } else {
+        println!("⏸️  Not playing");
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    let reader = AsyncBrainFmReader::new(BrainFmReader::new()?);
+    BrainFmStream::new(reader, Duration::from_secs(5))
+        .for_each(update_discord)
+        .await;
+    Ok(())
+}
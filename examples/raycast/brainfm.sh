@@ -0,0 +1,27 @@
+#!/bin/bash
+
+# Raycast script command showing what Brain.fm is playing, e.g.
+# "🧠 Focus — Nothing Remains • Electronic". Add this directory under
+# Raycast Settings → Extensions → Script Commands.
+#
+# Set BRAINFM_PRESENCE_BIN if the app is installed somewhere else.
+
+# Required parameters:
+# @raycast.schemaVersion 1
+# @raycast.title Brain.fm
+# @raycast.mode inline
+# @raycast.refreshTime 1m
+
+# Optional parameters:
+# @raycast.icon 🧠
+# @raycast.packageName Brain.fm Presence
+# @raycast.description Show the current Brain.fm mode, track and genre
+
+BIN="${BRAINFM_PRESENCE_BIN:-/Applications/Brain.fm Presence.app/Contents/MacOS/brainfm-presence}"
+
+if ! output=$("$BIN" --once --raycast 2>/dev/null); then
+    echo "Brain.fm not running"
+    exit 1
+fi
+
+echo "$output"
@@ -13,29 +13,40 @@
 //! 5. We decompress and parse the JSON to build a filename → metadata lookup table
 //! 6. The cache reader matches the currently playing audio URL against this table
 
-use crate::util::url_decode;
+use crate::cache_reader::{detect_cache_format_version, CacheFormatVersion};
+use crate::util::{self, normalize_activity, url_decode};
 use anyhow::Result;
 use flate2::read::GzDecoder;
-use log::{debug, trace};
+use log::{debug, trace, warn};
 use regex::Regex;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::io::Read;
-use std::path::Path;
-use std::sync::LazyLock;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Once};
+use std::time::SystemTime;
 
-/// Regex for matching Brain.fm servings API URLs in cache headers
+/// Logs the cache format version the first time the cache is read
+static LOG_CACHE_FORMAT: Once = Once::new();
+
+/// Regex for matching Brain.fm servings API URLs in cache headers, in any
+/// API version (captured first)
 static SERVINGS_URL_RE: LazyLock<Regex> = LazyLock::new(|| {
-    Regex::new(r"api\.brain\.fm/v3/users/[^/]+/servings/(recent|favorites)").unwrap()
+    Regex::new(r"api\.brain\.fm/v([0-9]+)/users/[^/]+/servings/(recent|favorites)").unwrap()
 });
 
 /// Rich metadata extracted from Brain.fm API responses
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TrackMetadata {
     /// Clean, human-readable track name (e.g., "Nothing Remains")
     pub name: String,
 
+    /// Brain.fm's track ID, stable across filename changes
+    #[serde(default)]
+    pub track_id: Option<String>,
+
     /// Genre from track tags (e.g., "Electronic", "Piano", "Atmospheric")
     pub genre: Option<String>,
 
@@ -51,6 +62,10 @@ pub struct TrackMetadata {
     /// Activity within the mental state (e.g., "Deep Work", "Creativity", "Recharge")
     pub activity: Option<String>,
 
+    /// Whether this is a guided meditation (see `build_track_metadata`)
+    #[serde(default)]
+    pub is_guided_meditation: bool,
+
     /// Track image URL (usually Unsplash)
     pub image_url: Option<String>,
 
@@ -62,36 +77,207 @@ pub struct TrackMetadata {
 
     /// Instrument tags (e.g., ["Acoustic Piano", "Electronic Percussion"])
     pub instruments: Vec<String>,
+
+    /// When this metadata was built from an API response (disk cache or live API)
+    pub loaded_at: SystemTime,
+}
+
+impl TrackMetadata {
+    /// Number of optional metadata fields that are populated (0-8).
+    ///
+    /// Used to decide which of two entries for the same track is more useful.
+    #[must_use]
+    pub fn completeness_score(&self) -> u32 {
+        let fields = [
+            self.genre.is_some(),
+            self.neural_effect.is_some(),
+            self.mental_state.is_some(),
+            self.activity.is_some(),
+            self.image_url.is_some(),
+            self.bpm.is_some(),
+            !self.moods.is_empty(),
+            !self.instruments.is_empty(),
+        ];
+        fields.into_iter().map(u32::from).sum()
+    }
+}
+
+/// Number of fields counted by `TrackMetadata::completeness_score`
+const COMPLETENESS_FIELDS: u32 = 8;
+
+/// Summary of an `ApiCacheData`, for diagnostics
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CacheStatistics {
+    /// Number of cached tracks
+    pub total_tracks: usize,
+
+    /// Number of distinct genres across all tracks
+    pub unique_genres: usize,
+
+    /// Number of distinct mental states (modes) across all tracks
+    pub unique_mental_states: usize,
+
+    /// Tracks that have an image URL
+    pub tracks_with_images: usize,
+
+    /// Tracks that have a BPM
+    pub tracks_with_bpm: usize,
+
+    /// Mean `completeness_score()` as a fraction of all fields (0.0 - 1.0)
+    pub average_completeness: f32,
+
+    /// Earliest `loaded_at` of any track
+    pub oldest_loaded_at: Option<SystemTime>,
+
+    /// Latest `loaded_at` of any track
+    pub newest_loaded_at: Option<SystemTime>,
+}
+
+impl std::fmt::Display for CacheStatistics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} tracks, {} genres, avg completeness {:.2}",
+            self.total_tracks, self.unique_genres, self.average_completeness
+        )
+    }
+}
+
+/// How `ApiCacheData::merge_with_strategy` resolves a track present in both caches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Incoming entries always replace existing ones
+    OverwriteAll,
+
+    /// Incoming entries only fill gaps; existing entries are never replaced
+    KeepExisting,
+
+    /// Keep whichever entry has the higher `completeness_score()`.
+    /// Ties go to the entry with the newer `loaded_at`.
+    MostComplete,
 }
 
 /// Maximum number of entries in the API cache
 const MAX_CACHE_ENTRIES: usize = 500;
 
+/// Version of the file format written by `ApiCacheData::save`
+pub const CACHE_SCHEMA_VERSION: u32 = 1;
+
+/// On-disk form of an exported `ApiCacheData`
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheFile {
+    /// `CACHE_SCHEMA_VERSION` at the time of export
+    schema_version: u32,
+
+    /// When the file was written, as an HTTP date (informational only)
+    exported_at: String,
+
+    #[serde(flatten)]
+    cache: ApiCacheData,
+}
+
+/// Where `ApiCacheData::prefetch_images` stores the artwork at `url`:
+/// `cache_dir/images/<hash of the URL>.<extension>`
+#[must_use]
+pub fn image_cache_path(cache_dir: &Path, url: &str) -> PathBuf {
+    // FNV-1a, which unlike `DefaultHasher` is stable across Rust releases
+    let hash = url.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    let extension = url
+        .split(['?', '#'])
+        .next()
+        .and_then(|path| path.rsplit_once('.'))
+        .map(|(_, extension)| extension)
+        .filter(|extension| {
+            (1..=4).contains(&extension.len())
+                && extension.chars().all(|c| c.is_ascii_alphanumeric())
+        });
+    let name = match extension {
+        Some(extension) => format!("{hash:016x}.{}", extension.to_ascii_lowercase()),
+        None => format!("{hash:016x}"),
+    };
+    cache_dir.join("images").join(name)
+}
+
+/// Download each of `urls` to its `image_cache_path`, skipping files that
+/// already exist. Failed downloads are logged and left out of the result.
+#[cfg(feature = "tokio-async")]
+fn download_images(
+    urls: &[String],
+    cache_dir: &Path,
+) -> Result<std::collections::HashMap<String, PathBuf>> {
+    let mut paths = std::collections::HashMap::new();
+    for url in urls {
+        let path = image_cache_path(cache_dir, url);
+        if !path.is_file() {
+            match crate::api_client::download(url) {
+                Ok(bytes) => util::write_file_atomic(&path, &bytes)?,
+                Err(e) => {
+                    debug!("Skipping artwork {url}: {e:#}");
+                    continue;
+                }
+            }
+        }
+        paths.insert(url.clone(), path);
+    }
+    Ok(paths)
+}
+
 /// Container for all API cache data, keyed by audio filename.
 ///
 /// Uses a `Vec`-based bounded LRU cache. Lookups move the accessed entry to
 /// the front; inserts evict the least-recently-used (last) entry when full.
-#[derive(Debug, Clone, Default)]
+/// Tracks with a `track_id` are also indexed by it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ApiCacheData {
     /// Ordered list of (filename, metadata) pairs — most recently used first.
     tracks: Vec<(String, TrackMetadata)>,
+
+    /// Track ID → metadata, for tracks whose filename has changed
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    by_track_id: HashMap<String, TrackMetadata>,
+
+    /// Newest API version among the cached responses (`/v3/`, `/v4/`, …)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    api_version: Option<u32>,
 }
 
 impl ApiCacheData {
     /// Create a new empty cache
     #[must_use]
     pub fn new() -> Self {
-        Self { tracks: Vec::new() }
+        Self::default()
     }
 
     /// Insert a key-value pair, enforcing the capacity bound.
-    fn insert(&mut self, key: String, value: TrackMetadata) {
+    pub fn insert(&mut self, key: String, value: TrackMetadata) {
+        if let Some(ref id) = value.track_id {
+            self.by_track_id.insert(id.clone(), value.clone());
+        }
         // Remove existing entry if present
         self.tracks.retain(|(k, _)| k != &key);
         // Insert at front (most recently used)
         self.tracks.insert(0, (key, value));
-        // Evict oldest if over capacity
-        self.tracks.truncate(MAX_CACHE_ENTRIES);
+        // Evict oldest if over capacity, dropping IDs no remaining entry has
+        if self.tracks.len() > MAX_CACHE_ENTRIES {
+            for (_, evicted) in self.tracks.split_off(MAX_CACHE_ENTRIES) {
+                let Some(id) = evicted.track_id else { continue };
+                if !self
+                    .tracks
+                    .iter()
+                    .any(|(_, m)| m.track_id == Some(id.clone()))
+                {
+                    self.by_track_id.remove(&id);
+                }
+            }
+        }
+    }
+
+    /// Look up metadata by Brain.fm track ID (see `track_id_from_url`).
+    #[must_use]
+    pub fn lookup_by_id(&self, id: &str) -> Option<&TrackMetadata> {
+        self.by_track_id.get(id)
     }
 
     /// Look up metadata by matching the audio URL's filename against cached data.
@@ -138,6 +324,24 @@ impl ApiCacheData {
         }
     }
 
+    /// Look up a track by name like `lookup_by_name`, without marking it as
+    /// recently used
+    #[must_use]
+    pub(crate) fn peek_by_name(&self, name: &str) -> Option<&TrackMetadata> {
+        let lower = name.to_lowercase();
+        self.tracks
+            .iter()
+            .map(|(_, meta)| meta)
+            .find(|meta| meta.name.to_lowercase() == lower)
+    }
+
+    /// The newest API version the cached responses came from, if any were
+    /// read from the Chromium cache
+    #[must_use]
+    pub fn api_version(&self) -> Option<u32> {
+        self.api_version
+    }
+
     /// Number of tracks in the cache
     #[must_use]
     pub fn len(&self) -> usize {
@@ -150,10 +354,112 @@ impl ApiCacheData {
         self.tracks.is_empty()
     }
 
-    /// Merge another ApiCacheData into this one.
+    /// Summarize the cached tracks
+    #[must_use]
+    pub fn statistics(&self) -> CacheStatistics {
+        let tracks = || self.tracks.iter().map(|(_, meta)| meta);
+        let distinct = |values: Vec<&String>| values.into_iter().collect::<HashSet<_>>().len();
+
+        let total_completeness: u32 = tracks().map(TrackMetadata::completeness_score).sum();
+        #[allow(clippy::cast_precision_loss)] // At most MAX_CACHE_ENTRIES * 8
+        let average_completeness = if self.tracks.is_empty() {
+            0.0
+        } else {
+            total_completeness as f32 / (self.tracks.len() as f32 * COMPLETENESS_FIELDS as f32)
+        };
+
+        CacheStatistics {
+            total_tracks: self.tracks.len(),
+            unique_genres: distinct(tracks().filter_map(|m| m.genre.as_ref()).collect()),
+            unique_mental_states: distinct(
+                tracks().filter_map(|m| m.mental_state.as_ref()).collect(),
+            ),
+            tracks_with_images: tracks().filter(|m| m.image_url.is_some()).count(),
+            tracks_with_bpm: tracks().filter(|m| m.bpm.is_some()).count(),
+            average_completeness,
+            oldest_loaded_at: tracks().map(|m| m.loaded_at).min(),
+            newest_loaded_at: tracks().map(|m| m.loaded_at).max(),
+        }
+    }
+
+    /// Download every cached track's artwork to `cache_dir/images/` so it
+    /// is available offline, returning a map from image URL to local file.
+    ///
+    /// Files are named by `image_cache_path` and are not downloaded again
+    /// when already present.
+    #[cfg(feature = "tokio-async")]
+    pub async fn prefetch_images(
+        &self,
+        cache_dir: &Path,
+    ) -> Result<std::collections::HashMap<String, PathBuf>> {
+        let mut urls: Vec<String> = self
+            .tracks
+            .iter()
+            .filter_map(|(_, meta)| meta.image_url.clone())
+            .collect();
+        urls.sort();
+        urls.dedup();
+        let cache_dir = cache_dir.to_path_buf();
+        // ureq blocks, so download off the async runtime
+        tokio::task::spawn_blocking(move || download_images(&urls, &cache_dir)).await?
+    }
+
+    /// Write the cache to `path` as JSON, with a `schema_version` and a
+    /// human-readable `exported_at` alongside the tracks.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        util::write_json_file(
+            path,
+            &CacheFile {
+                schema_version: CACHE_SCHEMA_VERSION,
+                exported_at: httpdate::fmt_http_date(SystemTime::now()),
+                cache: self.clone(),
+            },
+        )
+    }
+
+    /// Read a cache written by `save()`, rejecting newer schema versions
+    pub fn load(path: &Path) -> Result<Self> {
+        let file: CacheFile = util::read_json_file(path)?;
+        anyhow::ensure!(
+            file.schema_version <= CACHE_SCHEMA_VERSION,
+            "{} uses cache schema version {}, newer than the supported {CACHE_SCHEMA_VERSION}",
+            path.display(),
+            file.schema_version
+        );
+        debug!(
+            "Loaded {} cached tracks exported at {}",
+            file.cache.len(),
+            file.exported_at
+        );
+        Ok(file.cache)
+    }
+
+    /// Merge another `ApiCacheData` into this one, overwriting existing entries.
     pub fn merge(&mut self, other: &ApiCacheData) {
+        self.merge_with_strategy(other, MergeStrategy::OverwriteAll);
+    }
+
+    /// Merge another `ApiCacheData` into this one, resolving duplicates with `strategy`.
+    ///
+    /// Entries that are kept from `self` retain their LRU position. The
+    /// newer of the two API versions is kept.
+    pub fn merge_with_strategy(&mut self, other: &ApiCacheData, strategy: MergeStrategy) {
+        self.api_version = self.api_version.max(other.api_version);
         for (key, value) in &other.tracks {
-            self.insert(key.clone(), value.clone());
+            let existing = self.tracks.iter().find(|(k, _)| k == key).map(|(_, v)| v);
+            let replace = match (strategy, existing) {
+                (_, None) | (MergeStrategy::OverwriteAll, _) => true,
+                (MergeStrategy::KeepExisting, Some(_)) => false,
+                (MergeStrategy::MostComplete, Some(current)) => {
+                    let (new_score, old_score) =
+                        (value.completeness_score(), current.completeness_score());
+                    new_score > old_score
+                        || (new_score == old_score && value.loaded_at > current.loaded_at)
+                }
+            };
+            if replace {
+                self.insert(key.clone(), value.clone());
+            }
         }
     }
 
@@ -167,65 +473,111 @@ impl ApiCacheData {
 }
 
 // --- JSON deserialization types for Brain.fm API responses ---
+//
+// Field names map to the API's camelCase via `rename_all`, so a new field only
+// needs a matching snake_case name. With the `strict-parsing` feature, unknown
+// fields fail the parse, which surfaces API additions during development.
 
 #[derive(Debug, Deserialize)]
-struct ServingsResponse {
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict-parsing", serde(deny_unknown_fields))]
+pub(crate) struct ServingsResponse {
+    #[serde(default)]
     result: Vec<Serving>,
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict-parsing", serde(deny_unknown_fields))]
 struct Serving {
     track: Track,
-    #[serde(rename = "trackVariation")]
     track_variation: TrackVariation,
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict-parsing", serde(deny_unknown_fields))]
 struct Track {
     name: String,
 
-    #[serde(default, rename = "beatsPerMinute")]
+    // The ID has appeared under all three names
+    #[serde(default)]
+    id: Option<TrackId>,
+
+    #[serde(default, rename = "trackId")]
+    camel_id: Option<TrackId>,
+
+    #[serde(default, rename = "_id")]
+    underscore_id: Option<TrackId>,
+
+    #[serde(default)]
     beats_per_minute: Option<f64>,
 
-    #[serde(default, rename = "imageUrl")]
+    #[serde(default)]
     image_url: Option<String>,
 
-    #[serde(default, rename = "mentalState")]
+    #[serde(default)]
     mental_state: Option<MentalStateRef>,
 
-    #[serde(default, rename = "mobileActivity")]
+    #[serde(default)]
     mobile_activity: Option<ActivityRef>,
 
     #[serde(default)]
     tags: Vec<TrackTag>,
 }
 
+/// A track ID, which the API sends as a number or a string
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum TrackId {
+    Number(u64),
+    Text(String),
+}
+
+impl std::fmt::Display for TrackId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Number(id) => write!(f, "{id}"),
+            Self::Text(id) => f.write_str(id),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict-parsing", serde(deny_unknown_fields))]
 struct TrackVariation {
     #[serde(default)]
     url: Option<String>,
 
-    #[serde(default, rename = "neuralEffectLevel")]
+    #[serde(default)]
     neural_effect_level: Option<f64>,
 
-    #[serde(default, rename = "cdnUrl")]
+    #[serde(default)]
     cdn_url: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict-parsing", serde(deny_unknown_fields))]
 struct MentalStateRef {
-    #[serde(default, rename = "displayValue")]
+    #[serde(default)]
     display_value: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict-parsing", serde(deny_unknown_fields))]
 struct ActivityRef {
-    #[serde(default, rename = "displayValue")]
+    #[serde(default)]
     display_value: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict-parsing", serde(deny_unknown_fields))]
 struct TrackTag {
+    // `type` is a Rust keyword
     #[serde(rename = "type")]
     tag_type: String,
     value: String,
@@ -237,7 +589,45 @@ struct TrackTag {
 ///
 /// Returns an `ApiCacheData` containing a lookup table of filename → metadata.
 /// Safe to call even if no API data is cached — returns an empty table.
+///
+/// With the `rayon` feature the cache entries are read and parsed in
+/// parallel; results are still merged in index order, so the outcome is the
+/// same as [`read_api_cache_sequential`].
 pub fn read_api_cache(app_support_path: &Path) -> Result<ApiCacheData> {
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        read_api_cache_with(app_support_path, |candidates, version| {
+            candidates
+                .par_iter()
+                .filter_map(|path| parse_cache_entry(path, version))
+                .collect()
+        })
+    }
+    #[cfg(not(feature = "rayon"))]
+    read_api_cache_sequential(app_support_path)
+}
+
+/// Single-threaded [`read_api_cache`], regardless of the `rayon` feature.
+///
+/// Kept public as the baseline for the `api_cache_scan` benchmark.
+pub fn read_api_cache_sequential(app_support_path: &Path) -> Result<ApiCacheData> {
+    read_api_cache_with(app_support_path, |candidates, version| {
+        candidates
+            .iter()
+            .filter_map(|path| parse_cache_entry(path, version))
+            .collect()
+    })
+}
+
+/// Locate the cache entries and merge whatever `parse_all` extracts from them.
+///
+/// `parse_all` must return results in candidate order: entries merged later
+/// win, and the index lists the most recent entries first.
+fn read_api_cache_with(
+    app_support_path: &Path,
+    parse_all: impl FnOnce(&[PathBuf], CacheFormatVersion) -> Vec<ApiCacheData>,
+) -> Result<ApiCacheData> {
     let cache_path = app_support_path.join("Cache").join("Cache_Data");
 
     if !cache_path.exists() {
@@ -247,69 +637,162 @@ pub fn read_api_cache(app_support_path: &Path) -> Result<ApiCacheData> {
 
     let mut result = ApiCacheData::new();
 
-    // Scan all *_0 metadata files for API response patterns
-    let entries = fs::read_dir(&cache_path)?;
+    // Prefer the Simple Cache index (no directory listing, most recent first);
+    // fall back to scanning the directory if the index is missing or unreadable.
+    let candidates = match parse_chromium_cache_index(&cache_path) {
+        Ok(paths) if !paths.is_empty() => {
+            debug!("Cache index: {} entries", paths.len());
+            paths
+        }
+        Ok(_) => list_cache_entries(&cache_path)?,
+        Err(e) => {
+            debug!("Cache index unavailable ({e}), scanning directory");
+            list_cache_entries(&cache_path)?
+        }
+    };
 
-    for entry in entries.flatten() {
-        let filename = entry.file_name();
-        let filename_str = filename.to_string_lossy();
+    let version = detect_cache_format_version(&cache_path);
+    LOG_CACHE_FORMAT.call_once(|| debug!("Cache format: {version:?}"));
 
-        // Only look at *_0 metadata files (not *_s stream files)
-        if !filename_str.ends_with("_0") {
-            continue;
-        }
+    for parsed_tracks in parse_all(&candidates, version) {
+        result.merge(&parsed_tracks);
+    }
 
-        // Quick check: read the first 512 bytes to check if it's an API response
-        let file_path = entry.path();
-        let data = match fs::read(&file_path) {
-            Ok(d) => d,
-            Err(_) => continue,
-        };
+    debug!("API cache: loaded {} tracks total", result.len());
 
-        // Check the header area for our target URL pattern
-        let header_size = std::cmp::min(data.len(), 512);
-        let header_text = String::from_utf8_lossy(&data[..header_size]);
+    Ok(result)
+}
 
-        if !SERVINGS_URL_RE.is_match(&header_text) {
-            continue;
-        }
+/// Read one `*_0` cache entry and parse it if it holds a servings response.
+fn parse_cache_entry(file_path: &Path, version: CacheFormatVersion) -> Option<ApiCacheData> {
+    let filename_str = file_path.file_name().unwrap_or_default().to_string_lossy();
+
+    // Quick check: read the first 512 bytes to check if it's an API response
+    let data = fs::read(file_path).ok()?;
+
+    // Check the header area for our target URL pattern
+    let header_size = std::cmp::min(data.len(), 512);
+    let header_text = String::from_utf8_lossy(&data[..header_size]);
+
+    let api_version = SERVINGS_URL_RE
+        .captures(&header_text)?
+        .get(1)
+        .and_then(|version| version.as_str().parse().ok());
+
+    debug!("Found API cache entry: {:?}", file_path);
+
+    // Try to extract and decompress the JSON body
+    let Some(json_body) = extract_json_body(&data, version) else {
+        trace!("Could not extract JSON body from {:?}", filename_str);
+        return None;
+    };
+    let ParseResult { mut data, errors } = parse_servings_json_lenient(&json_body);
+    debug!(
+        "Parsed {} tracks from {:?} ({} servings skipped)",
+        data.len(),
+        filename_str,
+        errors.len()
+    );
+    data.api_version = api_version;
+    Some(data)
+}
 
-        debug!("Found API cache entry: {:?}", file_path);
+/// List all `*_0` entry files (not `*_s` stream files) in the cache directory.
+fn list_cache_entries(cache_path: &Path) -> Result<Vec<PathBuf>> {
+    Ok(fs::read_dir(cache_path)?
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().ends_with("_0"))
+        .map(|entry| entry.path())
+        .collect())
+}
 
-        // Try to extract and decompress the JSON body
-        match extract_json_body(&data) {
-            Some(json_body) => match parse_servings_response(&json_body) {
-                Ok(parsed_tracks) => {
-                    debug!(
-                        "Parsed {} tracks from {:?}",
-                        parsed_tracks.len(),
-                        filename_str
-                    );
-                    result.merge(&parsed_tracks);
-                }
-                Err(e) => {
-                    trace!("Failed to parse JSON from {:?}: {}", filename_str, e);
-                }
-            },
-            None => {
-                trace!("Could not extract JSON body from {:?}", filename_str);
-            }
-        }
+/// Magic number of Chromium's Simple Cache index (`index-dir/the-real-index`).
+const SIMPLE_INDEX_MAGIC: u64 = 0x656e_7465_7220_796f;
+
+/// Index format versions whose layout we understand.
+const SIMPLE_INDEX_VERSIONS: std::ops::RangeInclusive<u32> = 7..=9;
+
+/// Size of one serialized index entry: hash (u64) + last used (i64) + packed size (u64).
+const SIMPLE_INDEX_ENTRY_SIZE: usize = 24;
+
+/// Enumerate cache entry files via Chromium's Simple Cache index.
+///
+/// The index (`Cache_Data/index-dir/the-real-index`, layout documented in
+/// `net/disk_cache/simple/simple_index_file.h`) lists every entry's key hash and
+/// last-used time. Entry files are named `{hash:016x}_0`, so we can enumerate
+/// them without listing the directory, most recently used first.
+///
+/// The index stores hashes, not URLs, so callers still need to check each
+/// entry's header for the URL they want — but recently used API responses
+/// are reached first.
+pub fn parse_chromium_cache_index(cache_path: &Path) -> Result<Vec<PathBuf>> {
+    let index_path = cache_path.join("index-dir").join("the-real-index");
+    let data = fs::read(&index_path)?;
+
+    let Some(mut entries) = parse_simple_index(&data) else {
+        anyhow::bail!("Unrecognized cache index format: {}", index_path.display());
+    };
+
+    // Most recently used first
+    entries.sort_by_key(|&(_, last_used)| std::cmp::Reverse(last_used));
+
+    Ok(entries
+        .into_iter()
+        .map(|(hash, _)| cache_path.join(format!("{hash:016x}_0")))
+        .filter(|path| path.exists())
+        .collect())
+}
+
+/// Parse a Simple Cache index into `(entry_hash, last_used)` pairs.
+///
+/// Layout (`base::Pickle`, little-endian, 4-byte aligned):
+/// `[u32 payload_size][u32 crc][u64 magic][u32 version][u64 entry_count]
+/// [u64 cache_size][u32 reason]` followed by `entry_count` entries of
+/// `[u64 hash][i64 last_used][u64 packed_size]`.
+fn parse_simple_index(data: &[u8]) -> Option<Vec<(u64, i64)>> {
+    let read_u32 = |at: usize| -> Option<u32> {
+        Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+    };
+    let read_u64 = |at: usize| -> Option<u64> {
+        Some(u64::from_le_bytes(data.get(at..at + 8)?.try_into().ok()?))
+    };
+
+    if read_u64(8)? != SIMPLE_INDEX_MAGIC {
+        return None;
+    }
+    if !SIMPLE_INDEX_VERSIONS.contains(&read_u32(16)?) {
+        return None;
     }
+    let entry_count = usize::try_from(read_u64(20)?).ok()?;
 
-    debug!("API cache: loaded {} tracks total", result.len());
+    let entries_start = 40;
+    let available = data.len().saturating_sub(entries_start) / SIMPLE_INDEX_ENTRY_SIZE;
+    if entry_count > available {
+        return None;
+    }
 
-    Ok(result)
+    (0..entry_count)
+        .map(|i| {
+            let at = entries_start + i * SIMPLE_INDEX_ENTRY_SIZE;
+            let hash = read_u64(at)?;
+            let last_used = i64::from_le_bytes(data.get(at + 8..at + 16)?.try_into().ok()?);
+            Some((hash, last_used))
+        })
+        .collect()
 }
 
 /// Extract and decompress the JSON body from a Chromium cache entry.
 ///
-/// Chromium cache files have: HTTP response metadata + optional gzip body.
-/// We detect the gzip magic bytes (`1F 8B`) and decompress from there.
-fn extract_json_body(data: &[u8]) -> Option<String> {
+/// Chromium cache files have: a header, the URL, HTTP response metadata +
+/// optional gzip body. We detect the gzip magic bytes (`1F 8B`) and
+/// decompress from there, starting after the header and URL when `version`
+/// says where they end (so bytes in the URL can't pass for gzip magic).
+fn extract_json_body(data: &[u8], version: CacheFormatVersion) -> Option<String> {
+    let body_start = version.entry_body_offset(data).unwrap_or(0);
+
     // Strategy 1: Look for gzip magic bytes and decompress
-    if let Some(pos) = find_gzip_start(data) {
-        if let Ok(decompressed) = decompress_gzip(&data[pos..]) {
+    if let Some(pos) = find_gzip_start(&data[body_start..]) {
+        if let Ok(decompressed) = decompress_gzip(&data[body_start + pos..]) {
             return Some(decompressed);
         }
     }
@@ -385,35 +868,217 @@ pub fn parse_servings_json(json_body: &str) -> Result<ApiCacheData> {
     parse_servings_response(json_body)
 }
 
+/// What `parse_servings_json_lenient` made of a servings response
+#[derive(Debug, Default)]
+pub struct ParseResult {
+    /// The servings that parsed
+    pub data: ApiCacheData,
+    /// `(index in result, error message)` for each serving that did not
+    pub errors: Vec<(usize, String)>,
+}
+
+/// Parse a Brain.fm servings JSON response serving by serving.
+///
+/// Unlike `parse_servings_json`, a malformed serving is skipped and reported
+/// in `ParseResult::errors` instead of failing the whole response. A body
+/// that is not JSON or has no `result` array, such as an error envelope
+/// (`{"error": "..."}`), gives an empty `ApiCacheData` and a warning.
+#[must_use]
+pub fn parse_servings_json_lenient(json: &str) -> ParseResult {
+    let mut parsed = ParseResult::default();
+    let response: serde_json::Value = match serde_json::from_str(json) {
+        Ok(response) => response,
+        Err(e) => {
+            warn!("Servings response is not valid JSON: {e}");
+            return parsed;
+        }
+    };
+    let Some(servings) = response.get("result").and_then(serde_json::Value::as_array) else {
+        warn!(
+            "Servings response has no result array: {}",
+            util::truncate(&response.to_string(), 200)
+        );
+        return parsed;
+    };
+
+    for (index, serving) in servings.iter().enumerate() {
+        match Serving::deserialize(serving) {
+            Ok(serving) => {
+                for (key, metadata) in serving_entries(&serving) {
+                    parsed.data.insert(key, metadata);
+                }
+            }
+            Err(e) => {
+                debug!("Skipping malformed serving {index}: {e}");
+                parsed.errors.push((index, e.to_string()));
+            }
+        }
+    }
+    parsed
+}
+
+/// The filename → metadata cache of an already parsed servings response
+#[cfg(not(feature = "streaming-api"))]
+pub(crate) fn servings_cache(response: &ServingsResponse) -> ApiCacheData {
+    let mut cache = ApiCacheData::new();
+    for (key, metadata) in response.result.iter().flat_map(serving_entries) {
+        cache.insert(key, metadata);
+    }
+    cache
+}
+
 /// Parse a Brain.fm servings API response and build a filename → metadata cache
 fn parse_servings_response(json_body: &str) -> Result<ApiCacheData> {
-    let response: ServingsResponse = serde_json::from_str(json_body)?;
     let mut cache = ApiCacheData::new();
+    for (key, metadata) in parse_servings_entries(json_body)? {
+        cache.insert(key, metadata);
+    }
+    Ok(cache)
+}
+
+/// Parse a Brain.fm servings API response into the (key, metadata) pairs
+/// its cache is built from, in insertion order
+pub(crate) fn parse_servings_entries(json_body: &str) -> Result<Vec<(String, TrackMetadata)>> {
+    let parsed = serde_json::from_str::<ServingsResponse>(json_body);
+    #[cfg(feature = "strict-parsing")]
+    if let Err(ref e) = parsed {
+        log::warn!("Servings response does not match the parser (strict-parsing): {e}");
+    }
+    Ok(parsed?.result.iter().flat_map(serving_entries).collect())
+}
 
-    for serving in response.result {
-        let metadata = build_track_metadata(&serving.track, &serving.track_variation);
+/// The (key, metadata) pairs a serving adds to the cache, in insertion order
+fn serving_entries(serving: &Serving) -> Vec<(String, TrackMetadata)> {
+    let metadata = build_track_metadata(&serving.track, &serving.track_variation);
+    let mut entries = Vec::new();
 
-        // Key by the filename from trackVariation.url (just the filename, no CDN prefix)
-        if let Some(ref url) = serving.track_variation.url {
-            let decoded_url = url_decode(url);
-            cache.insert(decoded_url.clone(), metadata.clone());
+    // Key by the filename from trackVariation.url (just the filename, no CDN prefix)
+    if let Some(ref url) = serving.track_variation.url {
+        let decoded_url = url_decode(url);
+        entries.push((decoded_url.clone(), metadata.clone()));
 
-            // Also key by the raw URL (before decoding) for encoded filenames
-            if *url != decoded_url {
-                cache.insert(url.clone(), metadata.clone());
-            }
+        // Also key by the raw URL (before decoding) for encoded filenames
+        if *url != decoded_url {
+            entries.push((url.clone(), metadata.clone()));
+        }
+    }
+
+    // Also key by the CDN URL filename for broader matching
+    if let Some(ref cdn_url) = serving.track_variation.cdn_url {
+        if let Some(filename) = extract_filename_from_url(cdn_url) {
+            entries.push((url_decode(&filename), metadata));
         }
+    }
 
-        // Also key by the CDN URL filename for broader matching
-        if let Some(ref cdn_url) = serving.track_variation.cdn_url {
-            if let Some(filename) = extract_filename_from_url(cdn_url) {
-                let decoded = url_decode(&filename);
-                cache.insert(decoded, metadata);
+    entries
+}
+
+/// Parse a servings response from `reader` one serving at a time, yielding
+/// the (key, metadata) pairs `parse_servings_json` would insert, in the
+/// same order.
+///
+/// Parsing runs on a background thread that stays at most
+/// `STREAM_BUFFER_ENTRIES` entries ahead of the consumer, so memory use does
+/// not grow with the response. A parse error ends the iterator with an
+/// `Err`; dropping the iterator stops the parse.
+#[cfg(feature = "streaming-api")]
+pub fn stream_servings_json<R: Read + Send + 'static>(
+    reader: R,
+) -> impl Iterator<Item = Result<(String, TrackMetadata)>> {
+    let (tx, rx) = std::sync::mpsc::sync_channel(STREAM_BUFFER_ENTRIES);
+    std::thread::spawn(move || {
+        let mut deserializer =
+            serde_json::Deserializer::from_reader(std::io::BufReader::new(reader));
+        let parsed = serde::de::DeserializeSeed::deserialize(
+            streaming::ServingsSeed { tx: &tx },
+            &mut deserializer,
+        )
+        .and_then(|()| deserializer.end());
+        if let Err(e) = parsed {
+            let _ = tx.send(Err(e.into()));
+        }
+    });
+    rx.into_iter()
+}
+
+/// How many entries `stream_servings_json` parses ahead of its consumer
+#[cfg(feature = "streaming-api")]
+const STREAM_BUFFER_ENTRIES: usize = 16;
+
+/// `serde` visitors that send each serving's entries down a channel as soon
+/// as it is parsed, instead of collecting a `ServingsResponse`
+#[cfg(feature = "streaming-api")]
+mod streaming {
+    use super::{serving_entries, Serving, TrackMetadata};
+    use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+    use std::fmt;
+    use std::sync::mpsc::SyncSender;
+
+    type Entry = anyhow::Result<(String, TrackMetadata)>;
+
+    /// The top-level object; only its `result` array is read
+    pub(super) struct ServingsSeed<'a> {
+        pub(super) tx: &'a SyncSender<Entry>,
+    }
+
+    impl<'de> DeserializeSeed<'de> for ServingsSeed<'_> {
+        type Value = ();
+
+        fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+            deserializer.deserialize_map(self)
+        }
+    }
+
+    impl<'de> Visitor<'de> for ServingsSeed<'_> {
+        type Value = ();
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a servings response object")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+            while let Some(key) = map.next_key::<String>()? {
+                if key == "result" {
+                    map.next_value_seed(ResultSeed { tx: self.tx })?;
+                } else {
+                    map.next_value::<IgnoredAny>()?;
+                }
             }
+            Ok(())
         }
     }
 
-    Ok(cache)
+    /// The `result` array, sent serving by serving
+    struct ResultSeed<'a> {
+        tx: &'a SyncSender<Entry>,
+    }
+
+    impl<'de> DeserializeSeed<'de> for ResultSeed<'_> {
+        type Value = ();
+
+        fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+            deserializer.deserialize_seq(self)
+        }
+    }
+
+    impl<'de> Visitor<'de> for ResultSeed<'_> {
+        type Value = ();
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("an array of servings")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+            while let Some(serving) = seq.next_element::<Serving>()? {
+                for entry in serving_entries(&serving) {
+                    self.tx
+                        .send(Ok(entry))
+                        .map_err(|_| de::Error::custom("servings stream dropped"))?;
+                }
+            }
+            Ok(())
+        }
+    }
 }
 
 /// Build a `TrackMetadata` from parsed API data
@@ -430,14 +1095,29 @@ fn build_track_metadata(track: &Track, variation: &TrackVariation) -> TrackMetad
         .tags
         .iter()
         .find(|t| t.tag_type == "activity")
-        .map(|t| t.value.clone())
+        .map(|t| t.value.as_str())
         // Fallback to mobileActivity.displayValue
         .or_else(|| {
             track
                 .mobile_activity
                 .as_ref()
-                .and_then(|a| a.display_value.clone())
-        });
+                .and_then(|a| a.display_value.as_deref())
+        })
+        .map(normalize_activity);
+
+    let mental_state = track
+        .mental_state
+        .as_ref()
+        .and_then(|ms| ms.display_value.clone());
+
+    // Guided meditations are tagged as such, or say so in their activity
+    let is_guided_meditation = track
+        .tags
+        .iter()
+        .any(|t| t.tag_type == "meditationType" && t.value.eq_ignore_ascii_case("guided"))
+        || activity
+            .as_deref()
+            .is_some_and(|activity| is_guided_activity(activity, mental_state.as_deref()));
 
     // Extract moods
     let moods: Vec<String> = track
@@ -460,25 +1140,44 @@ fn build_track_metadata(track: &Track, variation: &TrackVariation) -> TrackMetad
         .neural_effect_level
         .map(|nel| nel_display_value(nel));
 
-    let mental_state = track
-        .mental_state
-        .as_ref()
-        .and_then(|ms| ms.display_value.clone());
+    let track_id = [&track.id, &track.camel_id, &track.underscore_id]
+        .into_iter()
+        .flatten()
+        .map(ToString::to_string)
+        .find(|id| !id.is_empty());
 
     TrackMetadata {
-        name: track.name.clone(),
+        name: util::normalize_track_name(&track.name),
+        track_id,
         genre,
         neural_effect,
         neural_effect_level: variation.neural_effect_level,
         mental_state,
         activity,
+        is_guided_meditation,
         image_url: track.image_url.clone(),
         bpm: track.beats_per_minute.map(|b| b as u32),
         moods,
         instruments,
+        loaded_at: SystemTime::now(),
     }
 }
 
+/// Whether `activity` names a guided meditation: "Guided Meditation", or
+/// "Guided" under a meditation mental state (unlike "Guided Sleep")
+fn is_guided_activity(activity: &str, mental_state: Option<&str>) -> bool {
+    let has_word = |word: &str| {
+        activity
+            .split_whitespace()
+            .any(|w| w.eq_ignore_ascii_case(word))
+    };
+    has_word("guided")
+        && (has_word("meditation")
+            || mental_state
+                .map(crate::BrainFmMode::parse)
+                .is_some_and(|mode| mode.is_meditate_category()))
+}
+
 /// Convert Neural Effect Level from numeric (0.0-1.0) to display text.
 ///
 /// This formula is extracted directly from Brain.fm's decompiled renderer JavaScript:
@@ -487,6 +1186,7 @@ fn build_track_metadata(track: &Track, variation: &TrackVariation) -> TrackMetad
 ///     return e <= .33 ? "Low" : e <= .66 ? "Medium" : "High";
 /// }
 /// ```
+#[must_use]
 pub fn nel_display_value(level: f64) -> String {
     if level <= 0.33 {
         "Low Neural Effect".to_string()
@@ -497,6 +1197,37 @@ pub fn nel_display_value(level: f64) -> String {
     }
 }
 
+/// Convert a Neural Effect Level display string back to a representative
+/// numeric level (0.25, 0.5 or 0.75) that `nel_display_value` maps back to it.
+///
+/// Accepts either the bare level (`"High"`) or the full display string
+/// (`"High Neural Effect"`), case-insensitively.
+#[must_use]
+pub fn nel_level_value(display: &str) -> Option<f64> {
+    let level = display.split_whitespace().next()?.to_lowercase();
+    match level.as_str() {
+        "low" => Some(0.25),
+        "medium" => Some(0.5),
+        "high" => Some(0.75),
+        _ => None,
+    }
+}
+
+/// The numeric track ID some CDN URLs embed as a path segment before the
+/// filename (e.g. `https://audio2.brain.fm/tracks/48213/Name_VBR5.mp3`)
+#[must_use]
+pub fn track_id_from_url(url: &str) -> Option<String> {
+    let path = url.split('?').next().unwrap_or(url);
+    let path = path.split_once("://").map_or(path, |(_, rest)| rest);
+    let mut segments: Vec<&str> = path.split('/').skip(1).collect();
+    segments.pop(); // the filename
+    segments
+        .into_iter()
+        .rev()
+        .find(|segment| !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit()))
+        .map(str::to_string)
+}
+
 /// Extract the filename portion from a URL (after the last `/`, before `?` query params)
 fn extract_filename_from_url(url: &str) -> Option<String> {
     // Strip query parameters
@@ -524,6 +1255,21 @@ mod tests {
         assert_eq!(nel_display_value(1.0), "High Neural Effect");
     }
 
+    #[test]
+    fn test_nel_level_value_roundtrip() {
+        for display in [
+            "Low Neural Effect",
+            "Medium Neural Effect",
+            "High Neural Effect",
+        ] {
+            let level = nel_level_value(display).unwrap();
+            assert_eq!(nel_display_value(level), display);
+        }
+        assert_eq!(nel_level_value("high"), Some(0.75));
+        assert_eq!(nel_level_value("Extreme Neural Effect"), None);
+        assert_eq!(nel_level_value(""), None);
+    }
+
     #[test]
     fn test_extract_filename_from_url() {
         assert_eq!(
@@ -546,6 +1292,171 @@ mod tests {
         assert_eq!(url_decode("no_encoding_here"), "no_encoding_here");
     }
 
+    /// A servings response with a field the parser does not know about
+    const SERVINGS_WITH_NEW_FIELD: &str = r#"{
+        "result": [{
+            "track": { "name": "Blooming", "isFavorite": true },
+            "trackVariation": { "url": "Blooming_Sleep.mp3" }
+        }]
+    }"#;
+
+    #[cfg(not(feature = "strict-parsing"))]
+    #[test]
+    fn test_parse_servings_ignores_unknown_fields() {
+        let mut tracks = parse_servings_response(SERVINGS_WITH_NEW_FIELD).unwrap();
+        assert!(tracks.lookup_by_name("Blooming").is_some());
+    }
+
+    #[cfg(feature = "strict-parsing")]
+    #[test]
+    fn test_parse_servings_strict_rejects_unknown_fields() {
+        let err = parse_servings_response(SERVINGS_WITH_NEW_FIELD).unwrap_err();
+        assert!(err.to_string().contains("isFavorite"), "{err}");
+    }
+
+    /// One serving of about 1 KB for `GeneratedServings`
+    #[cfg(feature = "streaming-api")]
+    fn generated_serving(i: usize) -> String {
+        let moods: Vec<String> = (0..24)
+            .map(|m| format!(r#"{{"type":"mood","value":"Mood {m}"}}"#))
+            .collect();
+        format!(
+            r#"{{"track":{{"name":"Track {i}","tags":[{{"type":"genre","value":"Piano"}},{}]}},"trackVariation":{{"url":"Track_{i}.mp3","neuralEffectLevel":0.5}}}}"#,
+            moods.join(",")
+        )
+    }
+
+    /// A servings response generated as it is read, counting the bytes read
+    #[cfg(feature = "streaming-api")]
+    struct GeneratedServings {
+        servings: usize,
+        next: usize,
+        chunk: Vec<u8>,
+        pos: usize,
+        bytes_read: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[cfg(feature = "streaming-api")]
+    impl Read for GeneratedServings {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.pos == self.chunk.len() {
+                self.chunk = match self.next {
+                    0 => format!(r#"{{"result": [{}"#, generated_serving(0)),
+                    n if n < self.servings => format!(",{}", generated_serving(n)),
+                    n if n == self.servings => "]}".to_string(),
+                    _ => return Ok(0),
+                }
+                .into_bytes();
+                self.next += 1;
+                self.pos = 0;
+            }
+            let n = buf.len().min(self.chunk.len() - self.pos);
+            buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+            self.pos += n;
+            self.bytes_read
+                .fetch_add(n, std::sync::atomic::Ordering::SeqCst);
+            Ok(n)
+        }
+    }
+
+    #[cfg(feature = "streaming-api")]
+    #[test]
+    fn test_stream_servings_json_reads_ahead_boundedly() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let bytes_read = Arc::new(AtomicUsize::new(0));
+        let mut entries = stream_servings_json(GeneratedServings {
+            servings: 4000,
+            next: 0,
+            chunk: Vec::new(),
+            pos: 0,
+            bytes_read: Arc::clone(&bytes_read),
+        });
+
+        let (key, metadata) = entries.next().unwrap().unwrap();
+        assert_eq!(key, "Track_0.mp3");
+        assert_eq!(metadata.moods.len(), 24);
+        // Let the parser run as far ahead as it will
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        let ahead = bytes_read.load(Ordering::SeqCst);
+        assert!(ahead < 100 * 1024, "read {ahead} bytes ahead");
+
+        let rest: Vec<_> = entries.map(Result::unwrap).collect();
+        assert_eq!(rest.len(), 3999);
+        assert_eq!(rest[3998].0, "Track_3999.mp3");
+        assert!(bytes_read.load(Ordering::SeqCst) > 3_000_000);
+    }
+
+    #[cfg(feature = "streaming-api")]
+    #[test]
+    fn test_stream_servings_json_matches_parse() {
+        let json = r#"{"status": 200, "result": [
+            {"track": {"name": "Stratosphere"}, "trackVariation": {
+                "url": "Stratosphere%20Relax.mp3",
+                "cdnUrl": "https://audio2.brain.fm/Stratosphere_Relax_VBR5.mp3"
+            }},
+            {"track": {"name": "Bare"}, "trackVariation": {}}
+        ]}"#;
+        let streamed: Vec<String> = stream_servings_json(json.as_bytes())
+            .map(|entry| entry.unwrap().0)
+            .collect();
+        let parsed: Vec<String> = parse_servings_entries(json)
+            .unwrap()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(streamed, parsed);
+        assert_eq!(streamed.len(), 3);
+
+        // A malformed serving ends the stream with an error
+        let mut entries = stream_servings_json(&br#"{"result": [1]}"#[..]);
+        assert!(entries.next().unwrap().is_err());
+        assert!(entries.next().is_none());
+    }
+
+    #[test]
+    fn test_parse_servings_missing_optional_fields() {
+        assert!(parse_servings_response("{}").unwrap().is_empty());
+
+        let json = r#"{"result": [{"track": {"name": "Bare"}, "trackVariation": {}}]}"#;
+        assert!(parse_servings_response(json).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_servings_lenient_skips_bad_servings() {
+        let json = r#"{
+            "result": [
+                { "track": { "name": "Blooming" }, "trackVariation": { "url": "Blooming_Sleep.mp3" } },
+                { "track": { "name": "Tidal" }, "trackVariation": { "url": "Tidal_Relax.mp3" } },
+                { "trackVariation": { "url": "Nameless_Focus.mp3" } },
+                { "track": { "name": "Nothing Remains" }, "trackVariation": { "url": "Nothing_Remains_Focus.mp3" } }
+            ]
+        }"#;
+        assert!(parse_servings_json(json).is_err());
+
+        let ParseResult { mut data, errors } = parse_servings_json_lenient(json);
+        assert_eq!(data.len(), 3);
+        for name in ["Blooming", "Tidal", "Nothing Remains"] {
+            assert!(data.lookup_by_name(name).is_some(), "{name}");
+        }
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, 2);
+        assert!(errors[0].1.contains("track"), "{}", errors[0].1);
+    }
+
+    #[test]
+    fn test_parse_servings_lenient_error_envelope() {
+        for json in [
+            r#"{"error": "Unauthorized"}"#,
+            r#"{"result": null}"#,
+            "not json",
+        ] {
+            let parsed = parse_servings_json_lenient(json);
+            assert!(parsed.data.is_empty() && parsed.errors.is_empty(), "{json}");
+        }
+    }
+
     #[test]
     fn test_parse_servings_response() {
         let json = r#"{
@@ -594,6 +1505,39 @@ mod tests {
         assert_eq!(meta.bpm, Some(120));
         assert_eq!(meta.moods, vec!["Calm", "Chill"]);
         assert_eq!(meta.instruments, vec!["Textural Soundscape"]);
+        assert!(!meta.is_guided_meditation);
+    }
+
+    #[test]
+    fn test_parse_guided_meditation() {
+        let json = r#"{"result": [
+            {"track": {"name": "Morning Calm with Ana", "mentalState": {"displayValue": "Meditate"},
+                "tags": [{"type": "meditationType", "value": "guided"}]},
+             "trackVariation": {"url": "Morning_Calm_Meditate.mp3"}},
+            {"track": {"name": "Breathe with Tom", "mentalState": {"displayValue": "Meditate"},
+                "mobileActivity": {"displayValue": "Guided"}},
+             "trackVariation": {"url": "Breathe_Meditate.mp3"}},
+            {"track": {"name": "Still Water", "mentalState": {"displayValue": "Meditate"},
+                "tags": [{"type": "activity", "value": "Unguided"},
+                         {"type": "meditationType", "value": "unguided"}]},
+             "trackVariation": {"url": "Still_Water_Meditate.mp3"}},
+            {"track": {"name": "Drift Off", "mentalState": {"displayValue": "Sleep"},
+                "tags": [{"type": "activity", "value": "Guided Sleep"}]},
+             "trackVariation": {"url": "Drift_Off_Sleep.mp3"}}
+        ]}"#;
+        let mut tracks = parse_servings_response(json).unwrap();
+
+        for (name, guided) in [
+            ("Morning Calm with Ana", true),
+            ("Breathe with Tom", true),
+            ("Still Water", false),
+            ("Drift Off", false),
+        ] {
+            let meta = tracks.lookup_by_name(name).unwrap();
+            assert_eq!(meta.is_guided_meditation, guided, "{name}");
+        }
+        let still_water = tracks.lookup_by_name("Still Water").unwrap();
+        assert_eq!(still_water.activity.as_deref(), Some("Unguided Meditation"));
     }
 
     #[test]
@@ -658,6 +1602,81 @@ mod tests {
         assert_eq!(meta.activity, Some("Creativity".to_string()));
     }
 
+    #[test]
+    fn test_lookup_by_id_survives_cdn_rename() {
+        let json = r#"{
+            "result": [
+                {
+                    "track": { "name": "Blooming", "id": 48213, "tags": [] },
+                    "trackVariation": {
+                        "url": "Blooming_Sleep_DeepSleep_Atmospheric_60_120bpm_Nrmlzd2_VBR5.mp3"
+                    }
+                },
+                {
+                    "track": { "name": "Waves", "trackId": "trk_77", "tags": [] },
+                    "trackVariation": { "url": "Waves_Relax_VBR5.mp3" }
+                }
+            ]
+        }"#;
+        let mut cache = parse_servings_response(json).unwrap();
+        assert_eq!(cache.lookup_by_id("trk_77").unwrap().name, "Waves");
+
+        // Re-released under a new filename: the URL no longer matches
+        let renamed =
+            "https://audio3.brain.fm/tracks/48213/Blooming_v2_Sleep_Nrmlzd3_VBR5.mp3?token=abc";
+        assert!(cache.lookup_by_url(renamed).is_none());
+        let id = track_id_from_url(renamed).unwrap();
+        assert_eq!(id, "48213");
+        assert_eq!(cache.lookup_by_id(&id).unwrap().name, "Blooming");
+
+        assert_eq!(
+            track_id_from_url("https://audio2.brain.fm/Blooming_VBR5.mp3"),
+            None
+        );
+    }
+
+    fn make_simple_index(version: u32, entries: &[(u64, i64)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&0u32.to_le_bytes()); // payload size (unchecked)
+        data.extend_from_slice(&0u32.to_le_bytes()); // crc (unchecked)
+        data.extend_from_slice(&SIMPLE_INDEX_MAGIC.to_le_bytes());
+        data.extend_from_slice(&version.to_le_bytes());
+        data.extend_from_slice(&(entries.len() as u64).to_le_bytes());
+        data.extend_from_slice(&0u64.to_le_bytes()); // cache size
+        data.extend_from_slice(&0u32.to_le_bytes()); // reason
+        for (hash, last_used) in entries {
+            data.extend_from_slice(&hash.to_le_bytes());
+            data.extend_from_slice(&last_used.to_le_bytes());
+            data.extend_from_slice(&0u64.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn test_parse_simple_index() {
+        let data = make_simple_index(9, &[(0xdead_beef, 10), (0x1234, 20)]);
+        assert_eq!(
+            parse_simple_index(&data),
+            Some(vec![(0xdead_beef, 10), (0x1234, 20)])
+        );
+    }
+
+    #[test]
+    fn test_parse_simple_index_rejects_bad_input() {
+        let mut bad_magic = make_simple_index(9, &[(1, 1)]);
+        bad_magic[8] ^= 0xff;
+        assert_eq!(parse_simple_index(&bad_magic), None);
+
+        assert_eq!(parse_simple_index(&make_simple_index(3, &[(1, 1)])), None);
+
+        // Entry count larger than the data actually present
+        let mut truncated = make_simple_index(9, &[(1, 1), (2, 2)]);
+        truncated.truncate(truncated.len() - 8);
+        assert_eq!(parse_simple_index(&truncated), None);
+
+        assert_eq!(parse_simple_index(&[]), None);
+    }
+
     #[test]
     fn test_find_json_end() {
         assert_eq!(find_json_end(r#"{"a": "b"}"#), Some(10));
@@ -711,18 +1730,161 @@ mod tests {
     fn make_meta(name: &str) -> TrackMetadata {
         TrackMetadata {
             name: name.to_string(),
+            track_id: None,
             genre: None,
             neural_effect: None,
             neural_effect_level: None,
             mental_state: None,
             activity: None,
+            is_guided_meditation: false,
             image_url: None,
             bpm: None,
             moods: vec![],
             instruments: vec![],
+            loaded_at: SystemTime::now(),
         }
     }
 
+    #[test]
+    fn test_image_cache_path() {
+        let dir = Path::new("/cache");
+        let url = "https://cdn.brain.fm/images/focus/Bloom.WEBP?w=512";
+        let path = image_cache_path(dir, url);
+        assert_eq!(path.parent(), Some(Path::new("/cache/images")));
+        assert_eq!(path.extension().unwrap(), "webp");
+        assert_eq!(path, image_cache_path(dir, url));
+        assert_ne!(
+            path,
+            image_cache_path(dir, "https://cdn.brain.fm/images/other.webp")
+        );
+        assert_eq!(
+            image_cache_path(dir, "https://cdn.brain.fm/art/12345").extension(),
+            None
+        );
+    }
+
+    #[cfg(feature = "tokio-async")]
+    #[tokio::test]
+    async fn test_prefetch_images_writes_hashed_files() {
+        let mut server = mockito::Server::new_async().await;
+        let artwork = server
+            .mock("GET", "/art/forest.webp")
+            .with_body("webp bytes")
+            .expect(1)
+            .create_async()
+            .await;
+        let missing = server
+            .mock("GET", "/art/gone.png")
+            .with_status(404)
+            .expect(2) // nothing saved, so retried on the second call
+            .create_async()
+            .await;
+
+        let mut cache = ApiCacheData::new();
+        for (key, image) in [
+            ("a.mp3", "/art/forest.webp"),
+            ("b.mp3", "/art/forest.webp"),
+            ("c.mp3", "/art/gone.png"),
+        ] {
+            let mut meta = make_meta(key);
+            meta.image_url = Some(format!("{}{image}", server.url()));
+            cache.insert(key.to_string(), meta);
+        }
+
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let paths = cache.prefetch_images(dir).await.unwrap();
+        // Already on disk: not downloaded again
+        let again = cache.prefetch_images(dir).await.unwrap();
+
+        let url = format!("{}/art/forest.webp", server.url());
+        let expected = image_cache_path(dir, &url);
+        let written = fs::read(&expected).unwrap();
+        let state = crate::BrainFmState {
+            image_url: Some(url.clone()),
+            ..Default::default()
+        };
+        let local = state.local_image_path(dir);
+
+        assert_eq!(
+            paths,
+            std::collections::HashMap::from([(url, expected.clone())])
+        );
+        assert_eq!(again, paths);
+        assert_eq!(written, b"webp bytes");
+        assert_eq!(local, Some(expected));
+        artwork.assert_async().await;
+        missing.assert_async().await;
+    }
+
+    #[test]
+    fn test_read_api_cache_scans_cache_data() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        let cache_data = root.join("Cache").join("Cache_Data");
+        fs::create_dir_all(&cache_data).unwrap();
+        for i in 0..8 {
+            let header = if i % 2 == 0 {
+                "https://api.brain.fm/v3/users/abc/servings/recent"
+            } else {
+                "https://images.unsplash.com/photo-123"
+            };
+            let body = format!(
+                r#"{header}{{"result":[{{"track":{{"name":"Track {i}"}},"trackVariation":{{"url":"Track_{i}.mp3"}}}}]}}"#
+            );
+            fs::write(cache_data.join(format!("{i:016x}_0")), body).unwrap();
+        }
+        fs::write(cache_data.join("0000000000000000_s"), "stream").unwrap();
+
+        let mut parsed = read_api_cache(root).unwrap();
+        let mut sequential = read_api_cache_sequential(root).unwrap();
+
+        assert_eq!(parsed.len(), 4);
+        assert_eq!(sequential.len(), 4);
+        for i in (0..8).step_by(2) {
+            let name = format!("Track {i}");
+            assert!(parsed.lookup_by_name(&name).is_some());
+            assert!(sequential.lookup_by_name(&name).is_some());
+        }
+        assert!(parsed.lookup_by_name("Track 1").is_none());
+        assert!(read_api_cache(Path::new("/nonexistent"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_read_api_cache_detects_api_version() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        let cache_data = root.join("Cache").join("Cache_Data");
+        fs::create_dir_all(&cache_data).unwrap();
+        let write_entry = |i: usize, version: u32| {
+            let body = format!(
+                r#"https://api.brain.fm/v{version}/users/abc/servings/favorites{{"result":[{{"track":{{"name":"Track {i}"}},"trackVariation":{{"url":"Track_{i}.mp3"}}}}]}}"#
+            );
+            fs::write(cache_data.join(format!("{i:016x}_0")), body).unwrap();
+        };
+
+        write_entry(0, 3);
+        assert_eq!(read_api_cache(root).unwrap().api_version(), Some(3));
+
+        write_entry(1, 4);
+        let mut parsed = read_api_cache(root).unwrap();
+
+        assert_eq!(parsed.api_version(), Some(4));
+        assert!(parsed.lookup_by_name("Track 0").is_some());
+        assert!(parsed.lookup_by_name("Track 1").is_some());
+
+        // Kept through a save and merges
+        let reloaded: ApiCacheData =
+            serde_json::from_str(&serde_json::to_string(&parsed).unwrap()).unwrap();
+        assert_eq!(reloaded.api_version(), Some(4));
+        let mut memory = ApiCacheData::new();
+        assert_eq!(memory.api_version(), None);
+        memory.merge_with_strategy(&parsed, MergeStrategy::MostComplete);
+        assert_eq!(memory.api_version(), Some(4));
+    }
+
     #[test]
     fn test_lru_capacity_enforced() {
         let mut cache = ApiCacheData::new();
@@ -785,6 +1947,156 @@ mod tests {
         a.merge(&b);
         assert!(a.len() <= MAX_CACHE_ENTRIES);
     }
+
+    // --- Merge strategy tests ---
+
+    fn make_rich_meta(name: &str) -> TrackMetadata {
+        TrackMetadata {
+            genre: Some("Piano".to_string()),
+            neural_effect: Some("High Neural Effect".to_string()),
+            image_url: Some("https://example.com/a.jpg".to_string()),
+            moods: vec!["Calm".to_string()],
+            ..make_meta(name)
+        }
+    }
+
+    fn merge_pair(
+        existing: TrackMetadata,
+        incoming: TrackMetadata,
+        strategy: MergeStrategy,
+    ) -> TrackMetadata {
+        let mut a = ApiCacheData::new();
+        a.insert("a.mp3".to_string(), existing);
+        let mut b = ApiCacheData::new();
+        b.insert("a.mp3".to_string(), incoming);
+        a.merge_with_strategy(&b, strategy);
+        assert_eq!(a.len(), 1);
+        a.tracks[0].1.clone()
+    }
+
+    #[test]
+    fn test_completeness_score() {
+        assert_eq!(make_meta("A").completeness_score(), 0);
+        assert_eq!(make_rich_meta("A").completeness_score(), 4);
+    }
+
+    #[test]
+    fn test_statistics() {
+        let empty = ApiCacheData::new().statistics();
+        assert_eq!(empty, CacheStatistics::default());
+
+        let old = SystemTime::UNIX_EPOCH;
+        let mut cache = ApiCacheData::new();
+        cache.insert(
+            "a.mp3".to_string(),
+            TrackMetadata {
+                mental_state: Some("Focus".to_string()),
+                bpm: Some(120),
+                loaded_at: old,
+                ..make_rich_meta("A")
+            },
+        );
+        cache.insert(
+            "b.mp3".to_string(),
+            TrackMetadata {
+                genre: Some("Electronic".to_string()),
+                mental_state: Some("Focus".to_string()),
+                ..make_meta("B")
+            },
+        );
+        cache.insert("c.mp3".to_string(), make_rich_meta("C"));
+
+        let stats = cache.statistics();
+        assert_eq!(stats.total_tracks, 3);
+        assert_eq!(stats.unique_genres, 2);
+        assert_eq!(stats.unique_mental_states, 1);
+        assert_eq!(stats.tracks_with_images, 2);
+        assert_eq!(stats.tracks_with_bpm, 1);
+        // (6 + 2 + 4) populated fields out of 3 * 8
+        assert!((stats.average_completeness - 0.5).abs() < f32::EPSILON);
+        assert_eq!(stats.oldest_loaded_at, Some(old));
+        assert!(stats.newest_loaded_at > Some(old));
+        assert_eq!(
+            stats.to_string(),
+            "3 tracks, 2 genres, avg completeness 0.50"
+        );
+    }
+
+    #[test]
+    fn test_save_load_round_trip_with_schema_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("api_cache.json");
+        let mut cache = ApiCacheData::new();
+        cache.insert("a.mp3".to_string(), make_rich_meta("A"));
+        cache.save(&path).unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(json["schema_version"], CACHE_SCHEMA_VERSION);
+        assert!(json["exported_at"].as_str().unwrap().ends_with("GMT"));
+        let loaded = ApiCacheData::load(&path).unwrap();
+        assert_eq!(loaded.tracks[0].1.name, "A");
+
+        let mut newer = json;
+        newer["schema_version"] = (CACHE_SCHEMA_VERSION + 1).into();
+        std::fs::write(&path, newer.to_string()).unwrap();
+        let err = ApiCacheData::load(&path).unwrap_err();
+        assert!(err.to_string().contains("schema version"));
+    }
+
+    #[test]
+    fn test_merge_most_complete_keeps_richer_entry() {
+        let kept = merge_pair(
+            make_rich_meta("Rich"),
+            make_meta("Sparse"),
+            MergeStrategy::MostComplete,
+        );
+        assert_eq!(kept.name, "Rich");
+
+        let kept = merge_pair(
+            make_meta("Sparse"),
+            make_rich_meta("Rich"),
+            MergeStrategy::MostComplete,
+        );
+        assert_eq!(kept.name, "Rich");
+    }
+
+    #[test]
+    fn test_merge_most_complete_tie_prefers_newer() {
+        let older = TrackMetadata {
+            loaded_at: SystemTime::UNIX_EPOCH,
+            ..make_meta("Old")
+        };
+        let kept = merge_pair(older, make_meta("New"), MergeStrategy::MostComplete);
+        assert_eq!(kept.name, "New");
+    }
+
+    #[test]
+    fn test_merge_keep_existing_and_overwrite_all() {
+        let kept = merge_pair(
+            make_meta("Sparse"),
+            make_rich_meta("Rich"),
+            MergeStrategy::KeepExisting,
+        );
+        assert_eq!(kept.name, "Sparse");
+
+        let kept = merge_pair(
+            make_rich_meta("Rich"),
+            make_meta("Sparse"),
+            MergeStrategy::OverwriteAll,
+        );
+        assert_eq!(kept.name, "Sparse");
+    }
+
+    #[test]
+    fn test_merge_keep_existing_fills_gaps() {
+        let mut a = ApiCacheData::new();
+        a.insert("a.mp3".to_string(), make_meta("A"));
+        let mut b = ApiCacheData::new();
+        b.insert("b.mp3".to_string(), make_meta("B"));
+        a.merge_with_strategy(&b, MergeStrategy::KeepExisting);
+        assert_eq!(a.len(), 2);
+    }
 }
 
 #[cfg(test)]
@@ -795,15 +2107,18 @@ mod proptests {
     fn arb_meta() -> impl Strategy<Value = TrackMetadata> {
         "[a-z]{1,10}".prop_map(|name| TrackMetadata {
             name,
+            track_id: None,
             genre: None,
             neural_effect: None,
             neural_effect_level: None,
             mental_state: None,
             activity: None,
+            is_guided_meditation: false,
             image_url: None,
             bpm: None,
             moods: vec![],
             instruments: vec![],
+            loaded_at: SystemTime::now(),
         })
     }
 
@@ -824,15 +2139,18 @@ mod proptests {
             let mut cache = ApiCacheData::new();
             let meta = TrackMetadata {
                 name: name.clone(),
+                track_id: None,
                 genre: None,
                 neural_effect: None,
                 neural_effect_level: None,
                 mental_state: None,
                 activity: None,
+                is_guided_meditation: false,
                 image_url: None,
                 bpm: None,
                 moods: vec![],
                 instruments: vec![],
+                loaded_at: SystemTime::now(),
             };
             cache.insert(key, meta);
             let found = cache.lookup_by_name(&name);
@@ -6,16 +6,29 @@
 //! The Brain.fm Electron app refreshes the JWT every ~5 minutes.
 //! If the token is expired, we skip the API call and let the caller
 //! fall back to cache scraping.
+//!
+//! On HTTP 429 the client honours `Retry-After`: no API call is made until
+//! the server's reset time has passed.
+//!
+//! `fetch_user_profile` reads the account's subscription tier from the same
+//! API, cached for an hour.
 
 use anyhow::Result;
 use base64::prelude::*;
 use log::{debug, warn};
 use regex::Regex;
-use std::path::Path;
-use std::sync::LazyLock;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use crate::api_cache_reader::{parse_servings_json, ApiCacheData};
+use crate::api_cache_reader::ApiCacheData;
+#[cfg(feature = "streaming-api")]
+use crate::api_cache_reader::{parse_servings_entries, stream_servings_json, TrackMetadata};
+#[cfg(not(feature = "streaming-api"))]
+use crate::api_cache_reader::{servings_cache, ServingsResponse};
+use crate::util::{self, HTTP_AGENT};
+use crate::BrainFmError;
 
 /// Regex for matching JWT tokens
 static JWT_RE: LazyLock<Regex> = LazyLock::new(|| {
@@ -30,13 +43,8 @@ static USER_ID_RE: LazyLock<Regex> =
 static EXP_RE: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r#""exp"\s*:\s*([0-9]+(?:\.[0-9]+)?)"#).unwrap());
 
-/// Shared HTTP agent with connection pooling and timeouts
-static HTTP_AGENT: LazyLock<ureq::Agent> = LazyLock::new(|| {
-    ureq::Agent::config_builder()
-        .timeout_global(Some(Duration::from_secs(10)))
-        .build()
-        .new_agent()
-});
+/// Timeout of each API request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// Safety buffer for token expiry check (seconds).
 /// Tokens expiring within this window are treated as expired to avoid race
@@ -46,7 +54,161 @@ const TOKEN_EXPIRY_BUFFER_SECS: f64 = 30.0;
 /// Retry delays for API calls (in seconds): immediate, 2s, 5s
 const RETRY_DELAYS: &[u64] = &[0, 2, 5];
 
+/// Base URL of the Brain.fm API
+const API_BASE_URL: &str = "https://api.brain.fm";
+
+/// API version used until the cache shows the app calling another one
+/// (`ApiCacheData::api_version`)
+pub const DEFAULT_API_VERSION: u32 = 3;
+
+/// Default for `Config::max_retry_after_secs`
+pub const DEFAULT_MAX_RETRY_AFTER_SECS: u64 = 30;
+
+/// Responses smaller than this are parsed in one go rather than streamed
+#[cfg(feature = "streaming-api")]
+const STREAMING_MIN_BYTES: u64 = 50 * 1024;
+
+/// Cache entries yielded by `fetch_recent_tracks_streaming`
+#[cfg(feature = "streaming-api")]
+type CacheEntries = Box<dyn Iterator<Item = Result<(String, TrackMetadata)>>>;
+
+/// Wait assumed when a 429 response has no usable `Retry-After` header
+const DEFAULT_RETRY_AFTER_SECS: u64 = 60;
+
+/// When the API's rate limit resets, set by the last HTTP 429 response.
+/// No API call is made before this instant.
+static RATE_LIMIT_RESET: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// How long `fetch_user_profile` reuses a fetched profile
+const USER_PROFILE_TTL: Duration = Duration::from_secs(3600);
+
+/// The last profile fetched by `fetch_user_profile`, the app support dir
+/// whose account it belongs to, and when it was fetched
+static USER_PROFILE: Mutex<Option<(PathBuf, UserProfile, Instant)>> = Mutex::new(None);
+
+/// Reads `extract_auth` may make in a burst; each one can scan every
+/// `LevelDB` file (tens of MB)
+const AUTH_READ_BURST: u32 = 3;
+
+/// Rate at which `extract_auth` regains reads (one every 5s)
+const AUTH_READS_PER_SEC: f64 = 0.2;
+
+/// Limits how often `extract_auth` reads `LevelDB`
+static AUTH_READ_BUCKET: LazyLock<Mutex<TokenBucket>> =
+    LazyLock::new(|| Mutex::new(TokenBucket::new(AUTH_READ_BURST, AUTH_READS_PER_SEC)));
+
+/// The last auth `extract_auth` read, and the app support dir it came from.
+/// Returned instead of reading when `AUTH_READ_BUCKET` is empty.
+static LAST_AUTH: LazyLock<Mutex<Option<(PathBuf, AuthInfo)>>> = LazyLock::new(|| Mutex::new(None));
+
+/// Token bucket rate limiter: allows bursts of up to `capacity` calls and
+/// `refill_rate` calls per second on average
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: u32,
+    tokens: f64,
+    last_refill: Instant,
+    /// Tokens added per second
+    refill_rate: f64,
+}
+
+impl TokenBucket {
+    /// A full bucket
+    fn new(capacity: u32, refill_rate: f64) -> Self {
+        Self {
+            capacity,
+            tokens: f64::from(capacity),
+            last_refill: Instant::now(),
+            refill_rate,
+        }
+    }
+
+    /// Add the tokens earned since the last refill, up to `capacity`
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let earned = now.duration_since(self.last_refill).as_secs_f64() * self.refill_rate;
+        self.tokens = (self.tokens + earned).min(f64::from(self.capacity));
+        self.last_refill = now;
+    }
+
+    /// Take a token if one is available
+    fn try_acquire(&mut self) -> bool {
+        self.refill();
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Brain.fm subscription of the signed-in account
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubscriptionTier {
+    Free,
+    Premium,
+    Team,
+    /// Missing or not recognized
+    Unknown,
+}
+
+impl SubscriptionTier {
+    /// Parse the API's subscription type, ignoring case
+    #[must_use]
+    pub fn parse(raw: &str) -> Self {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "free" | "trial" => Self::Free,
+            "premium" | "pro" | "individual" => Self::Premium,
+            "team" | "teams" | "enterprise" => Self::Team,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// The signed-in Brain.fm account, from `GET /v3/users/{userId}`
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserProfile {
+    /// Name shown in the app (empty when the API has none)
+    pub display_name: String,
+
+    /// Subscription the account is on
+    pub subscription_tier: SubscriptionTier,
+
+    /// Whether ADHD mode is turned on for the account
+    pub is_adhd_enabled: bool,
+
+    /// When the account was created, as sent by the API
+    pub joined_at: Option<String>,
+}
+
+/// `GET /v3/users/{userId}` response body
+#[derive(Debug, Deserialize)]
+struct UserResponse {
+    result: UserJson,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UserJson {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    subscription: Option<SubscriptionJson>,
+    #[serde(default)]
+    is_adhd_mode_enabled: bool,
+    #[serde(default)]
+    created_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubscriptionJson {
+    #[serde(rename = "type", default)]
+    tier: Option<String>,
+}
+
 /// Auth credentials extracted from LevelDB
+#[derive(Debug, Clone)]
 struct AuthInfo {
     token: String,
     user_id: String,
@@ -59,13 +221,144 @@ struct AuthInfo {
 ///
 /// Retries up to 3 times with delays `[0s, 2s, 5s]`. On HTTP 401, re-reads
 /// the JWT from LevelDB before retrying (the Electron app may have refreshed it).
-pub fn fetch_recent_tracks(app_support_path: &Path) -> Result<Option<ApiCacheData>> {
+/// On HTTP 429, waits for `Retry-After` and retries if that is at most
+/// `max_retry_after`; otherwise returns `Ok(None)`, as it does for every
+/// call made before the rate limit resets.
+///
+/// `api_version` picks the endpoint (`/v{api_version}/...`), normally
+/// `DEFAULT_API_VERSION` or the one found in the app's cache.
+pub fn fetch_recent_tracks(
+    app_support_path: &Path,
+    api_version: u32,
+    max_retry_after: Duration,
+) -> Result<Option<ApiCacheData>> {
+    fetch_recent_tracks_from(API_BASE_URL, app_support_path, api_version, max_retry_after)
+}
+
+/// `fetch_recent_tracks` against an arbitrary API base URL (for tests)
+fn fetch_recent_tracks_from(
+    api_base_url: &str,
+    app_support_path: &Path,
+    api_version: u32,
+    max_retry_after: Duration,
+) -> Result<Option<ApiCacheData>> {
+    // Build the cache as servings arrive rather than buffering the body
+    #[cfg(feature = "streaming-api")]
+    let data = {
+        let Some(response) =
+            fetch_recent_response(api_base_url, app_support_path, api_version, max_retry_after)?
+        else {
+            return Ok(None);
+        };
+        let mut data = ApiCacheData::new();
+        for entry in servings_entries(response)? {
+            let (key, metadata) = entry?;
+            data.insert(key, metadata);
+        }
+        data
+    };
+    #[cfg(not(feature = "streaming-api"))]
+    let data = {
+        let Some(response) = fetch_recent(
+            api_base_url,
+            app_support_path,
+            api_version,
+            max_retry_after,
+            |url, token| {
+                util::http_get_json::<ServingsResponse>(
+                    url,
+                    util::HttpAuth::Bearer(token),
+                    REQUEST_TIMEOUT,
+                )
+            },
+        )?
+        else {
+            return Ok(None);
+        };
+        servings_cache(&response)
+    };
+
+    debug!("API returned {} tracks", data.len());
+    Ok(Some(data))
+}
+
+/// Fetch recent tracks like `fetch_recent_tracks`, but yield the cache's
+/// (key, metadata) pairs as the response is parsed instead of buffering
+/// it, for accounts whose history runs to megabytes.
+///
+/// Responses with a `Content-Length` under `STREAMING_MIN_BYTES` are parsed
+/// in one go, which is cheaper. Rate limits wait at most
+/// `DEFAULT_MAX_RETRY_AFTER_SECS`.
+#[cfg(feature = "streaming-api")]
+pub fn fetch_recent_tracks_streaming(
+    app_support_path: &Path,
+    api_version: u32,
+) -> Result<Option<impl Iterator<Item = Result<(String, TrackMetadata)>>>> {
+    fetch_recent_response(
+        API_BASE_URL,
+        app_support_path,
+        api_version,
+        Duration::from_secs(DEFAULT_MAX_RETRY_AFTER_SECS),
+    )?
+    .map(servings_entries)
+    .transpose()
+}
+
+/// The cache entries in a servings `response`: streamed with
+/// `stream_servings_json`, unless `Content-Length` says it is small
+#[cfg(feature = "streaming-api")]
+fn servings_entries(response: ureq::http::Response<ureq::Body>) -> Result<CacheEntries> {
+    let content_length = response
+        .headers()
+        .get("Content-Length")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    if content_length.is_some_and(|len| len < STREAMING_MIN_BYTES) {
+        let entries = parse_servings_entries(&response.into_body().read_to_string()?)?;
+        return Ok(Box::new(entries.into_iter().map(Ok)));
+    }
+    Ok(Box::new(stream_servings_json(
+        response.into_body().into_reader(),
+    )))
+}
+
+/// Call `servings/recent` like `fetch_recent`, returning the successful
+/// response unread
+#[cfg(feature = "streaming-api")]
+fn fetch_recent_response(
+    api_base_url: &str,
+    app_support_path: &Path,
+    api_version: u32,
+    max_retry_after: Duration,
+) -> Result<Option<ureq::http::Response<ureq::Body>>> {
+    fetch_recent(
+        api_base_url,
+        app_support_path,
+        api_version,
+        max_retry_after,
+        get_with_token,
+    )
+}
+
+/// Call `servings/recent` with `request(url, token)` and the retries
+/// described on `fetch_recent_tracks`, returning what the first successful
+/// request gave (`None` when every attempt failed or was skipped). A
+/// response that doesn't parse is an error, as retrying won't fix it.
+fn fetch_recent<T>(
+    api_base_url: &str,
+    app_support_path: &Path,
+    api_version: u32,
+    max_retry_after: Duration,
+    request: impl Fn(&str, &str) -> Result<T, BrainFmError>,
+) -> Result<Option<T>> {
     let max_attempts = RETRY_DELAYS.len();
+    // Set after waiting out a rate limit, which replaces the next retry delay
+    let mut waited_for_rate_limit = false;
 
     for attempt in 0..max_attempts {
         // Apply delay (0 on first attempt)
         let delay = RETRY_DELAYS[attempt];
-        if delay > 0 {
+        if delay > 0 && !std::mem::take(&mut waited_for_rate_limit) {
             debug!(
                 "API retry {}/{}: waiting {}s before next attempt",
                 attempt + 1,
@@ -108,11 +401,13 @@ pub fn fetch_recent_tracks(app_support_path: &Path) -> Result<Option<ApiCacheDat
             continue;
         }
 
+        if is_rate_limited() {
+            debug!("API rate limited, skipping call until the limit resets");
+            return Ok(None);
+        }
+
         // 3. Call the API
-        let url = format!(
-            "https://api.brain.fm/v3/users/{}/servings/recent",
-            auth.user_id
-        );
+        let url = servings_url(api_base_url, api_version, &auth.user_id);
 
         debug!(
             "Fetching recent tracks from API (attempt {}/{}): {}",
@@ -121,32 +416,33 @@ pub fn fetch_recent_tracks(app_support_path: &Path) -> Result<Option<ApiCacheDat
             url
         );
 
-        match HTTP_AGENT
-            .get(&url)
-            .header("Authorization", &format!("Bearer {}", auth.token))
-            .header("Accept", "application/json")
-            .call()
-        {
-            Ok(mut response) => {
-                let body = response.body_mut().read_to_string()?;
-                let data = parse_servings_json(&body)?;
-                debug!("API returned {} tracks", data.len());
-                return Ok(Some(data));
+        match request(&url, &auth.token) {
+            Ok(response) => return Ok(Some(response)),
+            Err(BrainFmError::ApiHttpError {
+                status: 429,
+                retry_after,
+                ..
+            }) => {
+                let retry_after =
+                    retry_after.unwrap_or(Duration::from_secs(DEFAULT_RETRY_AFTER_SECS));
+                if !wait_for_rate_limit(retry_after, max_retry_after) {
+                    return Ok(None);
+                }
+                waited_for_rate_limit = true;
             }
-            Err(ureq::Error::StatusCode(401)) => {
+            Err(BrainFmError::ApiHttpError { status: 401, .. }) => {
                 warn!("API returned 401 Unauthorized (attempt {}/{}), token may have just expired — will re-read LevelDB", attempt + 1, max_attempts);
-                // Loop continues → next iteration will re-read LevelDB for a fresh token
-                continue;
+                // The next iteration re-reads LevelDB for a fresh token
             }
-            Err(ureq::Error::StatusCode(code)) => {
+            Err(BrainFmError::ApiHttpError { status: code, .. }) => {
                 warn!(
                     "API returned HTTP {} (attempt {}/{})",
                     code,
                     attempt + 1,
                     max_attempts
                 );
-                continue;
             }
+            Err(e @ BrainFmError::CacheParseError(_)) => return Err(e.into()),
             Err(e) => {
                 warn!(
                     "API request failed (attempt {}/{}): {}",
@@ -154,7 +450,6 @@ pub fn fetch_recent_tracks(app_support_path: &Path) -> Result<Option<ApiCacheDat
                     max_attempts,
                     e
                 );
-                continue;
             }
         }
     }
@@ -166,34 +461,247 @@ pub fn fetch_recent_tracks(app_support_path: &Path) -> Result<Option<ApiCacheDat
     Ok(None)
 }
 
+/// The `servings/recent` URL of `user_id` in API version `api_version`
+fn servings_url(api_base_url: &str, api_version: u32, user_id: &str) -> String {
+    format!(
+        "{api_base_url}/v{api_version}/users/{}/servings/recent",
+        util::percent_encode(user_id)
+    )
+}
+
+/// GET `url` with the bearer `token`, returning the response unread.
+/// Error statuses are `BrainFmError::ApiHttpError`s (see
+/// `util::check_status`).
+fn get_with_token(
+    url: &str,
+    token: &str,
+) -> Result<ureq::http::Response<ureq::Body>, BrainFmError> {
+    util::http_get(url, util::HttpAuth::Bearer(token), REQUEST_TIMEOUT)
+}
+
+/// Fetch the signed-in user's profile, reusing the last one for up to an hour.
+///
+/// Returns `Ok(None)` when there is no usable token, the API is rate
+/// limited or the request fails; profiles are not essential, so there are
+/// no retries.
+pub fn fetch_user_profile(app_support_path: &Path) -> Result<Option<UserProfile>> {
+    fetch_user_profile_cached(API_BASE_URL, app_support_path)
+}
+
+/// `fetch_user_profile` against an arbitrary API base URL (for tests)
+fn fetch_user_profile_cached(
+    api_base_url: &str,
+    app_support_path: &Path,
+) -> Result<Option<UserProfile>> {
+    // Not held across the request, so a slow API does not block other callers
+    let cached = USER_PROFILE
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .as_ref()
+        .filter(|(path, _, fetched_at)| {
+            path == app_support_path && fetched_at.elapsed() < USER_PROFILE_TTL
+        })
+        .map(|(_, profile, _)| profile.clone());
+    if cached.is_some() {
+        return Ok(cached);
+    }
+
+    let profile = fetch_user_profile_from(api_base_url, app_support_path)?;
+    if let Some(ref profile) = profile {
+        *USER_PROFILE
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Some((
+            app_support_path.to_path_buf(),
+            profile.clone(),
+            Instant::now(),
+        ));
+    }
+    Ok(profile)
+}
+
+/// Uncached `fetch_user_profile` against an arbitrary API base URL (for tests)
+fn fetch_user_profile_from(
+    api_base_url: &str,
+    app_support_path: &Path,
+) -> Result<Option<UserProfile>> {
+    let Some(auth) = extract_auth(app_support_path)? else {
+        return Ok(None);
+    };
+    if is_token_expired(&auth.token) || is_rate_limited() {
+        return Ok(None);
+    }
+
+    let url = format!(
+        "{api_base_url}/v3/users/{}",
+        util::percent_encode(&auth.user_id)
+    );
+    debug!("Fetching user profile: {url}");
+    match get_with_token(&url, &auth.token) {
+        Ok(mut response) => {
+            let body = response.body_mut().read_to_string()?;
+            Ok(Some(parse_user_profile(&body)?))
+        }
+        Err(e) => {
+            if let BrainFmError::ApiHttpError {
+                retry_after: Some(retry_after),
+                ..
+            } = e
+            {
+                *RATE_LIMIT_RESET
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner) =
+                    Some(Instant::now() + retry_after);
+            }
+            debug!("User profile request failed: {e}");
+            Ok(None)
+        }
+    }
+}
+
+/// Parse a `GET /v3/users/{userId}` response body
+fn parse_user_profile(json_body: &str) -> Result<UserProfile> {
+    let user = serde_json::from_str::<UserResponse>(json_body)?.result;
+    Ok(UserProfile {
+        display_name: user.name.unwrap_or_default(),
+        subscription_tier: user
+            .subscription
+            .and_then(|subscription| subscription.tier)
+            .map_or(SubscriptionTier::Unknown, |tier| {
+                SubscriptionTier::parse(&tier)
+            }),
+        is_adhd_enabled: user.is_adhd_mode_enabled,
+        joined_at: user.created_at,
+    })
+}
+
+/// Whether a HEAD request for `url` succeeds within `timeout`
+pub(crate) fn is_reachable(url: &str, timeout: Duration) -> bool {
+    HTTP_AGENT
+        .head(url)
+        .config()
+        .timeout_global(Some(timeout))
+        .build()
+        .call()
+        .inspect_err(|e| debug!("{url} is unreachable: {e}"))
+        .is_ok()
+}
+
+/// GET `url` without authentication (e.g. track artwork on the CDN)
+#[cfg(feature = "tokio-async")]
+pub(crate) fn download(url: &str) -> Result<Vec<u8>> {
+    let mut response = HTTP_AGENT.get(url).call()?;
+    Ok(response.body_mut().read_to_vec()?)
+}
+
+/// Record a rate limit lasting `retry_after` and wait it out if it is at most
+/// `max_retry_after`. Returns whether the caller should retry.
+fn wait_for_rate_limit(retry_after: Duration, max_retry_after: Duration) -> bool {
+    *RATE_LIMIT_RESET
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(Instant::now() + retry_after);
+
+    if retry_after > max_retry_after {
+        warn!(
+            "API returned 429 Too Many Requests, retry after {}s exceeds the {}s limit — skipping API until then",
+            retry_after.as_secs(),
+            max_retry_after.as_secs()
+        );
+        return false;
+    }
+
+    warn!(
+        "API returned 429 Too Many Requests, waiting {}s before retrying",
+        retry_after.as_secs()
+    );
+    std::thread::sleep(retry_after);
+    true
+}
+
+/// Whether the API is rate limited, i.e. a 429 response's reset time has not
+/// passed yet. While this is `true`, `fetch_recent_tracks` makes no requests.
+pub fn is_rate_limited() -> bool {
+    RATE_LIMIT_RESET
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .is_some_and(|reset| Instant::now() < reset)
+}
+
 /// Extract JWT access token and user ID from LevelDB's `persist:auth`.
 ///
 /// The Brain.fm Electron app stores its Redux auth state in LevelDB with the key
 /// `persist:auth`. The value contains a JSON object with `token` and `userId` fields.
+///
+/// Reads are rate limited by `AUTH_READ_BUCKET`: when it is empty, the auth
+/// last read from the same directory is returned instead.
 fn extract_auth(app_support_path: &Path) -> Result<Option<AuthInfo>> {
+    let can_read = AUTH_READ_BUCKET
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .try_acquire();
+    let mut last_auth = LAST_AUTH
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    if !can_read {
+        if let Some((_, auth)) = last_auth
+            .as_ref()
+            .filter(|(path, _)| path == app_support_path)
+        {
+            debug!("LevelDB auth reads rate limited, reusing the last auth");
+            return Ok(Some(auth.clone()));
+        }
+    }
+
+    let auth = read_auth(app_support_path)?;
+    if let Some(auth) = &auth {
+        *last_auth = Some((app_support_path.to_path_buf(), auth.clone()));
+    }
+    Ok(auth)
+}
+
+/// `extract_auth` without the rate limit
+fn read_auth(app_support_path: &Path) -> Result<Option<AuthInfo>> {
     let leveldb_path = app_support_path.join("Local Storage").join("leveldb");
 
     if !leveldb_path.exists() {
         return Ok(None);
     }
 
-    // Read strings from LevelDB files (same approach as leveldb_reader)
-    let leveldb_content = crate::util::read_leveldb_strings(&leveldb_path)?;
-    let content = leveldb_content;
+    // Search LevelDB files one at a time (same approach as leveldb_reader),
+    // stopping once a valid token and the user ID have been found
+    let mut token: Option<String> = None;
+    let mut expired_token: Option<String> = None;
+    let mut user_id: Option<String> = None;
+
+    for content in crate::util::iter_leveldb_strings(&leveldb_path) {
+        let content = content?;
+
+        // Prefer the last non-expired token (most recent in file order)
+        let tokens: Vec<&str> = JWT_RE.find_iter(&content).map(|m| m.as_str()).collect();
+        if token.is_none() {
+            token = tokens
+                .iter()
+                .rev() // Check newest first (last in file = most recent write)
+                .find(|t| !is_token_expired(t))
+                .map(|t| t.to_string());
+        }
+        if let Some(&last) = tokens.last() {
+            expired_token = Some(last.to_string());
+        }
+
+        if user_id.is_none() {
+            user_id = USER_ID_RE
+                .captures(&content)
+                .and_then(|c| c.get(1))
+                .map(|m| m.as_str().to_string());
+        }
 
-    // Collect all JWT tokens, prefer the last non-expired one (most recent in file order)
-    let all_tokens: Vec<&str> = JWT_RE.find_iter(&content).map(|m| m.as_str()).collect();
-    let token = all_tokens
-        .iter()
-        .rev() // Check newest first (last in file = most recent write)
-        .find(|t| !is_token_expired(t))
-        .or_else(|| all_tokens.last()) // If all expired, use the most recent anyway
-        .map(|t| t.to_string());
+        if token.is_some() && user_id.is_some() {
+            break;
+        }
+    }
 
-    let user_id = USER_ID_RE
-        .captures(&content)
-        .and_then(|c| c.get(1))
-        .map(|m| m.as_str().to_string());
+    // If all tokens are expired, use the most recent anyway
+    let token = token.or(expired_token);
 
     match (token, user_id) {
         (Some(t), Some(u)) => Ok(Some(AuthInfo {
@@ -248,6 +756,298 @@ fn is_token_expired(token: &str) -> bool {
 mod tests {
     use super::*;
 
+    /// Held by tests that depend on `RATE_LIMIT_RESET`, which is global
+    static RATE_LIMIT_LOCK: Mutex<()> = Mutex::new(());
+
+    /// Create an app support dir whose LevelDB holds a valid token for `user_id`
+    fn app_dir_with_auth(user_id: &str) -> tempfile::TempDir {
+        let app_dir = tempfile::tempdir().unwrap();
+        let leveldb = app_dir.path().join("Local Storage").join("leveldb");
+        std::fs::create_dir_all(&leveldb).unwrap();
+
+        let header = BASE64_URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256","typ":"JWT"}"#);
+        let payload =
+            BASE64_URL_SAFE_NO_PAD.encode(r#"{"_id":"test","exp":9999999999,"iat":9999999699}"#);
+        std::fs::write(
+            leveldb.join("000003.log"),
+            format!(
+                // redux-persist stores each value JSON-encoded a second time
+                r#"persist:auth {{"token":"\"{header}.{payload}.fakesig\"","userId":"\"{user_id}\""}}"#
+            ),
+        )
+        .unwrap();
+        app_dir
+    }
+
+    #[test]
+    fn test_rate_limit_waits_for_retry_after() {
+        let _lock = RATE_LIMIT_LOCK
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let temp = app_dir_with_auth("user123");
+        let app_dir = temp.path();
+        let mut server = mockito::Server::new();
+        let path = "/v3/users/user123/servings/recent";
+        let limited = server
+            .mock("GET", path)
+            .with_status(429)
+            .with_header("Retry-After", "2")
+            .expect(1)
+            .create();
+
+        let limits_api = {
+            let server_url = server.url();
+            let app_dir = app_dir.to_path_buf();
+            std::thread::spawn(move || {
+                let start = Instant::now();
+                let result = fetch_recent_tracks_from(
+                    &server_url,
+                    &app_dir,
+                    DEFAULT_API_VERSION,
+                    Duration::from_secs(30),
+                );
+                (result.map(|data| data.map(|d| d.len())), start.elapsed())
+            })
+        };
+
+        // Serve the retry successfully once the first request has been limited
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !limited.matched() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(is_rate_limited());
+        let ok = server
+            .mock("GET", path)
+            .with_status(200)
+            .with_body(r#"{"result": []}"#)
+            .expect(1)
+            .create();
+
+        let (result, elapsed) = limits_api.join().unwrap();
+
+        assert_eq!(result.unwrap(), Some(0));
+        assert!(
+            elapsed >= Duration::from_secs(2) && elapsed < Duration::from_secs(4),
+            "waited {elapsed:?}"
+        );
+        assert!(!is_rate_limited());
+        limited.assert();
+        ok.assert();
+    }
+
+    #[cfg(feature = "streaming-api")]
+    #[test]
+    fn test_servings_entries_streams_large_responses() {
+        let _lock = RATE_LIMIT_LOCK
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let temp = app_dir_with_auth("user456");
+        let app_dir = temp.path();
+        let servings = |count: usize| {
+            let servings: Vec<String> = (0..count)
+                .map(|i| {
+                    format!(
+                        r#"{{"track":{{"name":"Track {i}","tags":[{{"type":"genre","value":"{}"}}]}},"trackVariation":{{"url":"Track_{i}.mp3"}}}}"#,
+                        "Piano ".repeat(100)
+                    )
+                })
+                .collect();
+            format!(r#"{{"result": [{}]}}"#, servings.join(","))
+        };
+        let mut server = mockito::Server::new();
+        let path = "/v3/users/user456/servings/recent";
+
+        // Multi-megabyte: streamed
+        let large = servings(4000);
+        assert!(large.len() > 2_000_000);
+        let mock = server.mock("GET", path).with_body(&large).create();
+        let response = fetch_recent_response(
+            &server.url(),
+            app_dir,
+            DEFAULT_API_VERSION,
+            Duration::from_secs(1),
+        )
+        .unwrap()
+        .unwrap();
+        let keys: Vec<String> = servings_entries(response)
+            .unwrap()
+            .map(|entry| entry.unwrap().0)
+            .collect();
+        assert_eq!(keys.len(), 4000);
+        assert_eq!(keys[3999], "Track_3999.mp3");
+        mock.remove();
+
+        // Under `STREAMING_MIN_BYTES`: parsed in one go, same entries
+        let small = servings(3);
+        let mock = server.mock("GET", path).with_body(&small).create();
+        let data = fetch_recent_tracks_from(
+            &server.url(),
+            app_dir,
+            DEFAULT_API_VERSION,
+            Duration::from_secs(1),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(data.len(), 3);
+        mock.assert();
+    }
+
+    #[test]
+    fn test_fetch_recent_tracks_uses_api_version() {
+        let _lock = RATE_LIMIT_LOCK
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let temp = app_dir_with_auth("user789");
+        let app_dir = temp.path();
+        assert_eq!(
+            servings_url(API_BASE_URL, DEFAULT_API_VERSION, "user 7"),
+            "https://api.brain.fm/v3/users/user%207/servings/recent"
+        );
+        let mut server = mockito::Server::new();
+        let v4 = server
+            .mock("GET", "/v4/users/user789/servings/recent")
+            .with_body(
+                r#"{"result":[{"track":{"name":"Tidal"},"trackVariation":{"url":"Tidal.mp3"}}]}"#,
+            )
+            .expect(1)
+            .create();
+
+        let data = fetch_recent_tracks_from(&server.url(), app_dir, 4, Duration::from_secs(1));
+
+        assert_eq!(data.unwrap().map(|data| data.len()), Some(1));
+        v4.assert();
+    }
+
+    #[test]
+    fn test_token_bucket_limits_calls_per_second() {
+        let mut bucket = TokenBucket::new(3, 1.0);
+        let allowed = (0..10).filter(|_| bucket.try_acquire()).count();
+        assert_eq!(allowed, 3);
+
+        // A second later, one more token has been earned
+        bucket.last_refill -= Duration::from_secs(1);
+        assert!(bucket.try_acquire());
+        assert!(!bucket.try_acquire());
+
+        // Idle time never fills the bucket past its capacity
+        bucket.last_refill -= Duration::from_secs(60);
+        let allowed = (0..10).filter(|_| bucket.try_acquire()).count();
+        assert_eq!(allowed, 3);
+    }
+
+    #[test]
+    fn test_extract_auth_reuses_last_auth_when_limited() {
+        let temp = app_dir_with_auth("user789");
+        let app_dir = temp.path();
+        let first = extract_auth(app_dir).unwrap().unwrap();
+
+        // Drain the bucket, then change the stored user: the cached auth wins
+        while AUTH_READ_BUCKET.lock().unwrap().try_acquire() {}
+        let leveldb = app_dir.join("Local Storage").join("leveldb");
+        std::fs::remove_file(leveldb.join("000003.log")).unwrap();
+        let limited = extract_auth(app_dir).unwrap().unwrap();
+
+        assert_eq!(first.user_id, "user789");
+        assert_eq!(limited.user_id, "user789");
+        assert_eq!(limited.token, first.token);
+    }
+
+    #[test]
+    fn test_parse_user_profile_tiers() {
+        for (raw, tier) in [
+            ("free", SubscriptionTier::Free),
+            ("Premium", SubscriptionTier::Premium),
+            ("TEAM", SubscriptionTier::Team),
+            ("lifetime", SubscriptionTier::Unknown),
+        ] {
+            let json = format!(r#"{{"result": {{"subscription": {{"type": "{raw}"}}}}}}"#);
+            assert_eq!(parse_user_profile(&json).unwrap().subscription_tier, tier);
+        }
+
+        let bare = parse_user_profile(r#"{"result": {}}"#).unwrap();
+        assert_eq!(bare.subscription_tier, SubscriptionTier::Unknown);
+        assert_eq!(bare.display_name, "");
+        assert!(!bare.is_adhd_enabled);
+        assert!(parse_user_profile("{}").is_err());
+    }
+
+    #[test]
+    fn test_fetch_user_profile_from_mock_api() {
+        let _lock = RATE_LIMIT_LOCK
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let temp = app_dir_with_auth("user456");
+        let app_dir = temp.path();
+        let mut server = mockito::Server::new();
+        let profile = server
+            .mock("GET", "/v3/users/user456")
+            .match_header(
+                "Authorization",
+                mockito::Matcher::Regex("^Bearer eyJ".into()),
+            )
+            .with_body(
+                r#"{"result": {
+                    "name": "Ada",
+                    "subscription": {"type": "premium"},
+                    "isAdhdModeEnabled": true,
+                    "createdAt": "2023-04-01T12:00:00Z"
+                }}"#,
+            )
+            .expect(1)
+            .create();
+
+        let result = fetch_user_profile_from(&server.url(), app_dir);
+        let missing = fetch_user_profile_from(&server.url(), Path::new("/nonexistent"));
+
+        assert_eq!(
+            result.unwrap(),
+            Some(UserProfile {
+                display_name: "Ada".into(),
+                subscription_tier: SubscriptionTier::Premium,
+                is_adhd_enabled: true,
+                joined_at: Some("2023-04-01T12:00:00Z".into()),
+            })
+        );
+        assert_eq!(missing.unwrap(), None);
+        profile.assert();
+    }
+
+    #[test]
+    fn test_fetch_user_profile_cache_is_per_app_dir() {
+        let _lock = RATE_LIMIT_LOCK
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let first_dir = app_dir_with_auth("first");
+        let second_dir = app_dir_with_auth("second");
+        let mut server = mockito::Server::new();
+        let first = server
+            .mock("GET", "/v3/users/first")
+            .with_body(r#"{"result": {"name": "Ada"}}"#)
+            .expect(1)
+            .create();
+        let second = server
+            .mock("GET", "/v3/users/second")
+            .with_body(r#"{"result": {"name": "Grace"}}"#)
+            .expect(1)
+            .create();
+
+        let name = |dir: &Path| {
+            fetch_user_profile_cached(&server.url(), dir)
+                .unwrap()
+                .unwrap()
+                .display_name
+        };
+        let names = [
+            name(first_dir.path()),
+            name(first_dir.path()),
+            name(second_dir.path()),
+        ];
+
+        assert_eq!(names, ["Ada", "Ada", "Grace"]);
+        first.assert();
+        second.assert();
+    }
+
     #[test]
     fn test_is_token_expired_with_past_token() {
         // Create a fake JWT with exp in the past (exp: 1000000000 = Sep 2001)
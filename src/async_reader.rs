@@ -0,0 +1,189 @@
+//! Async reading for tokio runtimes
+//!
+//! Enabled with the `tokio-async` feature. `AsyncBrainFmReader` runs a
+//! reader's blocking `read_state()` on tokio's blocking thread pool, and
+//! `BrainFmStream` polls one on a `tokio::time::Interval`, yielding a state
+//! only when it changes (`BrainFmState::meaningful_change`).
+//!
+//! See `examples/async_stream.rs` for a stream driving presence updates.
+
+use crate::{BrainFmReader, BrainFmReaderTrait, BrainFmState};
+use anyhow::Result;
+use futures_core::Stream;
+use log::debug;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use tokio::time::{Interval, MissedTickBehavior};
+
+/// A reader whose `read_state()` can be awaited without blocking the
+/// runtime. Clones share the same reader.
+pub struct AsyncBrainFmReader<R = BrainFmReader> {
+    reader: Arc<Mutex<R>>,
+}
+
+impl<R> Clone for AsyncBrainFmReader<R> {
+    fn clone(&self) -> Self {
+        Self {
+            reader: Arc::clone(&self.reader),
+        }
+    }
+}
+
+impl<R: BrainFmReaderTrait + Send + 'static> AsyncBrainFmReader<R> {
+    /// Wrap `reader`, e.g. a `BrainFmReader`
+    #[must_use]
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader: Arc::new(Mutex::new(reader)),
+        }
+    }
+
+    /// `BrainFmReaderTrait::read_state` on the blocking thread pool
+    pub async fn read_state(&self) -> Result<BrainFmState> {
+        let reader = Arc::clone(&self.reader);
+        tokio::task::spawn_blocking(move || {
+            reader
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .read_state()
+        })
+        .await?
+    }
+}
+
+/// A `read_state()` call in progress
+type PendingRead = Pin<Box<dyn Future<Output = Result<BrainFmState>> + Send>>;
+
+/// Stream of states read every `interval`, yielding the first state and then
+/// each one with a `BrainFmState::meaningful_change` from the last yielded.
+///
+/// Read errors are logged and polling carries on, so the stream never ends.
+/// A read that takes longer than `interval` delays the next one rather
+/// than bunching reads up.
+pub struct BrainFmStream<R = BrainFmReader> {
+    reader: AsyncBrainFmReader<R>,
+    interval: Interval,
+    pending: Option<PendingRead>,
+    last_state: Option<BrainFmState>,
+}
+
+impl<R: BrainFmReaderTrait + Send + 'static> BrainFmStream<R> {
+    /// Poll `reader` every `interval`, starting right away. Must be called
+    /// from within a tokio runtime.
+    #[must_use]
+    pub fn new(reader: AsyncBrainFmReader<R>, interval: Duration) -> Self {
+        let mut interval = tokio::time::interval(interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        Self {
+            reader,
+            interval,
+            pending: None,
+            last_state: None,
+        }
+    }
+}
+
+impl<R: BrainFmReaderTrait + Send + 'static> Stream for BrainFmStream<R> {
+    type Item = BrainFmState;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<BrainFmState>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(read) = this.pending.as_mut() {
+                let result = ready!(read.as_mut().poll(cx));
+                this.pending = None;
+                match result {
+                    Ok(state)
+                        if this
+                            .last_state
+                            .as_ref()
+                            .is_none_or(|last| state.meaningful_change(last)) =>
+                    {
+                        this.last_state = Some(state.clone());
+                        return Poll::Ready(Some(state));
+                    }
+                    Ok(_) => {}
+                    Err(e) => debug!("Error reading state: {e:#}"),
+                }
+            }
+
+            ready!(this.interval.poll_tick(cx));
+            let reader = this.reader.clone();
+            this.pending = Some(Box::pin(async move { reader.read_state().await }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HealthReport;
+    use std::collections::VecDeque;
+
+    /// Replays `states`, then fails every read
+    struct ScriptedReader(VecDeque<BrainFmState>);
+
+    impl BrainFmReaderTrait for ScriptedReader {
+        fn read_state(&mut self) -> Result<BrainFmState> {
+            self.0
+                .pop_front()
+                .ok_or_else(|| anyhow::anyhow!("no more states"))
+        }
+
+        fn is_running(&self) -> bool {
+            true
+        }
+
+        fn check_health(&self) -> HealthReport {
+            unreachable!("not used by the stream")
+        }
+    }
+
+    fn playing(track: &str) -> BrainFmState {
+        BrainFmState {
+            is_playing: true,
+            mode: Some("Focus".into()),
+            track_name: Some(track.into()),
+            ..Default::default()
+        }
+    }
+
+    async fn next(stream: &mut BrainFmStream<ScriptedReader>) -> Option<BrainFmState> {
+        std::future::poll_fn(|cx| Pin::new(&mut *stream).poll_next(cx)).await
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stream_yields_only_changes() {
+        let blooming = playing("Blooming");
+        let tidal = playing("Tidal");
+        let script = [
+            blooming.clone(),
+            // Only the session time moved on: not a change
+            BrainFmState {
+                session_time: Some("0:00:05".into()),
+                ..blooming.clone()
+            },
+            tidal.clone(),
+            tidal.clone(),
+            blooming.clone(),
+        ];
+        let reader = AsyncBrainFmReader::new(ScriptedReader(script.into()));
+        let mut stream = BrainFmStream::new(reader.clone(), Duration::from_secs(5));
+
+        let start = tokio::time::Instant::now();
+        assert_eq!(next(&mut stream).await, Some(blooming.clone()));
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        assert_eq!(next(&mut stream).await, Some(tidal));
+        assert_eq!(start.elapsed(), Duration::from_secs(10));
+        assert_eq!(next(&mut stream).await, Some(blooming));
+        assert_eq!(start.elapsed(), Duration::from_secs(20));
+
+        // The script is used up: reads fail and nothing more is yielded
+        let more = tokio::time::timeout(Duration::from_secs(60), next(&mut stream)).await;
+        assert!(more.is_err());
+        assert!(reader.read_state().await.is_err());
+    }
+}
@@ -0,0 +1,2008 @@
+//! Brain.fm Discord Rich Presence - System Tray Application
+//!
+//! This binary provides Discord Rich Presence integration for Brain.fm.
+//! It runs as a system tray application without a visible window.
+//!
+//! Architecture:
+//! - Main thread: runs winit event loop for proper macOS menu handling
+//! - Background thread: reads Brain.fm state and updates Discord
+//! - Web outputs thread (`web_outputs`): passes the state on to web APIs
+
+mod tray;
+#[cfg(any(
+    feature = "zulip",
+    feature = "pushover",
+    feature = "linear",
+    feature = "activitywatch",
+    feature = "toggl"
+))]
+mod web_outputs;
+
+use anyhow::{Context, Result};
+use brainfm_presence::config::{Config, ConfigWatcher};
+use brainfm_presence::history::BrainFmStateHistory;
+#[cfg(all(feature = "editors", unix))]
+use brainfm_presence::integrations::editors::{HelixStatusSocket, KakouneSession};
+#[cfg(feature = "mqtt")]
+use brainfm_presence::integrations::home_assistant::HomeAssistantPublisher;
+#[cfg(all(feature = "plasma", target_os = "linux"))]
+use brainfm_presence::integrations::plasma::PlasmaDataEngineServer;
+#[cfg(feature = "stream-deck")]
+use brainfm_presence::integrations::stream_deck::{self, StreamDeckServer};
+use brainfm_presence::logging::{RotatingJsonFile, TeeLogger};
+use brainfm_presence::platform::{self, BrainFmInstallType, PathOverrideRegistry};
+use brainfm_presence::util::{mode_emoji, write_file_atomic};
+use brainfm_presence::{
+    aggregate_states, reports, BrainFmMode, BrainFmReader, BrainFmReaderTrait, BrainFmState,
+    StateDebouncer,
+};
+use chrono::{Local, NaiveDate};
+use discord_rich_presence::{activity, DiscordIpc, DiscordIpcClient};
+use image::imageops::{self, FilterType};
+use image::RgbaImage;
+use log::{debug, error, info, warn};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::io::Write as _;
+use std::net::UdpSocket;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tray::TrayManager;
+use tray_icon::{menu::MenuEvent, Icon};
+use winit::application::ApplicationHandler;
+use winit::event::WindowEvent;
+use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
+use winit::window::WindowId;
+
+/// Discord Application ID
+const DISCORD_APP_ID: &str = "1468727702675521547";
+
+/// How long `wait-for` waits without `--timeout`, in seconds
+const WAIT_FOR_DEFAULT_TIMEOUT_SECS: u64 = 60;
+
+/// Exponential backoff parameters for Discord reconnection
+const BACKOFF_BASE_SECS: u64 = 5;
+const BACKOFF_MAX_SECS: u64 = 300;
+
+/// Embedded tray icon, used when no size-specific icon is available
+const TRAY_ICON_PNG: &[u8] = include_bytes!("../../../assets/tray_icon.png");
+
+/// Square tray icon sizes (in pixels) used as-is without resizing
+const SUPPORTED_ICON_SIZES: [u32; 4] = [16, 22, 32, 64];
+
+/// Size the tray icon is resized to when its dimensions are not supported
+const DEFAULT_ICON_SIZE: u32 = 32;
+
+/// Windows draws notification area icons at the small icon size
+const WINDOWS_ICON_SIZE: u32 = 16;
+
+/// Events sent from background thread to main thread
+#[derive(Debug, Clone)]
+enum UserEvent {
+    /// Status text and recent tracks from background thread
+    StateUpdate(String, BrainFmStateHistory),
+    /// Menu event from tray
+    MenuEvent(tray_icon::menu::MenuEvent),
+}
+
+/// Application state
+struct App {
+    tray: TrayManager,
+    shutdown_tx: mpsc::Sender<()>,
+}
+
+impl ApplicationHandler<UserEvent> for App {
+    fn resumed(&mut self, _event_loop: &ActiveEventLoop) {
+        // Not used for tray-only app
+    }
+
+    fn window_event(&mut self, _event_loop: &ActiveEventLoop, _id: WindowId, _event: WindowEvent) {
+        // No windows in tray-only app
+    }
+
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        match event {
+            UserEvent::StateUpdate(status, history) => {
+                self.tray.update_with_state_and_history(&status, &history);
+            }
+            UserEvent::MenuEvent(menu_event) => {
+                if menu_event.id.0 == tray::MENU_ID_QUIT {
+                    info!("Quit requested, shutting down...");
+                    // Signal background thread to stop
+                    let _ = self.shutdown_tx.send(());
+                    event_loop.exit();
+                } else {
+                    self.tray.copy_recent_track(&menu_event.id);
+                }
+            }
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    init_logging(&Config::load());
+
+    // Hide from Dock and Cmd+Tab — tray-only mode
+    #[cfg(target_os = "macos")]
+    {
+        use objc2::MainThreadMarker;
+        use objc2_app_kit::{NSApplication, NSApplicationActivationPolicy};
+
+        // Safe: main() always runs on the main thread
+        let mtm = unsafe { MainThreadMarker::new_unchecked() };
+        let app = NSApplication::sharedApplication(mtm);
+        app.setActivationPolicy(NSApplicationActivationPolicy::Accessory);
+    }
+
+    info!("🧠 Brain.fm Discord Rich Presence starting...");
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let outputs = ExtraOutputs::from_args(&args)?;
+    // Force the Chrome PWA data directory instead of auto-detecting
+    let install_type = if args.iter().any(|arg| arg == "--pwa") {
+        BrainFmInstallType::ChromePWA
+    } else {
+        BrainFmInstallType::Unknown
+    };
+    // Read another account's Brain.fm, or a browser profile directly,
+    // instead of detecting one
+    let data_dir = match flag_value(&args, "--user")? {
+        Some(user) => Some(platform::get_brainfm_data_dir_for_user(&user)?),
+        None => flag_value(&args, "--windows-pwa-path")?.map(PathBuf::from),
+    };
+
+    // Markdown summary of a day's listening, from the history log
+    if args.first().map(String::as_str) == Some("daily-report") {
+        return run_daily_report(&args);
+    }
+
+    // Move the learned track cache between machines
+    if let Some(command @ ("export-cache" | "import-cache")) = args.first().map(String::as_str) {
+        return run_cache_transfer(command, args.get(1), install_type, data_dir);
+    }
+
+    // Point the reader at a non-standard install, or show what it finds
+    if args.first().map(String::as_str) == Some("configure") {
+        return run_configure(&args);
+    }
+
+    // The presence that would be sent to Discord right now
+    if args.first().map(String::as_str) == Some("dump-activity") {
+        if args.iter().any(|arg| arg == "--all-users") {
+            return run_dump_all_users_activity();
+        }
+        return run_dump_activity(install_type, data_dir);
+    }
+
+    // Block until Brain.fm plays (a given mode), for scripts
+    if args.first().map(String::as_str) == Some("wait-for") {
+        return run_wait_for(&args, install_type, data_dir);
+    }
+
+    // Add the Brain.fm session to commit messages
+    if args.first().map(String::as_str) == Some("install-git-hook") {
+        return run_install_git_hook(&args);
+    }
+
+    // Keep a headless Kakoune session's modeline showing the mode
+    #[cfg(all(feature = "editors", unix))]
+    if args.first().map(String::as_str) == Some("kakoune-daemon") {
+        return run_kakoune_daemon(install_type, data_dir);
+    }
+
+    // Headless modes for scripts: print the state and skip the tray
+    if args.iter().any(|arg| arg == "--once") {
+        return run_once(&args, install_type, data_dir);
+    }
+    if let Some(format_state) = watch_format(&args) {
+        return run_watch(install_type, data_dir, format_state);
+    }
+    if args
+        .iter()
+        .any(|arg| arg == "--i3blocks" || arg == "--polybar")
+    {
+        return run_once(&args, install_type, data_dir);
+    }
+
+    // A presence per account, each through its own Discord application
+    let all_users_app_ids = if args.iter().any(|arg| arg == "--all-users") {
+        let app_ids = flag_value(&args, "--discord-app-ids")?
+            .context("--all-users needs --discord-app-ids <id,id,...>")?;
+        Some(app_ids.split(',').map(|id| id.trim().to_string()).collect())
+    } else {
+        None
+    };
+
+    // Create event loop with custom user events
+    let event_loop = EventLoop::<UserEvent>::with_user_event()
+        .build()
+        .context("Failed to create event loop")?;
+
+    // Set control flow to wait (efficient, no busy loop)
+    event_loop.set_control_flow(ControlFlow::Wait);
+
+    // Create event loop proxy for sending events from background thread
+    let proxy = event_loop.create_proxy();
+
+    // Set up menu event handler to forward to event loop
+    let menu_proxy = event_loop.create_proxy();
+    MenuEvent::set_event_handler(Some(move |event| {
+        let _ = menu_proxy.send_event(UserEvent::MenuEvent(event));
+    }));
+
+    // Create tray icon and menu
+    let icon = if cfg!(windows) {
+        load_icon_at_size(WINDOWS_ICON_SIZE)?
+    } else {
+        load_icon()?
+    };
+    let tray = TrayManager::new(icon)?;
+
+    info!("✅ System tray initialized");
+
+    // Create shutdown channel
+    let (shutdown_tx, shutdown_rx) = mpsc::channel::<()>();
+
+    // Spawn background thread for Brain.fm reading and Discord updates
+    let worker = thread::spawn(move || match all_users_app_ids {
+        Some(app_ids) => run_all_users_worker(proxy, shutdown_rx, outputs, app_ids),
+        None => run_background_worker(proxy, shutdown_rx, outputs, install_type, data_dir),
+    });
+
+    // Create app handler
+    let mut app = App { tray, shutdown_tx };
+
+    // Run the event loop (this blocks and handles all events properly)
+    info!("🔄 Running event loop...");
+    event_loop.run_app(&mut app).context("Event loop error")?;
+    // The worker's outputs clean up as it exits (e.g. stopping a running
+    // Toggl entry), so wait for it
+    drop(app);
+    let _ = worker.join();
+    Ok(())
+}
+
+/// Load the tray icon, resizing it to 32×32 if its dimensions are unsupported
+fn load_icon() -> Result<Icon> {
+    icon_from_image(prepare_icon_image(&icon_png_bytes(), None)?)
+}
+
+/// Load the tray icon resized to exactly `size`×`size` pixels
+fn load_icon_at_size(size: u32) -> Result<Icon> {
+    icon_from_image(prepare_icon_image(&icon_png_bytes(), Some(size))?)
+}
+
+fn icon_from_image(image: RgbaImage) -> Result<Icon> {
+    let (width, height) = image.dimensions();
+    Icon::from_rgba(image.into_raw(), width, height).context("Failed to create icon from RGBA data")
+}
+
+/// Raw PNG bytes for the tray icon.
+///
+/// On macOS, a pre-sized `tray_icon_32.png` in the app bundle's `Resources`
+/// directory takes precedence over the embedded generic icon.
+fn icon_png_bytes() -> Cow<'static, [u8]> {
+    #[cfg(target_os = "macos")]
+    {
+        let bundled = std::env::current_exe().ok().and_then(|exe| {
+            let path = exe.parent()?.parent()?.join("Resources/tray_icon_32.png");
+            std::fs::read(path).ok()
+        });
+        if let Some(bytes) = bundled {
+            debug!("Using bundled tray_icon_32.png");
+            return Cow::Owned(bytes);
+        }
+    }
+
+    Cow::Borrowed(TRAY_ICON_PNG)
+}
+
+/// Decode a PNG and size it for the tray.
+///
+/// With `size`, the image is resized to exactly `size`×`size`. Without it,
+/// square images in `SUPPORTED_ICON_SIZES` are kept as-is and anything else
+/// is resized to `DEFAULT_ICON_SIZE`.
+fn prepare_icon_image(png: &[u8], size: Option<u32>) -> Result<RgbaImage> {
+    if size == Some(0) {
+        anyhow::bail!("Tray icon size must be non-zero");
+    }
+
+    let image = image::load_from_memory(png)
+        .context("Failed to load tray icon image")?
+        .into_rgba8();
+
+    let (width, height) = image.dimensions();
+    let target = match size {
+        Some(size) => size,
+        None if width == height && SUPPORTED_ICON_SIZES.contains(&width) => width,
+        None => DEFAULT_ICON_SIZE,
+    };
+
+    let image = if (width, height) == (target, target) {
+        image
+    } else {
+        imageops::resize(&image, target, target, FilterType::Lanczos3)
+    };
+
+    debug!("Tray icon: {target}x{target} (source {width}x{height})");
+    Ok(image)
+}
+
+/// Background worker that reads Brain.fm state and updates Discord
+#[allow(clippy::needless_pass_by_value)] // Both params are consumed by the thread closure
+fn run_background_worker(
+    proxy: winit::event_loop::EventLoopProxy<UserEvent>,
+    shutdown_rx: mpsc::Receiver<()>,
+    outputs: ExtraOutputs,
+    install_type: BrainFmInstallType,
+    data_dir: Option<PathBuf>,
+) {
+    let config = Config::load();
+
+    let mut reader = match create_reader(install_type, data_dir, &config) {
+        Ok(r) => r,
+        Err(e) => {
+            error!("Failed to create Brain.fm reader: {e}");
+            error!("Make sure Brain.fm is installed and has been run at least once.");
+            return;
+        }
+    };
+
+    // Resume from the state saved before the last shutdown or crash
+    if let Some(ref path) = config.state_file_path {
+        if let Err(e) = reader.restore_state(path) {
+            debug!("No previous state restored: {e:#}");
+        }
+    }
+
+    // Apply edits to the config file without a restart
+    let (config_tx, config_rx) = mpsc::channel();
+    let _config_watcher = Config::default_path().and_then(|path| {
+        ConfigWatcher::watch(&path, config_tx)
+            .map_err(|e| debug!("Not watching the config file: {e:#}"))
+            .ok()
+    });
+
+    run_worker_loop(
+        &mut reader,
+        config,
+        &config_rx,
+        &proxy,
+        &shutdown_rx,
+        outputs,
+        None,
+    );
+}
+
+/// `--all-users`: run a worker for each account with Brain.fm data, each
+/// showing its presence through the next Discord application in `app_ids`.
+/// The first account keeps the extra outputs, state file and history.
+#[allow(clippy::needless_pass_by_value)] // Both params are owned by the worker thread
+fn run_all_users_worker(
+    proxy: winit::event_loop::EventLoopProxy<UserEvent>,
+    shutdown_rx: mpsc::Receiver<()>,
+    outputs: ExtraOutputs,
+    app_ids: Vec<String>,
+) {
+    let config = Config::load();
+    let users = platform::discover_all_data_dirs();
+    if users.is_empty() {
+        error!("No Brain.fm data directory found for any user account");
+        return;
+    }
+    if users.len() > app_ids.len() {
+        warn!(
+            "{} accounts have Brain.fm data but only {} Discord application IDs were given; \
+             skipping the rest",
+            users.len(),
+            app_ids.len()
+        );
+    }
+
+    let mut outputs = Some(outputs);
+    let mut workers = Vec::new();
+    for ((user, data_dir), app_id) in users.into_iter().zip(app_ids) {
+        let mut reader = match create_reader(BrainFmInstallType::Unknown, Some(data_dir), &config) {
+            Ok(reader) => reader,
+            Err(e) => {
+                warn!("Skipping {user}: {e:#}");
+                continue;
+            }
+        };
+        let first = workers.is_empty();
+        let adjust = move |config: &mut Config| {
+            config.discord_app_id = Some(app_id.clone());
+            if !first {
+                config.state_file_path = None;
+                config.history_csv_path = None;
+            }
+        };
+        let mut user_config = config.clone();
+        adjust(&mut user_config);
+        let outputs = outputs.take().unwrap_or_default();
+        let (worker_shutdown_tx, worker_shutdown_rx) = mpsc::channel();
+        let proxy = proxy.clone();
+
+        info!("👥 Showing {user}'s Brain.fm presence");
+        let worker = thread::spawn(move || {
+            // Reloaded configs keep this account's adjustments
+            let (config_tx, config_rx) = mpsc::channel();
+            let (raw_tx, raw_rx) = mpsc::channel::<Config>();
+            thread::spawn(move || {
+                for mut config in raw_rx {
+                    adjust(&mut config);
+                    if config_tx.send(config).is_err() {
+                        break;
+                    }
+                }
+            });
+            let _config_watcher = Config::default_path().and_then(|path| {
+                ConfigWatcher::watch(&path, raw_tx)
+                    .map_err(|e| debug!("Not watching the config file: {e:#}"))
+                    .ok()
+            });
+
+            run_worker_loop(
+                &mut reader,
+                user_config,
+                &config_rx,
+                &proxy,
+                &worker_shutdown_rx,
+                outputs,
+                Some(&user),
+            );
+        });
+        workers.push((worker_shutdown_tx, worker));
+    }
+
+    let _ = shutdown_rx.recv();
+    for (worker_shutdown_tx, worker) in workers {
+        let _ = worker_shutdown_tx.send(());
+        let _ = worker.join();
+    }
+}
+
+/// Create a Brain.fm reader for the data directory chosen on the command
+/// line, configured from `config`
+fn create_reader(
+    install_type: BrainFmInstallType,
+    data_dir: Option<PathBuf>,
+    config: &Config,
+) -> Result<BrainFmReader> {
+    let demo = config.demo_mode || std::env::args().any(|arg| arg == "--demo");
+    let reader = match data_dir {
+        Some(data_dir) => BrainFmReader::with_data_dir(data_dir),
+        None => BrainFmReader::with_install_type(install_type),
+    };
+    let mut reader = match reader {
+        // Demo states need no Brain.fm install, so showcases work anywhere
+        Err(e) if demo => {
+            debug!("No Brain.fm data directory for demo mode: {e:#}");
+            BrainFmReader::demo()
+        }
+        reader => reader?,
+    };
+    reader.set_demo_mode(demo);
+    reader.apply_config(config);
+    Ok(reader)
+}
+
+/// `export-cache <file>` / `import-cache <file>`: copy the saved API cache to
+/// a file, or merge one into it (keeping tracks already cached here)
+fn run_cache_transfer(
+    command: &str,
+    file: Option<&String>,
+    install_type: BrainFmInstallType,
+    data_dir: Option<PathBuf>,
+) -> Result<()> {
+    let file =
+        PathBuf::from(file.with_context(|| format!("Usage: brainfm-presence {command} <file>"))?);
+    let config = Config::load();
+    let state_path = config
+        .state_file_path
+        .clone()
+        .context("No state_file_path configured, so there is no saved cache")?;
+
+    let mut reader = create_reader(install_type, data_dir, &config)?;
+    if let Err(e) = reader.restore_state(&state_path) {
+        debug!("No previous state restored: {e:#}");
+    }
+
+    if command == "export-cache" {
+        reader.export_memory_cache(&file)?;
+    } else {
+        reader.import_cache(&file)?;
+        reader.save_memory_cache(&state_path)?;
+    }
+    info!(
+        "{command}: {} ({})",
+        file.display(),
+        reader.cache_statistics()
+    );
+    Ok(())
+}
+
+/// `configure set-data-dir <path>`: save a data directory override for this
+/// platform. `configure detect`: print the override and every auto-detected
+/// data directory.
+fn run_configure(args: &[String]) -> Result<()> {
+    let registry_path = PathOverrideRegistry::default_path()
+        .context("No home directory to store path overrides in")?;
+    let platform = std::env::consts::OS;
+    match args.get(1).map(String::as_str) {
+        Some("set-data-dir") => {
+            let data_dir = args
+                .get(2)
+                .context("Usage: brainfm-presence configure set-data-dir <path>")?;
+            let data_dir = std::fs::canonicalize(data_dir)
+                .with_context(|| format!("No such data directory {data_dir}"))?;
+            anyhow::ensure!(
+                data_dir.is_dir(),
+                "{} is not a directory",
+                data_dir.display()
+            );
+
+            let mut registry = PathOverrideRegistry::load();
+            registry.set(platform, data_dir.clone());
+            registry.save(&registry_path)?;
+            info!(
+                "configure: {platform} data directory set to {} ({})",
+                data_dir.display(),
+                registry_path.display()
+            );
+        }
+        Some("detect") => {
+            let registry = PathOverrideRegistry::load();
+            let show = |path: Option<&Path>| {
+                path.map_or_else(
+                    || "not found".to_string(),
+                    |path| path.display().to_string(),
+                )
+            };
+            println!("Platform: {platform}");
+            println!(
+                "Override ({}): {}",
+                registry_path.display(),
+                show(registry.get(platform))
+            );
+            for (install_type, data_dir) in platform::detected_data_dirs() {
+                println!("{install_type:?}: {}", show(data_dir.as_deref()));
+            }
+        }
+        _ => anyhow::bail!("Usage: brainfm-presence configure <set-data-dir <path> | detect>"),
+    }
+    Ok(())
+}
+
+/// `daily-report [--date YYYY-MM-DD] [--stdout]`: summarize one day of the
+/// history log as Markdown, written to `~/brainfm-<date>.md` or printed
+fn run_daily_report(args: &[String]) -> Result<()> {
+    let date = match flag_value(args, "--date")? {
+        Some(date) => NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+            .with_context(|| format!("Invalid --date {date}, expected YYYY-MM-DD"))?,
+        None => Local::now().date_naive(),
+    };
+    let config = Config::load();
+    let path = config
+        .history_csv_path
+        .context("No history_csv_path configured, so there is no history")?;
+    let history = BrainFmStateHistory::load_csv_with_archive(&path)?.for_date(date);
+    anyhow::ensure!(!history.is_empty(), "No history recorded on {date}");
+
+    let report = reports::to_markdown_report(&history);
+    if args.iter().any(|arg| arg == "--stdout") {
+        print!("{report}");
+        return Ok(());
+    }
+    let output = dirs::home_dir()
+        .context("No home directory to write the report to")?
+        .join(format!("brainfm-{date}.md"));
+    write_file_atomic(&output, report.as_bytes())?;
+    info!("daily-report: {}", output.display());
+    Ok(())
+}
+
+/// `wait-for [--mode <mode>] [--timeout <secs>]`: wait until Brain.fm is
+/// playing, in `mode` if given, then print the state as JSON. Fails once the
+/// timeout (default `WAIT_FOR_DEFAULT_TIMEOUT_SECS`) runs out.
+fn run_wait_for(
+    args: &[String],
+    install_type: BrainFmInstallType,
+    data_dir: Option<PathBuf>,
+) -> Result<()> {
+    let mode = flag_value(args, "--mode")?.map(|mode| BrainFmMode::parse(&mode));
+    let timeout = match flag_value(args, "--timeout")? {
+        Some(secs) => secs
+            .parse()
+            .with_context(|| format!("Invalid --timeout {secs}, expected seconds"))?,
+        None => WAIT_FOR_DEFAULT_TIMEOUT_SECS,
+    };
+    let mut reader = create_reader(install_type, data_dir, &Config::load())?;
+    let state = reader.poll_until(
+        |state| wait_for_matches(state, mode.as_ref()),
+        Duration::from_secs(timeout),
+    )?;
+    println!("{}", serde_json::to_string(&state)?);
+    Ok(())
+}
+
+/// Whether `state` is playing in `mode` (as its mode or activity), or at
+/// all when no mode is given
+fn wait_for_matches(state: &BrainFmState, mode: Option<&BrainFmMode>) -> bool {
+    let Some(mode) = mode else {
+        return state.is_playing;
+    };
+    let is_mode = |value: &Option<String>| {
+        value.as_deref().is_some_and(|value| {
+            BrainFmMode::parse(value)
+                .as_str()
+                .eq_ignore_ascii_case(mode.as_str())
+        })
+    };
+    state.is_playing && (is_mode(&state.mode) || is_mode(&state.activity))
+}
+
+/// `kakoune-daemon`: run the `brainfm` Kakoune session
+/// (`integrations::editors::KakouneSession`) and update its `brainfm_mode`
+/// option as the state changes, until killed
+#[cfg(all(feature = "editors", unix))]
+fn run_kakoune_daemon(install_type: BrainFmInstallType, data_dir: Option<PathBuf>) -> Result<()> {
+    let config = Config::load();
+    let mut reader = create_reader(install_type, data_dir, &config)?;
+    let mut session = KakouneSession::start()?;
+    loop {
+        match reader.read_state() {
+            Ok(state) => session.update(&state),
+            Err(e) => debug!("Error reading state: {e}"),
+        }
+        thread::sleep(Duration::from_secs(config.update_interval_secs));
+    }
+}
+
+/// Marks hooks written by `install-git-hook`, which it may overwrite
+const GIT_HOOK_MARKER: &str = "# Brain.fm Presence:";
+
+/// `install-git-hook [--repo-path <path>] [--global]`: write the
+/// `prepare-commit-msg` hook (`BrainFmState::to_git_prepare_commit_msg_hook`)
+/// into the hooks directory of the repository at `--repo-path` (default: the
+/// current directory), or with `--global` into the global `core.hooksPath`.
+/// Setting that for the user would stop git running every repository's own
+/// `.git/hooks`, so when it is unset this prints the manual steps instead.
+/// An existing hook that this command didn't write is left alone.
+fn run_install_git_hook(args: &[String]) -> Result<()> {
+    let hooks_dir = if args.iter().any(|arg| arg == "--global") {
+        let configured = git_output(&["config", "--global", "--get", "core.hooksPath"], None)
+            .ok()
+            .filter(|path| !path.is_empty());
+        let Some(path) = configured else {
+            anyhow::bail!(
+                "No global core.hooksPath is set. Setting one makes git skip every \
+                 repository's .git/hooks, so install the hook per repository with \
+                 --repo-path, or opt in with `git config --global core.hooksPath \
+                 ~/.config/git/hooks` and run install-git-hook --global again"
+            );
+        };
+        match path.strip_prefix("~/") {
+            Some(rest) => dirs::home_dir()
+                .context("No home directory for core.hooksPath")?
+                .join(rest),
+            None => PathBuf::from(path),
+        }
+    } else {
+        let repo =
+            flag_value(args, "--repo-path")?.map_or_else(|| PathBuf::from("."), PathBuf::from);
+        let hooks = git_output(&["rev-parse", "--git-path", "hooks"], Some(&repo))
+            .with_context(|| format!("{} is not a git repository", repo.display()))?;
+        repo.join(hooks)
+    };
+
+    let hook = hooks_dir.join("prepare-commit-msg");
+    if let Ok(existing) = std::fs::read_to_string(&hook) {
+        anyhow::ensure!(
+            existing.contains(GIT_HOOK_MARKER),
+            "{} already exists; remove it or add the Brain.fm footer to it by hand",
+            hook.display()
+        );
+    }
+    write_file_atomic(
+        &hook,
+        BrainFmState::to_git_prepare_commit_msg_hook().as_bytes(),
+    )?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&hook, std::fs::Permissions::from_mode(0o755))
+            .with_context(|| format!("Failed to make {} executable", hook.display()))?;
+    }
+    info!("install-git-hook: {}", hook.display());
+    Ok(())
+}
+
+/// Run `git args` (in `dir` if given) and return its trimmed stdout
+fn git_output(args: &[&str], dir: Option<&Path>) -> Result<String> {
+    let mut command = std::process::Command::new("git");
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    let output = command.args(args).output().context("Failed to run git")?;
+    anyhow::ensure!(
+        output.status.success(),
+        "git {} failed: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// `--once`: print the current state and exit, as an `AppleScript` record with
+/// `--applescript`, an i3blocks block with `--i3blocks` (which implies
+/// `--once`), a Raycast line with `--raycast` or `--raycast-menu-bar`, a
+/// Polybar line with `--polybar` (also implying `--once`), or as JSON
+/// otherwise (`--json`)
+fn run_once(
+    args: &[String],
+    install_type: BrainFmInstallType,
+    data_dir: Option<PathBuf>,
+) -> Result<()> {
+    let config = Config::load();
+    let mut reader = create_reader(install_type, data_dir, &config)?;
+    let state = reader.read_state()?;
+    if args.iter().any(|arg| arg == "--applescript") {
+        println!("{}", state.to_applescript_record());
+    } else if args.iter().any(|arg| arg == "--i3blocks") {
+        println!("{}", state.to_i3blocks_json());
+    } else if args.iter().any(|arg| arg == "--raycast") {
+        println!("{}", state.to_raycast_script_output());
+    } else if args.iter().any(|arg| arg == "--raycast-menu-bar") {
+        println!("{}", state.to_raycast_menu_bar_output());
+    } else if args.iter().any(|arg| arg == "--polybar") {
+        println!(
+            "{}",
+            state.to_polybar_format(config.polybar_click_command.as_deref())
+        );
+    } else {
+        println!("{}", serde_json::to_string(&state)?);
+    }
+    Ok(())
+}
+
+/// `dump-activity`: print the Discord activity JSON for the current state
+/// (`null` when paused), as if the session had just started
+fn run_dump_activity(install_type: BrainFmInstallType, data_dir: Option<PathBuf>) -> Result<()> {
+    let config = Config::load();
+    let mut reader = create_reader(install_type, data_dir, &config)?;
+    let state = reader.read_state()?;
+    let activity = state.to_discord_activity_json(unix_now(), &config);
+    println!("{}", serde_json::to_string_pretty(&activity)?);
+    Ok(())
+}
+
+/// `dump-activity --all-users`: print one Discord activity JSON for every
+/// account with Brain.fm data, led by the most confident playing state and
+/// showing "N users focusing" when several play (see `aggregate_states`)
+fn run_dump_all_users_activity() -> Result<()> {
+    let config = Config::load();
+    let users = platform::discover_all_data_dirs();
+    anyhow::ensure!(
+        !users.is_empty(),
+        "No Brain.fm data directory found for any user account"
+    );
+    let mut states = Vec::new();
+    for (user, data_dir) in users {
+        match create_reader(BrainFmInstallType::Unknown, Some(data_dir), &config)
+            .and_then(|mut reader| reader.read_state())
+        {
+            Ok(state) => states.push(state),
+            Err(e) => warn!("Skipping {user}: {e:#}"),
+        }
+    }
+    let activity = aggregate_states(&states).to_discord_activity_json(unix_now(), &config);
+    println!("{}", serde_json::to_string_pretty(&activity)?);
+    Ok(())
+}
+
+/// Formats a state for `run_watch`
+type FormatState = Box<dyn FnMut(&BrainFmState, &Config) -> String>;
+
+/// The output of the watch mode picked in `args`, if any: `--i3blocks
+/// --watch`, `--polybar-watch`, `--applescript-watch` or `--dry-run`
+fn watch_format(args: &[String]) -> Option<FormatState> {
+    let has = |flag: &str| args.iter().any(|arg| arg == flag);
+    if has("--i3blocks") && has("--watch") {
+        // i3bar separates blocks with a blank line
+        Some(Box::new(|state: &BrainFmState, _: &Config| {
+            format!("{}\n\n", state.to_i3blocks_json())
+        }))
+    } else if has("--polybar-watch") {
+        // Polybar's `tail = true` shows the latest line
+        Some(Box::new(|state: &BrainFmState, config: &Config| {
+            format!(
+                "{}\n",
+                state.to_polybar_format(config.polybar_click_command.as_deref())
+            )
+        }))
+    } else if has("--applescript-watch") {
+        Some(Box::new(|state: &BrainFmState, _: &Config| {
+            format!("{}\n", state.to_applescript_record())
+        }))
+    } else if has("--dry-run") {
+        // Like the tray worker, restart the elapsed time on each new track
+        let mut session: Option<(Option<String>, i64)> = None;
+        Some(Box::new(move |state: &BrainFmState, config: &Config| {
+            let start = match session {
+                Some((ref track, start)) if *track == state.track_name => start,
+                _ => unix_now(),
+            };
+            session = Some((state.track_name.clone(), start));
+            format!("{}\n", state.to_discord_activity_json(start, config))
+        }))
+    } else {
+        None
+    }
+}
+
+/// `--applescript-watch` / `--i3blocks --watch` / `--polybar-watch` /
+/// `--dry-run`: print `format_state(state)` whenever the state changes,
+/// until stdout is closed
+fn run_watch(
+    install_type: BrainFmInstallType,
+    data_dir: Option<PathBuf>,
+    mut format_state: impl FnMut(&BrainFmState, &Config) -> String,
+) -> Result<()> {
+    let config = Config::load();
+    let mut reader = create_reader(install_type, data_dir, &config)?;
+    let mut stdout = std::io::stdout().lock();
+    let mut last_state: Option<BrainFmState> = None;
+
+    loop {
+        match reader.read_state() {
+            Ok(state)
+                if last_state
+                    .as_ref()
+                    .is_none_or(|old| state_changed(old, &state)) =>
+            {
+                // Readers consume output line by line, so flush every record
+                let written = write!(stdout, "{}", format_state(&state, &config))
+                    .and_then(|()| stdout.flush());
+                if written.is_err() {
+                    return Ok(());
+                }
+                last_state = Some(state);
+            }
+            Ok(_) => {}
+            Err(e) => debug!("Error reading state: {e}"),
+        }
+        thread::sleep(Duration::from_secs(config.update_interval_secs));
+    }
+}
+
+/// Poll `reader` every `update_interval_secs` and mirror its state to the
+/// tray, Discord and any `ExtraOutputs` until a shutdown signal arrives.
+///
+/// Configs received on `config_updates` are applied at the start of the
+/// next cycle.
+fn run_worker_loop(
+    reader: &mut impl BrainFmReaderTrait,
+    mut config: Config,
+    config_updates: &mpsc::Receiver<Config>,
+    proxy: &winit::event_loop::EventLoopProxy<UserEvent>,
+    shutdown_rx: &mpsc::Receiver<()>,
+    mut outputs: ExtraOutputs,
+    user: Option<&str>,
+) {
+    // Try to connect to Discord
+    info!("🔗 Connecting to Discord...");
+    // Shared with the connect closure so a reloaded config can change it
+    let app_id = Rc::new(RefCell::new(config.discord_app_id.clone()));
+    let mut discord = DiscordConnectionManager::new({
+        let app_id = Rc::clone(&app_id);
+        move || create_discord_client(app_id.borrow().as_deref().unwrap_or(DISCORD_APP_ID))
+    });
+
+    if !discord.try_reconnect() {
+        warn!("Discord not available, will retry in background");
+    }
+
+    // State changes are logged for `daily-report`
+    let (mut history, save_history) = load_history(&config);
+    let mut last_state: Option<BrainFmState> = None;
+    let mut track_start = unix_now();
+    let mut last_track: Option<String> = None;
+    // Whether the last state read was stale, to warn once per stale spell
+    let mut was_stale = false;
+    let mut debouncer = StateDebouncer::new(Duration::from_secs(config.debounce_window_secs));
+
+    loop {
+        if let Some(new_config) = config_updates.try_iter().last() {
+            reader.apply_config(&new_config);
+            if new_config.discord_app_id != config.discord_app_id {
+                info!("Discord application changed, reconnecting...");
+                app_id.replace(new_config.discord_app_id.clone());
+                discord.restart();
+                last_state = None;
+            }
+            debouncer.window = Duration::from_secs(new_config.debounce_window_secs);
+            config = new_config;
+            info!("Config reloaded");
+        }
+
+        // Reconnect to Discord if disconnected and the backoff delay has elapsed
+        discord.try_reconnect();
+
+        // Read current Brain.fm state and send status update to main thread
+        let (state, status_text) = read_status(reader, &mut debouncer, &config);
+        let tray_status = match user {
+            Some(user) => format!("{user}: {status_text}"),
+            None => status_text.clone(),
+        };
+        if let Some(ref state) = state {
+            record_history(&mut history, state, &config, save_history);
+        }
+        // Just the entries the tray lists, not the whole log
+        let recent = history
+            .recent_tracks(tray::RECENT_TRACKS)
+            .into_iter()
+            .rev()
+            .cloned()
+            .collect();
+        let _ = proxy.send_event(UserEvent::StateUpdate(tray_status, recent));
+
+        // A changed image (see `with_checked_image`) re-sends the presence
+        if let Some(state) = state.map(|state| with_checked_image(state, &config)) {
+            // Check if track changed - reset timer
+            let current_track = state.track_name.clone();
+            if current_track != last_track {
+                track_start = unix_now();
+                last_track = current_track;
+
+                if let Some(ref path) = config.state_file_path {
+                    if let Err(e) = reader.save_state(path) {
+                        warn!("Failed to save state: {e:#}");
+                    }
+                }
+            }
+
+            outputs.update(&state, &config);
+
+            let staleness = stale_data_secs(&state, &config);
+            if let Some(secs) = staleness {
+                if !was_stale {
+                    warn!(
+                        "Brain.fm data is {secs:.0}s old, clearing the presence until it updates"
+                    );
+                }
+                // Forgetting the last state re-sends the presence once fresh
+                if last_state.take().is_some() && discord.is_connected() {
+                    if let Err(e) = discord.clear_activity() {
+                        warn!("Discord update error: {e}");
+                    }
+                }
+            }
+            was_stale = staleness.is_some();
+
+            // A guessed track (e.g. parsed from a file name) is not worth
+            // replacing a better presence with
+            let low_confidence = state.is_playing && state.confidence_score < config.min_confidence;
+            if low_confidence {
+                debug!(
+                    "Skipping presence update: confidence {:.1} below {:.1}",
+                    state.confidence_score, config.min_confidence
+                );
+            }
+
+            // Update Discord if connected. After a reconnect Discord has
+            // dropped the old presence, so it is always re-sent.
+            if discord.is_connected() && !was_stale && !low_confidence {
+                let should_update = discord.take_needs_full_update()
+                    || match &last_state {
+                        None => true,
+                        Some(last) => state_changed(last, &state),
+                    };
+
+                if should_update {
+                    if let Err(e) = discord.set_activity(&state, track_start, &config) {
+                        warn!("Discord update error: {e}");
+                    } else {
+                        debug!("Updated presence: {status_text}");
+                    }
+                    last_state = Some(state);
+                }
+            }
+        }
+
+        // Sleep for update interval, or until told to shut down (or the
+        // event loop is gone)
+        let interval = Duration::from_secs(config.update_interval_secs);
+        if shutdown_rx.recv_timeout(interval) != Err(mpsc::RecvTimeoutError::Timeout) {
+            break;
+        }
+    }
+    info!("Background worker shutting down...");
+    discord.close();
+}
+
+/// Load the history log, starting a new one if it is missing.
+///
+/// Rows that fail to parse are skipped after copying the file aside, and an
+/// unreadable file is moved aside, so the next save does not lose them.
+/// Returns whether the log may be saved: not when that copy or move failed.
+fn load_history(config: &Config) -> (BrainFmStateHistory, bool) {
+    let Some(path) = config
+        .history_csv_path
+        .as_deref()
+        .filter(|path| path.exists())
+    else {
+        return (BrainFmStateHistory::new(), true);
+    };
+    let mut aside = path.as_os_str().to_owned();
+    aside.push(format!(".corrupt-{}", unix_now()));
+    let aside = PathBuf::from(aside);
+    match BrainFmStateHistory::load_csv(path) {
+        Ok((history, skipped)) if skipped.is_empty() => (history, true),
+        Ok((history, _)) => match std::fs::copy(path, &aside) {
+            Ok(_) => {
+                warn!("Kept a copy of the history as {}", aside.display());
+                (history, true)
+            }
+            Err(e) => {
+                warn!("Not saving history, failed to copy it aside: {e}");
+                (history, false)
+            }
+        },
+        Err(load_err) => match std::fs::rename(path, &aside) {
+            Ok(()) => {
+                warn!(
+                    "Starting a new history, moved the old one to {}: {load_err:#}",
+                    aside.display()
+                );
+                (BrainFmStateHistory::new(), true)
+            }
+            Err(e) => {
+                warn!("Not saving history, failed to move it aside ({e}): {load_err:#}");
+                (BrainFmStateHistory::new(), false)
+            }
+        },
+    }
+}
+
+/// Add `state` to the history (listed under "Recent Tracks" in the tray)
+/// and save the log when it changed, if `history_csv_path` is set and `save`
+fn record_history(
+    history: &mut BrainFmStateHistory,
+    state: &BrainFmState,
+    config: &Config,
+    save: bool,
+) {
+    // Keep only the logged fields; unchanged reads just raise the latest
+    // entry's stability, which is saved with the next change
+    let logged = BrainFmState {
+        is_playing: state.is_playing,
+        mode: state.mode.clone(),
+        activity: state.activity.clone(),
+        track_name: state.track_name.clone(),
+        genre: state.genre.clone(),
+        neural_effect: state.neural_effect.clone(),
+        ..BrainFmState::default()
+    };
+    if !history.push(logged) || !save {
+        return;
+    }
+    if let Some(ref path) = config.history_csv_path {
+        if let Err(e) = history.save_csv(path) {
+            warn!("Failed to save history: {e:#}");
+        }
+    }
+}
+
+/// Current Unix time in seconds, as Discord timestamps expect
+fn unix_now() -> i64 {
+    // Safety: u64 -> i64 wrap is harmless for Unix timestamps until year 292 billion
+    #[allow(clippy::cast_possible_wrap)]
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("system clock before UNIX epoch")
+        .as_secs() as i64;
+    now
+}
+
+/// Log to stderr at `config.log_level` (or as `RUST_LOG` says), and to
+/// `config.log_file` as JSON lines when set
+fn init_logging(config: &Config) {
+    let console = env_logger::Builder::from_env(
+        env_logger::Env::default().default_filter_or(config.log_level.as_str()),
+    )
+    .format_timestamp(None)
+    .build();
+    let file = config
+        .log_file
+        .as_deref()
+        .map(|path| RotatingJsonFile::open(path, config.log_max_size_bytes).map_err(|e| (path, e)));
+    let console_level = console.filter();
+    match file {
+        Some(Ok(file)) => {
+            let logger = TeeLogger::new(console, console_level, file, config.log_level);
+            log::set_max_level(logger.max_level());
+            let _ = log::set_boxed_logger(Box::new(logger));
+        }
+        file => {
+            log::set_max_level(console_level);
+            let _ = log::set_boxed_logger(Box::new(console));
+            if let Some(Err((path, e))) = file {
+                warn!("Not logging to {}: {e:#}", path.display());
+            }
+        }
+    }
+}
+
+/// Extract the value of `flag` given as `<flag> <value>` or `<flag>=<value>`.
+fn flag_value(args: &[String], flag: &str) -> Result<Option<String>> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            return args
+                .next()
+                .cloned()
+                .map(Some)
+                .with_context(|| format!("{flag} requires a value"));
+        }
+        if let Some(value) = arg
+            .strip_prefix(flag)
+            .and_then(|rest| rest.strip_prefix('='))
+        {
+            return Ok(Some(value.to_string()));
+        }
+    }
+    Ok(None)
+}
+
+/// Outputs besides Discord and the tray, each enabled by a command-line flag
+#[derive(Default)]
+struct ExtraOutputs {
+    /// `--osc-host <host:port>`
+    osc: Option<OscBroadcaster>,
+    /// `--html-overlay <output-path>`
+    html_overlay: Option<HtmlOverlayWriter>,
+    /// `--itunes-xml-out <path>`
+    itunes_xml: Option<ItunesXmlWriter>,
+    /// `--stream-deck`
+    #[cfg(feature = "stream-deck")]
+    stream_deck: Option<StreamDeckServer>,
+    /// `--mqtt-broker <host:port>`
+    #[cfg(feature = "mqtt")]
+    home_assistant: Option<HomeAssistantPublisher>,
+    /// The outputs calling web APIs (Zulip, Pushover, Linear, `ActivityWatch`
+    /// and Toggl), on their own thread
+    #[cfg(any(
+        feature = "zulip",
+        feature = "pushover",
+        feature = "linear",
+        feature = "activitywatch",
+        feature = "toggl"
+    ))]
+    web: Option<web_outputs::WebOutputsThread>,
+    /// `--plasma-dbus`
+    #[cfg(all(feature = "plasma", target_os = "linux"))]
+    plasma: Option<PlasmaDataEngineServer>,
+    /// `--helix-socket <path>`
+    #[cfg(all(feature = "editors", unix))]
+    helix: Option<HelixStatusSocket>,
+    /// `enable_macos_focus_mode` in the config rather than a flag
+    #[cfg(target_os = "macos")]
+    focus_mode: platform::macos::FocusModeSwitcher,
+    /// `enable_windows_focus_assist` in the config rather than a flag
+    #[cfg(target_os = "windows")]
+    focus_assist: platform::windows::FocusAssistSwitcher,
+}
+
+impl ExtraOutputs {
+    /// Enable the outputs whose flags are among `args`
+    fn from_args(args: &[String]) -> Result<Self> {
+        Ok(Self {
+            osc: flag_value(args, "--osc-host")?
+                .map(|target| OscBroadcaster::new(&target))
+                .transpose()?,
+            html_overlay: flag_value(args, "--html-overlay")?.map(HtmlOverlayWriter::new),
+            itunes_xml: flag_value(args, "--itunes-xml-out")?.map(ItunesXmlWriter::new),
+            #[cfg(feature = "stream-deck")]
+            stream_deck: args
+                .iter()
+                .any(|arg| arg == "--stream-deck")
+                .then(|| StreamDeckServer::start(("127.0.0.1", stream_deck::STREAM_DECK_PORT)))
+                .transpose()?,
+            #[cfg(feature = "mqtt")]
+            home_assistant: flag_value(args, "--mqtt-broker")?
+                .map(|broker| HomeAssistantPublisher::connect(&broker, "brainfm_presence"))
+                .transpose()?,
+            #[cfg(any(
+                feature = "zulip",
+                feature = "pushover",
+                feature = "linear",
+                feature = "activitywatch",
+                feature = "toggl"
+            ))]
+            web: web_outputs::WebOutputsThread::start(web_outputs::WebOutputs::from_args(args)?),
+            #[cfg(all(feature = "plasma", target_os = "linux"))]
+            plasma: args
+                .iter()
+                .any(|arg| arg == "--plasma-dbus")
+                .then(PlasmaDataEngineServer::start)
+                .transpose()?,
+            #[cfg(all(feature = "editors", unix))]
+            helix: flag_value(args, "--helix-socket")?.map(HelixStatusSocket::new),
+            #[cfg(target_os = "macos")]
+            focus_mode: platform::macos::FocusModeSwitcher::default(),
+            #[cfg(target_os = "windows")]
+            focus_assist: platform::windows::FocusAssistSwitcher::default(),
+        })
+    }
+
+    /// Pass the latest state to every enabled output
+    fn update(&mut self, state: &BrainFmState, config: &Config) {
+        if let Some(osc) = self.osc.as_mut() {
+            osc.send_if_changed(state);
+        }
+        if let Some(overlay) = self.html_overlay.as_mut() {
+            overlay.write_if_changed(state, config.html_overlay_template.as_deref());
+        }
+        if let Some(itunes_xml) = self.itunes_xml.as_mut() {
+            itunes_xml.write_if_changed(state);
+        }
+        #[cfg(feature = "stream-deck")]
+        if let Some(stream_deck) = self.stream_deck.as_ref() {
+            stream_deck.update(state);
+        }
+        #[cfg(feature = "mqtt")]
+        if let Some(home_assistant) = self.home_assistant.as_mut() {
+            home_assistant.update(state);
+        }
+        #[cfg(any(
+            feature = "zulip",
+            feature = "pushover",
+            feature = "linear",
+            feature = "activitywatch",
+            feature = "toggl"
+        ))]
+        if let Some(web) = self.web.as_ref() {
+            web.update(state);
+        }
+        #[cfg(all(feature = "plasma", target_os = "linux"))]
+        if let Some(plasma) = self.plasma.as_ref() {
+            plasma.update(state);
+        }
+        #[cfg(all(feature = "editors", unix))]
+        if let Some(helix) = self.helix.as_mut() {
+            helix.update(state);
+        }
+        #[cfg(target_os = "macos")]
+        if config.enable_macos_focus_mode {
+            self.focus_mode.update(state);
+        }
+        #[cfg(target_os = "windows")]
+        if config.enable_windows_focus_assist {
+            self.focus_assist.update(state);
+        }
+    }
+}
+
+/// Keeps an HTML overlay file (e.g. an OBS Browser Source) in sync with the state
+struct HtmlOverlayWriter {
+    path: PathBuf,
+    /// The HTML last written, to skip rewriting an unchanged overlay
+    last_html: Option<String>,
+}
+
+impl HtmlOverlayWriter {
+    fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        info!("🖼️  Writing HTML overlay to {}", path.display());
+        Self {
+            path,
+            last_html: None,
+        }
+    }
+
+    /// Render `state` and rewrite the overlay file if the HTML changed
+    fn write_if_changed(&mut self, state: &BrainFmState, template: Option<&Path>) {
+        let html = state.to_html_overlay(template);
+        if self.last_html.as_ref() == Some(&html) {
+            return;
+        }
+
+        // Atomic so OBS never reloads a half-written file
+        match write_file_atomic(&self.path, html.as_bytes()) {
+            Ok(()) => self.last_html = Some(html),
+            Err(e) => warn!("Failed to write HTML overlay: {e:#}"),
+        }
+    }
+}
+
+/// Keeps an iTunes Library XML file in sync with the state, for legacy
+/// "now playing" tools
+struct ItunesXmlWriter {
+    path: PathBuf,
+    /// The XML last written, to skip rewriting an unchanged file
+    last_xml: Option<String>,
+}
+
+impl ItunesXmlWriter {
+    fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        info!("🎵 Writing iTunes XML to {}", path.display());
+        Self {
+            path,
+            last_xml: None,
+        }
+    }
+
+    /// Rewrite the file if the state's XML changed
+    fn write_if_changed(&mut self, state: &BrainFmState) {
+        let xml = state.to_itunes_xml_document();
+        if self.last_xml.as_ref() == Some(&xml) {
+            return;
+        }
+
+        match write_file_atomic(&self.path, xml.as_bytes()) {
+            Ok(()) => self.last_xml = Some(xml),
+            Err(e) => warn!("Failed to write iTunes XML: {e:#}"),
+        }
+    }
+}
+
+/// Sends the Brain.fm state as an OSC bundle over UDP whenever it changes
+struct OscBroadcaster {
+    socket: UdpSocket,
+    target: String,
+    last_state: Option<BrainFmState>,
+}
+
+impl OscBroadcaster {
+    /// Bind an ephemeral UDP socket for sending to `target` (`host:port`).
+    ///
+    /// Broadcast is enabled so `target` may be a broadcast address.
+    fn new(target: &str) -> Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0").context("Failed to bind OSC socket")?;
+        socket
+            .set_broadcast(true)
+            .context("Failed to enable UDP broadcast")?;
+        info!("🎛️  Sending OSC to {target}");
+        Ok(Self {
+            socket,
+            target: target.to_string(),
+            last_state: None,
+        })
+    }
+
+    /// Send `state` unless it matches the last state sent
+    fn send_if_changed(&mut self, state: &BrainFmState) {
+        if self
+            .last_state
+            .as_ref()
+            .is_some_and(|last| !state_changed(last, state))
+        {
+            return;
+        }
+
+        match self
+            .socket
+            .send_to(&state.to_osc_bundle(), self.target.as_str())
+        {
+            Ok(_) => self.last_state = Some(state.clone()),
+            Err(e) => warn!("OSC send to {} failed: {e}", self.target),
+        }
+    }
+}
+
+/// Discord IPC connection status tracked by `DiscordConnectionManager`
+enum ConnectionState<C> {
+    Connected(C),
+    Disconnected {
+        next_retry: Instant,
+        retry_count: u32,
+    },
+}
+
+/// Owns the Discord IPC client and reconnects with exponential backoff.
+///
+/// The delay after each failed attempt starts at `BACKOFF_BASE_SECS` and
+/// doubles up to `BACKOFF_MAX_SECS`, so a closed Discord is not hammered
+/// with connection attempts.
+struct DiscordConnectionManager<C, F> {
+    state: ConnectionState<C>,
+    connect: F,
+    /// Set after (re)connecting; the next update must send the full presence
+    needs_full_update: bool,
+}
+
+impl<C, F: FnMut() -> Option<C>> DiscordConnectionManager<C, F> {
+    /// Create a disconnected manager whose first `try_reconnect()` connects immediately
+    fn new(connect: F) -> Self {
+        Self {
+            state: ConnectionState::Disconnected {
+                next_retry: Instant::now(),
+                retry_count: 0,
+            },
+            connect,
+            needs_full_update: false,
+        }
+    }
+
+    fn is_connected(&self) -> bool {
+        matches!(self.state, ConnectionState::Connected(_))
+    }
+
+    /// Connect if disconnected and the retry delay has elapsed.
+    ///
+    /// Returns whether a client is connected afterwards.
+    fn try_reconnect(&mut self) -> bool {
+        self.try_reconnect_at(Instant::now())
+    }
+
+    fn try_reconnect_at(&mut self, now: Instant) -> bool {
+        let ConnectionState::Disconnected {
+            next_retry,
+            retry_count,
+        } = self.state
+        else {
+            return true;
+        };
+        if now < next_retry {
+            return false;
+        }
+
+        if let Some(client) = (self.connect)() {
+            if retry_count == 0 {
+                info!("✅ Connected to Discord!");
+            } else {
+                info!("✅ Reconnected to Discord after {retry_count} failed attempt(s)");
+            }
+            self.state = ConnectionState::Connected(client);
+            self.needs_full_update = true;
+            true
+        } else {
+            let retry_count = retry_count + 1;
+            let delay = reconnect_delay(retry_count);
+            info!(
+                "Discord connection attempt {retry_count} failed, retrying in {}s",
+                delay.as_secs()
+            );
+            self.state = ConnectionState::Disconnected {
+                next_retry: now + delay,
+                retry_count,
+            };
+            false
+        }
+    }
+
+    /// Drop the client after an IPC error; the next `try_reconnect()` retries immediately.
+    fn mark_disconnected(&mut self) {
+        if self.is_connected() {
+            info!("Discord connection lost");
+        }
+        self.state = ConnectionState::Disconnected {
+            next_retry: Instant::now(),
+            retry_count: 0,
+        };
+    }
+
+    /// Whether a full presence update is due (consumes the flag).
+    fn take_needs_full_update(&mut self) -> bool {
+        std::mem::take(&mut self.needs_full_update)
+    }
+}
+
+impl<C: DiscordIpc, F: FnMut() -> Option<C>> DiscordConnectionManager<C, F> {
+    /// Update the presence; an IPC error marks the connection as lost.
+    fn set_activity(
+        &mut self,
+        state: &BrainFmState,
+        session_start: i64,
+        config: &Config,
+    ) -> Result<()> {
+        let ConnectionState::Connected(ref mut client) = self.state else {
+            anyhow::bail!("Discord is not connected");
+        };
+        let result = update_discord_presence(client, state, session_start, config);
+        if result.is_err() {
+            self.mark_disconnected();
+        }
+        result
+    }
+
+    /// Clear the presence; an IPC error marks the connection as lost.
+    fn clear_activity(&mut self) -> Result<()> {
+        let ConnectionState::Connected(ref mut client) = self.state else {
+            anyhow::bail!("Discord is not connected");
+        };
+        let result = client.clear_activity().map_err(Into::into);
+        if result.is_err() {
+            self.mark_disconnected();
+        }
+        result
+    }
+
+    /// Close the connection and connect again right away, e.g. under a new
+    /// Discord application
+    fn restart(&mut self) {
+        self.close();
+        self.state = ConnectionState::Disconnected {
+            next_retry: Instant::now(),
+            retry_count: 0,
+        };
+    }
+
+    /// Clear the presence and close the connection (on shutdown).
+    fn close(&mut self) {
+        if let ConnectionState::Connected(ref mut client) = self.state {
+            let _ = client.clear_activity();
+            let _ = client.close();
+        }
+    }
+}
+
+/// Delay before the next Discord connect attempt after `failures` consecutive failures
+fn reconnect_delay(failures: u32) -> Duration {
+    let secs = BACKOFF_BASE_SECS.saturating_mul(1 << failures.saturating_sub(1).min(16));
+    Duration::from_secs(secs.min(BACKOFF_MAX_SECS))
+}
+
+/// Create and connect a Discord client for the application `app_id`
+fn create_discord_client(app_id: &str) -> Option<DiscordIpcClient> {
+    let mut client = DiscordIpcClient::new(app_id);
+
+    // Try to connect with timeout
+    for _ in 0..3 {
+        if client.connect().is_ok() {
+            return Some(client);
+        }
+        thread::sleep(Duration::from_millis(500));
+    }
+
+    None
+}
+
+/// Read the next state through `debouncer`, paired with the tray status
+/// text describing it
+fn read_status(
+    reader: &mut impl BrainFmReaderTrait,
+    debouncer: &mut StateDebouncer,
+    config: &Config,
+) -> (Option<BrainFmState>, String) {
+    match reader.read_state() {
+        Ok(state) => {
+            let state = debouncer.debounce(state);
+            let status = if stale_data_secs(&state, config).is_some() {
+                "Updating...".to_string()
+            } else {
+                format_status(&state, config)
+            };
+            (Some(state), status)
+        }
+        Err(e) => {
+            debug!("Error reading state: {e}");
+            (None, "Brain.fm not running".to_string())
+        }
+    }
+}
+
+/// How old `state`'s data is, if older than `Config::max_data_staleness_secs`
+fn stale_data_secs(state: &BrainFmState, config: &Config) -> Option<f64> {
+    #[allow(clippy::cast_precision_loss)]
+    let max = config.max_data_staleness_secs as f64;
+    state.data_staleness_secs().filter(|&secs| secs > max)
+}
+
+/// Format status text for tray menu
+fn format_status(state: &BrainFmState, config: &Config) -> String {
+    if !state.is_playing {
+        return "Not playing".to_string();
+    }
+
+    let mut parts = Vec::new();
+
+    if let Some(ref mode) = state.mode {
+        let emoji = mode_emoji(config, mode);
+        parts.push(if emoji.is_empty() {
+            mode.clone()
+        } else {
+            format!("{emoji} {mode}")
+        });
+    }
+
+    if let Some(ref track) = state.track_name {
+        parts.push(track.clone());
+    }
+
+    if let Some(ref genre) = state.genre {
+        parts.push(genre.clone());
+    }
+
+    if let Some(goal) = state.to_goal_string() {
+        parts.push(goal);
+    }
+
+    if parts.is_empty() {
+        "Playing...".to_string()
+    } else {
+        parts.join(" - ")
+    }
+}
+
+/// Check if state has changed enough to warrant an update
+fn state_changed(old: &BrainFmState, new: &BrainFmState) -> bool {
+    old.is_playing != new.is_playing
+        || old.mode != new.mode
+        || old.track_name != new.track_name
+        || old.neural_effect != new.neural_effect
+        || old.genre != new.genre
+        || old.activity != new.activity
+        || old.image_url != new.image_url
+}
+
+/// `state` with an unreachable track image swapped for the mode artwork
+/// when `validate_image_urls` is set. The check runs in the background, so
+/// a new image is kept as it is until the check answers.
+fn with_checked_image(state: BrainFmState, config: &Config) -> BrainFmState {
+    if !config.validate_image_urls {
+        return state;
+    }
+    BrainFmState {
+        image_url: state.checked_image_url(),
+        ..state
+    }
+}
+
+/// Update Discord presence with current state
+fn update_discord_presence(
+    client: &mut impl DiscordIpc,
+    state: &BrainFmState,
+    session_start: i64,
+    config: &Config,
+) -> anyhow::Result<()> {
+    let payload = state.to_discord_activity_json(session_start, config);
+    if payload.is_null() {
+        client.clear_activity()?;
+        return Ok(());
+    }
+    client.set_activity(activity_from_json(&payload))?;
+
+    Ok(())
+}
+
+/// Build the `discord_rich_presence` activity for the JSON made by
+/// `BrainFmState::to_discord_activity_json`, so what is sent is exactly
+/// what `dump-activity` and `--dry-run` print
+fn activity_from_json(json: &serde_json::Value) -> activity::Activity<'_> {
+    fn text(value: &serde_json::Value) -> &str {
+        value.as_str().unwrap_or_default()
+    }
+    let assets = &json["assets"];
+
+    let mut timestamps =
+        activity::Timestamps::new().start(json["timestamps"]["start"].as_i64().unwrap_or(0));
+    if let Some(end) = json["timestamps"]["end"].as_i64() {
+        timestamps = timestamps.end(end);
+    }
+
+    let mut activity_payload = activity::Activity::new()
+        .activity_type(activity::ActivityType::Listening)
+        .state(text(&json["state"]))
+        .details(text(&json["details"]))
+        .timestamps(timestamps)
+        .assets(
+            activity::Assets::new()
+                .large_image(text(&assets["large_image"]))
+                .large_text(text(&assets["large_text"]))
+                .small_image(text(&assets["small_image"]))
+                .small_text(text(&assets["small_text"])),
+        );
+
+    let party_size: Option<Vec<i32>> = json["party"]["size"].as_array().map(|size| {
+        size.iter()
+            .filter_map(|n| n.as_i64().and_then(|n| i32::try_from(n).ok()))
+            .collect()
+    });
+    if let Some(&[current, max]) = party_size.as_deref() {
+        activity_payload = activity_payload.party(activity::Party::new().size([current, max]));
+    }
+
+    if let Some(buttons) = json["buttons"].as_array() {
+        activity_payload = activity_payload.buttons(
+            buttons
+                .iter()
+                .map(|button| activity::Button::new(text(&button["label"]), text(&button["url"])))
+                .collect(),
+        );
+    }
+
+    activity_payload
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use brainfm_presence::testing::MockBrainFmReader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_read_status_with_mock_reader() {
+        let playing = BrainFmState {
+            is_playing: true,
+            mode: Some("Focus".into()),
+            track_name: Some("Nothing Remains".into()),
+            ..Default::default()
+        };
+        let mut reader = MockBrainFmReader::new([playing, BrainFmState::new()]);
+
+        let config = Config::default();
+        let mut no_debounce = StateDebouncer::new(Duration::ZERO);
+        assert_eq!(
+            read_status(&mut reader, &mut no_debounce, &config).1,
+            "🧠 Focus - Nothing Remains"
+        );
+        assert_eq!(
+            read_status(&mut reader, &mut no_debounce, &config).1,
+            "Not playing"
+        );
+
+        let (state, status) = read_status(&mut reader, &mut no_debounce, &config);
+        assert!(state.is_none());
+        assert_eq!(status, "Brain.fm not running");
+    }
+
+    #[test]
+    fn test_read_status_holds_false_pause_between_tracks() {
+        let playing = |track: &str| BrainFmState {
+            is_playing: true,
+            mode: Some("Focus".into()),
+            track_name: Some(track.into()),
+            ..Default::default()
+        };
+        let paused = BrainFmState::new();
+        let mut reader = MockBrainFmReader::new([
+            playing("Blooming"),
+            paused.clone(),
+            paused.clone(),
+            paused,
+            playing("Tidal"),
+        ]);
+        let config = Config::default();
+        let mut debouncer = StateDebouncer::new(Duration::from_secs(60));
+
+        // What the worker would send: a null activity means clear_activity()
+        for expected in [
+            "🧠 Focus - Blooming",
+            "🧠 Focus - Blooming",
+            "🧠 Focus - Blooming",
+            "🧠 Focus - Blooming",
+            "🧠 Focus - Tidal",
+        ] {
+            let (state, status) = read_status(&mut reader, &mut debouncer, &config);
+            assert_eq!(status, expected);
+            let activity = state.unwrap().to_discord_activity_json(0, &config);
+            assert!(!activity.is_null(), "presence cleared at {expected}");
+        }
+    }
+
+    #[test]
+    fn test_read_status_shows_updating_for_stale_data() {
+        let stale = BrainFmState {
+            is_playing: true,
+            mode: Some("Focus".into()),
+            last_successful_read: Some(std::time::SystemTime::now() - Duration::from_secs(90)),
+            ..Default::default()
+        };
+        let fresh = BrainFmState {
+            last_successful_read: Some(std::time::SystemTime::now()),
+            ..stale.clone()
+        };
+        let mut reader = MockBrainFmReader::new([stale, fresh]);
+
+        let config = Config::default();
+        let mut no_debounce = StateDebouncer::new(Duration::ZERO);
+        assert_eq!(
+            read_status(&mut reader, &mut no_debounce, &config).1,
+            "Updating..."
+        );
+        assert_eq!(
+            read_status(&mut reader, &mut no_debounce, &config).1,
+            "🧠 Focus"
+        );
+    }
+
+    #[test]
+    fn test_wait_for_matches_mode_or_activity() {
+        let deep_work = BrainFmState {
+            is_playing: true,
+            mode: Some("Focus".into()),
+            activity: Some("Deep Work".into()),
+            ..Default::default()
+        };
+        let paused = BrainFmState {
+            is_playing: false,
+            ..deep_work.clone()
+        };
+
+        assert!(wait_for_matches(&deep_work, None));
+        assert!(wait_for_matches(&deep_work, Some(&BrainFmMode::Focus)));
+        assert!(wait_for_matches(
+            &deep_work,
+            Some(&BrainFmMode::parse("deep-work"))
+        ));
+        assert!(!wait_for_matches(&deep_work, Some(&BrainFmMode::Sleep)));
+        assert!(!wait_for_matches(&paused, None));
+        assert!(!wait_for_matches(&paused, Some(&BrainFmMode::Focus)));
+    }
+
+    #[test]
+    fn test_activity_from_json_sends_the_dumped_json() {
+        let state = BrainFmState {
+            mode: Some("Focus".into()),
+            track_name: Some("Nothing Remains".into()),
+            genre: Some("Piano".into()),
+            play_count: Some(2),
+            is_playing: true,
+            ..Default::default()
+        };
+        let json = state.to_discord_activity_json(1_700_000_000, &Config::default());
+        let sent = serde_json::to_value(activity_from_json(&json)).unwrap();
+        assert_eq!(sent, json);
+    }
+
+    #[test]
+    fn test_flag_value() {
+        let args = |list: &[&str]| list.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+        assert_eq!(flag_value(&args(&[]), "--osc-host").unwrap(), None);
+        assert_eq!(
+            flag_value(
+                &args(&["--pwa", "--osc-host", "127.0.0.1:9000"]),
+                "--osc-host"
+            )
+            .unwrap(),
+            Some("127.0.0.1:9000".to_string())
+        );
+        assert_eq!(
+            flag_value(&args(&["--osc-host=localhost:57120"]), "--osc-host").unwrap(),
+            Some("localhost:57120".to_string())
+        );
+        assert_eq!(
+            flag_value(&args(&["--osc-hostname=x"]), "--osc-host").unwrap(),
+            None
+        );
+        assert!(flag_value(&args(&["--html-overlay"]), "--html-overlay").is_err());
+    }
+
+    #[test]
+    fn test_html_overlay_rewritten_only_on_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("overlay.html");
+        let mut overlay = HtmlOverlayWriter::new(&path);
+        let mut state = BrainFmState {
+            track_name: Some("Nothing Remains".into()),
+            ..Default::default()
+        };
+
+        overlay.write_if_changed(&state, None);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            state.to_html_overlay(None)
+        );
+
+        // An unchanged overlay is not rewritten
+        std::fs::remove_file(&path).unwrap();
+        overlay.write_if_changed(&state, None);
+        assert!(!path.exists());
+
+        state.track_name = Some("Evolving Harmony".into());
+        overlay.write_if_changed(&state, None);
+        let html = std::fs::read_to_string(&path).unwrap();
+        assert!(html.contains("Evolving Harmony"));
+    }
+
+    #[test]
+    fn test_osc_broadcaster_sends_only_on_change() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_millis(200)))
+            .unwrap();
+        let mut osc = OscBroadcaster::new(&receiver.local_addr().unwrap().to_string()).unwrap();
+        let mut buf = [0u8; rosc::decoder::MTU];
+
+        let mut state = BrainFmState {
+            is_playing: true,
+            track_name: Some("Nothing Remains".into()),
+            ..Default::default()
+        };
+        osc.send_if_changed(&state);
+        let len = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], state.to_osc_bundle().as_slice());
+
+        // Unchanged state is not re-sent
+        osc.send_if_changed(&state);
+        assert!(receiver.recv(&mut buf).is_err());
+
+        state.is_playing = false;
+        osc.send_if_changed(&state);
+        let len = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], state.to_osc_bundle().as_slice());
+    }
+
+    fn fake_png(size: u32) -> Vec<u8> {
+        let mut png = Vec::new();
+        RgbaImage::from_pixel(size, size, image::Rgba([255, 0, 0, 255]))
+            .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        png
+    }
+
+    #[test]
+    fn test_connection_backoff_then_success() {
+        let attempts = std::cell::Cell::new(0);
+        let mut manager = DiscordConnectionManager::new(|| {
+            attempts.set(attempts.get() + 1);
+            (attempts.get() > 3).then_some(())
+        });
+        let mut now = Instant::now();
+
+        // Three failures: 5s, 10s, 20s backoff
+        for expected in [5, 10, 20] {
+            assert!(!manager.try_reconnect_at(now));
+            let ConnectionState::Disconnected { next_retry, .. } = manager.state else {
+                panic!("expected disconnected");
+            };
+            assert_eq!(next_retry - now, Duration::from_secs(expected));
+
+            // No attempt is made before the delay elapses
+            assert!(
+                !manager.try_reconnect_at(next_retry.checked_sub(Duration::from_secs(1)).unwrap())
+            );
+            now = next_retry;
+        }
+
+        // Fourth attempt succeeds and requests a full presence update
+        assert!(manager.try_reconnect_at(now));
+        assert!(manager.is_connected());
+        assert!(manager.take_needs_full_update());
+        assert!(!manager.take_needs_full_update());
+        assert_eq!(attempts.get(), 4);
+    }
+
+    #[test]
+    fn test_connection_backoff_is_capped() {
+        assert_eq!(reconnect_delay(1), Duration::from_secs(BACKOFF_BASE_SECS));
+        assert_eq!(reconnect_delay(7), Duration::from_secs(BACKOFF_MAX_SECS));
+        assert_eq!(
+            reconnect_delay(u32::MAX),
+            Duration::from_secs(BACKOFF_MAX_SECS)
+        );
+    }
+
+    #[test]
+    fn test_prepare_icon_image_at_size() {
+        let image = prepare_icon_image(&fake_png(64), Some(32)).unwrap();
+        assert_eq!(image.dimensions(), (32, 32));
+    }
+
+    #[test]
+    fn test_prepare_icon_image_keeps_supported_size() {
+        let image = prepare_icon_image(&fake_png(64), None).unwrap();
+        assert_eq!(image.dimensions(), (64, 64));
+    }
+
+    #[test]
+    fn test_prepare_icon_image_resizes_unsupported_size() {
+        let image = prepare_icon_image(&fake_png(100), None).unwrap();
+        assert_eq!(image.dimensions(), (DEFAULT_ICON_SIZE, DEFAULT_ICON_SIZE));
+
+        let image = prepare_icon_image(TRAY_ICON_PNG, None).unwrap();
+        assert_eq!(image.dimensions(), (DEFAULT_ICON_SIZE, DEFAULT_ICON_SIZE));
+    }
+
+    #[test]
+    fn test_prepare_icon_image_rejects_zero_size() {
+        assert!(prepare_icon_image(&fake_png(16), Some(0)).is_err());
+    }
+}
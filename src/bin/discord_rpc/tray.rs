@@ -0,0 +1,207 @@
+//! Tray icon menu
+//!
+//! The menu shows the status line, a "Recent Tracks" submenu and Quit.
+//! `TrayManager::update_with_state_and_history` refreshes the status and
+//! rebuilds the submenu after every read, and clicking a recent track copies
+//! its name to the clipboard.
+
+use anyhow::{Context, Result};
+use brainfm_presence::history::{BrainFmStateHistory, HistoryEntry};
+use log::{debug, warn};
+use std::cell::RefCell;
+use tray_icon::menu::{Menu, MenuId, MenuItem, PredefinedMenuItem, Submenu};
+use tray_icon::{Icon, TrayIcon, TrayIconBuilder};
+
+/// Menu item IDs
+pub const MENU_ID_STATUS: &str = "status";
+pub const MENU_ID_QUIT: &str = "quit";
+const MENU_ID_HISTORY: &str = "history";
+
+/// Prefix of the IDs of "Recent Tracks" items, followed by the track name
+const MENU_ID_RECENT_TRACK: &str = "recent-track:";
+
+/// How many tracks "Recent Tracks" lists
+pub const RECENT_TRACKS: usize = 5;
+
+/// The tray icon and the menu items that change while the app runs
+pub struct TrayManager {
+    status_item: MenuItem,
+    history_menu: Submenu,
+    /// Opened on the first copy and kept: on Linux, copied text is only
+    /// available while the clipboard that copied it is alive
+    clipboard: RefCell<Option<arboard::Clipboard>>,
+    _tray_icon: TrayIcon,
+}
+
+impl TrayManager {
+    /// Create the tray icon with its menu
+    pub fn new(icon: Icon) -> Result<Self> {
+        let status_item = MenuItem::with_id(MENU_ID_STATUS, "Brain.fm Presence", false, None);
+        let history_menu = Self::build_history_menu(&BrainFmStateHistory::new());
+        let quit_item = MenuItem::with_id(MENU_ID_QUIT, "Quit", true, None);
+
+        let menu = Menu::new();
+        menu.append(&status_item)
+            .context("Failed to append status item")?;
+        menu.append(&history_menu)
+            .context("Failed to append recent tracks")?;
+        menu.append(&PredefinedMenuItem::separator())
+            .context("Failed to append separator")?;
+        menu.append(&quit_item)
+            .context("Failed to append quit item")?;
+
+        let tray_icon = TrayIconBuilder::new()
+            .with_icon(icon)
+            .with_menu(Box::new(menu))
+            .with_tooltip("Brain.fm Presence")
+            .build()
+            .context("Failed to create tray icon")?;
+
+        Ok(Self {
+            status_item,
+            history_menu,
+            clipboard: RefCell::new(None),
+            _tray_icon: tray_icon,
+        })
+    }
+
+    /// The "Recent Tracks" submenu for `history`: the last `RECENT_TRACKS`
+    /// tracks, newest first, as `Track (Mode · Genre)`
+    pub fn build_history_menu(history: &BrainFmStateHistory) -> Submenu {
+        let menu = Submenu::with_id(MENU_ID_HISTORY, "Recent Tracks", true);
+        fill_history_menu(&menu, history);
+        menu
+    }
+
+    /// Show `status` and rebuild "Recent Tracks" from `history`
+    pub fn update_with_state_and_history(&self, status: &str, history: &BrainFmStateHistory) {
+        self.status_item.set_text(status);
+        fill_history_menu(&self.history_menu, history);
+    }
+
+    /// Copy the track of a "Recent Tracks" item to the clipboard; other
+    /// menu items are ignored
+    pub fn copy_recent_track(&self, id: &MenuId) {
+        let Some(track) = id.0.strip_prefix(MENU_ID_RECENT_TRACK) else {
+            return;
+        };
+        let mut clipboard = self.clipboard.borrow_mut();
+        let result = match clipboard.as_mut() {
+            Some(clipboard) => clipboard.set_text(track),
+            None => arboard::Clipboard::new().and_then(|new| clipboard.insert(new).set_text(track)),
+        };
+        match result {
+            Ok(()) => debug!("Copied '{track}' to the clipboard"),
+            Err(e) => warn!("Failed to copy '{track}' to the clipboard: {e}"),
+        }
+    }
+}
+
+/// Replace the items of `menu` with `history`'s recent tracks
+fn fill_history_menu(menu: &Submenu, history: &BrainFmStateHistory) {
+    while menu.remove_at(0).is_some() {}
+
+    let recent = history.recent_tracks(RECENT_TRACKS);
+    let appended = if recent.is_empty() {
+        menu.append(&MenuItem::new("No tracks yet", false, None))
+    } else {
+        recent.into_iter().try_for_each(|entry| {
+            let track = entry.state.track_name.as_deref().unwrap_or_default();
+            menu.append(&MenuItem::with_id(
+                format!("{MENU_ID_RECENT_TRACK}{track}"),
+                recent_track_text(entry),
+                true,
+                None,
+            ))
+        })
+    };
+    if let Err(e) = appended {
+        warn!("Failed to update recent tracks: {e}");
+    }
+}
+
+/// Menu text for a recent track: `Nothing Remains (Focus · Electronic)`
+fn recent_track_text(entry: &HistoryEntry) -> String {
+    let state = &entry.state;
+    let track = state.track_name.as_deref().unwrap_or_default();
+    let details: Vec<&str> = [state.mode.as_deref(), state.genre.as_deref()]
+        .into_iter()
+        .flatten()
+        .collect();
+    if details.is_empty() {
+        track.to_string()
+    } else {
+        format!("{track} ({})", details.join(" · "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use brainfm_presence::BrainFmState;
+
+    /// A history of `n` tracks played one after another
+    fn history_of(n: usize) -> BrainFmStateHistory {
+        let mut history = BrainFmStateHistory::new();
+        for i in 1..=n {
+            history.push(BrainFmState {
+                is_playing: true,
+                mode: Some("Focus".into()),
+                genre: Some("Electronic".into()),
+                track_name: Some(format!("Track {i}")),
+                ..Default::default()
+            });
+        }
+        history
+    }
+
+    /// (id, text, enabled) of each item
+    fn items(menu: &Submenu) -> Vec<(String, String, bool)> {
+        menu.items()
+            .iter()
+            .map(|item| {
+                let item = item.as_menuitem().unwrap();
+                (item.id().0.clone(), item.text(), item.is_enabled())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_history_menu_without_tracks() {
+        let menu = TrayManager::build_history_menu(&history_of(0));
+        assert_eq!(menu.text(), "Recent Tracks");
+        let items = items(&menu);
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].1, "No tracks yet");
+        assert!(!items[0].2);
+    }
+
+    #[test]
+    fn test_history_menu_with_one_track() {
+        let menu = TrayManager::build_history_menu(&history_of(1));
+        assert_eq!(
+            items(&menu),
+            [(
+                "recent-track:Track 1".to_string(),
+                "Track 1 (Focus · Electronic)".to_string(),
+                true
+            )]
+        );
+    }
+
+    #[test]
+    fn test_history_menu_rebuilds_with_last_five_tracks() {
+        let menu = TrayManager::build_history_menu(&history_of(1));
+        fill_history_menu(&menu, &history_of(7));
+        let texts: Vec<String> = items(&menu).into_iter().map(|(_, text, _)| text).collect();
+        assert_eq!(
+            texts,
+            [7, 6, 5, 4, 3].map(|i| format!("Track {i} (Focus · Electronic)"))
+        );
+
+        fill_history_menu(&menu, &history_of(5));
+        assert_eq!(items(&menu).len(), 5);
+        fill_history_menu(&menu, &history_of(0));
+        assert_eq!(items(&menu).len(), 1);
+    }
+}
@@ -0,0 +1,242 @@
+//! Outputs that call web APIs
+//!
+//! Zulip, Pushover, Linear, `ActivityWatch` and Toggl each make HTTP requests
+//! that can take seconds (or time out), so `WebOutputsThread` runs them on a
+//! thread of their own, passing them the states the poll loop reads.
+
+use super::flag_value;
+#[cfg(any(
+    feature = "zulip",
+    feature = "pushover",
+    feature = "linear",
+    feature = "toggl"
+))]
+use anyhow::Context;
+use anyhow::Result;
+#[cfg(feature = "activitywatch")]
+use brainfm_presence::integrations::activitywatch::{ActivityWatchClient, ActivityWatchPublisher};
+#[cfg(feature = "linear")]
+use brainfm_presence::integrations::linear::{LinearClient, LinearNotifier};
+#[cfg(feature = "pushover")]
+use brainfm_presence::integrations::pushover::{PushoverClient, PushoverNotifier};
+#[cfg(feature = "toggl")]
+use brainfm_presence::integrations::toggl::{TogglClient, TogglTracker};
+#[cfg(feature = "zulip")]
+use brainfm_presence::integrations::zulip::{ZulipClient, ZulipNotifier};
+use brainfm_presence::BrainFmState;
+use std::sync::mpsc;
+use std::thread;
+
+/// The outputs that call web APIs, each enabled by a command-line flag
+pub struct WebOutputs {
+    /// `--zulip-server <url>` and the other `--zulip-*` flags
+    #[cfg(feature = "zulip")]
+    zulip: Option<ZulipNotifier>,
+    /// `--pushover-user-key <key>` and `--pushover-api-token <token>`
+    #[cfg(feature = "pushover")]
+    pushover: Option<PushoverNotifier>,
+    /// `--linear-api-key <key>` and `--linear-issue <id>`
+    #[cfg(feature = "linear")]
+    linear: Option<LinearNotifier>,
+    /// `--aw-host <url>`
+    #[cfg(feature = "activitywatch")]
+    activitywatch: Option<ActivityWatchPublisher>,
+    /// `--toggl-token <token>` and `--toggl-workspace <id>`
+    #[cfg(feature = "toggl")]
+    toggl: Option<TogglTracker>,
+}
+
+impl WebOutputs {
+    /// Enable the outputs whose flags are among `args`
+    pub fn from_args(args: &[String]) -> Result<Self> {
+        Ok(Self {
+            #[cfg(feature = "zulip")]
+            zulip: zulip_notifier(args)?,
+            #[cfg(feature = "pushover")]
+            pushover: pushover_notifier(args)?,
+            #[cfg(feature = "linear")]
+            linear: linear_notifier(args)?,
+            #[cfg(feature = "activitywatch")]
+            activitywatch: flag_value(args, "--aw-host")?
+                .map(|host| ActivityWatchPublisher::new(ActivityWatchClient::new(&host))),
+            #[cfg(feature = "toggl")]
+            toggl: toggl_tracker(args)?,
+        })
+    }
+
+    /// Whether no output is enabled
+    fn is_empty(&self) -> bool {
+        #[cfg(feature = "zulip")]
+        if self.zulip.is_some() {
+            return false;
+        }
+        #[cfg(feature = "pushover")]
+        if self.pushover.is_some() {
+            return false;
+        }
+        #[cfg(feature = "linear")]
+        if self.linear.is_some() {
+            return false;
+        }
+        #[cfg(feature = "activitywatch")]
+        if self.activitywatch.is_some() {
+            return false;
+        }
+        #[cfg(feature = "toggl")]
+        if self.toggl.is_some() {
+            return false;
+        }
+        true
+    }
+
+    /// Pass the latest state to every enabled output
+    fn update(&mut self, state: &BrainFmState) {
+        #[cfg(feature = "zulip")]
+        if let Some(zulip) = self.zulip.as_mut() {
+            zulip.update(state);
+        }
+        #[cfg(feature = "pushover")]
+        if let Some(pushover) = self.pushover.as_mut() {
+            pushover.update(state);
+        }
+        #[cfg(feature = "linear")]
+        if let Some(linear) = self.linear.as_mut() {
+            linear.update(state);
+        }
+        #[cfg(feature = "activitywatch")]
+        if let Some(activitywatch) = self.activitywatch.as_mut() {
+            activitywatch.update(state);
+        }
+        #[cfg(feature = "toggl")]
+        if let Some(toggl) = self.toggl.as_mut() {
+            toggl.update(state);
+        }
+    }
+}
+
+/// Runs `WebOutputs` on a background thread, passing it each state through a
+/// channel, so a slow or unreachable API doesn't hold up the poll loop
+pub struct WebOutputsThread {
+    states: Option<mpsc::Sender<BrainFmState>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl WebOutputsThread {
+    /// Start a thread for `outputs`, unless none is enabled
+    pub fn start(mut outputs: WebOutputs) -> Option<Self> {
+        if outputs.is_empty() {
+            return None;
+        }
+        let (states, received) = mpsc::channel::<BrainFmState>();
+        let thread = thread::spawn(move || {
+            for state in received {
+                outputs.update(&state);
+            }
+        });
+        Some(Self {
+            states: Some(states),
+            thread: Some(thread),
+        })
+    }
+
+    /// Queue `state` for the outputs
+    pub fn update(&self, state: &BrainFmState) {
+        if let Some(states) = self.states.as_ref() {
+            let _ = states.send(state.clone());
+        }
+    }
+}
+
+impl Drop for WebOutputsThread {
+    /// Let the outputs handle the queued states and clean up (e.g. stop a
+    /// running Toggl entry) before the worker exits
+    fn drop(&mut self) {
+        drop(self.states.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// The Zulip notifier configured by `--zulip-server`, `--zulip-bot-email`,
+/// `--zulip-bot-key`, `--zulip-stream` and `--zulip-topic` (plus the
+/// optional `--zulip-user`, defaulting to `$USER`), if `--zulip-server` is set
+#[cfg(feature = "zulip")]
+fn zulip_notifier(args: &[String]) -> Result<Option<ZulipNotifier>> {
+    let Some(server_url) = flag_value(args, "--zulip-server")? else {
+        return Ok(None);
+    };
+    let required = |flag: &str| {
+        flag_value(args, flag)?.with_context(|| format!("--zulip-server needs {flag}"))
+    };
+    let client = ZulipClient {
+        bot_email: required("--zulip-bot-email")?,
+        bot_api_key: required("--zulip-bot-key")?,
+        server_url,
+        user: match flag_value(args, "--zulip-user")? {
+            Some(user) => user,
+            None => std::env::var("USER").context("--zulip-server needs --zulip-user")?,
+        },
+    };
+    Ok(Some(ZulipNotifier::new(
+        client,
+        required("--zulip-stream")?,
+        flag_value(args, "--zulip-topic")?.unwrap_or_else(|| "Brain.fm".to_string()),
+    )))
+}
+
+/// The Pushover notifier configured by `--pushover-user-key` and
+/// `--pushover-api-token`, or the `PUSHOVER_USER_KEY` and
+/// `PUSHOVER_API_TOKEN` environment variables, if a user key is set
+#[cfg(feature = "pushover")]
+fn pushover_notifier(args: &[String]) -> Result<Option<PushoverNotifier>> {
+    let setting = |flag: &str, var: &str| -> Result<Option<String>> {
+        Ok(flag_value(args, flag)?.or_else(|| std::env::var(var).ok()))
+    };
+    let Some(user_key) = setting("--pushover-user-key", "PUSHOVER_USER_KEY")? else {
+        return Ok(None);
+    };
+    let api_token = setting("--pushover-api-token", "PUSHOVER_API_TOKEN")?
+        .context("--pushover-user-key needs --pushover-api-token")?;
+    Ok(Some(PushoverNotifier::new(PushoverClient::new(
+        user_key, api_token,
+    ))))
+}
+
+/// The Linear notifier configured by `--linear-api-key` and
+/// `--linear-issue`, if an API key is set
+#[cfg(feature = "linear")]
+fn linear_notifier(args: &[String]) -> Result<Option<LinearNotifier>> {
+    let Some(api_key) = flag_value(args, "--linear-api-key")? else {
+        return Ok(None);
+    };
+    let issue_id =
+        flag_value(args, "--linear-issue")?.context("--linear-api-key needs --linear-issue")?;
+    Ok(Some(LinearNotifier::new(
+        LinearClient::new(api_key),
+        issue_id,
+    )))
+}
+
+/// The Toggl tracker configured by `--toggl-token`, `--toggl-workspace` and
+/// optionally `--toggl-project`, if a token is given
+#[cfg(feature = "toggl")]
+fn toggl_tracker(args: &[String]) -> Result<Option<TogglTracker>> {
+    let Some(api_token) = flag_value(args, "--toggl-token")? else {
+        return Ok(None);
+    };
+    let id = |flag: &str| -> Result<Option<u64>> {
+        flag_value(args, flag)?
+            .map(|id| {
+                id.parse()
+                    .with_context(|| format!("Invalid {flag} {id}, expected a numeric ID"))
+            })
+            .transpose()
+    };
+    let workspace_id = id("--toggl-workspace")?.context("--toggl-token needs --toggl-workspace")?;
+    Ok(Some(TogglTracker::new(
+        TogglClient::new(api_token),
+        workspace_id,
+        id("--toggl-project")?,
+    )))
+}
@@ -5,34 +5,58 @@
 //!
 //! # Enrichment Strategy
 //!
-//! When an audio URL is found via `lsof`, we first try to look it up
+//! When an audio URL is found in an open cache file, we first try to look it up
 //! in the API cache for rich, structured metadata (track name, genre,
 //! NEL, activity). Only falls back to heuristic filename parsing when
 //! no API cache match is available.
 
 use anyhow::Result;
 use log::debug;
+use memchr::memmem;
 use regex::Regex;
 use std::fs;
 use std::path::Path;
-use std::process::Command;
 use std::sync::LazyLock;
 
-use crate::api_cache_reader::ApiCacheData;
-use crate::util::{url_decode, KNOWN_GENRES, MP3_FILENAME_RE};
+use crate::api_cache_reader::{self, ApiCacheData};
+use crate::util::{
+    capitalize_first, normalize_activity, split_camel_case, url_decode, LsofCommand, KNOWN_GENRES,
+    MP3_FILENAME_RE,
+};
 use crate::BrainFmState;
 
-/// Regex for matching Brain.fm audio URLs in cache files
-static AUDIO_URL_RE: LazyLock<Regex> =
-    LazyLock::new(|| Regex::new(r#"(https?://audio\d*\.brain\.fm/[^\s\x00"'<>]+\.mp3)"#).unwrap());
+/// Regex for matching Brain.fm audio URLs in cache files: `audio*.brain.fm`
+/// for the desktop and web apps, `audio*.brainfm.com` for the iOS app
+static AUDIO_URL_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(https?://audio\d*\.(?:brain\.fm|brainfm\.com)/[^\s\x00"'<>]+\.mp3)"#).unwrap()
+});
+
+/// Literal shared by every `AUDIO_URL_RE` match, located with SIMD before running the regex
+const AUDIO_URL_ANCHOR: &[u8] = b"://audio";
+
+/// Bytes before the anchor that may belong to the URL scheme (`https`)
+const AUDIO_URL_SCHEME_LEN: usize = 5;
+
+/// Maximum number of bytes after the anchor searched for a complete URL
+const AUDIO_URL_WINDOW: usize = 512;
+
+/// Maximum number of bytes of a cache file searched for an audio URL
+const AUDIO_URL_SEARCH_LIMIT: usize = 32768;
+
+/// Processes whose open files `lsof` lists by default: the main process of
+/// either desktop build, and the helper Chromium opens the disk cache from
+#[cfg(not(target_os = "windows"))]
+const LSOF_PROCESS_NAMES: &[&str] = &["Brain.fm", "BrainFM", "Brain.fm Helper"];
 
 /// Read state from Cache directory.
 ///
 /// Accepts an optional `ApiCacheData` reference for enriching the detected
-/// audio URL with structured metadata from cached API responses.
+/// audio URL with structured metadata from cached API responses. `lsof`
+/// says how to list open cache files (unused on Windows).
 pub fn read_state(
     app_support_path: &Path,
     api_cache: Option<&mut ApiCacheData>,
+    lsof: &LsofCommand,
 ) -> Result<BrainFmState> {
     let cache_path = app_support_path.join("Cache").join("Cache_Data");
 
@@ -42,18 +66,19 @@ pub fn read_state(
 
     let mut state = BrainFmState::new();
 
-    // Use lsof as the authoritative play/pause signal.
+    // Use open cache files (lsof, or the handle table on Windows) as the
+    // authoritative play/pause signal.
     // When Brain.fm is playing, it holds Cache_Data file handles open.
     // When paused, it releases ALL Cache_Data handles (count drops to 0).
-    match find_audio_url_via_lsof(&cache_path)? {
+    match find_audio_url_via_open_files(&cache_path, lsof)? {
         Some(url) => {
-            // lsof found open Cache_Data files with an audio URL = actively playing
+            // Found open Cache_Data files with an audio URL = actively playing
             state = enrich_from_url(&url, state, api_cache);
             return Ok(state);
         }
         None => {
             // Check if Brain.fm has ANY Cache_Data files open (even without a parseable URL)
-            if has_open_cache_files()? {
+            if has_open_cache_files(lsof)? {
                 // Process has cache files open but we couldn't extract a URL.
                 // Fallback: scan cache files by access time.
                 if let Some(url) = find_audio_url_by_atime(&cache_path)? {
@@ -70,7 +95,8 @@ pub fn read_state(
 /// Enrich state from an audio URL.
 ///
 /// Strategy:
-/// 1. Try API cache lookup first (structured data, 100% accurate)
+/// 1. Try API cache lookup first (structured data, 100% accurate), by track
+///    ID when the URL carries one, then by filename
 /// 2. Fall back to heuristic filename parsing (lossy but always available)
 fn enrich_from_url(
     url: &str,
@@ -79,14 +105,25 @@ fn enrich_from_url(
 ) -> BrainFmState {
     // Strategy 1: API cache lookup (rich structured metadata)
     if let Some(cache) = api_cache {
-        if let Some(metadata) = cache.lookup_by_url(url) {
+        // A track ID in the URL survives filename changes, so try it first
+        let metadata = match api_cache_reader::track_id_from_url(url)
+            .filter(|id| cache.lookup_by_id(id).is_some())
+        {
+            Some(id) => cache.lookup_by_id(&id),
+            None => cache.lookup_by_url(url),
+        };
+        if let Some(metadata) = metadata {
             debug!("API cache hit for URL: track='{}'", metadata.name);
             state.track_name = Some(metadata.name.clone());
             state.genre = metadata.genre.clone();
             state.neural_effect = metadata.neural_effect.clone();
             state.mental_state_or_mode(&metadata);
-            state.activity = metadata.activity.clone();
+            state.activity = metadata.activity.as_deref().map(normalize_activity);
             state.image_url = metadata.image_url.clone();
+            state.bpm = metadata.bpm;
+            state.moods.clone_from(&metadata.moods);
+            state.instruments.clone_from(&metadata.instruments);
+            state.is_guided_meditation = metadata.is_guided_meditation;
             state.is_playing = true;
             return state;
         }
@@ -100,51 +137,20 @@ fn enrich_from_url(
 /// Check if Brain.fm has ANY Cache_Data files open (play/pause signal).
 /// Returns true if at least one Cache_Data file handle is open.
 /// When Brain.fm is paused, it releases ALL Cache_Data handles.
-fn has_open_cache_files() -> Result<bool> {
-    let output = crate::util::run_command_with_timeout(
-        Command::new("lsof").args(["-c", "Brain.fm"]),
-        crate::util::DEFAULT_COMMAND_TIMEOUT,
-    )?;
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-
-    Ok(stdout.lines().any(|line| line.contains("Cache_Data")))
+fn has_open_cache_files(lsof: &LsofCommand) -> Result<bool> {
+    Ok(!open_cache_file_names(lsof)?.is_empty())
 }
 
 /// Find audio URL by checking which cache file Brain.fm currently has open
-/// This is the most reliable method - lsof shows exactly what's being read
-fn find_audio_url_via_lsof(cache_path: &Path) -> Result<Option<String>> {
-    let output = crate::util::run_command_with_timeout(
-        Command::new("lsof").args(["-c", "Brain.fm"]),
-        crate::util::DEFAULT_COMMAND_TIMEOUT,
-    )?;
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-
-    // Look for Cache_Data files that are open
-    for line in stdout.lines() {
-        if line.contains("Cache_Data") && line.contains("_0") {
-            // Extract the filename from lsof output
-            // Format: Brain.fm 1073 user 22u REG ... /path/to/file
-            if let Some(path_start) = line.rfind('/') {
-                let file_path = &line[path_start..];
-
-                // Extract just the filename and read it
-                if let Some(filename) = file_path.split('/').last() {
-                    if filename.ends_with("_0") {
-                        let file_to_read = cache_path.join(filename);
-                        if file_to_read.exists() {
-                            if let Ok(content) = fs::read(&file_to_read) {
-                                let search_size = std::cmp::min(content.len(), 32768);
-                                let content_str = String::from_utf8_lossy(&content[..search_size]);
-
-                                if let Some(caps) = AUDIO_URL_RE.captures(&content_str) {
-                                    if let Some(url_match) = caps.get(1) {
-                                        return Ok(Some(url_match.as_str().to_string()));
-                                    }
-                                }
-                            }
-                        }
+/// This is the most reliable method - it shows exactly what's being read
+fn find_audio_url_via_open_files(cache_path: &Path, lsof: &LsofCommand) -> Result<Option<String>> {
+    for filename in open_cache_file_names(lsof)? {
+        if filename.ends_with("_0") {
+            let file_to_read = cache_path.join(&filename);
+            if file_to_read.exists() {
+                if let Ok(content) = fs::read(&file_to_read) {
+                    if let Some(url) = find_audio_url(&content) {
+                        return Ok(Some(url));
                     }
                 }
             }
@@ -154,6 +160,65 @@ fn find_audio_url_via_lsof(cache_path: &Path) -> Result<Option<String>> {
     Ok(None)
 }
 
+/// Names of the `Cache_Data` files Brain.fm has open, via `lsof` (or
+/// whatever `Config::lsof_path` points at)
+///
+/// With the default arguments, `lsof` is limited to the Brain.fm PIDs
+/// (`-p`) rather than matching every process by name, and is not run at all
+/// when no Brain.fm process exists.
+#[cfg(not(target_os = "windows"))]
+fn open_cache_file_names(lsof: &LsofCommand) -> Result<Vec<String>> {
+    let targeted;
+    let lsof = if lsof.uses_default_args() {
+        match brainfm_pids() {
+            Ok(pids) if pids.is_empty() => return Ok(Vec::new()),
+            Ok(pids) => {
+                targeted = lsof.for_pids(&pids);
+                &targeted
+            }
+            Err(e) => {
+                debug!("Could not look up Brain.fm PIDs, matching by name: {e:#}");
+                lsof
+            }
+        }
+    } else {
+        lsof
+    };
+    let output = lsof.run()?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    // Format: Brain.fm 1073 user 22u REG ... /path/to/file
+    Ok(stdout
+        .lines()
+        .filter(|line| line.contains("Cache_Data"))
+        .filter_map(|line| line.rsplit('/').next())
+        .map(str::to_string)
+        .collect())
+}
+
+/// PIDs of the processes in `LSOF_PROCESS_NAMES`
+#[cfg(not(target_os = "windows"))]
+fn brainfm_pids() -> Result<Vec<u32>> {
+    let mut pids = Vec::new();
+    for name in LSOF_PROCESS_NAMES {
+        pids.extend(crate::util::find_process_pids(name)?);
+    }
+    Ok(pids)
+}
+
+/// Names of the `Cache_Data` files Brain.fm has open, from the handle table
+/// (Windows has no `lsof`)
+#[cfg(target_os = "windows")]
+fn open_cache_file_names(_lsof: &LsofCommand) -> Result<Vec<String>> {
+    Ok(crate::platform::windows::brainfm_open_files()?
+        .iter()
+        .filter(|path| path.contains("\\Cache_Data\\"))
+        .filter_map(|path| path.rsplit('\\').next())
+        .map(str::to_string)
+        .collect())
+}
+
 /// Fallback: Find audio URL by access time (less reliable due to kernel caching)
 fn find_audio_url_by_atime(cache_path: &Path) -> Result<Option<String>> {
     let mut entries = fs::read_dir(cache_path)?
@@ -179,16 +244,8 @@ fn find_audio_url_by_atime(cache_path: &Path) -> Result<Option<String>> {
     // Scan recent metadata files for audio URLs
     for (path, _) in entries.iter().take(100) {
         if let Ok(content) = fs::read(path) {
-            // Search entire file content for audio URL (not just header)
-            // Use chunks to avoid loading huge files entirely into string
-            let search_size = std::cmp::min(content.len(), 32768); // 32KB should be enough
-            let content_str = String::from_utf8_lossy(&content[..search_size]);
-
-            // Look for brain.fm audio URLs - match various patterns
-            if let Some(captures) = AUDIO_URL_RE.captures(&content_str) {
-                if let Some(url_match) = captures.get(1) {
-                    return Ok(Some(url_match.as_str().to_string()));
-                }
+            if let Some(url) = find_audio_url(&content) {
+                return Ok(Some(url));
             }
         }
     }
@@ -196,6 +253,26 @@ fn find_audio_url_by_atime(cache_path: &Path) -> Result<Option<String>> {
     Ok(None)
 }
 
+/// Find the first Brain.fm audio URL in the first 32KB of raw cache file bytes.
+///
+/// Locates candidate positions with a SIMD literal search and only runs
+/// `AUDIO_URL_RE` on a small window around each one, avoiding a lossy UTF-8
+/// conversion of the whole buffer.
+#[must_use]
+pub fn find_audio_url(data: &[u8]) -> Option<String> {
+    let data = &data[..data.len().min(AUDIO_URL_SEARCH_LIMIT)];
+
+    memmem::find_iter(data, AUDIO_URL_ANCHOR).find_map(|anchor| {
+        let start = anchor.saturating_sub(AUDIO_URL_SCHEME_LEN);
+        let end = (anchor + AUDIO_URL_WINDOW).min(data.len());
+        let window = String::from_utf8_lossy(&data[start..end]);
+        AUDIO_URL_RE
+            .captures(&window)
+            .and_then(|caps| caps.get(1))
+            .map(|url| url.as_str().to_string())
+    })
+}
+
 /// Parse metadata from audio URL
 /// Examples:
 /// - https://audio2.brain.fm/Tied_In_Strings_Focus_Deep_Work_Electronic_30_120bpm_HighNEL_Nrmlzd2_VBR5.mp3
@@ -365,38 +442,78 @@ fn parse_audio_url(url: &str, mut state: BrainFmState) -> BrainFmState {
     state
 }
 
-/// Helper to split CamelCase into words, with acronym grouping.
-/// "NothingRemains" -> "Nothing Remains"
-/// "ABCName" -> "ABC Name"
-fn split_camel_case(s: &str) -> String {
-    let mut result = String::new();
-    let chars: Vec<char> = s.chars().collect();
-
-    for (i, &c) in chars.iter().enumerate() {
-        if c.is_uppercase() && i > 0 {
-            let prev = chars[i - 1];
-            let next = chars.get(i + 1).copied();
-            // Insert space before uppercase when:
-            // 1. Previous char was lowercase (standard CamelCase boundary)
-            // 2. Previous was uppercase AND next is lowercase (end of acronym, e.g. "ABCName" → "ABC Name")
-            if prev.is_lowercase()
-                || (prev.is_uppercase() && next.map_or(false, |n| n.is_lowercase()))
-            {
-                result.push(' ');
-            }
+/// Magic number at the start of Chromium's Simple Cache `index` file and of
+/// every entry file (`kSimpleInitialMagicNumber`)
+const SIMPLE_INITIAL_MAGIC: u64 = 0xfcfb_6d1b_a772_5c30;
+
+/// Chromium Simple Cache format version of a `Cache_Data` directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheFormatVersion {
+    V5,
+    V6,
+    V7,
+    V8,
+    V9,
+    /// A version this crate does not know, or 0 when the `index` file is
+    /// missing or not a Simple Cache index
+    Unknown(u32),
+}
+
+impl CacheFormatVersion {
+    fn from_number(version: u32) -> Self {
+        match version {
+            5 => Self::V5,
+            6 => Self::V6,
+            7 => Self::V7,
+            8 => Self::V8,
+            9 => Self::V9,
+            other => Self::Unknown(other),
+        }
+    }
+
+    /// Size of the header that starts each entry file, before the entry's
+    /// key (its URL): magic, version, key length and key hash, padded to
+    /// 8 bytes from v7 on. `None` for unknown versions.
+    #[must_use]
+    pub fn entry_header_size(self) -> Option<usize> {
+        match self {
+            Self::V5 | Self::V6 => Some(20),
+            Self::V7 | Self::V8 | Self::V9 => Some(24),
+            Self::Unknown(_) => None,
         }
-        result.push(c);
     }
 
-    result
+    /// Where the response data starts in an entry file of this version:
+    /// after the header and the key. `None` when the version is unknown or
+    /// `entry` is too short to tell.
+    #[must_use]
+    pub fn entry_body_offset(self, entry: &[u8]) -> Option<usize> {
+        let header_size = self.entry_header_size()?;
+        let key_length = u32::from_le_bytes(entry.get(12..16)?.try_into().ok()?);
+        let offset = header_size.checked_add(usize::try_from(key_length).ok()?)?;
+        (offset <= entry.len()).then_some(offset)
+    }
 }
 
-/// Capitalize first letter of a string
-fn capitalize_first(s: &str) -> String {
-    let mut chars = s.chars();
-    match chars.next() {
-        None => String::new(),
-        Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+/// Detect the Simple Cache format version of `cache_path` (a `Cache_Data`
+/// directory) from its `index` file: a little-endian `u64` magic number
+/// followed by a `u32` version.
+#[must_use]
+pub fn detect_cache_format_version(cache_path: &Path) -> CacheFormatVersion {
+    let Ok(index) = fs::read(cache_path.join("index")) else {
+        return CacheFormatVersion::Unknown(0);
+    };
+    let magic = index
+        .get(..8)
+        .and_then(|b| b.try_into().ok())
+        .map(u64::from_le_bytes);
+    let version = index
+        .get(8..12)
+        .and_then(|b| b.try_into().ok())
+        .map(u32::from_le_bytes);
+    match (magic, version) {
+        (Some(SIMPLE_INITIAL_MAGIC), Some(version)) => CacheFormatVersion::from_number(version),
+        _ => CacheFormatVersion::Unknown(0),
     }
 }
 
@@ -404,6 +521,143 @@ fn capitalize_first(s: &str) -> String {
 mod tests {
     use super::*;
 
+    const SAMPLE_URL: &str =
+        "https://audio2.brain.fm/Tied_In_Strings_Focus_Deep_Work_Electronic_30_120bpm_HighNEL_Nrmlzd2_VBR5.mp3";
+
+    fn write_cache_index(index: &[u8]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("index"), index).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_detect_cache_format_version() {
+        for (number, expected) in [
+            (5, CacheFormatVersion::V5),
+            (6, CacheFormatVersion::V6),
+            (7, CacheFormatVersion::V7),
+            (8, CacheFormatVersion::V8),
+            (9, CacheFormatVersion::V9),
+            (10, CacheFormatVersion::Unknown(10)),
+        ] {
+            let mut index = SIMPLE_INITIAL_MAGIC.to_le_bytes().to_vec();
+            index.extend_from_slice(&u32::to_le_bytes(number));
+            index.extend_from_slice(&[0; 4]);
+            let dir = write_cache_index(&index);
+            assert_eq!(detect_cache_format_version(dir.path()), expected);
+        }
+
+        let dir = write_cache_index(&[0xAB; 16]);
+        assert_eq!(
+            detect_cache_format_version(dir.path()),
+            CacheFormatVersion::Unknown(0)
+        );
+        assert_eq!(
+            detect_cache_format_version(Path::new("/nonexistent/Cache_Data")),
+            CacheFormatVersion::Unknown(0)
+        );
+    }
+
+    #[test]
+    fn test_entry_body_offset_skips_header_and_key() {
+        let key = b"https://api.brain.fm/v3/users/abc/servings/recent";
+        let mut entry = SIMPLE_INITIAL_MAGIC.to_le_bytes().to_vec();
+        entry.extend_from_slice(&5u32.to_le_bytes());
+        entry.extend_from_slice(&u32::try_from(key.len()).unwrap().to_le_bytes());
+        entry.extend_from_slice(&[0; 8]); // key hash + padding
+        entry.extend_from_slice(key);
+        entry.extend_from_slice(b"body");
+
+        let offset = CacheFormatVersion::V9.entry_body_offset(&entry).unwrap();
+        assert_eq!(&entry[offset..], b"body");
+        assert_eq!(
+            CacheFormatVersion::V5.entry_body_offset(&entry),
+            Some(20 + key.len())
+        );
+        assert_eq!(
+            CacheFormatVersion::Unknown(3).entry_body_offset(&entry),
+            None
+        );
+        assert_eq!(CacheFormatVersion::V9.entry_body_offset(&entry[..10]), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_open_files_via_custom_lsof_path() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let cache_path = dir.join("Cache").join("Cache_Data");
+        fs::create_dir_all(&cache_path).unwrap();
+        fs::write(cache_path.join("f_000001_0"), SAMPLE_URL).unwrap();
+
+        // A mock lsof that records its args and lists one open cache file
+        let script = dir.join("mock-lsof");
+        let args_file = dir.join("args.txt");
+        fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\necho \"$*\" > '{}'\necho 'Brain.fm 1073 user 22u REG 1,4 0 1 {}/f_000001_0'\n",
+                args_file.display(),
+                cache_path.display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let lsof = LsofCommand {
+            path: script,
+            args: vec!["-p".into(), "1073".into()],
+        };
+        let names = open_cache_file_names(&lsof).unwrap();
+        let url = find_audio_url_via_open_files(&cache_path, &lsof).unwrap();
+        let state = read_state(dir, None, &lsof).unwrap();
+        let args = fs::read_to_string(&args_file).unwrap();
+
+        assert_eq!(names, ["f_000001_0"]);
+        assert_eq!(args.trim(), "-p 1073");
+        assert_eq!(url.as_deref(), Some(SAMPLE_URL));
+        assert!(state.is_playing);
+    }
+
+    #[test]
+    fn test_find_audio_url_in_binary_data() {
+        let mut data = vec![0xffu8; 1000];
+        data.extend_from_slice(b"https://audio.example.com/not-an-mp3\x00");
+        data.extend_from_slice(b"1/0/_dk_https://brain.fm ");
+        data.extend_from_slice(SAMPLE_URL.as_bytes());
+        data.extend_from_slice(&[0, 0xfe, 0xfd]);
+
+        assert_eq!(find_audio_url(&data).as_deref(), Some(SAMPLE_URL));
+    }
+
+    #[test]
+    fn test_find_audio_url_plain_http_and_missing() {
+        let http = SAMPLE_URL.replacen("https", "http", 1);
+        assert_eq!(find_audio_url(http.as_bytes()), Some(http));
+        assert_eq!(find_audio_url(b"no audio here"), None);
+    }
+
+    #[test]
+    fn test_find_audio_url_ios_cdn() {
+        let ios = SAMPLE_URL.replacen("audio2.brain.fm", "audio3.brainfm.com", 1);
+        let mut data = b"\x00\x01bplist00".to_vec();
+        data.extend_from_slice(ios.as_bytes());
+        assert_eq!(find_audio_url(&data), Some(ios));
+        assert_eq!(
+            find_audio_url(b"https://audio.brainfm.co/Track_Focus.mp3"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_find_audio_url_respects_search_limit() {
+        let mut data = vec![b'x'; AUDIO_URL_SEARCH_LIMIT];
+        data.extend_from_slice(SAMPLE_URL.as_bytes());
+        assert_eq!(find_audio_url(&data), None);
+    }
+
     #[test]
     fn test_parse_url() {
         let url = "https://audio2.brain.fm/NothingRemains_Focus_DeepWork_Piano_30_90bpm_HighNEL_Nrmlzd2_VBR5.mp3?token=123";
@@ -414,20 +668,4 @@ mod tests {
         assert_eq!(state.genre, Some("Piano".to_string()));
         assert_eq!(state.neural_effect, Some("High Neural Effect".to_string()));
     }
-
-    #[test]
-    fn test_camel_case() {
-        assert_eq!(split_camel_case("NothingRemains"), "Nothing Remains");
-        assert_eq!(split_camel_case("Simple"), "Simple");
-        assert_eq!(split_camel_case("MyLongTrackName"), "My Long Track Name");
-    }
-
-    #[test]
-    fn test_split_camel_case_acronyms() {
-        assert_eq!(split_camel_case("ABCName"), "ABC Name");
-        assert_eq!(split_camel_case("XMLParser"), "XML Parser");
-        assert_eq!(split_camel_case("getHTTPResponse"), "get HTTP Response");
-        assert_eq!(split_camel_case("ABC"), "ABC");
-        assert_eq!(split_camel_case("ABCdef"), "AB Cdef");
-    }
 }
@@ -0,0 +1,457 @@
+//! User configuration for Brain.fm Presence
+//!
+//! Configuration is optional — the app works with zero config. When present,
+//! settings are read from `~/.config/brainfm-presence/config.toml`. Any field
+//! missing from the file falls back to its default value.
+//!
+//! `ConfigWatcher` reloads the file whenever it is saved, so long-running
+//! binaries can apply changes without a restart.
+
+use crate::ScanMode;
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Runtime configuration, deserialized from TOML.
+#[allow(clippy::struct_excessive_bools)] // independent on/off settings, not a state machine
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Template for the Discord `details` line (e.g., `"{track} • {genre}"`).
+    ///
+    /// Placeholders are the keys returned by `BrainFmState::template_vars`.
+    pub details_template: String,
+
+    /// Template for the Discord `state` line (e.g., `"{mode}"`).
+    pub state_template: String,
+
+    /// Where the last state is saved so a restart can resume without
+    /// re-fetching metadata (default `~/.local/share/brainfm-presence/last_state.json`).
+    pub state_file_path: Option<PathBuf>,
+
+    /// Encrypt the state file and the API cache saved next to it with
+    /// `state_encryption_key`, so the track and session time are not
+    /// readable on disk. Off by default.
+    pub encrypt_state_file: bool,
+
+    /// 32-character passphrase the state file is encrypted with when
+    /// `encrypt_state_file` is on; the file is not saved without one.
+    pub state_encryption_key: Option<String>,
+
+    /// CSV log of state changes, read by `daily-report` (default
+    /// `~/.local/share/brainfm-presence/history.csv`). Unset to disable.
+    pub history_csv_path: Option<PathBuf>,
+
+    /// Which data sources to read (e.g., `"no_api"` to never make HTTP requests).
+    pub scan_mode: ScanMode,
+
+    /// Append the track's instruments to the Discord `details` line.
+    pub show_instruments_in_presence: bool,
+
+    /// Maximum number of instruments shown when `show_instruments_in_presence` is set.
+    pub max_instruments: usize,
+
+    /// Discord truncates `details` beyond this many characters; we truncate first
+    /// so the ellipsis is ours.
+    pub discord_details_max_len: usize,
+
+    /// Seconds between state reads (presence updates, `wait-for` polling).
+    pub update_interval_secs: u64,
+
+    /// Longest `Retry-After` (in seconds) the API client waits out before
+    /// retrying; longer rate limits skip the API until they reset.
+    pub max_retry_after_secs: u64,
+
+    /// Consecutive failures after which a data source (`LevelDB`, `lsof`, the
+    /// API, …) is skipped for `source_suspension_secs`.
+    pub max_source_failures: u32,
+
+    /// How long (in seconds) a repeatedly failing data source is skipped.
+    pub source_suspension_secs: u64,
+
+    /// Check that track images load before showing them, falling back to
+    /// the mode artwork on the Brain.fm CDN (e.g. offline). Each new image
+    /// is checked in the background and shown as it is until the check
+    /// answers. Off by default.
+    pub validate_image_urls: bool,
+
+    /// Clear the Discord presence (and show "Updating..." in the tray) once
+    /// the last successful read is this many seconds old, rather than
+    /// showing stale data.
+    pub max_data_staleness_secs: u64,
+
+    /// Keep the last playing state for this many seconds after playback
+    /// appears to stop, so a brief false pause between tracks doesn't clear
+    /// the presence (see `StateDebouncer`). 0 passes pauses on at once.
+    pub debounce_window_secs: u64,
+
+    /// Leave the Discord presence as it is while the playing state's
+    /// `confidence_score` is below this, rather than showing a guess.
+    pub min_confidence: f32,
+
+    /// Show a rotating set of simulated states (Focus, Sleep, Relax,
+    /// Meditate, idle) instead of reading Brain.fm, for showcasing the
+    /// presence. Also enabled by `--demo`.
+    pub demo_mode: bool,
+
+    /// Custom template for the `--html-overlay` file; the built-in card is
+    /// used when unset.
+    pub html_overlay_template: Option<PathBuf>,
+
+    /// Command Polybar runs when the `--polybar` module is clicked, e.g.
+    /// `"brainfm-presence open"`; the module is not clickable when unset.
+    pub polybar_click_command: Option<String>,
+
+    /// Emoji shown next to each mode, looked up by `util::mode_emoji`.
+    ///
+    /// Keyed by mode name, with `"default"` for anything unlisted. Entries in
+    /// the config file override the defaults one by one.
+    #[serde(deserialize_with = "merge_mode_emoji")]
+    pub mode_emoji: HashMap<String, String>,
+
+    /// `lsof` binary used to find Brain.fm's open cache files (default
+    /// `"lsof"` on `PATH`). Overridden by `BRAINFM_LSOF_PATH`.
+    pub lsof_path: PathBuf,
+
+    /// Replaces the default `lsof` arguments (`["-c", "Brain.fm"]`)
+    /// entirely. Overridden by `BRAINFM_LSOF_ARGS`, split on whitespace.
+    pub lsof_args_override: Option<Vec<String>>,
+
+    /// Discord application the presence is shown under; the built-in
+    /// Brain.fm Presence application when unset.
+    pub discord_app_id: Option<String>,
+
+    /// Also write the log to this file as JSON lines (see
+    /// `logging::RotatingJsonFile`). Read at startup.
+    pub log_file: Option<PathBuf>,
+
+    /// `log_file` is rotated to `<log_file>.1` before it grows past this
+    /// many bytes (default 10 MB); three rotated files are kept.
+    pub log_max_size_bytes: u64,
+
+    /// Most verbose level logged (`"info"` by default); `RUST_LOG` still
+    /// overrides it for the console. Read at startup.
+    pub log_level: log::LevelFilter,
+
+    /// Run the `BrainFM-Deep-Work` Shortcut when Deep Work starts playing,
+    /// e.g. to turn on a Focus mode (see `platform::macos::set_focus_mode`).
+    /// macOS only; off by default.
+    pub enable_macos_focus_mode: bool,
+
+    /// Turn Focus Assist on while a Focus mode plays and off when it stops
+    /// (see `platform::windows::set_focus_assist`). Windows only; off by
+    /// default.
+    pub enable_windows_focus_assist: bool,
+}
+
+/// Built-in `Config::mode_emoji` entries
+fn default_mode_emoji() -> HashMap<String, String> {
+    [
+        ("Focus", "🧠"),
+        ("Deep Work", "🧠"),
+        ("Sleep", "🌙"),
+        ("Relax", "🌿"),
+        ("Meditate", "🧘"),
+        ("default", "🎵"),
+    ]
+    .into_iter()
+    .map(|(mode, emoji)| (mode.to_string(), emoji.to_string()))
+    .collect()
+}
+
+/// Deserialize `mode_emoji` on top of the defaults instead of replacing them
+fn merge_mode_emoji<'de, D>(deserializer: D) -> Result<HashMap<String, String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let mut emoji = default_mode_emoji();
+    emoji.extend(HashMap::<String, String>::deserialize(deserializer)?);
+    Ok(emoji)
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            details_template: "{track}".to_string(),
+            state_template: "{mode}".to_string(),
+            state_file_path: dirs::home_dir().map(|home| {
+                home.join(".local")
+                    .join("share")
+                    .join("brainfm-presence")
+                    .join("last_state.json")
+            }),
+            encrypt_state_file: false,
+            state_encryption_key: None,
+            history_csv_path: dirs::home_dir().map(|home| {
+                home.join(".local")
+                    .join("share")
+                    .join("brainfm-presence")
+                    .join("history.csv")
+            }),
+            scan_mode: ScanMode::default(),
+            show_instruments_in_presence: false,
+            max_instruments: 2,
+            discord_details_max_len: 128,
+            update_interval_secs: crate::DEFAULT_UPDATE_INTERVAL.as_secs(),
+            max_retry_after_secs: crate::api_client::DEFAULT_MAX_RETRY_AFTER_SECS,
+            max_source_failures: crate::DEFAULT_MAX_SOURCE_FAILURES,
+            source_suspension_secs: crate::DEFAULT_SOURCE_SUSPENSION.as_secs(),
+            validate_image_urls: false,
+            max_data_staleness_secs: 60,
+            debounce_window_secs: crate::DEFAULT_DEBOUNCE_WINDOW.as_secs(),
+            min_confidence: 0.3,
+            demo_mode: false,
+            html_overlay_template: None,
+            polybar_click_command: None,
+            mode_emoji: default_mode_emoji(),
+            lsof_path: PathBuf::from("lsof"),
+            lsof_args_override: None,
+            discord_app_id: None,
+            log_file: None,
+            log_max_size_bytes: crate::logging::DEFAULT_LOG_MAX_SIZE_BYTES,
+            log_level: log::LevelFilter::Info,
+            enable_macos_focus_mode: false,
+            enable_windows_focus_assist: false,
+        }
+    }
+}
+
+impl Config {
+    /// Default config file location: `~/.config/brainfm-presence/config.toml`
+    #[must_use]
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::home_dir().map(|home| {
+            home.join(".config")
+                .join("brainfm-presence")
+                .join("config.toml")
+        })
+    }
+
+    /// Load the config from the default location.
+    ///
+    /// Never fails: a missing file yields the defaults, and an unreadable or
+    /// invalid file is logged and also yields the defaults. Environment
+    /// overrides (`BRAINFM_LSOF_PATH`, `BRAINFM_LSOF_ARGS`) apply either way.
+    #[must_use]
+    pub fn load() -> Self {
+        let mut config = Self::load_file();
+        config.apply_env(|name| std::env::var(name).ok());
+        config
+    }
+
+    fn load_file() -> Self {
+        let Some(path) = Self::default_path() else {
+            return Self::default();
+        };
+
+        if !path.exists() {
+            debug!("No config file at {}, using defaults", path.display());
+            return Self::default();
+        }
+
+        match Self::from_file(&path) {
+            Ok(config) => {
+                debug!("Loaded config from {}", path.display());
+                config
+            }
+            Err(e) => {
+                warn!("Ignoring invalid config file: {e:#}");
+                Self::default()
+            }
+        }
+    }
+
+    /// Apply environment variable overrides, read through `var`
+    fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) {
+        if let Some(path) = var("BRAINFM_LSOF_PATH").filter(|path| !path.is_empty()) {
+            self.lsof_path = PathBuf::from(path);
+        }
+        if let Some(args) = var("BRAINFM_LSOF_ARGS") {
+            self.lsof_args_override = Some(args.split_whitespace().map(str::to_string).collect());
+        }
+    }
+
+    /// `from_file` with the environment overrides `load()` applies
+    fn load_from(path: &Path) -> Result<Self> {
+        let mut config = Self::from_file(path)?;
+        config.apply_env(|name| std::env::var(name).ok());
+        Ok(config)
+    }
+
+    /// Load and parse a config file from an explicit path.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        toml::from_str(&text)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))
+    }
+}
+
+/// How long `ConfigWatcher` lets a save finish before reading the file
+const CONFIG_SETTLE_DELAY: Duration = Duration::from_millis(100);
+
+/// Watches a config file and sends a freshly loaded `Config` each time it
+/// is written.
+///
+/// The parent directory is watched rather than the file, so editors that
+/// save by replacing the file are followed too. Saves that fail to parse
+/// are logged and skipped. Watching stops when the watcher is dropped.
+pub struct ConfigWatcher {
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl ConfigWatcher {
+    /// Start watching `path`, sending each reloaded config to `sender`
+    pub fn watch(path: &Path, sender: mpsc::Sender<Config>) -> Result<Self> {
+        use notify::Watcher as _;
+
+        let dir = path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."))
+            .to_path_buf();
+        let file_name = path.file_name().context("Config path has no file name")?;
+        let file_name = file_name.to_os_string();
+        let config_path = path.to_path_buf();
+        // Only changes are sent; saving the file unchanged does nothing
+        let mut last_config = Config::load_from(path).ok();
+
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(e) => {
+                        warn!("Config watcher error: {e}");
+                        return;
+                    }
+                };
+                let written = matches!(
+                    event.kind,
+                    notify::EventKind::Create(_) | notify::EventKind::Modify(_)
+                );
+                if !written
+                    || !event
+                        .paths
+                        .iter()
+                        .any(|changed| changed.file_name() == Some(file_name.as_os_str()))
+                {
+                    return;
+                }
+                // A save is several events (truncate, write, ...); reading
+                // right away could see a half-written file
+                std::thread::sleep(CONFIG_SETTLE_DELAY);
+                match Config::load_from(&config_path) {
+                    Ok(config) if last_config.as_ref() != Some(&config) => {
+                        last_config = Some(config.clone());
+                        let _ = sender.send(config);
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!("Ignoring invalid config file: {e:#}"),
+                }
+            })
+            .context("Failed to create the config watcher")?;
+        watcher
+            .watch(&dir, notify::RecursiveMode::NonRecursive)
+            .with_context(|| format!("Failed to watch {}", dir.display()))?;
+        debug!("Watching {} for config changes", path.display());
+
+        Ok(Self { _watcher: watcher })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_config_uses_defaults() {
+        let config: Config = toml::from_str(r#"details_template = "{track} • {genre}""#).unwrap();
+        assert_eq!(config.details_template, "{track} • {genre}");
+        assert_eq!(config.state_template, "{mode}");
+    }
+
+    #[test]
+    fn test_mode_emoji_overrides_merge_with_defaults() {
+        let config: Config = toml::from_str(
+            r#"
+            [mode_emoji]
+            Focus = "🎯"
+            Energize = "⚡"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.mode_emoji["Focus"], "🎯");
+        assert_eq!(config.mode_emoji["Energize"], "⚡");
+        assert_eq!(config.mode_emoji["Sleep"], "🌙");
+        assert_eq!(config.mode_emoji["default"], "🎵");
+    }
+
+    #[test]
+    fn test_lsof_env_overrides() {
+        let mut config: Config = toml::from_str(
+            r#"
+            lsof_path = "/opt/homebrew/bin/lsof"
+            lsof_args_override = ["-c", "Brain"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.lsof_path, PathBuf::from("/opt/homebrew/bin/lsof"));
+
+        config.apply_env(|name| match name {
+            "BRAINFM_LSOF_PATH" => Some("/usr/sbin/lsof".into()),
+            "BRAINFM_LSOF_ARGS" => Some(" -p  1073 ".into()),
+            _ => None,
+        });
+        assert_eq!(config.lsof_path, PathBuf::from("/usr/sbin/lsof"));
+        assert_eq!(
+            config.lsof_args_override,
+            Some(vec!["-p".to_string(), "1073".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_log_settings() {
+        let config: Config = toml::from_str(
+            r#"
+            log_file = "/var/log/brainfm-presence.log"
+            log_level = "debug"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.log_file,
+            Some(PathBuf::from("/var/log/brainfm-presence.log"))
+        );
+        assert_eq!(config.log_level, log::LevelFilter::Debug);
+        assert_eq!(config.log_max_size_bytes, 10 * 1024 * 1024);
+    }
+
+    #[test]
+    fn test_empty_config_is_default() {
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn test_config_watcher_sends_reloaded_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, r#"details_template = "{track}""#).unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let _watcher = ConfigWatcher::watch(&path, tx).unwrap();
+        std::fs::write(
+            &path,
+            "details_template = \"{track} • {genre}\"\nscan_mode = \"no_api\"",
+        )
+        .unwrap();
+
+        let config = rx.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!(config.details_template, "{track} • {genre}");
+        assert_eq!(config.scan_mode, ScanMode::NoApi);
+    }
+}
@@ -0,0 +1,216 @@
+//! Typed errors for callers that need to tell failures apart
+//!
+//! Most of the crate returns `anyhow::Error`. `BrainFmError` travels inside
+//! it where a caller may want to react to a specific failure (use
+//! `downcast_ref::<BrainFmError>()`), and converts from the error types of
+//! the crate's dependencies so `?` works in functions returning it.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::string::FromUtf8Error;
+use std::time::Duration;
+
+/// Errors callers may want to tell apart
+#[derive(Debug, thiserror::Error)]
+pub enum BrainFmError {
+    /// `poll_until` gave up before the predicate held
+    #[error("timed out after {:.1}s waiting for Brain.fm state", elapsed.as_secs_f64())]
+    TimeoutWaiting {
+        /// Time spent polling
+        elapsed: Duration,
+    },
+
+    /// Reading or writing a file failed
+    #[error("{}", io_message(path.as_deref()))]
+    IoError {
+        /// The file involved, when known
+        path: Option<PathBuf>,
+        source: io::Error,
+    },
+
+    /// An API answered with an error status
+    #[error("API returned HTTP {status}{}", error_body_message(body))]
+    ApiHttpError {
+        /// The HTTP status code
+        status: u16,
+        /// How long a 429 response asked to wait before retrying
+        retry_after: Option<Duration>,
+        /// The response body, usually the API's explanation (empty if
+        /// there was none)
+        body: String,
+    },
+
+    /// An API request got no response (boxed, as `ureq::Error` is large)
+    #[error("API request failed: {0}")]
+    ApiNetworkError(Box<ureq::Error>),
+
+    /// JSON did not parse, usually an API response that was not the JSON
+    /// expected
+    #[error("invalid JSON: {0}")]
+    CacheParseError(#[from] serde_json::Error),
+
+    /// A regex did not compile
+    #[error(transparent)]
+    Regex(#[from] regex::Error),
+
+    /// Inflating compressed data failed
+    #[error(transparent)]
+    Decompress(#[from] flate2::DecompressError),
+
+    /// Bytes were not valid UTF-8
+    #[error(transparent)]
+    Utf8(#[from] FromUtf8Error),
+
+    /// Another error with a message describing what was being done
+    #[error("{message}")]
+    Context {
+        /// What was being done
+        message: String,
+        source: Box<BrainFmError>,
+    },
+}
+
+impl BrainFmError {
+    /// An I/O error on `path`
+    pub fn io(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        Self::IoError {
+            path: Some(path.into()),
+            source,
+        }
+    }
+
+    /// Describe what was being done when the error happened.
+    ///
+    /// The error is wrapped in `Context`; `root()` still returns the
+    /// original variant, so matching on it keeps working.
+    #[must_use]
+    pub fn context(self, message: impl Into<String>) -> Self {
+        Self::Context {
+            message: message.into(),
+            source: Box::new(self),
+        }
+    }
+
+    /// The error beneath any `context()` layers
+    #[must_use]
+    pub fn root(&self) -> &Self {
+        match self {
+            Self::Context { source, .. } => source.root(),
+            other => other,
+        }
+    }
+}
+
+/// `ApiHttpError`'s explanation: `": <body>"`, trimmed and truncated, or
+/// nothing for an empty body
+fn error_body_message(body: &str) -> String {
+    match body.trim() {
+        "" => String::new(),
+        body => format!(": {}", crate::util::truncate(body, MAX_ERROR_BODY_CHARS)),
+    }
+}
+
+/// Longest response body an `ApiHttpError` message quotes
+const MAX_ERROR_BODY_CHARS: usize = 200;
+
+/// `IoError` message, naming the file when it is known. Like `Context`, it
+/// leaves the cause to `source()`, so `{:#}` does not repeat it.
+fn io_message(path: Option<&Path>) -> String {
+    match path {
+        Some(path) => format!("I/O error on {}", path.display()),
+        None => "I/O error".to_string(),
+    }
+}
+
+impl From<io::Error> for BrainFmError {
+    fn from(source: io::Error) -> Self {
+        Self::IoError { path: None, source }
+    }
+}
+
+/// An error status becomes an `ApiHttpError` (without a body, which
+/// `ureq` does not keep), anything else an `ApiNetworkError`, as from
+/// `util::check_status`
+impl From<ureq::Error> for BrainFmError {
+    fn from(source: ureq::Error) -> Self {
+        match source {
+            ureq::Error::StatusCode(status) => Self::ApiHttpError {
+                status,
+                retry_after: None,
+                body: String::new(),
+            },
+            source => Self::ApiNetworkError(Box::new(source)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error as _;
+
+    fn not_found() -> io::Error {
+        io::Error::new(io::ErrorKind::NotFound, "no such file")
+    }
+
+    #[test]
+    fn test_io_error_converts_to_io_variant() {
+        fn open() -> Result<(), BrainFmError> {
+            Err(not_found())?;
+            Ok(())
+        }
+
+        let err = open().unwrap_err();
+        assert!(matches!(err, BrainFmError::IoError { path: None, .. }));
+        assert_eq!(err.to_string(), "I/O error");
+        assert_eq!(err.source().unwrap().to_string(), "no such file");
+
+        let err = BrainFmError::io("/tmp/state.json", not_found());
+        assert_eq!(err.to_string(), "I/O error on /tmp/state.json");
+    }
+
+    #[test]
+    fn test_dependency_errors_convert() {
+        let json = serde_json::from_str::<u32>("x").unwrap_err();
+        assert!(matches!(
+            BrainFmError::from(json),
+            BrainFmError::CacheParseError(_)
+        ));
+
+        let unclosed = String::from("(");
+        let regex = regex::Regex::new(&unclosed).unwrap_err();
+        assert!(matches!(BrainFmError::from(regex), BrainFmError::Regex(_)));
+
+        let utf8 = String::from_utf8(vec![0xff]).unwrap_err();
+        let err = BrainFmError::from(utf8);
+        assert!(matches!(err, BrainFmError::Utf8(_)));
+        assert!(err.to_string().contains("invalid utf-8"));
+
+        let http = BrainFmError::from(ureq::Error::StatusCode(500));
+        assert!(matches!(
+            http,
+            BrainFmError::ApiHttpError {
+                status: 500,
+                retry_after: None,
+                ..
+            }
+        ));
+        assert_eq!(http.to_string(), "API returned HTTP 500");
+        let network = BrainFmError::from(ureq::Error::ConnectionFailed);
+        assert!(matches!(network, BrainFmError::ApiNetworkError(_)));
+    }
+
+    #[test]
+    fn test_context_keeps_root_variant() {
+        let err = BrainFmError::from(not_found())
+            .context("reading state")
+            .context("restoring");
+
+        assert_eq!(err.to_string(), "restoring");
+        assert!(matches!(err.root(), BrainFmError::IoError { .. }));
+        assert_eq!(
+            format!("{:#}", anyhow::Error::from(err)),
+            "restoring: reading state: I/O error: no such file"
+        );
+    }
+}
@@ -0,0 +1,710 @@
+//! Playback history for Brain.fm sessions
+//!
+//! `BrainFmStateHistory` records each state change with a timestamp. An
+//! entry lasts until the next one, and the latest entry until now, which is
+//! what the duration queries add up.
+//!
+//! The history can be saved as CSV (`to_csv` / `from_csv`), one row per
+//! entry with the fields reports need and how many repeated reads the entry
+//! absorbed. Entries past the size cap move to an archive file next to
+//! it when saved, so reports can still cover older days.
+
+use crate::BrainFmState;
+use anyhow::{Context, Result};
+use chrono::{Local, NaiveDate, NaiveTime, TimeDelta};
+use log::warn;
+use std::collections::{HashMap, VecDeque};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Maximum number of entries kept; the oldest move to `evicted` first
+const MAX_HISTORY_ENTRIES: usize = 10_000;
+
+/// Header row of the CSV format
+const CSV_HEADER: &str =
+    "timestamp,is_playing,mode,activity,track_name,genre,neural_effect,stability";
+
+/// A state and when it was first seen
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    /// When this state was recorded
+    pub timestamp: SystemTime,
+
+    /// The recorded state
+    pub state: BrainFmState,
+
+    /// How many later pushes repeated this state and were skipped; a
+    /// measure of how stable it was
+    pub compressed_count: u32,
+}
+
+/// Bounded, time-ordered log of Brain.fm states
+#[derive(Debug, Clone, Default)]
+pub struct BrainFmStateHistory {
+    entries: VecDeque<HistoryEntry>,
+
+    /// Entries the cap dropped since the last `save_csv`, which appends
+    /// them to the archive. Bounded by the cap too, for histories that are
+    /// never saved.
+    evicted: VecDeque<HistoryEntry>,
+}
+
+impl BrainFmStateHistory {
+    /// Create an empty history
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record `state` as of now, returning whether an entry was added.
+    ///
+    /// A state without a `BrainFmState::meaningful_change` from the latest
+    /// entry only bumps that entry's `compressed_count`, so polling the
+    /// reader every few seconds only adds an entry when something changed.
+    pub fn push(&mut self, state: BrainFmState) -> bool {
+        self.push_at(state, SystemTime::now())
+    }
+
+    fn push_at(&mut self, state: BrainFmState, timestamp: SystemTime) -> bool {
+        if let Some(last) = self.entries.back_mut() {
+            if !state.meaningful_change(&last.state) {
+                last.compressed_count = last.compressed_count.saturating_add(1);
+                return false;
+            }
+        }
+        self.push_entry(HistoryEntry {
+            timestamp,
+            state,
+            compressed_count: 0,
+        });
+        true
+    }
+
+    /// Append `entry` as is, evicting the oldest entry when full
+    fn push_entry(&mut self, entry: HistoryEntry) {
+        self.entries.push_back(entry);
+        if self.entries.len() > MAX_HISTORY_ENTRIES {
+            self.evicted.extend(self.entries.pop_front());
+            if self.evicted.len() > MAX_HISTORY_ENTRIES {
+                self.evicted.pop_front();
+            }
+        }
+    }
+
+    /// All entries, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &HistoryEntry> {
+        self.entries.iter()
+    }
+
+    /// The most recent entry
+    #[must_use]
+    pub fn latest(&self) -> Option<&HistoryEntry> {
+        self.entries.back()
+    }
+
+    /// The latest entry of each of the last `n` distinct tracks played,
+    /// newest first
+    #[must_use]
+    pub fn recent_tracks(&self, n: usize) -> Vec<&HistoryEntry> {
+        let mut recent: Vec<&HistoryEntry> = Vec::with_capacity(n);
+        for entry in self.entries.iter().rev() {
+            if recent.len() == n {
+                break;
+            }
+            let Some(track) = entry.state.track_name.as_deref() else {
+                continue;
+            };
+            if entry.state.is_playing
+                && !recent
+                    .iter()
+                    .any(|seen| seen.state.track_name.as_deref() == Some(track))
+            {
+                recent.push(entry);
+            }
+        }
+        recent
+    }
+
+    /// Number of recorded entries
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether nothing has been recorded yet
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Entries recorded in `start..end` (start inclusive, end exclusive)
+    #[must_use]
+    pub fn entries_in_range(&self, start: SystemTime, end: SystemTime) -> Vec<&HistoryEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| (start..end).contains(&entry.timestamp))
+            .collect()
+    }
+
+    /// Entries recorded since midnight in the local timezone
+    #[must_use]
+    pub fn entries_today(&self) -> Vec<&HistoryEntry> {
+        match local_day(Local::now().date_naive()) {
+            Some((start, end)) => self.entries_in_range(start, end),
+            None => Vec::new(),
+        }
+    }
+
+    /// A history holding only the entries recorded on `date` (local time)
+    #[must_use]
+    pub fn for_date(&self, date: NaiveDate) -> Self {
+        let entries = local_day(date).map_or_else(VecDeque::new, |(start, end)| {
+            self.entries_in_range(start, end)
+                .into_iter()
+                .cloned()
+                .collect()
+        });
+        Self {
+            entries,
+            ..Self::default()
+        }
+    }
+
+    /// Total playing time per mode.
+    ///
+    /// Paused entries and entries without a mode are not counted.
+    #[must_use]
+    pub fn total_duration_by_mode(&self) -> HashMap<String, Duration> {
+        self.total_duration_by_mode_at(SystemTime::now())
+    }
+
+    pub(crate) fn total_duration_by_mode_at(&self, now: SystemTime) -> HashMap<String, Duration> {
+        let mut totals: HashMap<String, Duration> = HashMap::new();
+        for (entry, duration) in self.spans(now) {
+            if let Some(mode) = playing_mode(&entry.state) {
+                *totals.entry(mode.to_string()).or_default() += duration;
+            }
+        }
+        totals
+    }
+
+    /// Longest uninterrupted stretch of playing `mode`.
+    ///
+    /// Track changes within the mode continue the streak; pausing or
+    /// switching mode ends it.
+    #[must_use]
+    pub fn longest_streak(&self, mode: &str) -> Duration {
+        self.longest_streak_at(mode, SystemTime::now())
+    }
+
+    fn longest_streak_at(&self, mode: &str, now: SystemTime) -> Duration {
+        self.longest_streak_by(now, |state| playing_mode(state) == Some(mode))
+    }
+
+    /// Longest run of consecutive entries matching `is_part`, timed up to `now`
+    pub(crate) fn longest_streak_by(
+        &self,
+        now: SystemTime,
+        is_part: impl Fn(&BrainFmState) -> bool,
+    ) -> Duration {
+        let mut longest = Duration::ZERO;
+        let mut current = Duration::ZERO;
+        for (entry, duration) in self.spans(now) {
+            if is_part(&entry.state) {
+                current += duration;
+                longest = longest.max(current);
+            } else {
+                current = Duration::ZERO;
+            }
+        }
+        longest
+    }
+
+    /// Each entry paired with how long it lasted: until the next entry, or
+    /// until `now` for the latest one
+    fn spans(&self, now: SystemTime) -> impl Iterator<Item = (&HistoryEntry, Duration)> {
+        let ends = self
+            .entries
+            .iter()
+            .skip(1)
+            .map(|next| next.timestamp)
+            .chain(std::iter::once(now));
+        self.entries.iter().zip(ends).map(|(entry, end)| {
+            let duration = end.duration_since(entry.timestamp).unwrap_or_default();
+            (entry, duration)
+        })
+    }
+}
+
+impl FromIterator<HistoryEntry> for BrainFmStateHistory {
+    /// A history of `entries` as they are (oldest first), without merging
+    /// repeated states
+    fn from_iter<I: IntoIterator<Item = HistoryEntry>>(entries: I) -> Self {
+        let mut history = Self::new();
+        for entry in entries {
+            history.push_entry(entry);
+        }
+        history
+    }
+}
+
+// ---------------------------------------------------------------------------
+// CSV persistence
+// ---------------------------------------------------------------------------
+
+impl BrainFmStateHistory {
+    /// Serialize as CSV with a header row.
+    ///
+    /// Timestamps are Unix seconds and `stability` is the entry's
+    /// `compressed_count`. Only the fields reports use are kept, so
+    /// `from_csv(to_csv())` drops the rest (session time, BPM, …).
+    #[must_use]
+    pub fn to_csv(&self) -> String {
+        csv_rows(&self.entries)
+    }
+
+    /// Parse CSV written by `to_csv`. Files from before the `stability`
+    /// column are read with a `compressed_count` of 0.
+    ///
+    /// Rows that fail to parse are skipped rather than failing the whole
+    /// file; their 1-based line numbers are returned with the history.
+    #[must_use]
+    pub fn from_csv(text: &str) -> (Self, Vec<usize>) {
+        let (entries, skipped) = parse_csv(text);
+        (entries.into_iter().collect(), skipped)
+    }
+
+    /// Load a CSV history file, with the line numbers of skipped rows as
+    /// in `from_csv`. Only failing to read the file is an error.
+    pub fn load_csv(path: &Path) -> Result<(Self, Vec<usize>)> {
+        let (entries, skipped) = read_csv(path)?;
+        Ok((entries.into_iter().collect(), skipped))
+    }
+
+    /// Load a CSV history file after the entries archived next to it by
+    /// `save_csv`, without the size cap, for reports on older days
+    pub fn load_csv_with_archive(path: &Path) -> Result<Self> {
+        let mut entries = VecDeque::new();
+        let archive = archive_path(path);
+        if archive.exists() {
+            entries.extend(read_csv(&archive)?.0);
+        }
+        entries.extend(read_csv(path)?.0);
+        Ok(Self {
+            entries,
+            ..Self::default()
+        })
+    }
+
+    /// Save as a CSV file, replacing it atomically.
+    ///
+    /// Entries the cap dropped since the last save are first appended to
+    /// the archive next to it (`history.archive.csv` for `history.csv`).
+    pub fn save_csv(&mut self, path: &Path) -> Result<()> {
+        if !self.evicted.is_empty() {
+            let archive = archive_path(path);
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&archive)
+                .with_context(|| format!("Failed to open history archive {}", archive.display()))?;
+            let mut rows = csv_rows(&self.evicted);
+            if file.metadata().is_ok_and(|metadata| metadata.len() > 0) {
+                // The header is already there
+                rows.drain(..=CSV_HEADER.len());
+            }
+            file.write_all(rows.as_bytes()).with_context(|| {
+                format!("Failed to write history archive {}", archive.display())
+            })?;
+            self.evicted.clear();
+        }
+        crate::util::write_file_atomic(path, self.to_csv().as_bytes())
+    }
+}
+
+/// Where `save_csv` archives the entries evicted from the history at `path`
+fn archive_path(path: &Path) -> PathBuf {
+    path.with_extension("archive.csv")
+}
+
+/// `entries` as CSV with a header row
+fn csv_rows<'a>(entries: impl IntoIterator<Item = &'a HistoryEntry>) -> String {
+    let mut csv = format!("{CSV_HEADER}\n");
+    for entry in entries {
+        let state = &entry.state;
+        let secs = entry
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let fields = [
+            secs.to_string(),
+            state.is_playing.to_string(),
+            csv_field(state.mode.as_deref()),
+            csv_field(state.activity.as_deref()),
+            csv_field(state.track_name.as_deref()),
+            csv_field(state.genre.as_deref()),
+            csv_field(state.neural_effect.as_deref()),
+            entry.compressed_count.to_string(),
+        ];
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Read and parse the CSV file at `path`, warning about skipped rows
+fn read_csv(path: &Path) -> Result<(Vec<HistoryEntry>, Vec<usize>)> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read history {}", path.display()))?;
+    let (entries, skipped) = parse_csv(&text);
+    if !skipped.is_empty() {
+        warn!(
+            "Skipped unreadable rows {skipped:?} of history {}",
+            path.display()
+        );
+    }
+    Ok((entries, skipped))
+}
+
+/// The entries of a CSV history and the line numbers of the rows skipped
+fn parse_csv(text: &str) -> (Vec<HistoryEntry>, Vec<usize>) {
+    let mut entries = Vec::new();
+    let mut skipped = Vec::new();
+    for (row, record) in csv_records(text).skip(1) {
+        if record.trim().is_empty() {
+            continue;
+        }
+        match parse_csv_row(&record) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => {
+                warn!("Skipping history row {row}: {e:#}");
+                skipped.push(row);
+            }
+        }
+    }
+    (entries, skipped)
+}
+
+/// Each CSV record with the line number it starts on. A quoted field may
+/// hold line breaks (see `csv_field`), so a line with an open quote
+/// continues on the next.
+fn csv_records(text: &str) -> impl Iterator<Item = (usize, String)> + '_ {
+    let mut lines = text.lines().enumerate();
+    std::iter::from_fn(move || {
+        let (index, line) = lines.next()?;
+        let mut record = line.to_string();
+        while record.matches('"').count() % 2 == 1 {
+            let Some((_, next)) = lines.next() else {
+                break;
+            };
+            record.push('\n');
+            record.push_str(next);
+        }
+        Some((index + 1, record))
+    })
+}
+
+/// Parse one CSV row written by `csv_rows`
+fn parse_csv_row(line: &str) -> Result<HistoryEntry> {
+    let mut fields = split_csv_line(line);
+    let compressed_count = if fields.len() == 8 {
+        let stability = fields.pop().unwrap_or_default();
+        stability.parse().context("Invalid stability")?
+    } else {
+        0
+    };
+    let [timestamp, is_playing, mode, activity, track_name, genre, neural_effect] =
+        <[String; 7]>::try_from(fields)
+            .map_err(|fields| anyhow::anyhow!("{} fields, expected 8", fields.len()))?;
+    let secs: u64 = timestamp.parse().context("Invalid timestamp")?;
+    let optional = |value: String| (!value.is_empty()).then_some(value);
+    Ok(HistoryEntry {
+        timestamp: UNIX_EPOCH + Duration::from_secs(secs),
+        state: BrainFmState {
+            is_playing: is_playing == "true",
+            mode: optional(mode),
+            activity: optional(activity),
+            track_name: optional(track_name),
+            genre: optional(genre),
+            neural_effect: optional(neural_effect),
+            ..BrainFmState::default()
+        },
+        compressed_count,
+    })
+}
+
+/// Quote a CSV field when it contains a delimiter, quote or line break
+fn csv_field(value: Option<&str>) -> String {
+    let value = value.unwrap_or_default();
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Split one CSV record into fields, undoing `csv_field` quoting
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// The mode of a playing state
+fn playing_mode(state: &BrainFmState) -> Option<&str> {
+    state.is_playing.then_some(state.mode.as_deref()).flatten()
+}
+
+/// Start and end of `date` in the local timezone
+pub(crate) fn local_day(date: NaiveDate) -> Option<(SystemTime, SystemTime)> {
+    Some((local_midnight(date)?, local_midnight(date.succ_opt()?)?))
+}
+
+/// Start of `date` in the local timezone.
+///
+/// Where a DST change skips midnight, the day starts an hour later; where
+/// midnight happens twice, the earlier one is used.
+fn local_midnight(date: NaiveDate) -> Option<SystemTime> {
+    let midnight = date.and_time(NaiveTime::MIN);
+    midnight
+        .and_local_timezone(Local)
+        .earliest()
+        .or_else(|| {
+            (midnight + TimeDelta::hours(1))
+                .and_local_timezone(Local)
+                .earliest()
+        })
+        .map(SystemTime::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    fn playing(mode: &str, track: &str) -> BrainFmState {
+        BrainFmState {
+            mode: Some(mode.into()),
+            track_name: Some(track.into()),
+            is_playing: true,
+            ..Default::default()
+        }
+    }
+
+    /// Two sessions a day apart: yesterday's Sleep, then Focus and Relax
+    /// ending `now`
+    fn two_day_history(now: SystemTime) -> BrainFmStateHistory {
+        let mut history = BrainFmStateHistory::new();
+        let yesterday = now - 25 * HOUR;
+        history.push_at(playing("Sleep", "Night"), yesterday);
+        history.push_at(BrainFmState::new(), yesterday + 2 * HOUR);
+
+        let today = now - Duration::from_secs(40 * 60);
+        history.push_at(playing("Focus", "A"), today);
+        history.push_at(playing("Focus", "B"), today + Duration::from_secs(10 * 60));
+        history.push_at(playing("Relax", "C"), today + Duration::from_secs(30 * 60));
+        history.push_at(BrainFmState::new(), now);
+        history
+    }
+
+    #[test]
+    fn test_push_skips_repeated_state() {
+        let mut history = BrainFmStateHistory::new();
+        history.push(playing("Focus", "A"));
+        history.push(playing("Focus", "A"));
+        history.push(playing("Focus", "B"));
+        assert_eq!(history.len(), 2);
+    }
+
+    #[test]
+    fn test_push_compresses_unchanged_states() {
+        let mut history = BrainFmStateHistory::new();
+        for second in 0..10 {
+            let mut state = playing("Focus", "A");
+            state.session_time = Some(format!("0:{second:02}"));
+            history.push(state);
+        }
+        assert_eq!(history.len(), 1);
+        assert_eq!(history.latest().unwrap().compressed_count, 9);
+
+        let mut paused = playing("Focus", "A");
+        paused.is_playing = false;
+        assert!(history.push(paused));
+        assert_eq!(history.latest().unwrap().compressed_count, 0);
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut history = two_day_history(now);
+        let mut quoted = playing("Focus", "Rain, \"Heavy\"");
+        quoted.genre = Some("Piano".into());
+        history.push_at(quoted.clone(), now + HOUR);
+        // Same track, so only the stability goes up
+        quoted.genre = Some("Ambient".into());
+        history.push_at(quoted, now + 2 * HOUR);
+
+        let csv = history.to_csv();
+        assert!(csv.starts_with(CSV_HEADER));
+        assert!(csv.contains(r#""Rain, ""Heavy""",Piano,,1"#));
+
+        // A line break inside a field stays within its row
+        let mut multiline = playing("Relax", "Waves");
+        multiline.activity = Some("Line one\nline \"two\", three".into());
+        history.push_at(multiline, now + 3 * HOUR);
+        history.push_at(playing("Relax", "After"), now + 4 * HOUR);
+        let csv = history.to_csv();
+
+        let (parsed, skipped) = BrainFmStateHistory::from_csv(&csv);
+        assert!(skipped.is_empty());
+        assert_eq!(
+            parsed.iter().collect::<Vec<_>>(),
+            history.iter().collect::<Vec<_>>()
+        );
+
+        // Written before the stability column
+        let (old, _) = BrainFmStateHistory::from_csv(
+            "timestamp,is_playing,mode,activity,track_name,genre,neural_effect\n1,true,Focus,,A,,\n",
+        );
+        assert_eq!(old.latest().unwrap().compressed_count, 0);
+    }
+
+    #[test]
+    fn test_from_csv_skips_bad_rows() {
+        let rows = [
+            "1,true,Focus,,A,,,0",
+            "1,true",
+            "soon,true,Focus,,B,,,0",
+            "2,false,,,,,,x",
+            "3,true,Relax,,C,,,2",
+        ];
+        let csv = format!("{CSV_HEADER}\n{}\n", rows.join("\n"));
+        let (history, skipped) = BrainFmStateHistory::from_csv(&csv);
+        assert_eq!(skipped, [3, 4, 5]);
+        let tracks: Vec<_> = history
+            .iter()
+            .filter_map(|entry| entry.state.track_name.as_deref())
+            .collect();
+        assert_eq!(tracks, ["A", "C"]);
+    }
+
+    #[test]
+    fn test_save_csv_archives_evicted_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.csv");
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut history = BrainFmStateHistory::new();
+        for i in 0..MAX_HISTORY_ENTRIES + 3 {
+            let track = format!("Track {i}");
+            let secs = u64::try_from(i).unwrap() * 60;
+            history.push_at(playing("Focus", &track), start + Duration::from_secs(secs));
+        }
+        history.save_csv(&path).unwrap();
+        history.push_at(playing("Focus", "Last"), SystemTime::now());
+        history.save_csv(&path).unwrap();
+
+        let (saved, skipped) = BrainFmStateHistory::load_csv(&path).unwrap();
+        assert!(skipped.is_empty());
+        assert_eq!(saved.len(), MAX_HISTORY_ENTRIES);
+        let archive = std::fs::read_to_string(dir.path().join("history.archive.csv")).unwrap();
+        assert_eq!(archive.matches(CSV_HEADER).count(), 1);
+        assert_eq!(archive.lines().count(), 5);
+
+        let full = BrainFmStateHistory::load_csv_with_archive(&path).unwrap();
+        assert_eq!(full.len(), MAX_HISTORY_ENTRIES + 4);
+        assert_eq!(
+            full.iter().next().unwrap().state.track_name.as_deref(),
+            Some("Track 0")
+        );
+    }
+
+    #[test]
+    fn test_entries_today_excludes_yesterday() {
+        let now = SystemTime::now();
+        let history = two_day_history(now);
+
+        // The recent session may straddle midnight, but its end (`now`) is
+        // always today and yesterday's Sleep session never is
+        let today = history.entries_today();
+        assert!(today.iter().any(|entry| entry.timestamp == now));
+        assert!(today
+            .iter()
+            .all(|entry| entry.state.track_name.as_deref() != Some("Night")));
+
+        let range = history.entries_in_range(now - 26 * HOUR, now - 20 * HOUR);
+        assert_eq!(range.len(), 2);
+    }
+
+    #[test]
+    fn test_recent_tracks_newest_first_without_repeats() {
+        let now = SystemTime::now();
+        let mut history = two_day_history(now);
+        history.push_at(playing("Focus", "A"), now);
+
+        let tracks = |history: &BrainFmStateHistory, n| -> Vec<String> {
+            history
+                .recent_tracks(n)
+                .into_iter()
+                .filter_map(|entry| entry.state.track_name.clone())
+                .collect()
+        };
+        assert_eq!(tracks(&history, 5), ["A", "C", "B", "Night"]);
+        assert_eq!(tracks(&history, 2), ["A", "C"]);
+
+        // Collecting entries keeps them as they are
+        let copy: BrainFmStateHistory = history
+            .recent_tracks(2)
+            .into_iter()
+            .rev()
+            .cloned()
+            .collect();
+        assert_eq!(copy.len(), 2);
+        assert_eq!(tracks(&copy, 5), ["A", "C"]);
+    }
+
+    #[test]
+    fn test_total_duration_by_mode() {
+        let now = SystemTime::now();
+        let totals = two_day_history(now).total_duration_by_mode_at(now);
+
+        assert_eq!(totals["Sleep"], 2 * HOUR);
+        assert_eq!(totals["Focus"], Duration::from_secs(30 * 60));
+        assert_eq!(totals["Relax"], Duration::from_secs(10 * 60));
+        assert_eq!(totals.len(), 3);
+    }
+
+    #[test]
+    fn test_longest_streak_spans_track_changes() {
+        let now = SystemTime::now();
+        let mut history = two_day_history(now);
+        assert_eq!(
+            history.longest_streak_at("Focus", now),
+            Duration::from_secs(30 * 60)
+        );
+
+        history.push_at(playing("Focus", "D"), now);
+        let later = now + Duration::from_secs(5 * 60);
+        assert_eq!(
+            history.longest_streak_at("Focus", later),
+            Duration::from_secs(30 * 60)
+        );
+        assert_eq!(history.longest_streak_at("Meditate", later), Duration::ZERO);
+    }
+}
@@ -0,0 +1,273 @@
+//! `ActivityWatch` integration for time tracking
+//!
+//! `create_bucket` registers a `brainfm` bucket with a local `ActivityWatch`
+//! server (`aw-server`, port 5600 by default), and `send_heartbeat` records
+//! the current mode, track, genre, neural effect and BPM in it.
+//! `ActivityWatchPublisher` sends a heartbeat every `HEARTBEAT_INTERVAL`
+//! while playing, and right away when the data changes; `ActivityWatch`
+//! merges consecutive heartbeats with the same data into one event.
+//!
+//! See <https://docs.activitywatch.net/en/latest/api/rest.html>.
+
+use crate::util::{self, HttpAuth};
+use crate::BrainFmState;
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::de::IgnoredAny;
+use serde_json::json;
+use std::process::Command;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+/// How often `ActivityWatchPublisher` sends a heartbeat while playing
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Heartbeats this many seconds apart (or less) with the same data are
+/// merged into one event; leaves room for a late heartbeat
+const PULSETIME_SECS: u64 = 2 * HEARTBEAT_INTERVAL.as_secs();
+
+/// How long an `ActivityWatch` request may take; the server is local
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// This machine's name, reported with the bucket
+static HOSTNAME: LazyLock<String> = LazyLock::new(|| {
+    std::env::var("COMPUTERNAME")
+        .ok()
+        .or_else(|| {
+            let output = util::run_command_with_timeout(
+                &mut Command::new("hostname"),
+                Duration::from_secs(2),
+            )
+            .ok()?;
+            Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        })
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+});
+
+/// An `ActivityWatch` server and the bucket events are recorded in
+#[derive(Debug, Clone)]
+pub struct ActivityWatchClient {
+    /// Server URL, e.g. `http://localhost:5600`
+    pub base_url: String,
+    /// Bucket ID, e.g. `aw-watcher-brainfm_<hostname>`
+    pub bucket_id: String,
+}
+
+impl ActivityWatchClient {
+    /// Client for `base_url` using the conventional bucket ID for this
+    /// machine, `aw-watcher-brainfm_<hostname>`
+    #[must_use]
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            bucket_id: format!("aw-watcher-brainfm_{}", *HOSTNAME),
+        }
+    }
+
+    /// URL of `path` under the bucket
+    fn bucket_url(&self, path: &str) -> String {
+        format!(
+            "{}/api/0/buckets/{}{path}",
+            self.base_url.trim_end_matches('/'),
+            util::percent_encode(&self.bucket_id)
+        )
+    }
+}
+
+/// Create the client's bucket. `ActivityWatch` answers 304 when it already
+/// exists, which is not an error.
+pub fn create_bucket(client: &ActivityWatchClient) -> Result<()> {
+    let bucket = json!({
+        "client": "brainfm-presence",
+        "type": "brainfm",
+        "hostname": *HOSTNAME,
+    });
+    util::http_post_json::<_, IgnoredAny>(
+        &client.bucket_url(""),
+        &bucket,
+        HttpAuth::None,
+        REQUEST_TIMEOUT,
+    )
+    .with_context(|| format!("Failed to create ActivityWatch bucket {}", client.bucket_id))?;
+    Ok(())
+}
+
+/// Record `state` in the client's bucket as a zero-length heartbeat event,
+/// merged with the previous one if its data is the same
+pub fn send_heartbeat(client: &ActivityWatchClient, state: &BrainFmState) -> Result<()> {
+    util::http_post_json::<_, IgnoredAny>(
+        &client.bucket_url(&format!("/heartbeat?pulsetime={PULSETIME_SECS}")),
+        &heartbeat_event(state),
+        HttpAuth::None,
+        REQUEST_TIMEOUT,
+    )
+    .context("Failed to send ActivityWatch heartbeat")?;
+    Ok(())
+}
+
+/// The heartbeat event for `state`, timestamped now
+fn heartbeat_event(state: &BrainFmState) -> serde_json::Value {
+    json!({
+        "timestamp": chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        "duration": 0,
+        "data": {
+            "mode": state.mode,
+            "track": state.track_name,
+            "genre": state.genre,
+            "nel": state.neural_effect,
+            "bpm": state.bpm,
+        },
+    })
+}
+
+/// Sends heartbeats for the states passed to `update()`
+pub struct ActivityWatchPublisher {
+    client: ActivityWatchClient,
+    bucket_created: bool,
+    /// When the last heartbeat was sent, and its data
+    last_heartbeat: Option<(Instant, serde_json::Value)>,
+}
+
+impl ActivityWatchPublisher {
+    /// Publish to `client`'s bucket, creating it before the first heartbeat
+    #[must_use]
+    pub fn new(client: ActivityWatchClient) -> Self {
+        info!(
+            "⏱️  Recording Brain.fm in ActivityWatch bucket {}",
+            client.bucket_id
+        );
+        Self {
+            client,
+            bucket_created: false,
+            last_heartbeat: None,
+        }
+    }
+
+    /// Pass the latest state; `ActivityWatch` errors are logged, not returned
+    pub fn update(&mut self, state: &BrainFmState) {
+        if !state.is_playing {
+            self.last_heartbeat = None;
+            return;
+        }
+        let data = heartbeat_event(state)["data"].take();
+        let due = self
+            .last_heartbeat
+            .as_ref()
+            .is_none_or(|(sent, last_data)| {
+                *last_data != data || sent.elapsed() >= HEARTBEAT_INTERVAL
+            });
+        if !due {
+            return;
+        }
+
+        if !self.bucket_created {
+            match create_bucket(&self.client) {
+                Ok(()) => self.bucket_created = true,
+                Err(e) => {
+                    warn!("{e:#}");
+                    return;
+                }
+            }
+        }
+        match send_heartbeat(&self.client, state) {
+            Ok(()) => self.last_heartbeat = Some((Instant::now(), data)),
+            Err(e) => warn!("{e:#}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixture::focus_state;
+    use mockito::Matcher;
+
+    fn client(server: &mockito::Server) -> ActivityWatchClient {
+        ActivityWatchClient {
+            base_url: server.url(),
+            bucket_id: "aw-watcher-brainfm_test".into(),
+        }
+    }
+
+    #[test]
+    fn test_create_bucket_request() {
+        let mut server = mockito::Server::new();
+        let bucket = server
+            .mock("POST", "/api/0/buckets/aw-watcher-brainfm_test")
+            .match_header("content-type", "application/json")
+            .match_body(Matcher::PartialJson(json!({
+                "client": "brainfm-presence",
+                "type": "brainfm",
+                "hostname": *HOSTNAME,
+            })))
+            .with_status(200)
+            .expect(1)
+            .create();
+
+        create_bucket(&client(&server)).unwrap();
+        bucket.assert();
+    }
+
+    #[test]
+    fn test_heartbeat_format() {
+        let mut server = mockito::Server::new();
+        let heartbeat = server
+            .mock("POST", "/api/0/buckets/aw-watcher-brainfm_test/heartbeat")
+            .match_query(Matcher::UrlEncoded(
+                "pulsetime".into(),
+                PULSETIME_SECS.to_string(),
+            ))
+            .match_body(Matcher::PartialJson(json!({
+                "duration": 0,
+                "data": {
+                    "mode": "Focus",
+                    "track": "Nothing Remains",
+                    "genre": "Piano",
+                    "nel": "High Neural Effect",
+                    "bpm": 120,
+                },
+            })))
+            .with_status(200)
+            .expect(1)
+            .create();
+
+        send_heartbeat(&client(&server), &focus_state()).unwrap();
+        heartbeat.assert();
+
+        let event = heartbeat_event(&focus_state());
+        let timestamp = event["timestamp"].as_str().unwrap();
+        assert!(
+            timestamp.ends_with('Z') && timestamp.contains('T'),
+            "{timestamp}"
+        );
+    }
+
+    #[test]
+    fn test_publisher_creates_bucket_once_and_skips_repeats() {
+        let mut server = mockito::Server::new();
+        let bucket = server
+            .mock("POST", "/api/0/buckets/aw-watcher-brainfm_test")
+            .expect(1)
+            .create();
+        let heartbeats = server
+            .mock("POST", "/api/0/buckets/aw-watcher-brainfm_test/heartbeat")
+            .match_query(Matcher::Any)
+            .expect(2)
+            .create();
+
+        let mut publisher = ActivityWatchPublisher::new(client(&server));
+        publisher.update(&focus_state());
+        // Same data within the interval: nothing sent
+        publisher.update(&focus_state());
+        // Paused: nothing recorded
+        publisher.update(&BrainFmState::new());
+        publisher.update(&BrainFmState {
+            track_name: Some("Blooming".into()),
+            ..focus_state()
+        });
+
+        bucket.assert();
+        heartbeats.assert();
+    }
+}
@@ -0,0 +1,244 @@
+//! Helix and Kakoune status line integration (Unix)
+//!
+//! `HelixStatusSocket` writes `BrainFmState::to_helix_status_item` as a line
+//! to a Unix socket each time it changes, for a status line provider
+//! listening there. `KakouneSession` keeps a headless Kakoune session
+//! (`kak -d -s brainfm`) whose `brainfm_mode` option holds the same text and
+//! whose modeline is `BrainFmState::to_kakoune_modeline`; clients join it with
+//! `kak -c brainfm`.
+
+use crate::{util, BrainFmState};
+use anyhow::{Context, Result};
+use log::{info, warn};
+use std::io::Write as _;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// Name of the Kakoune session `KakouneSession` runs
+pub const KAKOUNE_SESSION: &str = "brainfm";
+
+/// Kakoune option holding the status text
+pub const KAKOUNE_MODE_OPTION: &str = "brainfm_mode";
+
+/// Attempts to reach a just-started Kakoune session before giving up
+const KAKOUNE_CONNECT_ATTEMPTS: u32 = 20;
+
+/// Pause between those attempts
+const KAKOUNE_CONNECT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long `kak -l` may take to list the sessions
+const KAKOUNE_LIST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Writes the Helix status item to a Unix socket when it changes. Each write
+/// is a new connection, so the listener may come and go.
+#[derive(Debug)]
+pub struct HelixStatusSocket {
+    path: PathBuf,
+    /// The status last written, to skip writing an unchanged one
+    last_status: Option<String>,
+}
+
+impl HelixStatusSocket {
+    /// Write to the socket at `path`
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        info!("📝 Writing the Helix status to {}", path.display());
+        Self {
+            path,
+            last_status: None,
+        }
+    }
+
+    /// Write `state`'s status item if it changed. A failed write is retried
+    /// on the next update.
+    pub fn update(&mut self, state: &BrainFmState) {
+        let status = state.to_helix_status_item();
+        if self.last_status.as_ref() == Some(&status) {
+            return;
+        }
+        match self.write_status(&status) {
+            Ok(()) => self.last_status = Some(status),
+            Err(e) => warn!("Failed to write the Helix status: {e:#}"),
+        }
+    }
+
+    /// Connect and write `status` as one line
+    fn write_status(&self, status: &str) -> Result<()> {
+        let mut stream = UnixStream::connect(&self.path)
+            .with_context(|| format!("Failed to connect to {}", self.path.display()))?;
+        stream
+            .write_all(format!("{status}\n").as_bytes())
+            .with_context(|| format!("Failed to write to {}", self.path.display()))
+    }
+}
+
+/// A headless Kakoune session showing the state in its modeline
+#[derive(Debug)]
+pub struct KakouneSession {
+    /// The status last set, to skip setting an unchanged one
+    last_status: Option<String>,
+}
+
+impl KakouneSession {
+    /// Start the `KAKOUNE_SESSION` daemon (or join one left running), declare
+    /// `KAKOUNE_MODE_OPTION` and set the modeline
+    pub fn start() -> Result<Self> {
+        if is_session_running()? {
+            info!("📝 Joining the running Kakoune session {KAKOUNE_SESSION}");
+        } else {
+            let status = Command::new("kak")
+                .args(["-d", "-s", KAKOUNE_SESSION])
+                .stdin(Stdio::null())
+                .status()
+                .context("Failed to run kak; is Kakoune installed?")?;
+            anyhow::ensure!(
+                status.success(),
+                "kak -d -s {KAKOUNE_SESSION} failed: {status}"
+            );
+            info!("📝 Kakoune session {KAKOUNE_SESSION} started; join it with `kak -c {KAKOUNE_SESSION}`");
+        }
+
+        // The daemon may not be listening yet
+        let setup = format!(
+            "declare-option str {KAKOUNE_MODE_OPTION}\n{}\n",
+            BrainFmState::new().to_kakoune_modeline()
+        );
+        let mut attempt = 1;
+        while let Err(e) = send_commands(&setup) {
+            if attempt == KAKOUNE_CONNECT_ATTEMPTS {
+                return Err(e);
+            }
+            attempt += 1;
+            std::thread::sleep(KAKOUNE_CONNECT_INTERVAL);
+        }
+        Ok(Self { last_status: None })
+    }
+
+    /// Set `KAKOUNE_MODE_OPTION` to `state`'s status item if it changed
+    pub fn update(&mut self, state: &BrainFmState) {
+        let status = state.to_helix_status_item();
+        if self.last_status.as_ref() == Some(&status) {
+            return;
+        }
+        match send_commands(&set_mode_command(&status)) {
+            Ok(()) => self.last_status = Some(status),
+            Err(e) => warn!("Failed to update Kakoune: {e:#}"),
+        }
+    }
+}
+
+/// Whether `kak -l` lists `KAKOUNE_SESSION`
+fn is_session_running() -> Result<bool> {
+    let output =
+        util::run_command_with_timeout(Command::new("kak").arg("-l"), KAKOUNE_LIST_TIMEOUT)
+            .context("Failed to run kak; is Kakoune installed?")?;
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .any(|session| session.trim() == KAKOUNE_SESSION))
+}
+
+/// Run `commands` in the session through `kak -p`
+fn send_commands(commands: &str) -> Result<()> {
+    let mut child = Command::new("kak")
+        .args(["-p", KAKOUNE_SESSION])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("Failed to run kak; is Kakoune installed?")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(commands.as_bytes())
+            .context("Failed to write to kak -p")?;
+    }
+    let status = child.wait().context("Failed to wait for kak -p")?;
+    anyhow::ensure!(
+        status.success(),
+        "kak -p {KAKOUNE_SESSION} failed: {status}"
+    );
+    Ok(())
+}
+
+/// `set-option global brainfm_mode '…'`, with `status` quoted for Kakoune
+/// (single quotes doubled)
+fn set_mode_command(status: &str) -> String {
+    format!(
+        "set-option global {KAKOUNE_MODE_OPTION} '{}'\n",
+        status.replace('\'', "''")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::net::UnixListener;
+
+    fn state(mode: &str, track: &str) -> BrainFmState {
+        BrainFmState {
+            mode: Some(mode.into()),
+            track_name: Some(track.into()),
+            is_playing: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_helix_socket_written_on_state_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("helix.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let received = || -> Vec<String> {
+            let mut lines = Vec::new();
+            while let Ok((stream, _)) = listener.accept() {
+                stream.set_nonblocking(false).unwrap();
+                let mut line = String::new();
+                BufReader::new(stream).read_line(&mut line).unwrap();
+                lines.push(line);
+            }
+            lines
+        };
+
+        let mut socket = HelixStatusSocket::new(&path);
+        socket.update(&state("Deep Work", "Nothing Remains"));
+        assert_eq!(received(), ["🧠 Deep Work\n"]);
+
+        // Same status (a new track in the same mode): nothing written
+        socket.update(&state("Deep Work", "Blooming"));
+        assert!(received().is_empty());
+
+        socket.update(&BrainFmState::new());
+        assert_eq!(received(), ["🧠 Brain.fm\n"]);
+    }
+
+    #[test]
+    fn test_helix_socket_retries_after_failed_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("helix.sock");
+        let mut socket = HelixStatusSocket::new(&path);
+        socket.update(&state("Focus", "Blooming"));
+        assert_eq!(socket.last_status, None);
+
+        let listener = UnixListener::bind(&path).unwrap();
+        socket.update(&state("Focus", "Blooming"));
+        let (stream, _) = listener.accept().unwrap();
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line).unwrap();
+        assert_eq!(line, "🧠 Focus\n");
+    }
+
+    #[test]
+    fn test_kakoune_set_mode_command_quotes() {
+        assert_eq!(
+            set_mode_command("🧠 Deep Work"),
+            "set-option global brainfm_mode '🧠 Deep Work'\n"
+        );
+        assert_eq!(
+            set_mode_command("🧠 Rock 'n' Roll"),
+            "set-option global brainfm_mode '🧠 Rock ''n'' Roll'\n"
+        );
+    }
+}
@@ -0,0 +1,212 @@
+//! Home Assistant integration over MQTT
+//!
+//! `HomeAssistantPublisher` connects to an MQTT broker and:
+//!
+//! - publishes `BrainFmState::to_ha_discovery_payload` (retained) to
+//!   `homeassistant/sensor/<device_id>/mode/config` on startup, so Home
+//!   Assistant creates a "Brain.fm Mode" sensor without any YAML
+//! - publishes `BrainFmState::to_ha_state_payload` (retained) to
+//!   `HA_STATE_TOPIC` whenever it changes
+//!
+//! The MQTT event loop runs on a background thread that reconnects on its
+//! own; `update()` never blocks on the network.
+
+use crate::{BrainFmState, HA_STATE_TOPIC};
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use rumqttc::{Client, ConnectionError, Event, MqttOptions, Packet, QoS};
+use std::time::Duration;
+
+/// Port used when `--mqtt-broker` has none
+pub const DEFAULT_MQTT_PORT: u16 = 1883;
+
+/// Home Assistant's default discovery prefix
+const DISCOVERY_PREFIX: &str = "homeassistant";
+
+/// How long the event loop waits before reconnecting after an error
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Split `host[:port]`, defaulting to `DEFAULT_MQTT_PORT`
+pub fn parse_broker(broker: &str) -> Result<(String, u16)> {
+    match broker.rsplit_once(':') {
+        Some((host, port)) => {
+            let port = port
+                .parse()
+                .with_context(|| format!("Invalid MQTT broker port in {broker:?}"))?;
+            Ok((host.to_string(), port))
+        }
+        None => Ok((broker.to_string(), DEFAULT_MQTT_PORT)),
+    }
+}
+
+/// Topic the discovery config for `device_id` is published to
+#[must_use]
+pub fn discovery_topic(device_id: &str) -> String {
+    format!("{DISCOVERY_PREFIX}/sensor/{device_id}/mode/config")
+}
+
+/// Publishes the state to Home Assistant through an MQTT broker
+pub struct HomeAssistantPublisher {
+    client: Client,
+    last_payload: Option<String>,
+}
+
+impl HomeAssistantPublisher {
+    /// Connect to `broker` (`host[:port]`) and queue the discovery config
+    /// for the device `device_id`
+    pub fn connect(broker: &str, device_id: &str) -> Result<Self> {
+        let (host, port) = parse_broker(broker)?;
+        let mut options = MqttOptions::new(format!("brainfm-presence-{device_id}"), host, port);
+        options.set_keep_alive(Duration::from_secs(30));
+        let (client, mut connection) = Client::new(options, 10);
+
+        let broker = broker.to_string();
+        std::thread::spawn(move || {
+            for event in connection.iter() {
+                match event {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        info!("🏠 Connected to MQTT broker {broker}");
+                    }
+                    Ok(_) => {}
+                    // `Client` dropped: the publisher is gone
+                    Err(ConnectionError::RequestsDone) => break,
+                    Err(e) => {
+                        debug!("MQTT connection error: {e}");
+                        std::thread::sleep(RECONNECT_DELAY);
+                    }
+                }
+            }
+        });
+
+        let discovery = BrainFmState::new().to_ha_discovery_payload(device_id);
+        client
+            .publish(
+                discovery_topic(device_id),
+                QoS::AtLeastOnce,
+                true,
+                discovery.to_string(),
+            )
+            .context("Failed to queue the Home Assistant discovery config")?;
+
+        Ok(Self {
+            client,
+            last_payload: None,
+        })
+    }
+
+    /// Publish `state` if its payload differs from the last one sent
+    pub fn update(&mut self, state: &BrainFmState) {
+        let payload = state.to_ha_state_payload();
+        if self.last_payload.as_ref() == Some(&payload) {
+            return;
+        }
+        match self
+            .client
+            .try_publish(HA_STATE_TOPIC, QoS::AtLeastOnce, true, payload.clone())
+        {
+            Ok(()) => self.last_payload = Some(payload),
+            Err(e) => warn!("Failed to publish the Home Assistant state: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::mpsc::{self, Receiver};
+
+    /// Read one MQTT packet: (first header byte, body)
+    fn read_packet(stream: &mut TcpStream) -> Option<(u8, Vec<u8>)> {
+        let mut byte = [0u8];
+        stream.read_exact(&mut byte).ok()?;
+        let header = byte[0];
+        let (mut len, mut shift) = (0usize, 0);
+        loop {
+            stream.read_exact(&mut byte).ok()?;
+            len |= usize::from(byte[0] & 0x7f) << shift;
+            if byte[0] & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+        }
+        let mut body = vec![0; len];
+        stream.read_exact(&mut body).ok()?;
+        Some((header, body))
+    }
+
+    /// Minimal in-process broker: accepts one client, acknowledges its
+    /// `CONNECT` and `QoS` 1 `PUBLISH` packets, and forwards (topic, payload,
+    /// retain)
+    fn mock_broker() -> (u16, Receiver<(String, String, bool)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            while let Some((header, body)) = read_packet(&mut stream) {
+                match header >> 4 {
+                    // CONNECT → CONNACK
+                    1 => stream.write_all(&[0x20, 0x02, 0x00, 0x00]).unwrap(),
+                    // PUBLISH (QoS 1) → PUBACK
+                    3 => {
+                        let topic_len = usize::from(u16::from_be_bytes([body[0], body[1]]));
+                        let topic = String::from_utf8(body[2..2 + topic_len].to_vec()).unwrap();
+                        let id = &body[2 + topic_len..4 + topic_len];
+                        let payload = String::from_utf8(body[4 + topic_len..].to_vec()).unwrap();
+                        stream.write_all(&[0x40, 0x02, id[0], id[1]]).unwrap();
+                        let _ = tx.send((topic, payload, header & 0x01 != 0));
+                    }
+                    // PINGREQ → PINGRESP
+                    12 => stream.write_all(&[0xd0, 0x00]).unwrap(),
+                    _ => {}
+                }
+            }
+        });
+        (port, rx)
+    }
+
+    #[test]
+    fn test_parse_broker() {
+        assert_eq!(
+            parse_broker("homeassistant.local").unwrap(),
+            ("homeassistant.local".to_string(), DEFAULT_MQTT_PORT)
+        );
+        assert_eq!(
+            parse_broker("10.0.0.2:8883").unwrap(),
+            ("10.0.0.2".to_string(), 8883)
+        );
+        assert!(parse_broker("broker:mqtt").is_err());
+    }
+
+    #[test]
+    fn test_publishes_discovery_then_state_changes() {
+        let (port, messages) = mock_broker();
+        let mut publisher =
+            HomeAssistantPublisher::connect(&format!("127.0.0.1:{port}"), "test_device").unwrap();
+        let next = || messages.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        let (topic, payload, retain) = next();
+        assert_eq!(topic, discovery_topic("test_device"));
+        assert!(retain);
+        let config: serde_json::Value = serde_json::from_str(&payload).unwrap();
+        assert_eq!(config["unique_id"], "test_device_mode");
+
+        let focus = BrainFmState {
+            mode: Some("Focus".into()),
+            is_playing: true,
+            ..Default::default()
+        };
+        publisher.update(&focus);
+        // Unchanged: not published again
+        publisher.update(&focus);
+        publisher.update(&BrainFmState::new());
+
+        let (topic, payload, _) = next();
+        assert_eq!(topic, HA_STATE_TOPIC);
+        assert_eq!(payload, focus.to_ha_state_payload());
+        let (_, payload, _) = next();
+        assert_eq!(payload, BrainFmState::new().to_ha_state_payload());
+    }
+}
@@ -0,0 +1,251 @@
+//! Linear integration for engineering productivity tracking
+//!
+//! `add_session_comment` comments on an issue with a summary of a Brain.fm
+//! session, and `LinearNotifier` posts one whenever a session ends (the
+//! state stops playing).
+//!
+//! Requests use Linear's GraphQL API with a personal API key; see
+//! <https://linear.app/developers/graphql>.
+
+use crate::util::{self, HttpAuth};
+use crate::BrainFmState;
+use anyhow::Result;
+use log::{info, warn};
+use serde_json::{json, Value};
+use std::fmt::Write as _;
+use std::time::{Duration, Instant};
+
+/// Linear's GraphQL endpoint
+const GRAPHQL_URL: &str = "https://api.linear.app/graphql";
+
+/// Creates a comment on an issue
+const COMMENT_CREATE_MUTATION: &str = "mutation CommentCreate($input: CommentCreateInput!) { \
+     commentCreate(input: $input) { success comment { id } } }";
+
+/// How long a Linear request may take
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Linear credentials
+#[derive(Debug, Clone)]
+pub struct LinearClient {
+    /// Personal API key (`lin_api_…`)
+    pub api_key: String,
+    /// GraphQL endpoint, `GRAPHQL_URL` except in tests
+    graphql_url: String,
+}
+
+impl LinearClient {
+    /// Act as the owner of `api_key`
+    #[must_use]
+    pub fn new(api_key: String) -> Self {
+        Self {
+            api_key,
+            graphql_url: GRAPHQL_URL.to_string(),
+        }
+    }
+
+    /// Run `query` with `variables` and return its `data`. GraphQL reports
+    /// errors in the response body, so those are errors here too. (Linear
+    /// keys go in the `Authorization` header as they are, without `Bearer`.)
+    fn graphql(&self, query: &str, variables: &Value) -> Result<Value> {
+        let body: Value = util::http_post_json(
+            &self.graphql_url,
+            &json!({ "query": query, "variables": variables }),
+            HttpAuth::Header(&self.api_key),
+            REQUEST_TIMEOUT,
+        )?;
+        if let Some(errors) = body.get("errors").and_then(Value::as_array) {
+            let messages: Vec<&str> = errors
+                .iter()
+                .filter_map(|error| error["message"].as_str())
+                .collect();
+            anyhow::bail!("Linear request failed: {}", messages.join("; "));
+        }
+        Ok(body["data"].clone())
+    }
+}
+
+/// Comment on `issue_id` (e.g. `ENG-123`) with a summary of a session of
+/// `duration_secs` in `state`: see `session_comment`
+pub fn add_session_comment(
+    client: &LinearClient,
+    issue_id: &str,
+    state: &BrainFmState,
+    duration_secs: f64,
+) -> Result<()> {
+    let variables = json!({
+        "input": {
+            "issueId": issue_id,
+            "body": session_comment(state, duration_secs),
+        }
+    });
+    let data = client.graphql(COMMENT_CREATE_MUTATION, &variables)?;
+    anyhow::ensure!(
+        data["commentCreate"]["success"].as_bool() == Some(true),
+        "Linear didn't create the comment on {issue_id}"
+    );
+    Ok(())
+}
+
+/// The comment posted by `add_session_comment`:
+/// `"**Brain.fm session** (Deep Work, 42 min)\n- Track: Nothing Remains\n- Genre: Piano\n- Neural Effect: High"`.
+///
+/// The heading shows the activity, or the mode when there is none; lines
+/// for unknown values are left out.
+fn session_comment(state: &BrainFmState, duration_secs: f64) -> String {
+    let label = state
+        .activity
+        .as_deref()
+        .or(state.mode.as_deref())
+        .unwrap_or("Brain.fm");
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)] // clamped non-negative
+    let minutes = (duration_secs.max(0.0) / 60.0).round() as u64;
+    let mut comment = format!("**Brain.fm session** ({label}, {minutes} min)");
+    let neural_effect = state
+        .neural_effect
+        .as_deref()
+        .map(|level| level.trim_end_matches(" Neural Effect"));
+    for (name, value) in [
+        ("Track", state.track_name.as_deref()),
+        ("Genre", state.genre.as_deref()),
+        ("Neural Effect", neural_effect),
+    ] {
+        if let Some(value) = value {
+            let _ = write!(comment, "\n- {name}: {value}");
+        }
+    }
+    comment
+}
+
+/// Comments on a Linear issue at the end of each session
+pub struct LinearNotifier {
+    client: LinearClient,
+    issue_id: String,
+    /// When the current session started, `None` while not playing
+    session_start: Option<Instant>,
+    /// The last playing state, summarized when the session ends
+    last_playing: Option<BrainFmState>,
+}
+
+impl LinearNotifier {
+    /// Comment on `issue_id`
+    #[must_use]
+    pub fn new(client: LinearClient, issue_id: String) -> Self {
+        Self {
+            client,
+            issue_id,
+            session_start: None,
+            last_playing: None,
+        }
+    }
+
+    /// Pass the latest state; Linear errors are logged, not returned
+    pub fn update(&mut self, state: &BrainFmState) {
+        if state.is_playing {
+            self.session_start.get_or_insert_with(Instant::now);
+            self.last_playing = Some(state.clone());
+            return;
+        }
+        let (Some(start), Some(last)) = (self.session_start.take(), self.last_playing.take())
+        else {
+            return;
+        };
+        let duration = start.elapsed().as_secs_f64();
+        match add_session_comment(&self.client, &self.issue_id, &last, duration) {
+            Ok(()) => info!("📋 Commented the session on Linear {}", self.issue_id),
+            Err(e) => warn!("Failed to comment on Linear: {e:#}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixture::focus_state;
+    use mockito::Matcher;
+
+    fn client(server: &mockito::Server) -> LinearClient {
+        LinearClient {
+            graphql_url: format!("{}/graphql", server.url()),
+            ..LinearClient::new("lin_api_secret".into())
+        }
+    }
+
+    #[test]
+    fn test_session_comment() {
+        assert_eq!(
+            session_comment(&focus_state(), 2520.0),
+            "**Brain.fm session** (Deep Work, 42 min)\n- Track: Nothing Remains\n- Genre: Piano\n- Neural Effect: High"
+        );
+        let mode_only = BrainFmState {
+            mode: Some("Sleep".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            session_comment(&mode_only, 89.0),
+            "**Brain.fm session** (Sleep, 1 min)"
+        );
+    }
+
+    #[test]
+    fn test_add_session_comment_sends_mutation() {
+        let mut server = mockito::Server::new();
+        let mutation = server
+            .mock("POST", "/graphql")
+            .match_header("Authorization", "lin_api_secret")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::PartialJson(json!({
+                    "variables": {
+                        "input": {
+                            "issueId": "ENG-123",
+                            "body": session_comment(&focus_state(), 2520.0),
+                        }
+                    }
+                })),
+                Matcher::Regex(
+                    r"mutation CommentCreate\(\$input: CommentCreateInput!\) \{ +commentCreate\(input: \$input\) \{ success ".into(),
+                ),
+            ]))
+            .with_body(r#"{"data":{"commentCreate":{"success":true,"comment":{"id":"c1"}}}}"#)
+            .expect(1)
+            .create();
+
+        add_session_comment(&client(&server), "ENG-123", &focus_state(), 2520.0).unwrap();
+        mutation.assert();
+    }
+
+    #[test]
+    fn test_add_session_comment_reports_graphql_errors() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("POST", "/graphql")
+            .with_status(400)
+            .with_body(r#"{"errors":[{"message":"Entity not found: Issue"}]}"#)
+            .create();
+
+        let error =
+            add_session_comment(&client(&server), "ENG-0", &focus_state(), 60.0).unwrap_err();
+        assert!(error.to_string().contains("Entity not found"), "{error:#}");
+    }
+
+    #[test]
+    fn test_notifier_comments_when_session_ends() {
+        let mut server = mockito::Server::new();
+        let mutation = server
+            .mock("POST", "/graphql")
+            .match_body(Matcher::PartialJson(json!({
+                "variables": { "input": { "issueId": "ENG-7" } }
+            })))
+            .with_body(r#"{"data":{"commentCreate":{"success":true}}}"#)
+            .expect(1)
+            .create();
+
+        let mut notifier = LinearNotifier::new(client(&server), "ENG-7".into());
+        notifier.update(&BrainFmState::new());
+        notifier.update(&focus_state());
+        notifier.update(&focus_state());
+        notifier.update(&BrainFmState::new());
+        notifier.update(&BrainFmState::new());
+        mutation.assert();
+    }
+}
@@ -0,0 +1,31 @@
+//! Integrations with third-party apps, each behind its own feature flag
+//!
+//! - `stream_deck` (`stream-deck`): WebSocket server for an Elgato Stream
+//!   Deck plugin
+//! - `home_assistant` (`mqtt`): Home Assistant sensor via MQTT discovery
+//! - `zulip` (`zulip`): focus session posts and user status on Zulip
+//! - `pushover` (`pushover`): phone notifications when a session ends
+//! - `linear` (`linear`): session summaries as Linear issue comments
+//! - `activitywatch` (`activitywatch`): time tracking events in `ActivityWatch`
+//! - `editors` (`editors`, Unix only): Helix and Kakoune status lines
+//! - `plasma` (`plasma`, Linux only): D-Bus service for a KDE Plasma widget
+//! - `toggl` (`toggl`): Toggl Track time entries while playing
+
+#[cfg(feature = "activitywatch")]
+pub mod activitywatch;
+#[cfg(all(feature = "editors", unix))]
+pub mod editors;
+#[cfg(feature = "mqtt")]
+pub mod home_assistant;
+#[cfg(feature = "linear")]
+pub mod linear;
+#[cfg(all(feature = "plasma", target_os = "linux"))]
+pub mod plasma;
+#[cfg(feature = "pushover")]
+pub mod pushover;
+#[cfg(feature = "stream-deck")]
+pub mod stream_deck;
+#[cfg(feature = "toggl")]
+pub mod toggl;
+#[cfg(feature = "zulip")]
+pub mod zulip;
@@ -0,0 +1,143 @@
+//! KDE Plasma widget integration (Linux)
+//!
+//! `PlasmaDataEngineServer` owns the `org.kde.brainfm` name on the session
+//! bus and serves `/org/kde/brainfm`, whose `org.kde.brainfm` interface has
+//! one method: `GetCurrentState() -> s`, the state last passed to
+//! `update()` as `BrainFmState::to_plasma_dataengine_json`. A plasmoid can
+//! poll it with `qdbus org.kde.brainfm /org/kde/brainfm GetCurrentState`
+//! and parse the JSON into its `DataEngine` sources.
+
+use crate::BrainFmState;
+use anyhow::{Context, Result};
+use log::info;
+use std::sync::{Arc, Mutex, PoisonError};
+use zbus::blocking::{connection, Connection};
+
+/// Well-known name the server owns on the session bus
+pub const BUS_NAME: &str = "org.kde.brainfm";
+
+/// Path of the object serving `INTERFACE_NAME`
+pub const OBJECT_PATH: &str = "/org/kde/brainfm";
+
+/// Interface with the `GetCurrentState` method
+pub const INTERFACE_NAME: &str = "org.kde.brainfm";
+
+/// The D-Bus object, answering with the shared state
+struct BrainFmObject {
+    state: Arc<Mutex<BrainFmState>>,
+}
+
+#[zbus::interface(name = "org.kde.brainfm")]
+impl BrainFmObject {
+    /// The current state as `DataEngine` JSON
+    fn get_current_state(&self) -> String {
+        self.state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .to_plasma_dataengine_json()
+            .to_string()
+    }
+}
+
+/// D-Bus service that keeps a Plasma widget showing the current state.
+/// Calls are answered on zbus's own thread until the server is dropped.
+pub struct PlasmaDataEngineServer {
+    /// Kept open to keep serving
+    _connection: Connection,
+    state: Arc<Mutex<BrainFmState>>,
+}
+
+impl PlasmaDataEngineServer {
+    /// Serve on the session bus as `BUS_NAME`, failing if another instance
+    /// already owns it
+    pub fn start() -> Result<Self> {
+        let builder = connection::Builder::session()
+            .and_then(|builder| builder.name(BUS_NAME))
+            .context("Failed to connect to the D-Bus session bus")?;
+        let server = Self::serve(builder)?;
+        info!("🖥️  Plasma D-Bus service running as {BUS_NAME}");
+        Ok(server)
+    }
+
+    /// Serve `OBJECT_PATH` on the connection `builder` makes
+    fn serve(builder: connection::Builder<'_>) -> Result<Self> {
+        let state = Arc::new(Mutex::new(BrainFmState::new()));
+        let connection = builder
+            .serve_at(
+                OBJECT_PATH,
+                BrainFmObject {
+                    state: Arc::clone(&state),
+                },
+            )
+            .and_then(connection::Builder::build)
+            .with_context(|| format!("Failed to serve {BUS_NAME} on D-Bus"))?;
+        Ok(Self {
+            _connection: connection,
+            state,
+        })
+    }
+
+    /// Answer `GetCurrentState` with `state` from now on
+    pub fn update(&self, state: &BrainFmState) {
+        *self.state.lock().unwrap_or_else(PoisonError::into_inner) = state.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixStream;
+
+    /// A server and a client connected to it directly, without a bus
+    fn connected_pair() -> (PlasmaDataEngineServer, Connection) {
+        let (server_socket, client_socket) = UnixStream::pair().unwrap();
+        let server = std::thread::spawn(move || {
+            let builder = connection::Builder::async_io_unix_stream(server_socket)
+                .p2p()
+                .server(zbus::Guid::generate())
+                .unwrap();
+            PlasmaDataEngineServer::serve(builder).unwrap()
+        });
+        let client = connection::Builder::async_io_unix_stream(client_socket)
+            .p2p()
+            .build()
+            .unwrap();
+        (server.join().unwrap(), client)
+    }
+
+    fn get_current_state(client: &Connection) -> serde_json::Value {
+        let reply = client
+            .call_method(
+                None::<&str>,
+                OBJECT_PATH,
+                Some(INTERFACE_NAME),
+                "GetCurrentState",
+                &(),
+            )
+            .unwrap();
+        let json: String = reply.body().deserialize().unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn test_get_current_state_over_dbus() {
+        let (server, client) = connected_pair();
+        assert_eq!(
+            get_current_state(&client),
+            BrainFmState::new().to_plasma_dataengine_json()
+        );
+
+        let state = BrainFmState {
+            mode: Some("Focus".into()),
+            track_name: Some("Blooming".into()),
+            genre: Some("Piano".into()),
+            neural_effect: Some("High Neural Effect".into()),
+            is_playing: true,
+            ..Default::default()
+        };
+        server.update(&state);
+        let reply = get_current_state(&client);
+        assert_eq!(reply, state.to_plasma_dataengine_json());
+        assert_eq!(reply["sources"]["BrainFM"]["track"], "Blooming");
+    }
+}
@@ -0,0 +1,319 @@
+//! Pushover integration for phone notifications
+//!
+//! `send_session_end_notification` tells the user a session ended, with
+//! its duration, mode and tracks, and `send_goal_reached_notification` that
+//! the session goal (`BrainFmState::session_goal_secs`) was reached.
+//! `PushoverNotifier` sends both from the states passed to `update()`: the
+//! end when playback stops, and the goal once per session.
+//!
+//! See <https://pushover.net/api>.
+
+use crate::util::{self, HttpAuth};
+use crate::BrainFmState;
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::de::IgnoredAny;
+use std::time::{Duration, Instant};
+
+/// Pushover's message endpoint
+const MESSAGES_URL: &str = "https://api.pushover.net/1/messages.json";
+
+/// Priority of session end notifications: normal, with sound
+const SESSION_END_PRIORITY: i8 = 0;
+
+/// Priority of goal notifications: low, without sound or vibration
+const GOAL_REACHED_PRIORITY: i8 = -1;
+
+/// How long a Pushover request may take
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Pushover credentials
+#[derive(Debug, Clone)]
+pub struct PushoverClient {
+    /// User (or group) key the notifications go to
+    pub user_key: String,
+    /// Application API token
+    pub api_token: String,
+    /// Message endpoint, `MESSAGES_URL` except in tests
+    messages_url: String,
+}
+
+impl PushoverClient {
+    /// Notify `user_key` through the application `api_token`
+    #[must_use]
+    pub fn new(user_key: String, api_token: String) -> Self {
+        Self {
+            user_key,
+            api_token,
+            messages_url: MESSAGES_URL.to_string(),
+        }
+    }
+
+    /// Send `message` titled `title` at `priority` (-2 to 2)
+    fn send(&self, title: &str, message: &str, priority: i8) -> Result<()> {
+        let priority = priority.to_string();
+        let params = [
+            ("token", self.api_token.as_str()),
+            ("user", self.user_key.as_str()),
+            ("title", title),
+            ("message", message),
+            ("priority", priority.as_str()),
+        ];
+        util::http_post_form::<IgnoredAny>(
+            &self.messages_url,
+            &params,
+            HttpAuth::None,
+            REQUEST_TIMEOUT,
+        )
+        .context("Pushover request failed")?;
+        Ok(())
+    }
+}
+
+/// Notify that a session of `session_duration_secs` ended:
+/// `"Focus · Deep Work for 1:23:45\nTracks: Nothing Remains"`. The track is
+/// `final_state`'s; `PushoverNotifier` lists every track of the session.
+pub fn send_session_end_notification(
+    client: &PushoverClient,
+    final_state: &BrainFmState,
+    session_duration_secs: f64,
+) -> Result<()> {
+    let tracks: Vec<&str> = final_state.track_name.as_deref().into_iter().collect();
+    send_session_summary(client, final_state, session_duration_secs, &tracks)
+}
+
+/// `send_session_end_notification` listing `tracks`
+fn send_session_summary(
+    client: &PushoverClient,
+    final_state: &BrainFmState,
+    session_duration_secs: f64,
+    tracks: &[&str],
+) -> Result<()> {
+    client.send(
+        "Brain.fm session ended",
+        &session_end_message(final_state, session_duration_secs, tracks),
+        SESSION_END_PRIORITY,
+    )
+}
+
+/// Notify that `state`'s session goal was reached: `"Focus goal of 45 min
+/// reached"`. Does nothing when there is no goal or it isn't reached yet.
+pub fn send_goal_reached_notification(client: &PushoverClient, state: &BrainFmState) -> Result<()> {
+    let Some(message) = goal_reached_message(state) else {
+        return Ok(());
+    };
+    client.send("Brain.fm goal reached 🎯", &message, GOAL_REACHED_PRIORITY)
+}
+
+/// The message sent by `send_session_end_notification`
+fn session_end_message(state: &BrainFmState, duration_secs: f64, tracks: &[&str]) -> String {
+    let mode = match (state.mode.as_deref(), state.activity.as_deref()) {
+        (Some(mode), Some(activity)) if mode != activity => format!("{mode} · {activity}"),
+        (Some(mode), _) => mode.to_string(),
+        (None, _) => "Brain.fm".to_string(),
+    };
+    let mut message = format!("{mode} for {}", util::format_duration(duration_secs));
+    if !tracks.is_empty() {
+        message.push_str("\nTracks: ");
+        message.push_str(&tracks.join(", "));
+    }
+    message
+}
+
+/// The message sent by `send_goal_reached_notification`, if the goal is
+/// reached
+fn goal_reached_message(state: &BrainFmState) -> Option<String> {
+    let goal = state.session_goal_secs.filter(|&goal| goal > 0)?;
+    if state.session_goal_progress_secs? < goal {
+        return None;
+    }
+    let kind = state
+        .session_goal_type
+        .as_deref()
+        .or(state.mode.as_deref())
+        .unwrap_or("Session");
+    Some(format!("{kind} goal of {} min reached", goal / 60))
+}
+
+/// The session `PushoverNotifier` is following
+struct Session {
+    started: Instant,
+    /// Tracks heard, in order, without repeats
+    tracks: Vec<String>,
+    goal_notified: bool,
+}
+
+/// Sends Pushover notifications as the state changes: the session summary
+/// when playback stops, and the goal once per session when it's reached
+pub struct PushoverNotifier {
+    client: PushoverClient,
+    /// The current session, `None` while not playing
+    session: Option<Session>,
+    /// The last playing state, summarized when the session ends
+    last_playing: Option<BrainFmState>,
+}
+
+impl PushoverNotifier {
+    /// Notify through `client`
+    #[must_use]
+    pub fn new(client: PushoverClient) -> Self {
+        Self {
+            client,
+            session: None,
+            last_playing: None,
+        }
+    }
+
+    /// Pass the latest state; Pushover errors are logged, not returned
+    pub fn update(&mut self, state: &BrainFmState) {
+        if !state.is_playing {
+            if let (Some(session), Some(last)) = (self.session.take(), self.last_playing.take()) {
+                let tracks: Vec<&str> = session.tracks.iter().map(String::as_str).collect();
+                let duration = session.started.elapsed().as_secs_f64();
+                match send_session_summary(&self.client, &last, duration, &tracks) {
+                    Ok(()) => info!("📱 Sent the session summary to Pushover"),
+                    Err(e) => warn!("Failed to notify Pushover: {e:#}"),
+                }
+            }
+            return;
+        }
+
+        let session = self.session.get_or_insert_with(|| Session {
+            started: Instant::now(),
+            tracks: Vec::new(),
+            goal_notified: false,
+        });
+        if let Some(track) = state.track_name.as_deref() {
+            if !session.tracks.iter().any(|heard| heard == track) {
+                session.tracks.push(track.to_string());
+            }
+        }
+        if !session.goal_notified && goal_reached_message(state).is_some() {
+            session.goal_notified = true;
+            match send_goal_reached_notification(&self.client, state) {
+                Ok(()) => info!("📱 Sent the goal to Pushover"),
+                Err(e) => warn!("Failed to notify Pushover: {e:#}"),
+            }
+        }
+        self.last_playing = Some(state.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixture::focus_state;
+    use mockito::Matcher;
+
+    fn client(server: &mockito::Server) -> PushoverClient {
+        PushoverClient {
+            messages_url: format!("{}/1/messages.json", server.url()),
+            ..PushoverClient::new("user-key".into(), "app-token".into())
+        }
+    }
+
+    #[test]
+    fn test_session_end_message() {
+        assert_eq!(
+            session_end_message(&focus_state(), 5025.0, &["Nothing Remains", "Blooming"]),
+            "Focus · Deep Work for 1:23:45\nTracks: Nothing Remains, Blooming"
+        );
+        assert_eq!(
+            session_end_message(&BrainFmState::new(), 90.0, &[]),
+            "Brain.fm for 1:30"
+        );
+    }
+
+    #[test]
+    fn test_send_session_end_notification() {
+        let mut server = mockito::Server::new();
+        let message = server
+            .mock("POST", "/1/messages.json")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("token".into(), "app-token".into()),
+                Matcher::UrlEncoded("user".into(), "user-key".into()),
+                Matcher::UrlEncoded("title".into(), "Brain.fm session ended".into()),
+                Matcher::UrlEncoded(
+                    "message".into(),
+                    "Focus · Deep Work for 25:00\nTracks: Nothing Remains".into(),
+                ),
+                Matcher::UrlEncoded("priority".into(), "0".into()),
+            ]))
+            .with_body(r#"{"status":1,"request":"647d2300-702c-4b38-8b2f-d56326ae460b"}"#)
+            .expect(1)
+            .create();
+
+        send_session_end_notification(&client(&server), &focus_state(), 1500.0).unwrap();
+        message.assert();
+    }
+
+    #[test]
+    fn test_send_goal_reached_notification() {
+        let mut server = mockito::Server::new();
+        let message = server
+            .mock("POST", "/1/messages.json")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("message".into(), "Focus goal of 45 min reached".into()),
+                Matcher::UrlEncoded("priority".into(), "-1".into()),
+            ]))
+            .with_body(r#"{"status":1}"#)
+            .expect(1)
+            .create();
+
+        let in_progress = BrainFmState {
+            session_goal_secs: Some(2700),
+            session_goal_progress_secs: Some(1320),
+            ..focus_state()
+        };
+        send_goal_reached_notification(&client(&server), &in_progress).unwrap();
+        let reached = BrainFmState {
+            session_goal_progress_secs: Some(2700),
+            ..in_progress
+        };
+        send_goal_reached_notification(&client(&server), &reached).unwrap();
+        message.assert();
+    }
+
+    #[test]
+    fn test_notifier_summarizes_session_and_goal_once() {
+        let mut server = mockito::Server::new();
+        let goal = server
+            .mock("POST", "/1/messages.json")
+            .match_body(Matcher::UrlEncoded("priority".into(), "-1".into()))
+            .with_body(r#"{"status":1}"#)
+            .expect(1)
+            .create();
+        let end = server
+            .mock("POST", "/1/messages.json")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded(
+                    "message".into(),
+                    "Focus · Deep Work for 0:00\nTracks: Nothing Remains, Blooming".into(),
+                ),
+                Matcher::UrlEncoded("priority".into(), "0".into()),
+            ]))
+            .with_body(r#"{"status":1}"#)
+            .expect(1)
+            .create();
+
+        let reached = BrainFmState {
+            session_goal_secs: Some(60),
+            session_goal_progress_secs: Some(60),
+            ..focus_state()
+        };
+        let mut notifier = PushoverNotifier::new(client(&server));
+        notifier.update(&BrainFmState::new());
+        notifier.update(&focus_state());
+        notifier.update(&reached);
+        notifier.update(&BrainFmState {
+            track_name: Some("Blooming".into()),
+            ..reached
+        });
+        notifier.update(&focus_state());
+        notifier.update(&BrainFmState::new());
+        notifier.update(&BrainFmState::new());
+
+        goal.assert();
+        end.assert();
+    }
+}
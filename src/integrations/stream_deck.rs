@@ -0,0 +1,226 @@
+//! Elgato Stream Deck integration
+//!
+//! `StreamDeckServer` listens for WebSocket connections from the companion
+//! Stream Deck plugin (see `MANIFEST_JSON`). A plugin registers with
+//! `{"event":"registerPlugin","uuid":"<context>"}`; from then on every
+//! `update()` sends it, for that context:
+//!
+//! - `setTitle`, `setState` and `setImage` with the fields of
+//!   `BrainFmState::to_stream_deck_json`, which the plugin forwards to its key
+//! - `sendToPlugin` with the whole payload, for the property inspector
+//!
+//! The latest state is also sent right after registration, so a key shows
+//! the current mode as soon as it appears.
+
+use crate::BrainFmState;
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use serde_json::{json, Value};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tungstenite::{Message, WebSocket};
+
+/// Port the plugin's manifest connects to
+pub const STREAM_DECK_PORT: u16 = 28196;
+
+/// Manifest of the companion Stream Deck plugin
+pub const MANIFEST_JSON: &str = include_str!("../../templates/manifest.json");
+
+/// UUID of the "Now Playing" action in `MANIFEST_JSON`
+pub const ACTION_UUID: &str = "fm.brain.presence.status";
+
+/// How long a new connection has to send `registerPlugin`
+const REGISTRATION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A registered plugin connection
+struct Plugin {
+    /// The `uuid` it registered with, sent back as each event's `context`
+    context: String,
+    socket: WebSocket<TcpStream>,
+}
+
+/// Registered plugins and the state last passed to `update()`
+#[derive(Default)]
+struct Shared {
+    plugins: Vec<Plugin>,
+    last_state: Option<BrainFmState>,
+}
+
+/// WebSocket server that keeps Stream Deck plugins showing the current state
+pub struct StreamDeckServer {
+    local_addr: SocketAddr,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl StreamDeckServer {
+    /// Listen on `addr` (usually `("127.0.0.1", STREAM_DECK_PORT)`),
+    /// accepting plugins on a background thread
+    pub fn start(addr: impl ToSocketAddrs) -> Result<Self> {
+        let listener = TcpListener::bind(addr).context("Failed to bind the Stream Deck server")?;
+        let local_addr = listener.local_addr()?;
+        let shared = Arc::new(Mutex::new(Shared::default()));
+
+        let accepted = Arc::clone(&shared);
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let plugin = stream
+                    .context("Failed to accept a Stream Deck connection")
+                    .and_then(register);
+                match plugin {
+                    Ok(mut plugin) => {
+                        info!("🎛️  Stream Deck plugin registered ({})", plugin.context);
+                        let mut shared = accepted.lock().unwrap_or_else(PoisonError::into_inner);
+                        let sent = shared
+                            .last_state
+                            .as_ref()
+                            .map_or(Ok(()), |state| send_state(&mut plugin, state));
+                        match sent {
+                            Ok(()) => shared.plugins.push(plugin),
+                            Err(e) => debug!("Stream Deck plugin went away: {e:#}"),
+                        }
+                    }
+                    Err(e) => warn!("Stream Deck plugin failed to register: {e:#}"),
+                }
+            }
+        });
+
+        info!("🎛️  Stream Deck server listening on ws://{local_addr}");
+        Ok(Self { local_addr, shared })
+    }
+
+    /// The address the server listens on
+    #[must_use]
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Send `state` to every registered plugin, dropping disconnected ones
+    pub fn update(&self, state: &BrainFmState) {
+        let mut shared = self.shared.lock().unwrap_or_else(PoisonError::into_inner);
+        shared
+            .plugins
+            .retain_mut(|plugin| match send_state(plugin, state) {
+                Ok(()) => true,
+                Err(e) => {
+                    debug!("Dropping Stream Deck plugin {}: {e:#}", plugin.context);
+                    false
+                }
+            });
+        shared.last_state = Some(state.clone());
+    }
+}
+
+/// Complete the WebSocket handshake and wait for `registerPlugin`
+fn register(stream: TcpStream) -> Result<Plugin> {
+    stream.set_read_timeout(Some(REGISTRATION_TIMEOUT))?;
+    let mut socket = tungstenite::accept(stream)
+        .map_err(|e| anyhow::anyhow!("WebSocket handshake failed: {e}"))?;
+    let context = loop {
+        let Message::Text(text) = socket.read()? else {
+            continue;
+        };
+        let message: Value = serde_json::from_str(&text)?;
+        anyhow::ensure!(
+            message["event"] == "registerPlugin",
+            "Expected registerPlugin, got {}",
+            message["event"]
+        );
+        break message["uuid"]
+            .as_str()
+            .context("registerPlugin without a uuid")?
+            .to_string();
+    };
+    socket.get_ref().set_read_timeout(None)?;
+    Ok(Plugin { context, socket })
+}
+
+/// Send `state` to `plugin` as the events listed in the module docs
+fn send_state(plugin: &mut Plugin, state: &BrainFmState) -> Result<()> {
+    for event in state_events(&plugin.context, state) {
+        plugin.socket.send(Message::Text(event.to_string()))?;
+    }
+    Ok(())
+}
+
+/// The events describing `state` to the plugin registered as `context`
+fn state_events(context: &str, state: &BrainFmState) -> [Value; 4] {
+    let payload = state.to_stream_deck_json();
+    [
+        json!({
+            "event": "setTitle",
+            "context": context,
+            "payload": { "title": payload["title"], "target": 0, "state": payload["state"] },
+        }),
+        json!({
+            "event": "setState",
+            "context": context,
+            "payload": { "state": payload["state"] },
+        }),
+        json!({
+            "event": "setImage",
+            "context": context,
+            "payload": { "image": payload["image"], "target": 0, "state": payload["state"] },
+        }),
+        json!({
+            "event": "sendToPlugin",
+            "context": context,
+            "action": ACTION_UUID,
+            "payload": payload,
+        }),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_declares_action() {
+        let manifest: Value = serde_json::from_str(MANIFEST_JSON).unwrap();
+        assert_eq!(manifest["Actions"][0]["UUID"], ACTION_UUID);
+        assert_eq!(
+            manifest["Actions"][0]["States"].as_array().unwrap().len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_plugin_receives_title_after_registering() {
+        let server = StreamDeckServer::start("127.0.0.1:0").unwrap();
+        server.update(&BrainFmState {
+            mode: Some("Focus".into()),
+            activity: Some("Deep Work".into()),
+            is_playing: true,
+            ..Default::default()
+        });
+
+        let (mut client, _) =
+            tungstenite::connect(format!("ws://{}", server.local_addr())).unwrap();
+        client
+            .send(Message::Text(
+                json!({ "event": "registerPlugin", "uuid": "key-1" }).to_string(),
+            ))
+            .unwrap();
+
+        let Message::Text(text) = client.read().unwrap() else {
+            panic!("expected a text message");
+        };
+        let set_title: Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(set_title["event"], "setTitle");
+        assert_eq!(set_title["context"], "key-1");
+        assert_eq!(set_title["payload"]["title"], "🧠\nDeep Work");
+        assert_eq!(set_title["payload"]["state"], 0);
+
+        // Later updates reach the registered plugin too
+        server.update(&BrainFmState::new());
+        let titles: Vec<Value> = std::iter::from_fn(|| match client.read().ok()? {
+            Message::Text(text) => serde_json::from_str(&text).ok(),
+            _ => None,
+        })
+        .filter(|event: &Value| event["event"] == "setTitle")
+        .take(1)
+        .collect();
+        assert_eq!(titles[0]["payload"]["title"], "🧠\nBrain.fm");
+    }
+}
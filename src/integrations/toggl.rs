@@ -0,0 +1,324 @@
+//! Toggl Track integration for automatic time tracking
+//!
+//! `start_time_entry` starts a running time entry for the current session,
+//! described as `"Focus — Nothing Remains"` and tagged `brain.fm` and the
+//! genre, and `stop_time_entry` stops it. `TogglTracker` starts one when
+//! playback starts and stops it when playback stops.
+//!
+//! Requests use Toggl's API v9 with the user's API token (Basic auth, the
+//! token as the user name and `api_token` as the password); see
+//! <https://engineering.toggl.com/docs/api/time_entries>.
+
+use crate::util::{self, HttpAuth};
+use crate::BrainFmState;
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/// Toggl's API v9
+const API_URL: &str = "https://api.track.toggl.com/api/v9";
+
+/// `created_with` of the entries, required by Toggl
+const CREATED_WITH: &str = "brainfm-presence";
+
+/// How long a Toggl request may take
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Toggl credentials
+#[derive(Debug, Clone)]
+pub struct TogglClient {
+    /// API token, from the Toggl Track profile page
+    pub api_token: String,
+    /// API root, `API_URL` except in tests
+    api_url: String,
+}
+
+impl TogglClient {
+    /// Act as the owner of `api_token`
+    #[must_use]
+    pub fn new(api_token: String) -> Self {
+        Self {
+            api_token,
+            api_url: API_URL.to_string(),
+        }
+    }
+
+    /// POST `body` as JSON to `path` under the API root and return the
+    /// JSON response
+    fn post(&self, path: &str, body: &Value) -> Result<Value> {
+        let url = format!("{}{path}", self.api_url);
+        Ok(util::http_post_json(
+            &url,
+            body,
+            self.auth(),
+            REQUEST_TIMEOUT,
+        )?)
+    }
+
+    /// PATCH `path` under the API root without a body and return the JSON
+    /// response
+    fn patch(&self, path: &str) -> Result<Value> {
+        let url = format!("{}{path}", self.api_url);
+        Ok(util::http_patch_json(&url, self.auth(), REQUEST_TIMEOUT)?)
+    }
+
+    /// Basic auth with the token as the user name
+    fn auth(&self) -> HttpAuth<'_> {
+        HttpAuth::Basic {
+            user: &self.api_token,
+            password: "api_token",
+        }
+    }
+}
+
+/// Start a running time entry for `state` in `workspace_id` (and
+/// `project_id`, if given) and return its ID. See `time_entry_body`.
+pub fn start_time_entry(
+    client: &TogglClient,
+    state: &BrainFmState,
+    workspace_id: u64,
+    project_id: Option<u64>,
+) -> Result<u64> {
+    let body = time_entry_body(
+        state,
+        workspace_id,
+        project_id,
+        &chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+    );
+    let entry = client.post(&format!("/workspaces/{workspace_id}/time_entries"), &body)?;
+    entry["id"]
+        .as_u64()
+        .with_context(|| format!("Toggl returned no time entry ID: {entry}"))
+}
+
+/// Stop the running time entry `entry_id` in `workspace_id` (Toggl's stop
+/// endpoint is per workspace)
+pub fn stop_time_entry(client: &TogglClient, workspace_id: u64, entry_id: u64) -> Result<()> {
+    client.patch(&format!(
+        "/workspaces/{workspace_id}/time_entries/{entry_id}/stop"
+    ))?;
+    Ok(())
+}
+
+/// A running entry (`duration` -1) started at `start`:
+/// `{"description":"Focus — Nothing Remains","tags":["brain.fm","Piano"],…}`.
+///
+/// The description is just the mode without a track, and "Brain.fm"
+/// without a mode; the genre tag is left out when it is unknown.
+fn time_entry_body(
+    state: &BrainFmState,
+    workspace_id: u64,
+    project_id: Option<u64>,
+    start: &str,
+) -> Value {
+    let mode = state.mode.as_deref().unwrap_or("Brain.fm");
+    let description = match state.track_name.as_deref() {
+        Some(track) => format!("{mode} — {track}"),
+        None => mode.to_string(),
+    };
+    let tags: Vec<&str> = std::iter::once("brain.fm")
+        .chain(state.genre.as_deref())
+        .collect();
+    json!({
+        "created_with": CREATED_WITH,
+        "description": description,
+        "tags": tags,
+        "workspace_id": workspace_id,
+        "project_id": project_id,
+        "start": start,
+        "duration": -1,
+    })
+}
+
+/// Keeps a Toggl time entry running while Brain.fm plays, stopping it when
+/// dropped
+pub struct TogglTracker {
+    client: TogglClient,
+    workspace_id: u64,
+    project_id: Option<u64>,
+    /// The running entry, `None` while not playing
+    toggl_entry_id: Option<u64>,
+}
+
+impl TogglTracker {
+    /// Track time in `workspace_id` (and `project_id`, if given)
+    #[must_use]
+    pub fn new(client: TogglClient, workspace_id: u64, project_id: Option<u64>) -> Self {
+        Self {
+            client,
+            workspace_id,
+            project_id,
+            toggl_entry_id: None,
+        }
+    }
+
+    /// Pass the latest state; Toggl errors are logged, not returned. A
+    /// failed start is retried on the next update.
+    pub fn update(&mut self, state: &BrainFmState) {
+        match (state.is_playing, self.toggl_entry_id) {
+            (true, None) => {
+                match start_time_entry(&self.client, state, self.workspace_id, self.project_id) {
+                    Ok(entry_id) => {
+                        info!("⏱️  Started Toggl time entry {entry_id}");
+                        self.toggl_entry_id = Some(entry_id);
+                    }
+                    Err(e) => warn!("Failed to start a Toggl time entry: {e:#}"),
+                }
+            }
+            (false, Some(_)) => self.stop(),
+            _ => {}
+        }
+    }
+
+    /// Stop the running entry, if any
+    fn stop(&mut self) {
+        let Some(entry_id) = self.toggl_entry_id.take() else {
+            return;
+        };
+        match stop_time_entry(&self.client, self.workspace_id, entry_id) {
+            Ok(()) => info!("⏱️  Stopped Toggl time entry {entry_id}"),
+            Err(e) => warn!("Failed to stop Toggl time entry {entry_id}: {e:#}"),
+        }
+    }
+}
+
+impl Drop for TogglTracker {
+    /// An entry left running would keep counting after exit
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixture::focus_state;
+    use mockito::Matcher;
+
+    fn client(server: &mockito::Server) -> TogglClient {
+        TogglClient {
+            api_url: format!("{}/api/v9", server.url()),
+            ..TogglClient::new("toggl-secret".into())
+        }
+    }
+
+    /// `Basic base64("toggl-secret:api_token")`
+    const AUTH: &str = "Basic dG9nZ2wtc2VjcmV0OmFwaV90b2tlbg==";
+
+    #[test]
+    fn test_time_entry_body() {
+        let body = time_entry_body(&focus_state(), 42, Some(7), "2025-01-01T12:00:00Z");
+        assert_eq!(
+            body,
+            json!({
+                "created_with": "brainfm-presence",
+                "description": "Focus — Nothing Remains",
+                "tags": ["brain.fm", "Piano"],
+                "workspace_id": 42,
+                "project_id": 7,
+                "start": "2025-01-01T12:00:00Z",
+                "duration": -1,
+            })
+        );
+
+        let body = time_entry_body(&BrainFmState::new(), 42, None, "2025-01-01T12:00:00Z");
+        assert_eq!(body["description"], "Brain.fm");
+        assert_eq!(body["tags"], json!(["brain.fm"]));
+        assert!(body["project_id"].is_null());
+    }
+
+    #[test]
+    fn test_start_and_stop_time_entry() {
+        let mut server = mockito::Server::new();
+        let start = server
+            .mock("POST", "/api/v9/workspaces/42/time_entries")
+            .match_header("Authorization", AUTH)
+            .match_body(Matcher::PartialJson(json!({
+                "description": "Focus — Nothing Remains",
+                "tags": ["brain.fm", "Piano"],
+                "workspace_id": 42,
+                "duration": -1,
+                "created_with": "brainfm-presence",
+            })))
+            .with_body(r#"{"id":3141592653,"workspace_id":42,"duration":-1}"#)
+            .expect(1)
+            .create();
+        let stop = server
+            .mock(
+                "PATCH",
+                "/api/v9/workspaces/42/time_entries/3141592653/stop",
+            )
+            .match_header("Authorization", AUTH)
+            .with_body(r#"{"id":3141592653,"duration":2520}"#)
+            .expect(1)
+            .create();
+
+        let client = client(&server);
+        let entry_id = start_time_entry(&client, &focus_state(), 42, None).unwrap();
+        assert_eq!(entry_id, 3_141_592_653);
+        stop_time_entry(&client, 42, entry_id).unwrap();
+        start.assert();
+        stop.assert();
+    }
+
+    #[test]
+    fn test_start_time_entry_reports_errors() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("POST", "/api/v9/workspaces/42/time_entries")
+            .with_status(403)
+            .with_body("Incorrect username and/or password")
+            .create();
+
+        let error = start_time_entry(&client(&server), &focus_state(), 42, None).unwrap_err();
+        assert!(error.to_string().contains("HTTP 403"), "{error:#}");
+    }
+
+    #[test]
+    fn test_tracker_starts_on_play_and_stops_on_pause() {
+        let mut server = mockito::Server::new();
+        let start = server
+            .mock("POST", "/api/v9/workspaces/42/time_entries")
+            .match_body(Matcher::PartialJson(json!({ "project_id": 7 })))
+            .with_body(r#"{"id":99}"#)
+            .expect(2)
+            .create();
+        let stop = server
+            .mock("PATCH", "/api/v9/workspaces/42/time_entries/99/stop")
+            .with_body(r#"{"id":99}"#)
+            .expect(1)
+            .create();
+
+        let mut tracker = TogglTracker::new(client(&server), 42, Some(7));
+        tracker.update(&BrainFmState::new());
+        tracker.update(&focus_state());
+        tracker.update(&focus_state());
+        assert_eq!(tracker.toggl_entry_id, Some(99));
+        tracker.update(&BrainFmState::new());
+        tracker.update(&BrainFmState::new());
+        assert_eq!(tracker.toggl_entry_id, None);
+        tracker.update(&focus_state());
+        start.assert();
+        stop.assert();
+    }
+
+    #[test]
+    fn test_tracker_stops_entry_when_dropped() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("POST", "/api/v9/workspaces/42/time_entries")
+            .with_body(r#"{"id":99}"#)
+            .create();
+        let stop = server
+            .mock("PATCH", "/api/v9/workspaces/42/time_entries/99/stop")
+            .with_body(r#"{"id":99}"#)
+            .expect(1)
+            .create();
+
+        let mut tracker = TogglTracker::new(client(&server), 42, None);
+        tracker.update(&focus_state());
+        drop(tracker);
+        stop.assert();
+    }
+}
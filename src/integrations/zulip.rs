@@ -0,0 +1,256 @@
+//! Zulip integration for team focus notifications
+//!
+//! A bot posts to a stream when its user starts a focus session
+//! (`post_focus_start`) and mirrors the current mode in a Zulip status
+//! (`ZulipClient::update_user_status`). The status belongs to the account
+//! whose API key is used, so a personal API key puts it on the user's own
+//! profile. `ZulipNotifier` drives both from the states passed to `update()`.
+//!
+//! Requests use Zulip's REST API: HTTP basic auth with the bot's email and
+//! API key, and form-encoded parameters.
+
+use crate::util::{self, HttpAuth};
+use crate::BrainFmState;
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::de::IgnoredAny;
+use std::fmt::Write as _;
+use std::time::Duration;
+
+/// Zulip truncates statuses longer than this many characters
+const MAX_STATUS_LEN: usize = 60;
+
+/// Modes whose start is announced by `ZulipNotifier`
+const FOCUS_MODES: &[&str] = &["Focus", "Deep Work"];
+
+/// How long a Zulip request may take
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Credentials of a Zulip bot and the user it posts about
+#[derive(Debug, Clone)]
+pub struct ZulipClient {
+    /// Bot email, e.g. `brainfm-bot@example.zulipchat.com`
+    pub bot_email: String,
+    /// Bot API key
+    pub bot_api_key: String,
+    /// Organization URL, e.g. `https://example.zulipchat.com`
+    pub server_url: String,
+    /// Zulip full name of the person listening, mentioned in posts
+    pub user: String,
+}
+
+impl ZulipClient {
+    /// Set the account's status to the current mode (`Focus · Deep Work`,
+    /// with the 🧠 emoji), or clear it when nothing is playing
+    pub fn update_user_status(&self, state: &BrainFmState) -> Result<()> {
+        let (text, emoji) = match status_text(state) {
+            Some(text) => (text, "brain"),
+            None => (String::new(), ""),
+        };
+        self.post(
+            "/api/v1/users/me/status",
+            &[("status_text", text.as_str()), ("emoji_name", emoji)],
+        )
+    }
+
+    /// POST form `params` to the API endpoint `path`
+    fn post(&self, path: &str, params: &[(&str, &str)]) -> Result<()> {
+        let url = format!("{}{path}", self.server_url.trim_end_matches('/'));
+        let auth = HttpAuth::Basic {
+            user: &self.bot_email,
+            password: &self.bot_api_key,
+        };
+        util::http_post_form::<IgnoredAny>(&url, params, auth, REQUEST_TIMEOUT)
+            .with_context(|| format!("Zulip request to {path} failed"))?;
+        Ok(())
+    }
+}
+
+/// Post `"@**user** started Brain.fm: **Focus** 🧠 — listening to _track_
+/// (genre)"` to `stream` under `topic`. The track and genre are left out
+/// when unknown.
+pub fn post_focus_start(
+    client: &ZulipClient,
+    state: &BrainFmState,
+    stream: &str,
+    topic: &str,
+) -> Result<()> {
+    let content = focus_start_message(&client.user, state);
+    client.post(
+        "/api/v1/messages",
+        &[
+            ("type", "stream"),
+            ("to", stream),
+            ("topic", topic),
+            ("content", content.as_str()),
+        ],
+    )
+}
+
+/// The message posted by `post_focus_start`
+fn focus_start_message(user: &str, state: &BrainFmState) -> String {
+    let mode = state.mode.as_deref().unwrap_or("Brain.fm");
+    let mut message = format!("@**{user}** started Brain.fm: **{mode}** 🧠");
+    if let Some(track) = state.track_name.as_deref() {
+        let _ = write!(message, " — listening to _{track}_");
+        if let Some(genre) = state.genre.as_deref() {
+            let _ = write!(message, " ({genre})");
+        }
+    }
+    message
+}
+
+/// Status text for `state`, or `None` when nothing is playing
+fn status_text(state: &BrainFmState) -> Option<String> {
+    if !state.is_playing {
+        return None;
+    }
+    let text = match (state.mode.as_deref(), state.activity.as_deref()) {
+        (Some(mode), Some(activity)) if mode != activity => format!("{mode} · {activity}"),
+        (Some(mode), _) => mode.to_string(),
+        (None, _) => "Brain.fm".to_string(),
+    };
+    Some(util::truncate(&text, MAX_STATUS_LEN))
+}
+
+/// Keeps Zulip in sync with the state: announces each focus session once
+/// and updates the user status whenever the mode changes
+pub struct ZulipNotifier {
+    client: ZulipClient,
+    stream: String,
+    topic: String,
+    /// Status text last sent (empty once cleared), to skip unchanged
+    /// updates; `None` before the first one
+    last_status: Option<String>,
+    /// Whether the last state was a focus session
+    in_focus: bool,
+}
+
+impl ZulipNotifier {
+    /// Post focus sessions to `stream` under `topic`
+    #[must_use]
+    pub fn new(client: ZulipClient, stream: String, topic: String) -> Self {
+        Self {
+            client,
+            stream,
+            topic,
+            last_status: None,
+            in_focus: false,
+        }
+    }
+
+    /// Pass the latest state; Zulip errors are logged, not returned
+    pub fn update(&mut self, state: &BrainFmState) {
+        let in_focus = state.is_playing
+            && [state.mode.as_deref(), state.activity.as_deref()]
+                .into_iter()
+                .flatten()
+                .any(|name| FOCUS_MODES.contains(&name));
+        if in_focus && !self.in_focus {
+            match post_focus_start(&self.client, state, &self.stream, &self.topic) {
+                Ok(()) => info!("💬 Posted focus session to Zulip #{}", self.stream),
+                Err(e) => warn!("Failed to post to Zulip: {e:#}"),
+            }
+        }
+        self.in_focus = in_focus;
+
+        let status = status_text(state).unwrap_or_default();
+        if self.last_status.as_ref() != Some(&status) {
+            match self.client.update_user_status(state) {
+                Ok(()) => self.last_status = Some(status),
+                Err(e) => warn!("Failed to update the Zulip status: {e:#}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixture::focus_state;
+    use base64::prelude::*;
+    use mockito::Matcher;
+
+    fn client(server: &mockito::Server) -> ZulipClient {
+        ZulipClient {
+            bot_email: "bot@example.com".into(),
+            bot_api_key: "secret".into(),
+            server_url: server.url(),
+            user: "Ada Lovelace".into(),
+        }
+    }
+
+    #[test]
+    fn test_focus_start_message() {
+        assert_eq!(
+            focus_start_message("Ada", &focus_state()),
+            "@**Ada** started Brain.fm: **Focus** 🧠 — listening to _Nothing Remains_ (Piano)"
+        );
+        let no_track = BrainFmState {
+            mode: Some("Focus".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            focus_start_message("Ada", &no_track),
+            "@**Ada** started Brain.fm: **Focus** 🧠"
+        );
+    }
+
+    #[test]
+    fn test_post_focus_start_sends_stream_message() {
+        let mut server = mockito::Server::new();
+        let auth = format!("Basic {}", BASE64_STANDARD.encode("bot@example.com:secret"));
+        let message = server
+            .mock("POST", "/api/v1/messages")
+            .match_header("Authorization", auth.as_str())
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("type".into(), "stream".into()),
+                Matcher::UrlEncoded("to".into(), "focus".into()),
+                Matcher::UrlEncoded("topic".into(), "Deep work".into()),
+                Matcher::UrlEncoded(
+                    "content".into(),
+                    focus_start_message("Ada Lovelace", &focus_state()),
+                ),
+            ]))
+            .with_body(r#"{"result": "success", "id": 42}"#)
+            .expect(1)
+            .create();
+
+        post_focus_start(&client(&server), &focus_state(), "focus", "Deep work").unwrap();
+        message.assert();
+    }
+
+    #[test]
+    fn test_notifier_posts_once_and_updates_status() {
+        let mut server = mockito::Server::new();
+        let message = server
+            .mock("POST", "/api/v1/messages")
+            .with_body(r#"{"result": "success"}"#)
+            .expect(1)
+            .create();
+        let focus_status = server
+            .mock("POST", "/api/v1/users/me/status")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("status_text".into(), "Focus · Deep Work".into()),
+                Matcher::UrlEncoded("emoji_name".into(), "brain".into()),
+            ]))
+            .with_body(r#"{"result": "success"}"#)
+            .expect(1)
+            .create();
+        let cleared_status = server
+            .mock("POST", "/api/v1/users/me/status")
+            .match_body(Matcher::UrlEncoded("status_text".into(), String::new()))
+            .with_body(r#"{"result": "success"}"#)
+            .expect(1)
+            .create();
+
+        let mut notifier = ZulipNotifier::new(client(&server), "focus".into(), "Ada".into());
+        notifier.update(&focus_state());
+        notifier.update(&focus_state());
+        notifier.update(&BrainFmState::new());
+
+        message.assert();
+        focus_status.assert();
+        cleared_status.assert();
+    }
+}
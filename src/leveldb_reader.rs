@@ -2,7 +2,9 @@
 //!
 //! Reads persistently stored data from the Electron app's LevelDB storage.
 
-use crate::util::{KNOWN_GENRES, MODE_PATTERNS, MP3_FILENAME_RE};
+use crate::util::{
+    normalize_activity, ACTIVITY_NORMALIZATIONS, KNOWN_GENRES, MODE_PATTERNS, MP3_FILENAME_RE,
+};
 use crate::BrainFmState;
 use anyhow::Result;
 use regex::Regex;
@@ -21,6 +23,14 @@ static TRACK_NAME_RE: LazyLock<Regex> =
 static TRACK_URL_RE: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r#""url"\s*:\s*"([^"]+\.mp3[^"]*)""#).unwrap());
 
+/// Regexes for the session goal fields stored under `persist:goals`
+static GOAL_DURATION_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"goalDuration["\s:\\]+(\d+)"#).unwrap());
+static GOAL_PROGRESS_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"goalProgress["\s:\\]+(\d+)"#).unwrap());
+static GOAL_TYPE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"goalType["\s:\\]+([A-Za-z_]+)"#).unwrap());
+
 /// Read Brain.fm state from LevelDB files using strings extraction
 ///
 /// Note: We use `strings` command because LevelDB files might be locked by the app.
@@ -29,20 +39,197 @@ pub fn read_state(app_support_path: &Path) -> Result<BrainFmState> {
     let leveldb_path = app_support_path.join("Local Storage").join("leveldb");
 
     if !leveldb_path.exists() {
+        // Electron 25+ may have migrated localStorage to `IndexedDB`
+        #[cfg(feature = "sqlite-fallback")]
+        if let Some(content) = try_read_indexeddb(app_support_path)? {
+            return Ok(parse_fallback_content(&content));
+        }
         anyhow::bail!("LevelDB path not found: {:?}", leveldb_path);
     }
 
     let mut state = BrainFmState::new();
+    let mut focus_mode = None;
+    let mut files_read = 0;
+
+    // Parse one LevelDB file at a time so the whole store is never held in
+    // memory. Once track, mode and the ADHD flag are all found, later files
+    // have nothing left to add.
+    for content in crate::util::iter_leveldb_strings(&leveldb_path) {
+        let content = content?;
+        files_read += 1;
+        state = parse_leveldb_content(&content, state);
+        if focus_mode.is_none() {
+            focus_mode = find_focus_mode(&content);
+        }
+
+        if state.track_name.is_some() && state.mode.is_some() && state.adhd_mode {
+            break;
+        }
+    }
 
-    // Read strings from all LevelDB files using native Rust I/O
-    let content = crate::util::read_leveldb_strings(&leveldb_path)?;
+    // Newer Electron versions may keep localStorage in LMDB instead
+    #[cfg(feature = "lmdb-support")]
+    if files_read == 0 {
+        if let Some(content) = try_read_lmdb(&leveldb_path)? {
+            state = parse_leveldb_content(&content, state);
+            focus_mode = find_focus_mode(&content);
+        }
+    }
+    #[cfg(feature = "sqlite-fallback")]
+    if files_read == 0 && state.mode.is_none() && focus_mode.is_none() {
+        if let Some(content) = try_read_indexeddb(app_support_path)? {
+            return Ok(parse_fallback_content(&content));
+        }
+    }
+    #[cfg(not(any(feature = "lmdb-support", feature = "sqlite-fallback")))]
+    let _ = files_read;
 
-    // Parse the content for Brain.fm data
-    state = parse_leveldb_content(&content, state);
+    // Loose focus-mode matches only count when no file had a definite mode
+    if state.mode.is_none() {
+        state.mode = focus_mode;
+    }
 
     Ok(state)
 }
 
+/// Read the printable strings of an LMDB localStorage store in `leveldb_path`.
+///
+/// Looks for `data.mdb` in the directory itself, then for an `LMDB` store
+/// (a directory holding `data.mdb`, or a single file) inside it. Every key
+/// and value goes through the same `strings`-style extraction as `LevelDB`
+/// files, so `parse_leveldb_content` works unchanged. Returns `None` when no
+/// LMDB store exists.
+#[cfg(feature = "lmdb-support")]
+#[allow(unsafe_code)]
+pub fn try_read_lmdb(leveldb_path: &Path) -> Result<Option<String>> {
+    use anyhow::Context;
+    use heed::types::Bytes;
+    use heed::{Database, EnvFlags, EnvOpenOptions};
+
+    let lmdb = leveldb_path.join("LMDB");
+    let (env_path, mut flags) = if leveldb_path.join("data.mdb").is_file() {
+        (leveldb_path.to_path_buf(), EnvFlags::empty())
+    } else if lmdb.join("data.mdb").is_file() {
+        (lmdb, EnvFlags::empty())
+    } else if lmdb.is_file() {
+        (lmdb, EnvFlags::NO_SUB_DIR)
+    } else {
+        return Ok(None);
+    };
+    // Never write to the app's store, not even a lock file
+    flags |= EnvFlags::READ_ONLY | EnvFlags::NO_LOCK;
+
+    let mut options = EnvOpenOptions::new();
+    // SAFETY: the environment is opened read-only and only once in this
+    // process, which is what heed requires of `flags` and `open`.
+    let env = unsafe {
+        options.flags(flags);
+        options.open(&env_path)
+    }
+    .with_context(|| format!("Failed to open LMDB store: {}", env_path.display()))?;
+
+    let rtxn = env.read_txn()?;
+    let Some(db): Option<Database<Bytes, Bytes>> = env.open_database(&rtxn, None)? else {
+        return Ok(None);
+    };
+
+    let mut content = String::new();
+    for entry in db.iter(&rtxn)? {
+        let (key, value) = entry?;
+        crate::util::extract_printable_strings(key, crate::util::MIN_STRING_LEN, &mut content);
+        crate::util::extract_printable_strings(value, crate::util::MIN_STRING_LEN, &mut content);
+    }
+    Ok(Some(content))
+}
+
+/// Parse the strings of a whole store read in one go (`IndexedDB`), the
+/// same way `read_state` parses `LevelDB` files
+#[cfg(feature = "sqlite-fallback")]
+fn parse_fallback_content(content: &str) -> BrainFmState {
+    let mut state = parse_leveldb_content(content, BrainFmState::new());
+    if state.mode.is_none() {
+        state.mode = find_focus_mode(content);
+    }
+    state
+}
+
+/// Read the strings of the `IndexedDB` databases (`IndexedDB/*.sqlite`) in
+/// `app_support_path`, in file name order. Returns `None` when there are none.
+#[cfg(feature = "sqlite-fallback")]
+fn try_read_indexeddb(app_support_path: &Path) -> Result<Option<String>> {
+    let Ok(entries) = std::fs::read_dir(app_support_path.join("IndexedDB")) else {
+        return Ok(None);
+    };
+    let mut databases: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "sqlite"))
+        .collect();
+    if databases.is_empty() {
+        return Ok(None);
+    }
+    databases.sort();
+
+    let mut content = String::new();
+    for db_path in databases {
+        content.push_str(&read_indexeddb_strings(&db_path)?);
+    }
+    Ok(Some(content))
+}
+
+/// Read the stored values of an `IndexedDB` `SQLite` database at `db_path`.
+///
+/// Values come from the `object_data` table, or `object_store_data` in
+/// older schemas. Each value's UTF-8 strings of at least `MIN_STRING_LEN`
+/// characters are returned one per line, like `read_leveldb_strings`, so
+/// the `LevelDB` pattern matchers work unchanged. The database is opened
+/// read-only.
+#[cfg(feature = "sqlite-fallback")]
+pub fn read_indexeddb_strings(db_path: &Path) -> Result<String> {
+    use anyhow::Context;
+    use rusqlite::{Connection, OpenFlags};
+
+    let conn = Connection::open_with_flags(
+        db_path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .with_context(|| format!("Failed to open IndexedDB database: {}", db_path.display()))?;
+    indexeddb_strings(&conn)
+        .with_context(|| format!("Failed to read IndexedDB database: {}", db_path.display()))
+}
+
+/// The strings of the values in `conn`'s `IndexedDB` object table
+#[cfg(feature = "sqlite-fallback")]
+fn indexeddb_strings(conn: &rusqlite::Connection) -> Result<String> {
+    use crate::util::{extract_unicode_strings, MIN_STRING_LEN};
+    use rusqlite::types::ValueRef;
+
+    let mut content = String::new();
+    for table in ["object_data", "object_store_data"] {
+        let exists: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+            [table],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            continue;
+        }
+
+        let mut statement = conn.prepare(&format!("SELECT value FROM {table}"))?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            let (ValueRef::Blob(bytes) | ValueRef::Text(bytes)) = row.get_ref(0)? else {
+                continue;
+            };
+            for string in extract_unicode_strings(bytes, MIN_STRING_LEN) {
+                content.push_str(&string);
+                content.push('\n');
+            }
+        }
+        break;
+    }
+    Ok(content)
+}
+
 /// Parse the extracted strings content for Brain.fm data
 fn parse_leveldb_content(content: &str, mut state: BrainFmState) -> BrainFmState {
     // First, try to find the most recent playback event which has accurate track info
@@ -83,6 +270,10 @@ fn parse_leveldb_content(content: &str, mut state: BrainFmState) -> BrainFmState
         }
     }
 
+    if state.session_goal_secs.is_none() {
+        state = parse_session_goal(content, state);
+    }
+
     // Check for ADHD mode
     if content.contains("\"isAdhdModeEnabled\":\"true\"")
         || content.contains("isAdhdModeEnabled\":true")
@@ -90,28 +281,39 @@ fn parse_leveldb_content(content: &str, mut state: BrainFmState) -> BrainFmState
         state.adhd_mode = true;
     }
 
-    // Try to find session information
-    // Look for patterns like "focus" or "sleep" in recent context
-    if state.mode.is_none() {
-        // Use simpler pattern matching
-        let focus_indicators = [
-            ("deep_work", "Deep Work"),
-            ("light_work", "Light Work"),
-            ("Deep Work", "Deep Work"),
-            ("Light Work", "Light Work"),
-        ];
-
-        for (indicator, mode) in &focus_indicators {
-            if content.contains(indicator) {
-                state.mode = Some(mode.to_string());
-                break;
-            }
-        }
-    }
+    state
+}
 
+/// Parse the session goal from the last `persist:goals` entry in `content`,
+/// e.g. `{"goalType":"FOCUS","goalDuration":2700,"goalProgress":1340}`
+fn parse_session_goal(content: &str, mut state: BrainFmState) -> BrainFmState {
+    let Some(start) = content.rfind("persist:goals") else {
+        return state;
+    };
+    let goals = &content[start..];
+    let number = |re: &Regex| {
+        re.captures(goals)
+            .and_then(|caps| caps[1].parse::<u32>().ok())
+    };
+    state.session_goal_secs = number(&GOAL_DURATION_RE);
+    state.session_goal_progress_secs = number(&GOAL_PROGRESS_RE);
+    state.session_goal_type = GOAL_TYPE_RE.captures(goals).map(|caps| caps[1].to_string());
     state
 }
 
+/// Look for a focus activity name anywhere in `content`.
+///
+/// A weaker signal than `parse_leveldb_content`'s mode detection, used only
+/// when no LevelDB file yields a mode.
+fn find_focus_mode(content: &str) -> Option<String> {
+    // Use simpler pattern matching against the focus activity spellings
+    ACTIVITY_NORMALIZATIONS
+        .iter()
+        .filter(|(_, canonical)| matches!(*canonical, "Deep Work" | "Light Work"))
+        .find(|(indicator, _)| content.contains(indicator))
+        .map(|(indicator, _)| normalize_activity(indicator))
+}
+
 /// Parse playback events to get the current track
 /// These events contain the most accurate real-time track information
 fn parse_playback_events(content: &str, mut state: BrainFmState) -> BrainFmState {
@@ -225,4 +427,185 @@ mod tests {
         let state = parse_leveldb_content(content, BrainFmState::new());
         assert!(state.adhd_mode);
     }
+
+    #[test]
+    fn test_parse_session_goal() {
+        let content = r#"_https://my.brain.fm persist:goals
+{\"goalType\":\"FOCUS\",\"goalDuration\":2700,\"goalProgress\":1340}"#;
+        let state = parse_leveldb_content(content, BrainFmState::new());
+        assert_eq!(state.session_goal_type.as_deref(), Some("FOCUS"));
+        assert_eq!(state.session_goal_secs, Some(2700));
+        assert_eq!(state.session_goal_progress_secs, Some(1340));
+        assert_eq!(state.to_goal_string().as_deref(), Some("Goal: 22/45 min"));
+        assert!((state.goal_percentage().unwrap() - 49.629_63).abs() < 0.001);
+
+        // Goal fields outside persist:goals are ignored
+        let state = parse_leveldb_content("{\"goalDuration\":600}", BrainFmState::new());
+        assert_eq!(state.session_goal_secs, None);
+        assert_eq!(state.goal_percentage(), None);
+    }
+
+    #[test]
+    fn test_find_focus_mode() {
+        assert_eq!(
+            find_focus_mode("activity=light_work"),
+            Some("Light Work".to_string())
+        );
+        assert_eq!(find_focus_mode("nothing here"), None);
+    }
+
+    #[cfg(feature = "lmdb-support")]
+    #[test]
+    #[allow(unsafe_code)]
+    fn test_read_state_falls_back_to_lmdb() {
+        use heed::types::Bytes;
+        use heed::{Database, EnvOpenOptions};
+
+        let temp = tempfile::tempdir().unwrap();
+        let app_dir = temp.path();
+        let leveldb_dir = app_dir.join("Local Storage").join("leveldb");
+        std::fs::create_dir_all(&leveldb_dir).unwrap();
+
+        // Write a minimal data.mdb fixture
+        {
+            // SAFETY: the fixture environment is opened once, by this test only.
+            let env = unsafe { EnvOpenOptions::new().open(&leveldb_dir) }.unwrap();
+            let mut wtxn = env.write_txn().unwrap();
+            let db: Database<Bytes, Bytes> = env.create_database(&mut wtxn, None).unwrap();
+            db.put(
+                &mut wtxn,
+                b"_https://my.brain.fm\x00\x01persist:activities",
+                b"\x01{\"displayValue\":\"Deep Work\",\"isAdhdModeEnabled\":true}",
+            )
+            .unwrap();
+            wtxn.commit().unwrap();
+        }
+
+        let content = try_read_lmdb(&leveldb_dir).unwrap().unwrap();
+        let state = read_state(app_dir).unwrap();
+
+        assert!(content.contains("persist:activities"));
+        assert_eq!(state.mode, Some("Deep Work".to_string()));
+        assert!(state.adhd_mode);
+    }
+
+    #[cfg(feature = "lmdb-support")]
+    #[test]
+    #[allow(unsafe_code)]
+    fn test_try_read_lmdb_single_file_store() {
+        use heed::types::Bytes;
+        use heed::{Database, EnvFlags, EnvOpenOptions};
+
+        let leveldb_dir = tempfile::tempdir().unwrap();
+        assert!(try_read_lmdb(leveldb_dir.path()).unwrap().is_none());
+
+        // An `LMDB` file rather than a directory holding `data.mdb`
+        let store = leveldb_dir.path().join("LMDB");
+        {
+            let mut options = EnvOpenOptions::new();
+            // SAFETY: the fixture environment is opened once, by this test only.
+            let env = unsafe {
+                options.flags(EnvFlags::NO_SUB_DIR);
+                options.open(&store)
+            }
+            .unwrap();
+            let mut wtxn = env.write_txn().unwrap();
+            let db: Database<Bytes, Bytes> = env.create_database(&mut wtxn, None).unwrap();
+            db.put(
+                &mut wtxn,
+                b"_https://my.brain.fm\x00\x01persist:auth",
+                b"\x01{\"token\":\"abc\"}",
+            )
+            .unwrap();
+            wtxn.commit().unwrap();
+        }
+
+        let content = try_read_lmdb(leveldb_dir.path()).unwrap().unwrap();
+        assert!(content.contains("persist:auth"));
+        assert!(content.contains("token"));
+    }
+
+    #[cfg(feature = "sqlite-fallback")]
+    fn seed_indexeddb(conn: &rusqlite::Connection, table: &str) {
+        conn.execute_batch(&format!(
+            "CREATE TABLE {table} (object_store_id INTEGER, key BLOB, value BLOB NOT NULL)"
+        ))
+        .unwrap();
+        for value in [
+            &b"\xff\x0f{\"displayValue\":\"Deep Work\",\"isAdhdModeEnabled\":true}"[..],
+            b"\x00\x01{\"event\":\"core_playback_start_success\",\"name\":\"Nothing Remains\",\"url\":\"https://audio.brain.fm/Nothing_Remains_Focus_Electronic_120_HNE.mp3\"}",
+        ] {
+            conn.execute(
+                &format!("INSERT INTO {table} (object_store_id, key, value) VALUES (1, x'00', ?1)"),
+                [value],
+            )
+            .unwrap();
+        }
+    }
+
+    #[cfg(feature = "sqlite-fallback")]
+    #[test]
+    fn test_indexeddb_strings_from_either_table() {
+        for table in ["object_data", "object_store_data"] {
+            let conn = rusqlite::Connection::open_in_memory().unwrap();
+            seed_indexeddb(&conn, table);
+            let content = indexeddb_strings(&conn).unwrap();
+            assert!(content.contains("Deep Work"), "{table}: {content}");
+
+            let state = parse_fallback_content(&content);
+            assert_eq!(state.mode.as_deref(), Some("Deep Work"));
+            assert_eq!(state.track_name.as_deref(), Some("Nothing Remains"));
+            assert!(state.adhd_mode);
+        }
+
+        let empty = rusqlite::Connection::open_in_memory().unwrap();
+        assert_eq!(indexeddb_strings(&empty).unwrap(), "");
+    }
+
+    #[cfg(feature = "sqlite-fallback")]
+    #[test]
+    fn test_read_state_falls_back_to_indexeddb() {
+        let temp = tempfile::tempdir().unwrap();
+        let app_dir = temp.path();
+        let indexeddb_dir = app_dir.join("IndexedDB");
+        std::fs::create_dir_all(&indexeddb_dir).unwrap();
+        let conn =
+            rusqlite::Connection::open(indexeddb_dir.join("https_my.brain.fm_0.sqlite")).unwrap();
+        seed_indexeddb(&conn, "object_data");
+        drop(conn);
+
+        // No LevelDB directory at all
+        let state = read_state(app_dir).unwrap();
+        assert_eq!(state.mode.as_deref(), Some("Deep Work"));
+        assert_eq!(state.track_name.as_deref(), Some("Nothing Remains"));
+
+        // An empty one
+        std::fs::create_dir_all(app_dir.join("Local Storage").join("leveldb")).unwrap();
+        let state = read_state(app_dir).unwrap();
+        assert_eq!(state.mode.as_deref(), Some("Deep Work"));
+    }
+
+    #[test]
+    fn test_read_state_across_files() {
+        let temp = tempfile::tempdir().unwrap();
+        let app_dir = temp.path();
+        let leveldb_dir = app_dir.join("Local Storage").join("leveldb");
+        std::fs::create_dir_all(&leveldb_dir).unwrap();
+        std::fs::write(
+            leveldb_dir.join("000001.ldb"),
+            "\x00deep_work\x00{\"isAdhdModeEnabled\":\"true\"}\x00",
+        )
+        .unwrap();
+        std::fs::write(
+            leveldb_dir.join("000002.log"),
+            "\x00persist:activities{\"displayValue\":\"Motivation\"}\x00",
+        )
+        .unwrap();
+
+        let state = read_state(app_dir).unwrap();
+
+        // The definite mode wins over the loose focus match, whatever the file order
+        assert_eq!(state.mode, Some("Motivation".to_string()));
+        assert!(state.adhd_mode);
+    }
 }
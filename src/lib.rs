@@ -1,4 +1,4 @@
-#![deny(unsafe_code)]
+#![deny(unsafe_code, clippy::must_use_candidate)]
 
 //! Brain.fm information reader
 //!
@@ -12,20 +12,38 @@
 //! 4. **LevelDB** — Persisted Redux state (baseline data, may be stale)
 
 use anyhow::Result;
-use log::{debug, warn};
+use log::{debug, info, warn};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
 
 pub mod api_cache_reader;
 pub mod api_client;
+#[cfg(feature = "tokio-async")]
+pub mod async_reader;
 pub mod cache_reader;
+pub mod config;
+pub mod error;
+pub mod history;
+pub mod integrations;
 pub mod leveldb_reader;
+pub mod logging;
 pub mod media_remote_reader;
 pub mod platform;
+pub mod reports;
+#[cfg(test)]
+pub(crate) mod test_fixture;
+#[cfg(feature = "test-utils")]
+pub mod testing;
 pub mod util;
 
+pub use error::BrainFmError;
+
 /// Represents the current state of Brain.fm playback
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[allow(clippy::struct_excessive_bools)] // independent flags from different sources
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub struct BrainFmState {
     /// Current mental state mode (e.g., "Focus", "Sleep", "Relax", "Meditate")
     pub mode: Option<String>,
@@ -48,17 +66,61 @@ pub struct BrainFmState {
     /// Track image URL (usually from Unsplash, used for Discord large image)
     pub image_url: Option<String>,
 
+    /// Beats per minute (from API metadata)
+    pub bpm: Option<u32>,
+
+    /// Mood tags (e.g., `["Calm", "Chill"]`)
+    pub moods: Vec<String>,
+
+    /// Instrument tags (e.g., `["Acoustic Piano", "Electronic Percussion"]`)
+    pub instruments: Vec<String>,
+
     /// Session state (e.g., "IN FOCUS")
     pub session_state: Option<String>,
 
     /// Time in current session (formatted as "H:MM:SS")
     pub session_time: Option<String>,
 
+    /// Elapsed session time in seconds, for sources that report it numerically.
+    ///
+    /// Displayed via `util::format_duration` when `session_time` is not set.
+    pub session_elapsed_secs: Option<f64>,
+
     /// Whether infinite play is enabled
     pub infinite_play: bool,
 
     /// Whether ADHD mode is enabled
     pub adhd_mode: bool,
+
+    /// How many times the current track has started playing since the
+    /// reader was created, counting this play (set by `BrainFmReader::read_state`)
+    pub play_count: Option<u32>,
+
+    /// When a data source last answered successfully (set by
+    /// `BrainFmReader::read_state`). A state assembled while every source
+    /// was failing, e.g. `lsof` erroring or `LevelDB` locked, keeps the time
+    /// of the last good read; see `data_staleness_secs()`.
+    pub last_successful_read: Option<SystemTime>,
+
+    /// How far the track metadata can be trusted, from 0.0 to 1.0, going by
+    /// where it came from (set by `BrainFmReader::read_state`; see
+    /// `MetadataSource::confidence`)
+    #[serde(default)]
+    pub confidence_score: f32,
+
+    /// Kind of session goal set in Brain.fm, as stored (e.g., "FOCUS")
+    pub session_goal_type: Option<String>,
+
+    /// Length of the session goal in seconds (e.g., 2700 for 45 minutes)
+    pub session_goal_secs: Option<u32>,
+
+    /// Seconds of the session goal completed so far
+    pub session_goal_progress_secs: Option<u32>,
+
+    /// Whether the track is a guided meditation, from the API metadata (see
+    /// `meditation_type()`)
+    #[serde(default)]
+    pub is_guided_meditation: bool,
 }
 
 impl BrainFmState {
@@ -68,12 +130,113 @@ impl BrainFmState {
         Self::default()
     }
 
+    /// Whether the state holds no data at all: every field is `None`, empty
+    /// or `false`, as in `BrainFmState::default()`.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Seconds since `last_successful_read`, or `None` if it is unset (or in
+    /// the future)
+    #[must_use]
+    pub fn data_staleness_secs(&self) -> Option<f64> {
+        SystemTime::now()
+            .duration_since(self.last_successful_read?)
+            .ok()
+            .map(|d| d.as_secs_f64())
+    }
+
+    /// Whether this state differs from `previous` in a way worth recording:
+    /// `track_name`, `mode`, `activity`, `is_playing` or `neural_effect`
+    /// changed. Fields that tick on their own, like `session_time`, are
+    /// ignored.
+    #[must_use]
+    pub fn meaningful_change(&self, previous: &BrainFmState) -> bool {
+        self.track_name != previous.track_name
+            || self.mode != previous.mode
+            || self.activity != previous.activity
+            || self.is_playing != previous.is_playing
+            || self.neural_effect != previous.neural_effect
+    }
+
     /// Check if Brain.fm is actively playing
     #[must_use]
     pub fn is_active(&self) -> bool {
         self.is_playing && self.mode.is_some()
     }
 
+    /// Normalize inconsistent formatting from different data sources.
+    ///
+    /// - Trims whitespace from all string fields; empty strings become `None`
+    ///   (empty tags are dropped), so `is_active()` never reports an empty mode
+    /// - Maps mode strings to canonical names (`"deep_work"` → `"Deep Work"`)
+    /// - Maps activity names to canonical names (`"DeepWork"` → `"Deep Work"`)
+    /// - Capitalizes the first letter of `genre`
+    /// - Title-cases `neural_effect` (`"high neural effect"` → `"High Neural Effect"`)
+    pub fn sanitize(&mut self) {
+        for field in [
+            &mut self.mode,
+            &mut self.track_name,
+            &mut self.neural_effect,
+            &mut self.genre,
+            &mut self.activity,
+            &mut self.image_url,
+            &mut self.session_state,
+            &mut self.session_time,
+            &mut self.session_goal_type,
+        ] {
+            *field = field
+                .take()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty());
+        }
+        for tags in [&mut self.moods, &mut self.instruments] {
+            tags.retain(|tag| !tag.trim().is_empty());
+            for tag in tags.iter_mut() {
+                *tag = tag.trim().to_string();
+            }
+        }
+
+        if let Some(mode) = self.mode.as_mut() {
+            if let Some(canonical) = util::canonical_mode(mode) {
+                *mode = canonical.to_string();
+            }
+        }
+        if let Some(activity) = self.activity.as_mut() {
+            *activity = util::normalize_activity(activity);
+        }
+        if let Some(genre) = self.genre.as_mut() {
+            *genre = util::capitalize_first(genre);
+        }
+        if let Some(nel) = self.neural_effect.as_mut() {
+            *nel = util::title_case(nel);
+        }
+    }
+
+    /// Build a playing state from API track metadata.
+    ///
+    /// Only metadata-derived fields are set; session fields, ADHD mode, etc.
+    /// are left at their defaults so this can be used as a merge overlay.
+    #[must_use]
+    pub fn from_track_metadata(metadata: &crate::api_cache_reader::TrackMetadata) -> Self {
+        let mut state = Self {
+            is_playing: true,
+            track_name: Some(metadata.name.clone()),
+            genre: metadata.genre.clone(),
+            neural_effect: metadata.neural_effect.clone(),
+            activity: metadata.activity.clone(),
+            image_url: metadata.image_url.clone(),
+            bpm: metadata.bpm,
+            moods: metadata.moods.clone(),
+            instruments: metadata.instruments.clone(),
+            is_guided_meditation: metadata.is_guided_meditation,
+            ..Self::default()
+        };
+        state.mental_state_or_mode(metadata);
+        state
+    }
+
     /// Set mode from API cache metadata.
     ///
     /// The API distinguishes between "mental state" (Focus, Sleep, Relax, Meditate)
@@ -89,319 +252,4886 @@ impl BrainFmState {
         }
     }
 
-    /// Get a display string for Discord Rich Presence
-    pub fn to_presence_string(&self) -> String {
-        let mut parts = Vec::new();
+    /// Collect all populated fields as named template variables.
+    ///
+    /// Keys: `mode`, `track`, `genre`, `nel`, `activity`, `session_time`, `bpm`,
+    /// `moods` (comma-joined), `instruments` (first one), `session_state`, and
+    /// `is_playing` (always present). Fields that are `None` or empty are omitted,
+    /// so `util::render_template` leaves their placeholders untouched.
+    #[must_use]
+    pub fn template_vars(&self) -> HashMap<&'static str, String> {
+        let mut vars = HashMap::new();
 
-        if let Some(ref mode) = self.mode {
-            parts.push(mode.clone());
+        let optional = [
+            ("mode", &self.mode),
+            ("track", &self.track_name),
+            ("genre", &self.genre),
+            ("nel", &self.neural_effect),
+            ("activity", &self.activity),
+            ("session_state", &self.session_state),
+        ];
+        for (key, value) in optional {
+            if let Some(value) = value {
+                vars.insert(key, value.clone());
+            }
         }
 
-        if let Some(ref state) = self.session_state {
-            parts.push(format!("({})", state));
+        if let Some(time) = self.session_time_display() {
+            vars.insert("session_time", time);
         }
-
-        if let Some(ref time) = self.session_time {
-            parts.push(format!("[{}]", time));
+        if let Some(bpm) = self.bpm {
+            vars.insert("bpm", bpm.to_string());
         }
-
-        if parts.is_empty() {
-            "Brain.fm".to_string()
-        } else {
-            parts.join(" ")
+        if !self.moods.is_empty() {
+            vars.insert("moods", self.moods.join(", "));
         }
+        if let Some(instrument) = self.instruments.first() {
+            vars.insert("instruments", instrument.clone());
+        }
+        vars.insert("is_playing", self.is_playing.to_string());
+
+        vars
     }
 
-    /// Get details string for Discord Rich Presence.
+    /// Render an HTML overlay (e.g. for an OBS Browser Source).
     ///
-    /// Format: "Track Name • Genre • Neural Effect"
-    /// Example: "Nothing Remains • Piano • High Neural Effect"
-    pub fn to_details_string(&self) -> Option<String> {
-        let mut parts = Vec::new();
+    /// Uses the template at `template_path`, or the built-in dark card when
+    /// `None` or when the file can't be read. `{mode}`, `{track}`, `{genre}`,
+    /// `{nel}` and `{image_url}` are replaced with HTML-escaped values (empty
+    /// when unknown), as are the other keys from `template_vars`.
+    #[must_use]
+    pub fn to_html_overlay(&self, template_path: Option<&Path>) -> String {
+        let custom = template_path.and_then(|path| {
+            std::fs::read_to_string(path)
+                .inspect_err(|e| {
+                    warn!("Failed to read overlay template {}: {e}", path.display());
+                })
+                .ok()
+        });
+        let template = custom.as_deref().unwrap_or(DEFAULT_HTML_OVERLAY);
 
-        if let Some(ref track) = self.track_name {
-            parts.push(track.clone());
+        let mut vars = self.template_vars();
+        for key in ["mode", "track", "genre", "nel"] {
+            vars.entry(key).or_default();
         }
-
-        if let Some(ref genre) = self.genre {
-            parts.push(genre.clone());
+        // Percent-encode characters that could end the CSS `url('…')` the image is placed in
+        let mut image_url = String::new();
+        for c in self.image_url.as_deref().unwrap_or_default().chars() {
+            match c {
+                '\'' | '"' | '(' | ')' | '\\' => {
+                    let _ = write!(image_url, "%{:02X}", u32::from(c));
+                }
+                _ => image_url.push(c),
+            }
         }
-
-        if let Some(ref effect) = self.neural_effect {
-            parts.push(effect.clone());
+        vars.insert("image_url", image_url);
+        for value in vars.values_mut() {
+            *value = util::html_escape(value);
         }
 
-        if parts.is_empty() {
-            None
-        } else {
-            Some(parts.join(" • "))
-        }
+        util::render_template(template, &vars)
     }
-}
 
-/// Number of read_state cycles between periodic API refreshes.
-/// With a 5-second update interval, this means ~30 seconds between refreshes.
-const API_REFRESH_INTERVAL: u32 = 6;
+    /// Load a state previously written with `to_json_file`.
+    pub fn from_json_file(path: &Path) -> Result<Self> {
+        Self::from_json_file_with(path, None)
+    }
 
-/// Main reader that combines multiple data sources
-pub struct BrainFmReader {
-    /// Path to Brain.fm app support directory
-    app_support_path: PathBuf,
+    /// Load a state written by `to_json_file_with`, decrypting it with
+    /// `encryption_key` if the file is encrypted (see
+    /// `util::decrypt_with_passphrase`). Plain JSON files load either way.
+    pub fn from_json_file_with(path: &Path, encryption_key: Option<&str>) -> Result<Self> {
+        util::read_json_file_with(path, encryption_key)
+    }
 
-    /// In-memory cache of API responses to persist metadata even if token expires
-    memory_cache: api_cache_reader::ApiCacheData,
+    /// Save this state as JSON, creating parent directories as needed.
+    pub fn to_json_file(&self, path: &Path) -> Result<()> {
+        self.to_json_file_with(path, None)
+    }
 
-    /// Counts cycles since the last successful API call.
-    /// When this reaches `API_REFRESH_INTERVAL`, a periodic refresh is triggered.
-    api_refresh_counter: u32,
+    /// Save this state as JSON like `to_json_file`, encrypted with
+    /// `encryption_key` when one is given (`Config::encrypt_state_file`;
+    /// see `util::encrypt_with_passphrase`).
+    pub fn to_json_file_with(&self, path: &Path, encryption_key: Option<&str>) -> Result<()> {
+        util::write_json_file_with(path, self, encryption_key)
+    }
 
-    /// The audio URL (or track name) that was last enriched via the Direct API.
-    /// Used to detect track changes and trigger immediate API calls.
-    last_api_track: Option<String>,
-}
+    /// Session time for display: `session_time` if set, otherwise
+    /// `session_elapsed_secs` formatted with `util::format_duration`.
+    #[must_use]
+    pub fn session_time_display(&self) -> Option<String> {
+        self.session_time
+            .clone()
+            .or_else(|| self.session_elapsed_secs.map(util::format_duration))
+    }
 
-impl BrainFmReader {
-    /// Create a new reader
-    pub fn new() -> Result<Self> {
-        let app_support_path = platform::get_brainfm_data_dir()?;
-        let memory_cache = api_cache_reader::ApiCacheData::new();
-        Ok(Self {
-            app_support_path,
-            memory_cache,
-            api_refresh_counter: API_REFRESH_INTERVAL, // trigger API on first cycle
-            last_api_track: None,
+    /// Session length in seconds: `session_elapsed_secs`, or `session_time`
+    /// parsed
+    fn session_secs(&self) -> Option<f64> {
+        self.session_elapsed_secs.or_else(|| {
+            self.session_time
+                .as_deref()
+                .and_then(util::parse_duration_string)
+                .map(|duration| duration.as_secs_f64())
         })
     }
 
-    /// Check if Brain.fm is running
-    pub fn is_running(&self) -> bool {
-        platform::is_brainfm_running()
+    /// When the session started: the session length before
+    /// `last_successful_read` (or now, if unset)
+    #[must_use]
+    pub fn session_start_time(&self) -> Option<SystemTime> {
+        let elapsed = Duration::try_from_secs_f64(self.session_secs()?).ok()?;
+        self.last_successful_read
+            .unwrap_or_else(SystemTime::now)
+            .checked_sub(elapsed)
     }
 
-    /// Read current state using all available methods.
+    /// Describe the session as a Schema.org `MusicEvent` in JSON-LD, for
+    /// web pages and dashboards:
+    /// `{"@context":"https://schema.org","@type":"MusicEvent","name":"Blooming","performer":{"@type":"MusicGroup","name":"Brain.fm"},…}`.
     ///
-    /// Priority order:
-    /// 1. LevelDB — baseline data (mode, ADHD mode), may be stale
-    /// 2. Cache Reader — real-time audio URL detection via `lsof`
-    /// 3. Direct API — called on track change or periodic refresh for fresh metadata
-    /// 4. Memory Cache + Disk cache — fallback when API is unavailable
-    /// 5. MediaRemote — macOS Now Playing fallback when `lsof` detection fails
-    pub fn read_state(&mut self) -> Result<BrainFmState> {
-        let mut state = BrainFmState::new();
-
-        // Check if app is running
-        if !self.is_running() {
-            return Ok(state);
+    /// `name` is the track ("Brain.fm" when unknown), `genre` the genre,
+    /// `description` the neural effect, `offers.category` the mode and
+    /// `startDate` the ISO-8601 `session_start_time`; unknown ones are left
+    /// out.
+    #[must_use]
+    pub fn to_json_ld(&self) -> serde_json::Value {
+        let mut event = serde_json::json!({
+            "@context": "https://schema.org",
+            "@type": "MusicEvent",
+            "name": self.track_name.as_deref().unwrap_or("Brain.fm"),
+            "performer": { "@type": "MusicGroup", "name": "Brain.fm" },
+        });
+        if let Some(genre) = &self.genre {
+            event["genre"] = genre.as_str().into();
         }
-
-        // 1. LevelDB (baseline data, may be stale)
-        if let Ok(leveldb_state) = self.read_from_leveldb() {
-            state = Self::merge_state(state, leveldb_state);
-        }
-
-        // 2. Fast path: if we already have complete metadata in memory cache
-        //    for the current track, just use MediaRemote for play/pause detection
-        //    and skip expensive disk cache parsing + lsof scanning.
-        if !self.memory_cache.is_empty() {
-            if let Some(mr_state) = media_remote_reader::read_state() {
-                let current_track = mr_state.track_name.clone();
-                let track_changed = current_track != self.last_api_track;
-
-                if mr_state.is_playing {
-                    // Try to enrich from memory cache
-                    if let Some(ref title) = current_track {
-                        if let Some(metadata) = self.memory_cache.lookup_by_name(title) {
-                            let has_complete =
-                                metadata.neural_effect.is_some() && metadata.image_url.is_some();
-
-                            if !track_changed && has_complete {
-                                // Fast path: same track, complete data — no I/O needed
-                                debug!(
-                                    "Fast path: '{}' fully cached in memory, skipping disk I/O",
-                                    title
-                                );
-                                state.is_playing = true;
-                                state.track_name = Some(metadata.name.clone());
-                                state.genre = metadata.genre.clone().or(state.genre);
-                                state.neural_effect =
-                                    metadata.neural_effect.clone().or(state.neural_effect);
-                                state.mental_state_or_mode(metadata);
-                                state.activity = metadata.activity.clone().or(state.activity);
-                                state.image_url = metadata.image_url.clone().or(state.image_url);
-                                return Ok(state);
-                            }
-                        }
-                    }
-                } else if !track_changed && self.last_api_track.is_some() {
-                    // MediaRemote says not playing, same track context — quick not-playing
-                    debug!("Fast path: not playing");
-                    return Ok(state);
-                }
-            }
+        if let Some(neural_effect) = &self.neural_effect {
+            event["description"] = neural_effect.as_str().into();
         }
+        if let Some(mode) = &self.mode {
+            event["offers"] = serde_json::json!({ "@type": "Offer", "category": mode });
+        }
+        if let Some(start) = self.session_start_time() {
+            event["startDate"] = chrono::DateTime::<chrono::Utc>::from(start)
+                .to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+                .into();
+        }
+        event
+    }
 
-        // 3. Full path: read disk cache + lsof (needed for first detection or incomplete data)
-        let mut combined_cache = self.memory_cache.clone();
+    /// Encode the state as an OSC bundle for DAWs and other music software.
+    ///
+    /// The bundle is tagged "immediately" and always contains
+    /// `/brainfm/playing` (bool). `/brainfm/mode`, `/brainfm/track`,
+    /// `/brainfm/genre` (strings), `/brainfm/nel` (float, see
+    /// `api_cache_reader::nel_level_value`), `/brainfm/bpm` (int) and
+    /// `/brainfm/session_time` (float seconds) are included when known.
+    ///
+    /// # Panics
+    ///
+    /// Never in practice: encoding only fails for malformed addresses, and
+    /// all addresses here are fixed.
+    #[must_use]
+    pub fn to_osc_bundle(&self) -> Vec<u8> {
+        use rosc::{OscBundle, OscMessage, OscPacket, OscType};
 
-        if let Ok(disk_cache) = api_cache_reader::read_api_cache(&self.app_support_path) {
-            combined_cache.merge(&disk_cache);
+        let mut messages = vec![("/brainfm/playing", OscType::Bool(self.is_playing))];
+        for (addr, value) in [
+            ("/brainfm/mode", &self.mode),
+            ("/brainfm/track", &self.track_name),
+            ("/brainfm/genre", &self.genre),
+        ] {
+            if let Some(value) = value {
+                messages.push((addr, OscType::String(value.clone())));
+            }
         }
-
-        if !combined_cache.is_empty() {
-            debug!(
-                "Combined cache: {} tracks available (Memory: {}, Total unique: {})",
-                combined_cache.len(),
-                self.memory_cache.len(),
-                combined_cache.len()
-            );
+        if let Some(level) = self
+            .neural_effect
+            .as_deref()
+            .and_then(api_cache_reader::nel_level_value)
+        {
+            #[allow(clippy::cast_possible_truncation)]
+            messages.push(("/brainfm/nel", OscType::Float(level as f32)));
+        }
+        if let Some(bpm) = self.bpm.and_then(|bpm| i32::try_from(bpm).ok()) {
+            messages.push(("/brainfm/bpm", OscType::Int(bpm)));
+        }
+        if let Some(secs) = self.session_secs() {
+            #[allow(clippy::cast_possible_truncation)]
+            messages.push(("/brainfm/session_time", OscType::Float(secs as f32)));
         }
 
-        // 4. Cache reader — detect what's currently playing via lsof
-        let cache_state =
-            match cache_reader::read_state(&self.app_support_path, Some(&mut combined_cache)) {
-                Ok(s) => s,
-                Err(e) => {
-                    debug!("Cache reader error: {}", e);
-                    BrainFmState::new()
-                }
-            };
+        let bundle = OscPacket::Bundle(OscBundle {
+            // OSC time tag 1 means "immediately"
+            timetag: (0, 1).into(),
+            content: messages
+                .into_iter()
+                .map(|(addr, arg)| {
+                    OscPacket::Message(OscMessage {
+                        addr: addr.to_string(),
+                        args: vec![arg],
+                    })
+                })
+                .collect(),
+        });
+        rosc::encoder::encode(&bundle).expect("OSC bundle with fixed addresses encodes")
+    }
 
-        // 5. Determine if playing — lsof is primary, MediaRemote is fallback
-        let (is_playing, current_track_key, detection_source) = if cache_state.is_playing {
-            let track_key = cache_state.track_name.clone();
-            (true, track_key, "lsof")
-        } else if let Some(mr_state) = media_remote_reader::read_state() {
-            if mr_state.is_playing {
-                debug!("MediaRemote: Brain.fm is playing (lsof missed it)");
-                let track_key = mr_state.track_name.clone();
-                (true, track_key, "MediaRemote")
-            } else {
-                (false, None, "none")
-            }
-        } else {
-            (false, None, "none")
+    /// Describe a repeat play: `"2nd time"`, `"3rd time"`, …
+    ///
+    /// `None` for a first play or when `play_count` is unknown.
+    #[must_use]
+    pub fn to_play_count_string(&self) -> Option<String> {
+        let count = self.play_count.filter(|&count| count > 1)?;
+        let suffix = match (count % 10, count % 100) {
+            (_, 11..=13) => "th",
+            (1, _) => "st",
+            (2, _) => "nd",
+            (3, _) => "rd",
+            _ => "th",
         };
+        Some(format!("{count}{suffix} time"))
+    }
 
-        if !is_playing {
-            state = Self::merge_state(state, cache_state);
-            return Ok(state);
-        }
+    /// How much of the session goal is done, in percent (over 100 once the
+    /// goal is passed). `None` without a goal or its progress.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)] // goals are far below 2^24 seconds
+    pub fn goal_percentage(&self) -> Option<f32> {
+        let goal = self.session_goal_secs.filter(|&goal| goal > 0)?;
+        let progress = self.session_goal_progress_secs?;
+        Some(progress as f32 / goal as f32 * 100.0)
+    }
 
-        // 6. Decide whether to call the Direct API:
-        //    - ALWAYS on track change (new song needs fresh metadata)
-        //    - Periodically every N cycles ONLY if metadata is incomplete
-        self.api_refresh_counter += 1;
-        let track_changed = current_track_key != self.last_api_track;
+    /// Describe the session goal's progress in whole minutes:
+    /// `"Goal: 22/45 min"`. `None` without a goal or its progress.
+    #[must_use]
+    pub fn to_goal_string(&self) -> Option<String> {
+        let goal = self.session_goal_secs.filter(|&goal| goal > 0)?;
+        let progress = self.session_goal_progress_secs?;
+        Some(format!("Goal: {}/{} min", progress / 60, goal / 60))
+    }
 
-        // Check if current data is incomplete (missing key fields)
-        let has_complete_metadata = cache_state.track_name.is_some()
-            && cache_state.neural_effect.is_some()
-            && cache_state.image_url.is_some();
-        let periodic_refresh =
-            !has_complete_metadata && self.api_refresh_counter >= API_REFRESH_INTERVAL;
+    /// Render as an `AppleScript` record literal, for `do shell script` in
+    /// `AppleScript` or Shortcuts:
+    /// `{mode:"Focus", track:"Blooming", genre:"Piano", nelLevel:0.75, isPlaying:true, sessionTime:"1:23:45"}`.
+    ///
+    /// Unknown values are `missing value`; `nelLevel` is the numeric level
+    /// of `neural_effect`.
+    #[must_use]
+    pub fn to_applescript_record(&self) -> String {
+        fn text(value: Option<&str>) -> String {
+            value.map_or_else(
+                || "missing value".to_string(),
+                |value| format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"")),
+            )
+        }
 
-        let should_call_api = track_changed || periodic_refresh;
+        let nel_level = self
+            .neural_effect
+            .as_deref()
+            .and_then(api_cache_reader::nel_level_value)
+            .map_or_else(|| "missing value".to_string(), |level| level.to_string());
 
-        if should_call_api {
-            if track_changed {
-                debug!(
-                    "Track changed ({:?} → {:?}), calling API for fresh metadata [detected by {}]",
-                    self.last_api_track, current_track_key, detection_source
-                );
-            } else {
-                debug!(
-                    "Incomplete metadata, periodic API refresh (cycle {}) [detected by {}]",
-                    self.api_refresh_counter, detection_source
+        format!(
+            "{{mode:{}, track:{}, genre:{}, nelLevel:{nel_level}, isPlaying:{}, sessionTime:{}}}",
+            text(self.mode.as_deref()),
+            text(self.track_name.as_deref()),
+            text(self.genre.as_deref()),
+            self.is_playing,
+            text(self.session_time_display().as_deref()),
+        )
+    }
+
+    /// Render as an iTunes Library XML track `<dict>`, for "now playing"
+    /// tools that read `iTunes Music Library.xml`:
+    /// `<dict><key>Name</key><string>Blooming</string><key>Artist</key><string>Brain.fm</string>…</dict>`.
+    ///
+    /// `Name` (track), `Album` (mode), `Composer` (genre) and `BPM` are left
+    /// out when unknown; `Artist` is always "Brain.fm".
+    #[must_use]
+    pub fn to_itunes_xml_entry(&self) -> String {
+        let mut entry = String::from("<dict>");
+        for (key, value) in [
+            ("Name", self.track_name.as_deref()),
+            ("Artist", Some("Brain.fm")),
+            ("Album", self.mode.as_deref()),
+            ("Composer", self.genre.as_deref()),
+        ] {
+            if let Some(value) = value {
+                let _ = write!(
+                    entry,
+                    "<key>{key}</key><string>{}</string>",
+                    util::html_escape(value)
                 );
             }
+        }
+        if let Some(bpm) = self.bpm {
+            let _ = write!(entry, "<key>BPM</key><integer>{bpm}</integer>");
+        }
+        entry.push_str("</dict>");
+        entry
+    }
 
-            match api_client::fetch_recent_tracks(&self.app_support_path) {
-                Ok(Some(api_data)) if !api_data.is_empty() => {
-                    debug!("Direct API: {} tracks loaded", api_data.len());
+    /// Wrap `to_itunes_xml_entry` in a minimal iTunes Library plist, as
+    /// track 1 of its `Tracks` dictionary
+    #[must_use]
+    pub fn to_itunes_xml_document(&self) -> String {
+        format!(
+            "{}\n<plist version=\"1.0\">\n<dict>\n\t<key>Major Version</key><integer>1</integer>\n\t\
+             <key>Application Version</key><string>{}</string>\n\t\
+             <key>Tracks</key>\n\t<dict>\n\t\t<key>1</key>{}\n\t</dict>\n</dict>\n</plist>\n",
+            ITUNES_XML_PROLOG,
+            env!("CARGO_PKG_VERSION"),
+            self.to_itunes_xml_entry(),
+        )
+    }
 
-                    // Update memory cache with fresh data
-                    self.memory_cache.merge(&api_data);
-                    combined_cache.merge(&api_data);
-                    self.api_refresh_counter = 0;
-                    self.last_api_track = current_track_key.clone();
-                }
-                Ok(Some(_)) => {
-                    debug!("API returned empty result");
-                }
-                Ok(None) => {
-                    warn!("API unavailable (token expired or not found), using cached data");
-                }
-                Err(e) => {
-                    warn!("API error: {}, using cached data", e);
-                }
-            }
+    /// Render as an i3blocks/i3bar JSON block:
+    /// `{"full_text":"🧠 Deep Work — Nothing Remains","short_text":"🧠 Deep Work","color":"#00D0FF","urgent":false}`.
+    ///
+    /// The color follows the mode's category (see `i3blocks_color`); while
+    /// paused the block shows just "🧠 Brain.fm" in grey.
+    #[must_use]
+    pub fn to_i3blocks_json(&self) -> String {
+        let (short_text, full_text) = self.status_bar_text();
+        serde_json::json!({
+            "full_text": full_text,
+            "short_text": short_text,
+            "color": self.i3blocks_color(),
+            "urgent": false,
+        })
+        .to_string()
+    }
+
+    /// Render as a Polybar module line: `%{F#00D0FF}🧠 Deep Work — Nothing
+    /// Remains%{F-}`, colored like `to_i3blocks_json`.
+    ///
+    /// With `click_command`, clicking the module runs it (`%{A:command:}…%{A}`);
+    /// colons in the command are escaped as Polybar requires.
+    #[must_use]
+    pub fn to_polybar_format(&self, click_command: Option<&str>) -> String {
+        let (_, text) = self.status_bar_text();
+        let colored = format!("%{{F{}}}{text}%{{F-}}", self.i3blocks_color());
+        match click_command {
+            Some(command) => format!("%{{A:{}:}}{colored}%{{A}}", command.replace(':', "\\:")),
+            None => colored,
         }
+    }
 
-        // 7. Enrich track data depending on detection source
-        if detection_source == "lsof" {
-            // Re-run cache reader with (potentially) API-enriched combined cache
-            if let Ok(enriched_state) =
-                cache_reader::read_state(&self.app_support_path, Some(&mut combined_cache))
-            {
-                state = Self::merge_state(state, enriched_state);
-            } else {
-                state = Self::merge_state(state, cache_state);
-            }
-        } else {
-            // MediaRemote detected — enrich track name via cache lookup
-            state.is_playing = true;
-            if let Some(ref title) = current_track_key {
-                if let Some(metadata) = combined_cache.lookup_by_name(title) {
-                    debug!("MediaRemote: enriched '{}' from cache/API", title);
-                    state.track_name = Some(metadata.name.clone());
-                    state.genre = metadata.genre.clone().or(state.genre);
-                    state.neural_effect = metadata.neural_effect.clone().or(state.neural_effect);
-                    state.mental_state_or_mode(metadata);
-                    state.activity = metadata.activity.clone().or(state.activity);
-                    state.image_url = metadata.image_url.clone().or(state.image_url);
+    /// Status item for the Helix status line: `🧠 Deep Work`, or `🧠 Brain.fm`
+    /// while paused
+    #[must_use]
+    pub fn to_helix_status_item(&self) -> String {
+        self.status_bar_text().0
+    }
+
+    /// Kakoune command adding the `brainfm_mode` option to the modeline:
+    /// `set-option global modelinefmt "%val{bufname} {{context_info}}
+    /// {{mode_info}} - BrainFM: %opt{brainfm_mode}"`.
+    ///
+    /// The option itself holds `to_helix_status_item` and is kept current by
+    /// `integrations::editors::KakouneSession`.
+    #[must_use]
+    #[allow(clippy::unused_self)] // a renderer like the others; the option carries the state
+    pub fn to_kakoune_modeline(&self) -> String {
+        r#"set-option global modelinefmt "%val{bufname} {{context_info}} {{mode_info}} - BrainFM: %opt{brainfm_mode}""#
+            .to_string()
+    }
+
+    /// Short and full text for the status bar outputs: `🧠 Deep Work` and
+    /// `🧠 Deep Work — Nothing Remains`, or `🧠 Brain.fm` for both while paused
+    fn status_bar_text(&self) -> (String, String) {
+        let short_text = match (self.is_playing, self.mode.as_deref()) {
+            (true, Some(mode)) => format!("🧠 {mode}"),
+            _ => "🧠 Brain.fm".to_string(),
+        };
+        let full_text = match (self.is_playing, self.track_name.as_deref()) {
+            (true, Some(track)) => format!("{short_text} — {track}"),
+            _ => short_text.clone(),
+        };
+        (short_text, full_text)
+    }
+
+    /// Text color for `to_i3blocks_json` and `to_polybar_format`: Focus
+    /// `#00D0FF`, Sleep `#6B5ECD`, Relax `#3DDC84`, Meditate `#FF9500`, and
+    /// grey `#888888` when paused or the mode is unknown
+    fn i3blocks_color(&self) -> &'static str {
+        match self.parsed_mode() {
+            Some(mode) if self.is_playing => {
+                if mode.is_focus_category() {
+                    "#00D0FF"
+                } else if mode.is_sleep_category() {
+                    "#6B5ECD"
+                } else if mode.is_relax_category() {
+                    "#3DDC84"
+                } else if mode.is_meditate_category() {
+                    "#FF9500"
                 } else {
-                    debug!(
-                        "MediaRemote: no cache/API match for '{}', using raw title",
-                        title
-                    );
-                    state.track_name = Some(title.clone());
+                    "#888888"
                 }
             }
+            _ => "#888888",
         }
-
-        Ok(state)
     }
 
-    /// Read from LevelDB local storage
-    fn read_from_leveldb(&self) -> Result<BrainFmState> {
-        leveldb_reader::read_state(&self.app_support_path)
+    /// Render as the output of a Raycast script command (`@raycast.mode
+    /// inline`): `🧠 Focus — Nothing Remains • Electronic`.
+    ///
+    /// The emoji follows the mode's category (see `raycast_emoji`); the
+    /// track and genre are left out when unknown, and while paused the
+    /// output is just "⏸️ Brain.fm".
+    #[must_use]
+    pub fn to_raycast_script_output(&self) -> String {
+        if !self.is_playing {
+            return "⏸️ Brain.fm".to_string();
+        }
+        let mut output = format!(
+            "{} {}",
+            self.raycast_emoji(),
+            self.mode.as_deref().unwrap_or("Brain.fm")
+        );
+        if let Some(track) = self.track_name.as_deref() {
+            let _ = write!(output, " — {track}");
+            if let Some(genre) = self.genre.as_deref() {
+                let _ = write!(output, " • {genre}");
+            }
+        }
+        output
     }
 
-    /// Merge two states, preferring non-None values from the overlay state.
+    /// Render as a line for a Raycast menu bar command, `icon text |
+    /// tooltip`: `🧠 Focus | Nothing Remains • Electronic • High Neural Effect`.
     ///
-    /// For `is_playing`: overlay always wins (cache reader is authoritative for play/pause).
-    fn merge_state(base: BrainFmState, overlay: BrainFmState) -> BrainFmState {
-        BrainFmState {
-            mode: overlay.mode.or(base.mode),
-            is_playing: overlay.is_playing,
-            track_name: overlay.track_name.or(base.track_name),
-            neural_effect: overlay.neural_effect.or(base.neural_effect),
-            genre: overlay.genre.or(base.genre),
-            activity: overlay.activity.or(base.activity),
-            image_url: overlay.image_url.or(base.image_url),
-            session_state: overlay.session_state.or(base.session_state),
-            session_time: overlay.session_time.or(base.session_time),
-            infinite_play: overlay.infinite_play || base.infinite_play,
-            adhd_mode: overlay.adhd_mode || base.adhd_mode,
+    /// The menu bar shows the emoji and mode; the tooltip lists whatever is
+    /// known of the track, genre and neural effect.
+    #[must_use]
+    pub fn to_raycast_menu_bar_output(&self) -> String {
+        if !self.is_playing {
+            return "⏸️ Brain.fm | Not playing".to_string();
         }
+        let tooltip: Vec<&str> = [
+            self.track_name.as_deref(),
+            self.genre.as_deref(),
+            self.neural_effect.as_deref(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        format!(
+            "{} {} | {}",
+            self.raycast_emoji(),
+            self.mode.as_deref().unwrap_or("Brain.fm"),
+            if tooltip.is_empty() {
+                "Playing".to_string()
+            } else {
+                tooltip.join(" • ")
+            }
+        )
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Emoji for the Raycast outputs, by mode category as in the default
+    /// `Config::mode_emoji`: Focus 🧠, Sleep 🌙, Relax 🌿, Meditate 🧘 and 🎵
+    /// for anything else
+    fn raycast_emoji(&self) -> &'static str {
+        match self.parsed_mode() {
+            Some(mode) if mode.is_focus_category() => "🧠",
+            Some(mode) if mode.is_sleep_category() => "🌙",
+            Some(mode) if mode.is_relax_category() => "🌿",
+            Some(mode) if mode.is_meditate_category() => "🧘",
+            _ => "🎵",
+        }
+    }
 
-    #[test]
+    /// Render as a JSON:API document with this state as its single
+    /// resource:
+    /// `{"data":{"type":"brain-fm-state","id":"current","attributes":{…},"relationships":{}},"meta":{"generatedAt":"…","confidence":0.9}}`.
+    ///
+    /// `attributes` holds every field in camelCase, as in
+    /// `BrainFmStateJson`; `meta.generatedAt` is the current UTC time.
+    ///
+    /// See <https://jsonapi.org/format/>.
+    #[must_use]
+    pub fn to_jsonapi_document(&self) -> serde_json::Value {
+        serde_json::json!({
+            "data": {
+                "type": "brain-fm-state",
+                "id": "current",
+                "attributes": BrainFmStateJson::from(self.clone()),
+                "relationships": {},
+            },
+            "meta": {
+                "generatedAt": chrono::Utc::now()
+                    .to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+                "confidence": self.confidence_score,
+            },
+        })
+    }
+
+    /// A `prepare-commit-msg` git hook (Bourne shell) that appends the
+    /// Brain.fm session to commit messages: `[Brain.fm: Deep Work — Piano]`.
+    ///
+    /// The footer shows the activity, or the mode when there is none, and
+    /// the genre when known. The hook runs `brainfm-presence --once --json`
+    /// (or `$BRAINFM_PRESENCE`) and reads the JSON with `python3`; it leaves
+    /// the message alone when Brain.fm isn't playing, either is missing,
+    /// the message already has a footer, or git is merging, squashing or
+    /// reusing a message.
+    #[must_use]
+    pub fn to_git_prepare_commit_msg_hook() -> String {
+        GIT_PREPARE_COMMIT_MSG_HOOK.to_string()
+    }
+
+    /// Render as the payload of a Stream Deck "Show Title" key:
+    /// `{"title":"🧠\nDeep Work","state":0,"image":"data:image/png;base64,…"}`.
+    ///
+    /// The title is two lines (keys are narrow) showing the activity, or
+    /// the mode when there is none. `state` picks the key image: 0 while
+    /// playing, 1 while paused.
+    #[must_use]
+    pub fn to_stream_deck_json(&self) -> serde_json::Value {
+        use base64::prelude::*;
+
+        let label = match (
+            self.is_playing,
+            self.activity.as_deref(),
+            self.mode.as_deref(),
+        ) {
+            (true, Some(activity), _) => activity,
+            (true, None, Some(mode)) => mode,
+            _ => "Brain.fm",
+        };
+        serde_json::json!({
+            "title": format!("🧠\n{label}"),
+            "state": u8::from(!self.is_playing),
+            "image": format!(
+                "data:image/png;base64,{}",
+                BASE64_STANDARD.encode(STREAM_DECK_KEY_IMAGE)
+            ),
+        })
+    }
+
+    /// Render as the data of a KDE Plasma `DataEngine` with one `BrainFM`
+    /// source:
+    /// `{"sources":{"BrainFM":{"mode":"Focus","track":"Blooming","isPlaying":true,"genre":"Piano","nel":0.75}}}`.
+    ///
+    /// Unknown values are `null`; `nel` is the numeric level of
+    /// `neural_effect`.
+    #[must_use]
+    pub fn to_plasma_dataengine_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "sources": {
+                "BrainFM": {
+                    "mode": self.mode,
+                    "track": self.track_name,
+                    "isPlaying": self.is_playing,
+                    "genre": self.genre,
+                    "nel": self
+                        .neural_effect
+                        .as_deref()
+                        .and_then(api_cache_reader::nel_level_value),
+                }
+            }
+        })
+    }
+
+    /// Payload for a macOS Focus Filter app intent, or a Shortcuts
+    /// automation reading it:
+    /// `{"focusMode":"Deep-Work","shortcut":"BrainFM-Deep-Work","isFocus":true,"isPlaying":true,"mode":"Focus","track":"Nothing Remains"}`.
+    ///
+    /// `focusMode` is the activity (or the mode without one) with spaces
+    /// turned into hyphens, and `shortcut` the Shortcut that
+    /// `platform::macos::set_focus_mode` runs for it; both are `null` when
+    /// neither is known. `isFocus` is whether a Focus-category mode is
+    /// playing.
+    #[must_use]
+    pub fn to_focus_filter_payload(&self) -> serde_json::Value {
+        let focus_mode = self
+            .activity
+            .as_deref()
+            .or(self.mode.as_deref())
+            .map(|mode| mode.replace(' ', "-"));
+        serde_json::json!({
+            "focusMode": focus_mode,
+            "shortcut": focus_mode
+                .as_deref()
+                .map(|mode| format!("{FOCUS_SHORTCUT_PREFIX}{mode}")),
+            "isFocus": self.is_playing
+                && self.parsed_mode().is_some_and(|mode| mode.is_focus_category()),
+            "isPlaying": self.is_playing,
+            "mode": self.mode,
+            "track": self.track_name,
+        })
+    }
+
+    /// Home Assistant MQTT discovery config for a "Brain.fm Mode" sensor,
+    /// published (retained) to `homeassistant/sensor/<device_id>/mode/config`.
+    ///
+    /// The sensor reads `mode` from the `to_ha_state_payload()` JSON on
+    /// `HA_STATE_TOPIC`; the other fields become its attributes.
+    #[must_use]
+    pub fn to_ha_discovery_payload(&self, device_id: &str) -> serde_json::Value {
+        serde_json::json!({
+            "name": "Brain.fm Mode",
+            "unique_id": format!("{device_id}_mode"),
+            "state_topic": HA_STATE_TOPIC,
+            "value_template": "{{value_json.mode}}",
+            "json_attributes_topic": HA_STATE_TOPIC,
+            "icon": "mdi:brain",
+            "device": {
+                "identifiers": [device_id],
+                "name": "Brain.fm",
+                "manufacturer": "Brain.fm",
+                "model": "brainfm-presence",
+                "sw_version": env!("CARGO_PKG_VERSION"),
+            },
+        })
+    }
+
+    /// JSON published to `HA_STATE_TOPIC`: `mode` is the sensor value
+    /// (`"Idle"` while paused), the rest its attributes
+    #[must_use]
+    pub fn to_ha_state_payload(&self) -> String {
+        let mode = match (self.is_playing, self.mode.as_deref()) {
+            (true, Some(mode)) => mode,
+            _ => "Idle",
+        };
+        serde_json::json!({
+            "mode": mode,
+            "is_playing": self.is_playing,
+            "activity": self.activity,
+            "track": self.track_name,
+            "genre": self.genre,
+            "neural_effect": self.neural_effect,
+        })
+        .to_string()
+    }
+
+    /// The activity object the Discord presence sends over IPC for this
+    /// state (`SET_ACTIVITY`'s `activity` argument), for inspection.
+    ///
+    /// Pure: nothing is sent. `session_start` is the Unix time shown as
+    /// elapsed time; with a session goal, the goal's end is sent too. A
+    /// guided meditation's details read `"Guided Meditation — {track}"`
+    /// whatever `config.details_template` says.
+    /// Returns `null` when paused, which clears the presence.
+    #[must_use]
+    pub fn to_discord_activity_json(
+        &self,
+        session_start: i64,
+        config: &config::Config,
+    ) -> serde_json::Value {
+        if !self.is_playing {
+            return serde_json::Value::Null;
+        }
+
+        // Build strings from the configured templates (defaults: details = track, state = mode)
+        let mut vars = self.template_vars();
+        vars.entry("track")
+            .or_insert_with(|| "Brain.fm".to_string());
+        vars.entry("mode").or_insert_with(|| "Focus".to_string());
+        let state_text = util::render_template(&config.state_template, &vars);
+        // A guided meditation is named after its guide, the track name
+        let details = match self.track_name.as_deref() {
+            Some(guide) if self.is_guided_meditation => format!("Guided Meditation — {guide}"),
+            _ => util::render_template(&config.details_template, &vars),
+        };
+        let details = self.finalize_details(&details, config);
+
+        // Large image: prefer track-specific image from API cache, fall back to mode image from CDN
+        let large_image = self
+            .image_url
+            .clone()
+            .unwrap_or_else(|| self.mode_image_url());
+
+        // Small image = genre from Brain.fm CDN (case-insensitive)
+        let small_image = self.genre.as_deref().map_or(
+            "https://cdn.brain.fm/icons/electronic.png",
+            util::genre_icon_url,
+        );
+
+        // ActivityType::Listening (2) shows as "Listening to brain.fm"
+        let mut activity = serde_json::json!({
+            "state": state_text,
+            "details": details,
+            "timestamps": { "start": session_start },
+            "assets": {
+                "large_image": large_image,
+                "large_text": self.neural_effect.as_deref().unwrap_or("Neural Effect Level"),
+                "small_image": small_image,
+                "small_text": self.genre.as_deref().unwrap_or("Brain.fm"),
+            },
+            "type": 2,
+        });
+
+        // With a session goal, an end time turns the elapsed time into a
+        // progress bar
+        if let (Some(goal), Some(_)) = (self.session_goal_secs, self.session_goal_progress_secs) {
+            activity["timestamps"]["end"] = (session_start + i64::from(goal)).into();
+        }
+
+        // Repeat plays show as a party size; Discord rejects a max below the
+        // current size, so both are the play count
+        if let Some(count) = self
+            .play_count
+            .filter(|&count| count > 1)
+            .and_then(|count| i32::try_from(count).ok())
+        {
+            activity["party"] = serde_json::json!({ "size": [count, count] });
+        }
+
+        if self.mode.is_some() {
+            activity["buttons"] = serde_json::json!([{
+                "label": "Open in Browser",
+                "url": util::brainfm_deep_link(&self.mode, &self.activity),
+            }]);
+        }
+
+        activity
+    }
+
+    /// Get a display string for Discord Rich Presence
+    #[must_use]
+    pub fn to_presence_string(&self) -> String {
+        let mut parts = Vec::new();
+
+        if let Some(ref mode) = self.mode {
+            parts.push(mode.clone());
+        }
+
+        if let Some(ref state) = self.session_state {
+            parts.push(format!("({})", state));
+        }
+
+        if let Some(time) = self.session_time_display() {
+            parts.push(format!("[{}]", time));
+        }
+
+        if parts.is_empty() {
+            "Brain.fm".to_string()
+        } else {
+            parts.join(" ")
+        }
+    }
+
+    /// Get details string for Discord Rich Presence.
+    ///
+    /// Format: "Track Name • Genre • Neural Effect"
+    /// Example: "Nothing Remains • Piano • High Neural Effect"
+    #[must_use]
+    pub fn to_details_string(&self) -> Option<String> {
+        let mut parts = Vec::new();
+
+        if let Some(ref track) = self.track_name {
+            parts.push(track.clone());
+        }
+
+        if let Some(ref genre) = self.genre {
+            parts.push(genre.clone());
+        }
+
+        if let Some(ref effect) = self.neural_effect {
+            parts.push(effect.clone());
+        }
+
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join(" • "))
+        }
+    }
+
+    /// `to_details_string()` with instruments appended (if enabled in `config`)
+    /// and truncated to `config.discord_details_max_len`.
+    #[must_use]
+    pub fn to_details_string_with(&self, config: &config::Config) -> Option<String> {
+        self.to_details_string()
+            .map(|details| self.finalize_details(&details, config))
+    }
+
+    /// Apply presence display settings to an already-built details line.
+    ///
+    /// Appends the first `config.max_instruments` instruments joined by `" & "`
+    /// when `config.show_instruments_in_presence` is set, then truncates to
+    /// `config.discord_details_max_len` characters.
+    #[must_use]
+    pub fn finalize_details(&self, details: &str, config: &config::Config) -> String {
+        let mut out = details.to_string();
+
+        let instruments = self.instruments.iter().take(config.max_instruments);
+        let instruments = instruments.map(String::as_str).collect::<Vec<_>>();
+        if config.show_instruments_in_presence && !instruments.is_empty() {
+            if !out.is_empty() {
+                out.push_str(" • ");
+            }
+            out.push_str(&instruments.join(" & "));
+        }
+
+        util::truncate(&out, config.discord_details_max_len)
+    }
+
+    /// Serialize to a JSON object with camelCase keys (`isPlaying`,
+    /// `trackName`, …), matching Brain.fm's own API.
+    ///
+    /// The `Serialize` impl on `BrainFmState` itself keeps `snake_case` keys.
+    #[must_use]
+    pub fn to_camel_json(&self) -> serde_json::Value {
+        serde_json::to_value(BrainFmStateJson::from(self.clone()))
+            .expect("BrainFmStateJson serializes to a JSON object")
+    }
+
+    /// Local copy of the track artwork downloaded by
+    /// `ApiCacheData::prefetch_images` into `cache_dir`, if there is one.
+    ///
+    /// Discord cannot show local files, so this is for serving the image
+    /// from here rather than for the presence itself.
+    #[must_use]
+    pub fn local_image_path(&self, cache_dir: &Path) -> Option<PathBuf> {
+        let url = self.image_url.as_deref()?;
+        Some(api_cache_reader::image_cache_path(cache_dir, url)).filter(|path| path.is_file())
+    }
+
+    /// The mode as a `BrainFmMode`, or `None` when no mode is known
+    #[must_use]
+    pub fn parsed_mode(&self) -> Option<BrainFmMode> {
+        self.mode.as_deref().map(BrainFmMode::parse)
+    }
+
+    /// Which kind of meditation is playing: `Guided` per
+    /// `is_guided_meditation`, `Unguided` when the mode or activity says so,
+    /// `Unknown` for any other meditation and `None` outside meditation.
+    #[must_use]
+    pub fn meditation_type(&self) -> Option<MeditationType> {
+        let mentions = |word: &str| {
+            [&self.mode, &self.activity]
+                .into_iter()
+                .flatten()
+                .flat_map(|name| name.split_whitespace())
+                .any(|w| w.eq_ignore_ascii_case(word))
+        };
+        if self.is_guided_meditation {
+            Some(MeditationType::Guided)
+        } else if mentions("unguided") {
+            Some(MeditationType::Unguided)
+        } else if mentions("meditation")
+            || self
+                .parsed_mode()
+                .is_some_and(|mode| mode.is_meditate_category())
+        {
+            Some(MeditationType::Unknown)
+        } else {
+            None
+        }
+    }
+
+    /// Brain.fm CDN artwork for the mode's category (Focus when unknown),
+    /// shown when there is no usable track image
+    #[must_use]
+    pub fn mode_image_url(&self) -> String {
+        let mode = self.parsed_mode();
+        let in_category = |is: fn(&BrainFmMode) -> bool| mode.as_ref().is_some_and(is);
+        let category = if in_category(BrainFmMode::is_sleep_category) {
+            "sleep"
+        } else if in_category(BrainFmMode::is_relax_category) {
+            "relax"
+        } else if in_category(BrainFmMode::is_meditate_category) {
+            "meditate"
+        } else {
+            "focus"
+        };
+        format!("https://cdn.brain.fm/images/{category}/{category}_mental_state_bg_small_aura.webp")
+    }
+
+    /// `image_url` if it answers a HEAD request, otherwise `mode_image_url()`.
+    /// `None` when there is no `image_url`.
+    ///
+    /// Blocks for up to `IMAGE_CHECK_TIMEOUT`; results are cached per URL
+    /// for `IMAGE_CHECK_TTL`. See `checked_image_url` for a caller that
+    /// can't wait.
+    #[must_use]
+    pub fn validate_image_url(&self) -> Option<String> {
+        let url = self.image_url.as_deref()?;
+        let cached = image_url_checks().reachable(url);
+        let reachable = cached.unwrap_or_else(|| {
+            let reachable = api_client::is_reachable(url, IMAGE_CHECK_TIMEOUT);
+            image_url_checks().insert(url, reachable);
+            reachable
+        });
+        Some(self.image_url_if(reachable))
+    }
+
+    /// `validate_image_url` without waiting for the image host: the cached
+    /// result if there is one, otherwise `image_url` as it is while a
+    /// background thread checks it for the next call. Used when
+    /// `Config::validate_image_urls` is set.
+    #[must_use]
+    pub fn checked_image_url(&self) -> Option<String> {
+        let url = self.image_url.as_deref()?;
+        let mut checks = image_url_checks();
+        if let Some(reachable) = checks.reachable(url) {
+            return Some(self.image_url_if(reachable));
+        }
+        if checks.pending.insert(url.to_string()) {
+            let url = url.to_string();
+            std::thread::spawn(move || {
+                let reachable = api_client::is_reachable(&url, IMAGE_CHECK_TIMEOUT);
+                let mut checks = image_url_checks();
+                checks.pending.remove(&url);
+                checks.insert(&url, reachable);
+            });
+        }
+        Some(url.to_string())
+    }
+
+    /// `image_url` if `reachable`, otherwise `mode_image_url()`
+    fn image_url_if(&self, reachable: bool) -> String {
+        match self.image_url.as_deref() {
+            Some(url) if reachable => url.to_string(),
+            _ => self.mode_image_url(),
+        }
+    }
+
+    /// `validate_image_url` without blocking the async runtime
+    #[cfg(feature = "tokio-async")]
+    pub async fn validate_and_cache_image_url(&self) -> Option<String> {
+        let state = self.clone();
+        tokio::task::spawn_blocking(move || state.validate_image_url())
+            .await
+            .ok()
+            .flatten()
+    }
+}
+
+/// How long `BrainFmState::validate_image_url` waits for an image host
+const IMAGE_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long an image reachability check is reused
+const IMAGE_CHECK_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Most image URLs whose checks are remembered; the oldest is dropped first
+const IMAGE_CHECK_CAPACITY: usize = 64;
+
+/// Results of `BrainFmState::validate_image_url` and `checked_image_url`
+#[derive(Debug, Default)]
+struct ImageUrlChecks {
+    /// Per URL: reachable, and when it was checked
+    results: HashMap<String, (bool, Instant)>,
+    /// URLs being checked by `checked_image_url`
+    pending: std::collections::HashSet<String>,
+}
+
+impl ImageUrlChecks {
+    /// The result for `url`, unless there is none or it is older than
+    /// `IMAGE_CHECK_TTL`
+    fn reachable(&self, url: &str) -> Option<bool> {
+        self.results
+            .get(url)
+            .filter(|(_, checked_at)| checked_at.elapsed() < IMAGE_CHECK_TTL)
+            .map(|&(reachable, _)| reachable)
+    }
+
+    /// Record the result for `url`, dropping expired results and, past
+    /// `IMAGE_CHECK_CAPACITY`, the oldest one
+    fn insert(&mut self, url: &str, reachable: bool) {
+        self.results
+            .retain(|_, (_, checked_at)| checked_at.elapsed() < IMAGE_CHECK_TTL);
+        if self.results.len() >= IMAGE_CHECK_CAPACITY {
+            let oldest = self
+                .results
+                .iter()
+                .min_by_key(|(_, (_, checked_at))| *checked_at)
+                .map(|(url, _)| url.clone());
+            if let Some(oldest) = oldest {
+                self.results.remove(&oldest);
+            }
+        }
+        self.results
+            .insert(url.to_string(), (reachable, Instant::now()));
+    }
+}
+
+/// Image URL checks shared by every state
+static IMAGE_URL_CHECKS: std::sync::LazyLock<std::sync::Mutex<ImageUrlChecks>> =
+    std::sync::LazyLock::new(Default::default);
+
+/// Lock `IMAGE_URL_CHECKS`
+fn image_url_checks() -> std::sync::MutexGuard<'static, ImageUrlChecks> {
+    IMAGE_URL_CHECKS
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// The states of several users (e.g. one per `BrainFmReader::for_all_users`
+/// reader) combined into one presence; see `aggregate_states`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AggregatedState {
+    /// The state shown: the most confident one, preferring playing states
+    pub primary: BrainFmState,
+    /// Every playing state, in input order
+    pub all_playing: Vec<BrainFmState>,
+    /// How many users are playing
+    pub total_active_users: usize,
+}
+
+/// Combine users' states, picking as `primary` the one with the highest
+/// `confidence_score * (1 + is_playing)` (the first on a tie). An empty
+/// slice gives an empty, paused state.
+#[must_use]
+pub fn aggregate_states(states: &[BrainFmState]) -> AggregatedState {
+    let score = |state: &BrainFmState| {
+        state.confidence_score * (1.0 + f32::from(u8::from(state.is_playing)))
+    };
+    let primary = states
+        .iter()
+        .fold(None::<&BrainFmState>, |best, state| match best {
+            Some(best) if score(best) >= score(state) => Some(best),
+            _ => Some(state),
+        })
+        .cloned()
+        .unwrap_or_default();
+    let all_playing: Vec<BrainFmState> = states
+        .iter()
+        .filter(|state| state.is_playing)
+        .cloned()
+        .collect();
+    AggregatedState {
+        primary,
+        total_active_users: all_playing.len(),
+        all_playing,
+    }
+}
+
+impl AggregatedState {
+    /// `"3 users focusing"` when more than one user is playing, otherwise
+    /// `primary.to_presence_string()`
+    #[must_use]
+    pub fn to_presence_string(&self) -> String {
+        if self.total_active_users > 1 {
+            format!("{} users focusing", self.total_active_users)
+        } else {
+            self.primary.to_presence_string()
+        }
+    }
+
+    /// What the playing users are in, e.g. `"Deep Work · Sleep"` (the
+    /// activity, or the mode when there is none, each listed once), when
+    /// more than one user is playing; otherwise
+    /// `primary.to_details_string()`
+    #[must_use]
+    pub fn to_details_string(&self) -> Option<String> {
+        if self.total_active_users <= 1 {
+            return self.primary.to_details_string();
+        }
+        let mut labels: Vec<&str> = Vec::new();
+        for state in &self.all_playing {
+            let label = state
+                .activity
+                .as_deref()
+                .or(state.mode.as_deref())
+                .unwrap_or("Brain.fm");
+            if !labels.contains(&label) {
+                labels.push(label);
+            }
+        }
+        Some(labels.join(" · "))
+    }
+
+    /// `primary.to_discord_activity_json`, with the `state` line replaced
+    /// by `to_presence_string()` when more than one user is playing
+    #[must_use]
+    pub fn to_discord_activity_json(
+        &self,
+        session_start: i64,
+        config: &config::Config,
+    ) -> serde_json::Value {
+        let mut activity = self.primary.to_discord_activity_json(session_start, config);
+        if self.total_active_users > 1 && !activity.is_null() {
+            activity["state"] = self.to_presence_string().into();
+        }
+        activity
+    }
+}
+
+/// `BrainFmState` with camelCase JSON keys.
+///
+/// Converts losslessly to and from `BrainFmState`; see the field docs there.
+#[allow(clippy::struct_excessive_bools)] // independent flags from different sources
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct BrainFmStateJson {
+    pub mode: Option<String>,
+    pub is_playing: bool,
+    pub track_name: Option<String>,
+    pub neural_effect: Option<String>,
+    pub genre: Option<String>,
+    pub activity: Option<String>,
+    pub image_url: Option<String>,
+    pub bpm: Option<u32>,
+    pub moods: Vec<String>,
+    pub instruments: Vec<String>,
+    pub session_state: Option<String>,
+    pub session_time: Option<String>,
+    pub session_elapsed_secs: Option<f64>,
+    pub infinite_play: bool,
+    pub adhd_mode: bool,
+    pub play_count: Option<u32>,
+    pub last_successful_read: Option<SystemTime>,
+    #[serde(default)]
+    pub confidence_score: f32,
+    pub session_goal_type: Option<String>,
+    pub session_goal_secs: Option<u32>,
+    pub session_goal_progress_secs: Option<u32>,
+    #[serde(default)]
+    pub is_guided_meditation: bool,
+}
+
+impl From<BrainFmState> for BrainFmStateJson {
+    fn from(state: BrainFmState) -> Self {
+        // Destructure so a new field on either struct fails to compile here
+        let BrainFmState {
+            mode,
+            is_playing,
+            track_name,
+            neural_effect,
+            genre,
+            activity,
+            image_url,
+            bpm,
+            moods,
+            instruments,
+            session_state,
+            session_time,
+            session_elapsed_secs,
+            infinite_play,
+            adhd_mode,
+            play_count,
+            last_successful_read,
+            confidence_score,
+            session_goal_type,
+            session_goal_secs,
+            session_goal_progress_secs,
+            is_guided_meditation,
+        } = state;
+        Self {
+            mode,
+            is_playing,
+            track_name,
+            neural_effect,
+            genre,
+            activity,
+            image_url,
+            bpm,
+            moods,
+            instruments,
+            session_state,
+            session_time,
+            session_elapsed_secs,
+            infinite_play,
+            adhd_mode,
+            play_count,
+            last_successful_read,
+            confidence_score,
+            session_goal_type,
+            session_goal_secs,
+            session_goal_progress_secs,
+            is_guided_meditation,
+        }
+    }
+}
+
+impl From<BrainFmStateJson> for BrainFmState {
+    fn from(json: BrainFmStateJson) -> Self {
+        let BrainFmStateJson {
+            mode,
+            is_playing,
+            track_name,
+            neural_effect,
+            genre,
+            activity,
+            image_url,
+            bpm,
+            moods,
+            instruments,
+            session_state,
+            session_time,
+            session_elapsed_secs,
+            infinite_play,
+            adhd_mode,
+            play_count,
+            last_successful_read,
+            confidence_score,
+            session_goal_type,
+            session_goal_secs,
+            session_goal_progress_secs,
+            is_guided_meditation,
+        } = json;
+        Self {
+            mode,
+            is_playing,
+            track_name,
+            neural_effect,
+            genre,
+            activity,
+            image_url,
+            bpm,
+            moods,
+            instruments,
+            session_state,
+            session_time,
+            session_elapsed_secs,
+            infinite_play,
+            adhd_mode,
+            play_count,
+            last_successful_read,
+            confidence_score,
+            session_goal_type,
+            session_goal_secs,
+            session_goal_progress_secs,
+            is_guided_meditation,
+        }
+    }
+}
+
+/// Built-in overlay template used by `BrainFmState::to_html_overlay`
+const DEFAULT_HTML_OVERLAY: &str = include_str!("../assets/overlay.html");
+
+/// XML declaration and doctype of the plist written by
+/// `BrainFmState::to_itunes_xml_document`
+const ITUNES_XML_PROLOG: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple Computer//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">"#;
+
+/// See `BrainFmState::to_git_prepare_commit_msg_hook`
+const GIT_PREPARE_COMMIT_MSG_HOOK: &str = r#"#!/bin/sh
+# Brain.fm Presence: append the Brain.fm session to the commit message.
+# Installed by `brainfm-presence install-git-hook`.
+
+COMMIT_MSG_FILE=$1
+COMMIT_SOURCE=$2
+BRAINFM_PRESENCE=${BRAINFM_PRESENCE:-brainfm-presence}
+
+case "$COMMIT_SOURCE" in
+    merge | squash | commit) exit 0 ;;
+esac
+command -v "$BRAINFM_PRESENCE" >/dev/null 2>&1 || exit 0
+command -v python3 >/dev/null 2>&1 || exit 0
+grep -qF '[Brain.fm:' "$COMMIT_MSG_FILE" && exit 0
+
+STATE=$("$BRAINFM_PRESENCE" --once --json 2>/dev/null) || exit 0
+FOOTER=$(printf '%s' "$STATE" | python3 -c '
+import json, sys
+state = json.load(sys.stdin)
+label = state.get("activity") or state.get("mode")
+if state.get("is_playing") and label:
+    genre = state.get("genre")
+    print("[Brain.fm: %s]" % (label + " \u2014 " + genre if genre else label))
+' 2>/dev/null) || exit 0
+
+if [ -n "$FOOTER" ]; then
+    printf '\n%s\n' "$FOOTER" >>"$COMMIT_MSG_FILE"
+fi
+"#;
+
+/// MQTT topic the Home Assistant state (`BrainFmState::to_ha_state_payload`)
+/// is published to
+pub const HA_STATE_TOPIC: &str = "brainfm/state";
+
+/// Prefix of the Shortcuts that switch macOS Focus modes, e.g.
+/// `BrainFM-Deep-Work` (see `BrainFmState::to_focus_filter_payload`)
+pub const FOCUS_SHORTCUT_PREFIX: &str = "BrainFM-";
+
+/// Key image sent by `BrainFmState::to_stream_deck_json`
+const STREAM_DECK_KEY_IMAGE: &[u8] = include_bytes!("../assets/tray_icon.png");
+
+/// Field names reported by `BrainFmReader::explain_state`, in display order.
+///
+/// These are the same keys produced by `BrainFmState::template_vars`.
+const EXPLAIN_FIELDS: &[&str] = &[
+    "mode",
+    "activity",
+    "track",
+    "genre",
+    "nel",
+    "bpm",
+    "moods",
+    "instruments",
+    "session_state",
+    "session_time",
+    "is_playing",
+];
+
+/// Diagnostics about recent `read_state()` calls, reported by `explain_state`.
+#[derive(Debug, Clone, Default)]
+struct ReadDiagnostics {
+    /// Data source that provided each field of the last returned state
+    field_sources: HashMap<&'static str, &'static str>,
+
+    /// When LevelDB was last read successfully
+    last_leveldb_read: Option<SystemTime>,
+
+    /// Number of tracks found in the disk API cache on the last full read
+    disk_cache_len: Option<usize>,
+
+    /// Outcome of the last Direct API call
+    last_api_result: Option<String>,
+
+    /// Outcome of the last `lsof` cache scan
+    last_lsof_result: Option<String>,
+}
+
+/// Brain.fm mode (mental state or activity), parsed from `BrainFmState::mode`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BrainFmMode {
+    Focus,
+    DeepWork,
+    LightWork,
+    Motivation,
+    Sleep,
+    DeepSleep,
+    LightSleep,
+    Relax,
+    Recharge,
+    Chill,
+    Meditate,
+    Unguided,
+    Guided,
+
+    /// Any other mode, kept as given
+    Unknown(String),
+}
+
+impl BrainFmMode {
+    /// Every known variant, in declaration order
+    pub const KNOWN: [Self; 13] = [
+        Self::Focus,
+        Self::DeepWork,
+        Self::LightWork,
+        Self::Motivation,
+        Self::Sleep,
+        Self::DeepSleep,
+        Self::LightSleep,
+        Self::Relax,
+        Self::Recharge,
+        Self::Chill,
+        Self::Meditate,
+        Self::Unguided,
+        Self::Guided,
+    ];
+
+    /// Parse a mode string.
+    ///
+    /// Ignores case, spaces, `_` and `-`, so `"deep_work"` and `"Deep Work"`
+    /// both yield `DeepWork`. Anything else becomes `Unknown`.
+    #[must_use]
+    pub fn parse(raw: &str) -> Self {
+        let key = |s: &str| {
+            s.chars()
+                .filter(|c| !matches!(c, ' ' | '_' | '-'))
+                .collect::<String>()
+                .to_ascii_lowercase()
+        };
+        let wanted = key(raw);
+        Self::KNOWN
+            .into_iter()
+            .find(|mode| key(mode.as_str()) == wanted)
+            .unwrap_or_else(|| Self::Unknown(raw.trim().to_string()))
+    }
+
+    /// Canonical display name, as Brain.fm shows it
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Focus => "Focus",
+            Self::DeepWork => "Deep Work",
+            Self::LightWork => "Light Work",
+            Self::Motivation => "Motivation",
+            Self::Sleep => "Sleep",
+            Self::DeepSleep => "Deep Sleep",
+            Self::LightSleep => "Light Sleep",
+            Self::Relax => "Relax",
+            Self::Recharge => "Recharge",
+            Self::Chill => "Chill",
+            Self::Meditate => "Meditate",
+            Self::Unguided => "Unguided",
+            Self::Guided => "Guided",
+            Self::Unknown(raw) => raw,
+        }
+    }
+
+    /// Focus and its work activities
+    #[must_use]
+    pub const fn is_focus_category(&self) -> bool {
+        matches!(
+            self,
+            Self::Focus | Self::DeepWork | Self::LightWork | Self::Motivation
+        )
+    }
+
+    /// Sleep and its variants
+    #[must_use]
+    pub const fn is_sleep_category(&self) -> bool {
+        matches!(self, Self::Sleep | Self::DeepSleep | Self::LightSleep)
+    }
+
+    /// Relax and its activities
+    #[must_use]
+    pub const fn is_relax_category(&self) -> bool {
+        matches!(self, Self::Relax | Self::Recharge | Self::Chill)
+    }
+
+    /// Meditate and its guided/unguided sessions
+    #[must_use]
+    pub const fn is_meditate_category(&self) -> bool {
+        matches!(self, Self::Meditate | Self::Unguided | Self::Guided)
+    }
+}
+
+impl std::fmt::Display for BrainFmMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Kind of meditation session, from `BrainFmState::meditation_type()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeditationType {
+    /// Led by a guide's voice
+    Guided,
+    /// Music only
+    Unguided,
+    /// A meditation of unknown kind
+    Unknown,
+}
+
+/// Which data sources `BrainFmReader::read_state()` consults.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanMode {
+    /// All sources (default)
+    #[default]
+    Full,
+
+    /// `LevelDB` plus `MediaRemote` enriched from the in-memory cache; no
+    /// disk cache parsing, `lsof` or API calls
+    FastPathOnly,
+
+    /// Direct API and `MediaRemote` only; skips `lsof`, `LevelDB` and the
+    /// disk cache
+    ApiOnly,
+
+    /// `LevelDB`, disk cache and `lsof` only
+    LevelDbAndCache,
+
+    /// Every local source, but never makes HTTP requests
+    NoApi,
+}
+
+/// Individual data source that a `ScanMode` can enable or disable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DataSource {
+    LevelDb,
+    MediaRemote,
+    DiskCache,
+    Lsof,
+    Api,
+}
+
+/// Where `read_state()` found the metadata of the current track, from most
+/// to least trustworthy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetadataSource {
+    /// Returned by the Direct API this cycle
+    DirectApi,
+    /// Complete entry (neural effect and image) in the in-memory API cache
+    MemoryCache,
+    /// Entry in the disk cache, or an incomplete one in memory
+    DiskCache,
+    /// Title reported by `MediaRemote`, not found in any cache
+    MediaRemote,
+    /// Parsed from the name of the audio file Brain.fm has open
+    Filename,
+    /// No track detected; only `LevelDB`'s mode and settings
+    LevelDb,
+}
+
+impl MetadataSource {
+    /// `BrainFmState::confidence_score` for metadata from this source
+    #[must_use]
+    pub const fn confidence(self) -> f32 {
+        match self {
+            Self::DirectApi => 1.0,
+            Self::MemoryCache => 0.9,
+            Self::DiskCache => 0.7,
+            Self::MediaRemote => 0.5,
+            Self::Filename => 0.4,
+            Self::LevelDb => 0.3,
+        }
+    }
+}
+
+impl ScanMode {
+    /// Whether this mode reads from `source`
+    #[must_use]
+    pub const fn uses(self, source: DataSource) -> bool {
+        match self {
+            Self::Full => true,
+            Self::FastPathOnly => matches!(source, DataSource::LevelDb | DataSource::MediaRemote),
+            Self::ApiOnly => matches!(source, DataSource::Api | DataSource::MediaRemote),
+            Self::LevelDbAndCache => matches!(
+                source,
+                DataSource::LevelDb | DataSource::DiskCache | DataSource::Lsof
+            ),
+            Self::NoApi => !matches!(source, DataSource::Api),
+        }
+    }
+}
+
+/// Consecutive failures a source may have before it is suspended
+pub const DEFAULT_MAX_SOURCE_FAILURES: u32 = 10;
+
+/// How long a suspended source is skipped
+pub const DEFAULT_SOURCE_SUSPENSION: Duration = Duration::from_secs(60);
+
+/// Tracks consecutive failures per data source, so a source that keeps
+/// failing is skipped for a while instead of being retried (and logged)
+/// every cycle.
+#[derive(Debug, Clone)]
+pub struct ErrorBudget {
+    /// Consecutive failures per source; reset when the source succeeds
+    pub failures: HashMap<DataSource, u32>,
+
+    /// Failures after which a source is suspended
+    pub max_failures: u32,
+
+    /// How long a suspended source is skipped before it is tried again
+    pub suspension: Duration,
+
+    /// When each suspended source may be tried again
+    suspended_until: HashMap<DataSource, Instant>,
+}
+
+impl Default for ErrorBudget {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_SOURCE_FAILURES, DEFAULT_SOURCE_SUSPENSION)
+    }
+}
+
+impl ErrorBudget {
+    /// Suspend a source for `suspension` after `max_failures` failures in a row
+    #[must_use]
+    pub fn new(max_failures: u32, suspension: Duration) -> Self {
+        Self {
+            failures: HashMap::new(),
+            max_failures,
+            suspension,
+            suspended_until: HashMap::new(),
+        }
+    }
+
+    /// Whether `source` may be read now
+    #[must_use]
+    pub fn allows(&self, source: DataSource) -> bool {
+        self.allows_at(source, Instant::now())
+    }
+
+    fn allows_at(&self, source: DataSource, now: Instant) -> bool {
+        self.suspended_until
+            .get(&source)
+            .is_none_or(|&until| now >= until)
+    }
+
+    /// Sources currently suspended
+    #[must_use]
+    pub fn suspended_sources(&self) -> Vec<DataSource> {
+        let now = Instant::now();
+        let mut sources: Vec<_> = self
+            .suspended_until
+            .keys()
+            .copied()
+            .filter(|&source| !self.allows_at(source, now))
+            .collect();
+        sources.sort_by_key(|source| format!("{source:?}"));
+        sources
+    }
+
+    /// Count a failure of `source`, suspending it once the budget is spent.
+    ///
+    /// The count is kept while suspended, so a source that fails again
+    /// right after its suspension ends is suspended again at once.
+    pub fn record_failure(&mut self, source: DataSource) {
+        self.record_failure_at(source, Instant::now());
+    }
+
+    fn record_failure_at(&mut self, source: DataSource, now: Instant) {
+        let failures = self.failures.entry(source).or_default();
+        *failures += 1;
+        let failures = *failures;
+        if failures >= self.max_failures && self.allows_at(source, now) {
+            warn!(
+                "{source:?} failed {failures} times in a row, skipping it for {}s",
+                self.suspension.as_secs()
+            );
+            self.suspended_until.insert(source, now + self.suspension);
+        }
+    }
+
+    /// Reset the failure count of `source` after it worked
+    pub fn record_success(&mut self, source: DataSource) {
+        let failures = self.failures.remove(&source).unwrap_or(0);
+        if self.suspended_until.remove(&source).is_some() {
+            info!("{source:?} recovered after {failures} failures");
+        }
+    }
+
+    /// Record the outcome of reading `source`
+    pub fn record<T, E>(&mut self, source: DataSource, result: &std::result::Result<T, E>) {
+        if result.is_ok() {
+            self.record_success(source);
+        } else {
+            self.record_failure(source);
+        }
+    }
+}
+
+/// How long a pause is held back by default (`Config::debounce_window_secs`),
+/// less than one `DEFAULT_UPDATE_INTERVAL`
+pub const DEFAULT_DEBOUNCE_WINDOW: Duration = Duration::from_secs(3);
+
+/// Holds the last playing state for a short while after playback appears
+/// to stop. Between tracks `lsof` may briefly see no open cache file, and
+/// passing that false pause on would clear the Discord presence only to set
+/// it again on the next read.
+#[derive(Debug, Clone)]
+pub struct StateDebouncer {
+    /// How long after the first paused state the last playing one is kept
+    pub window: Duration,
+
+    /// The last playing state, and when it was read
+    last_playing_state: Option<(Instant, BrainFmState)>,
+
+    /// When the current run of paused states began
+    paused_since: Option<Instant>,
+}
+
+impl Default for StateDebouncer {
+    fn default() -> Self {
+        Self::new(DEFAULT_DEBOUNCE_WINDOW)
+    }
+}
+
+impl StateDebouncer {
+    /// Hold pauses back for `window`; `Duration::ZERO` passes every state on
+    #[must_use]
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            last_playing_state: None,
+            paused_since: None,
+        }
+    }
+
+    /// The state to show for the newly read `state`: the last playing state
+    /// while a pause is younger than `window`, otherwise `state` itself
+    pub fn debounce(&mut self, state: BrainFmState) -> BrainFmState {
+        self.debounce_at(state, Instant::now())
+    }
+
+    fn debounce_at(&mut self, state: BrainFmState, now: Instant) -> BrainFmState {
+        if state.is_playing {
+            self.paused_since = None;
+            self.last_playing_state = Some((now, state.clone()));
+            return state;
+        }
+        let paused_since = *self.paused_since.get_or_insert(now);
+        match self.last_playing_state.take() {
+            Some((read_at, held)) if now.duration_since(paused_since) < self.window => {
+                debug!(
+                    "Holding the playing state {:.1}s into a pause",
+                    now.duration_since(paused_since).as_secs_f64()
+                );
+                self.last_playing_state = Some((read_at, held.clone()));
+                held
+            }
+            _ => state,
+        }
+    }
+}
+
+/// How often the state is read by default (`Config::update_interval_secs`)
+pub const DEFAULT_UPDATE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Snapshot of the reader's prerequisites, for diagnosing a missing presence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthReport {
+    /// Whether the Brain.fm app is running
+    pub brainfm_running: bool,
+
+    /// Whether the LevelDB local storage directory exists
+    pub leveldb_found: bool,
+
+    /// Whether the Chromium network cache directory exists
+    pub cache_found: bool,
+
+    /// Number of tracks in the in-memory API cache
+    pub memory_cache_tracks: usize,
+
+    /// Summary of the in-memory API cache
+    pub cache_statistics: api_cache_reader::CacheStatistics,
+
+    /// Outcome of the last Direct API call, if one was made
+    pub last_api_result: Option<String>,
+
+    /// Sources skipped for now because they kept failing
+    pub suspended_sources: Vec<DataSource>,
+
+    /// Subscription of the signed-in account, when the API could tell
+    pub subscription_tier: Option<api_client::SubscriptionTier>,
+
+    /// Minimum supported Rust version declared by this build
+    /// (`BrainFmReader::declared_rust_version`)
+    pub rust_version: Option<String>,
+
+    /// `BrainFmState::confidence_score` of the last state read, if any
+    pub confidence_score: Option<f32>,
+}
+
+impl HealthReport {
+    /// Whether everything needed for a full presence is available
+    #[must_use]
+    pub fn is_healthy(&self) -> bool {
+        self.brainfm_running && self.leveldb_found && self.cache_found
+    }
+}
+
+/// Interface shared by `BrainFmReader` and test doubles such as
+/// `testing::MockBrainFmReader` (behind the `test-utils` feature).
+pub trait BrainFmReaderTrait {
+    /// Read the current Brain.fm state
+    fn read_state(&mut self) -> Result<BrainFmState>;
+
+    /// Check if Brain.fm is running
+    fn is_running(&self) -> bool;
+
+    /// Report which data sources are available
+    fn check_health(&self) -> HealthReport;
+
+    /// Persist reader state for restart recovery (no-op by default)
+    fn save_state(&self, _path: &Path) -> Result<()> {
+        Ok(())
+    }
+
+    /// How long `poll_until` waits between reads
+    fn update_interval(&self) -> Duration {
+        DEFAULT_UPDATE_INTERVAL
+    }
+
+    /// Apply the reader settings from `config`, e.g. after it was reloaded
+    /// (no-op by default)
+    fn apply_config(&mut self, _config: &config::Config) {}
+
+    /// Read the state every `update_interval()` until `predicate` holds.
+    ///
+    /// Returns the first matching state, or `BrainFmError::TimeoutWaiting`
+    /// once `timeout` has elapsed. Read errors are returned immediately.
+    fn poll_until<F>(&mut self, predicate: F, timeout: Duration) -> Result<BrainFmState>
+    where
+        F: Fn(&BrainFmState) -> bool,
+        Self: Sized,
+    {
+        let start = Instant::now();
+        loop {
+            let state = self.read_state()?;
+            if predicate(&state) {
+                return Ok(state);
+            }
+            let elapsed = start.elapsed();
+            if elapsed >= timeout {
+                return Err(BrainFmError::TimeoutWaiting { elapsed }.into());
+            }
+            std::thread::sleep(self.update_interval().min(timeout.saturating_sub(elapsed)));
+        }
+    }
+}
+
+/// Maximum number of track plays remembered for `play_count`
+const MAX_TRACK_HISTORY: usize = 1000;
+
+/// Number of read_state cycles between periodic API refreshes.
+/// With a 5-second update interval, this means ~30 seconds between refreshes.
+const API_REFRESH_INTERVAL: u32 = 6;
+
+/// How long each step of the demo cycle lasts (`Config::demo_mode`)
+pub const DEMO_STEP_DURATION: Duration = Duration::from_secs(30);
+
+/// Playing states shown by demo mode, in order, before an idle step
+const DEMO_STATES: [(&str, &str, &str, &str, &str); 4] = [
+    (
+        "Focus",
+        "Deep Work",
+        "Nothing Remains",
+        "Electronic",
+        "High Neural Effect",
+    ),
+    (
+        "Sleep",
+        "Deep Sleep",
+        "Blooming",
+        "Atmospheric",
+        "Low Neural Effect",
+    ),
+    (
+        "Relax",
+        "Recharge",
+        "Stratosphere",
+        "Piano",
+        "Medium Neural Effect",
+    ),
+    (
+        "Meditate",
+        "Unguided",
+        "Still Water",
+        "Drone",
+        "Low Neural Effect",
+    ),
+];
+
+/// The state demo mode shows `elapsed` into the demo: Focus, Sleep, Relax,
+/// Meditate and then idle, `DEMO_STEP_DURATION` each, on repeat.
+#[must_use]
+pub fn demo_state(elapsed: Duration) -> BrainFmState {
+    let step = elapsed.as_secs() / DEMO_STEP_DURATION.as_secs() % (DEMO_STATES.len() as u64 + 1);
+    let Some(&(mode, activity, track, genre, neural_effect)) = usize::try_from(step)
+        .ok()
+        .and_then(|step| DEMO_STATES.get(step))
+    else {
+        return BrainFmState::new();
+    };
+    BrainFmState {
+        is_playing: true,
+        mode: Some(mode.to_string()),
+        activity: Some(activity.to_string()),
+        track_name: Some(track.to_string()),
+        genre: Some(genre.to_string()),
+        neural_effect: Some(neural_effect.to_string()),
+        confidence_score: 1.0,
+        ..BrainFmState::new()
+    }
+}
+
+/// Main reader that combines multiple data sources
+pub struct BrainFmReader {
+    /// Path to Brain.fm app support directory
+    app_support_path: PathBuf,
+
+    /// In-memory cache of API responses to persist metadata even if token expires
+    memory_cache: api_cache_reader::ApiCacheData,
+
+    /// Counts cycles since the last successful API call.
+    /// When this reaches `API_REFRESH_INTERVAL`, a periodic refresh is triggered.
+    api_refresh_counter: u32,
+
+    /// The audio URL (or track name) that was last enriched via the Direct API.
+    /// Used to detect track changes and trigger immediate API calls.
+    last_api_track: Option<String>,
+
+    /// Source tracking and last-result bookkeeping for `explain_state`
+    diagnostics: ReadDiagnostics,
+
+    /// The state most recently returned by `read_state()`, persisted by `save_state()`
+    last_state: Option<BrainFmState>,
+
+    /// Tracks in the order they started playing, oldest first
+    track_history: VecDeque<String>,
+
+    /// Which data sources `read_state()` consults
+    scan_mode: ScanMode,
+
+    /// Longest HTTP 429 `Retry-After` the API client waits out
+    max_retry_after: Duration,
+
+    /// Brain.fm API version the app was last seen calling in its cache
+    api_version: u32,
+
+    /// How the cache reader runs `lsof`
+    lsof: util::LsofCommand,
+
+    /// Failure counts that suspend repeatedly failing sources
+    error_budget: ErrorBudget,
+
+    /// Delay between reads in `poll_until`
+    update_interval: Duration,
+
+    /// State returned by `read_state()` instead of reading any source
+    simulated_state: Option<BrainFmState>,
+
+    /// How long a state passed to `simulate_state()` lasts (`None`: until
+    /// `clear_simulation()`)
+    simulate_for: Option<Duration>,
+
+    /// When the current simulation ends
+    simulation_expires: Option<Instant>,
+
+    /// When demo mode was enabled, if it is
+    demo_started: Option<Instant>,
+
+    /// When a data source last answered, copied into each state as
+    /// `BrainFmState::last_successful_read`
+    last_successful_read: Option<SystemTime>,
+
+    /// Where the last read's track metadata came from (`None`: Brain.fm was
+    /// not running), scored into `BrainFmState::confidence_score`
+    metadata_source: Option<MetadataSource>,
+
+    /// Passphrase `save_state()` encrypts the state file with, set when
+    /// `Config::encrypt_state_file` is on
+    state_encryption_key: Option<String>,
+
+    /// When `read_state()` last returned, and what, for `read_state_cached()`
+    last_read_result: Option<(Instant, BrainFmState)>,
+
+    /// Fixture directory set by `set_test_fixture`, removed with the reader
+    #[cfg(test)]
+    test_fixture: Option<tempfile::TempDir>,
+
+    /// What `is_running()` returns instead of looking for the process
+    #[cfg(test)]
+    test_running: Option<bool>,
+}
+
+/// Path of the memory cache file saved alongside the state file at `state_path`.
+fn memory_cache_path(state_path: &Path) -> PathBuf {
+    state_path.with_file_name("api_cache.json")
+}
+
+impl BrainFmReader {
+    /// Create a new reader, auto-detecting where Brain.fm stores its data
+    pub fn new() -> Result<Self> {
+        Self::with_install_type(platform::BrainFmInstallType::Unknown)
+    }
+
+    /// Create a reader for a specific install type (e.g. `ChromePWA` for `--pwa`).
+    ///
+    /// See `platform::get_brainfm_data_dir_for` for how each type is located.
+    pub fn with_install_type(install_type: platform::BrainFmInstallType) -> Result<Self> {
+        Self::with_data_dir(platform::get_brainfm_data_dir_for(install_type)?)
+    }
+
+    /// Create a reader for each user account with Brain.fm data, in
+    /// username order (see `platform::discover_all_data_dirs`)
+    pub fn for_all_users() -> Result<Vec<Self>> {
+        let dirs = platform::discover_all_data_dirs();
+        anyhow::ensure!(
+            !dirs.is_empty(),
+            "No Brain.fm data directory found for any user account"
+        );
+        dirs.into_iter()
+            .map(|(_, path)| Self::with_data_dir(path))
+            .collect()
+    }
+
+    /// Create a reader for an explicit data directory, skipping detection
+    /// (e.g. a browser profile passed with `--windows-pwa-path`).
+    pub fn with_data_dir(app_support_path: PathBuf) -> Result<Self> {
+        anyhow::ensure!(
+            app_support_path.is_dir(),
+            "Brain.fm data directory {} does not exist",
+            app_support_path.display()
+        );
+        Ok(Self::unchecked(app_support_path))
+    }
+
+    /// Create a reader in demo mode (see `set_demo_mode`) that needs no
+    /// Brain.fm data directory and reads nothing from disk, for showcases
+    /// on machines without Brain.fm
+    #[must_use]
+    pub fn demo() -> Self {
+        let mut reader = Self::unchecked(PathBuf::new());
+        reader.set_demo_mode(true);
+        reader
+    }
+
+    /// A reader for `app_support_path`, which is not checked
+    fn unchecked(app_support_path: PathBuf) -> Self {
+        let memory_cache = api_cache_reader::ApiCacheData::new();
+        Self {
+            app_support_path,
+            memory_cache,
+            api_refresh_counter: API_REFRESH_INTERVAL, // trigger API on first cycle
+            last_api_track: None,
+            diagnostics: ReadDiagnostics::default(),
+            last_state: None,
+            scan_mode: ScanMode::default(),
+            max_retry_after: Duration::from_secs(api_client::DEFAULT_MAX_RETRY_AFTER_SECS),
+            api_version: api_client::DEFAULT_API_VERSION,
+            track_history: VecDeque::new(),
+            lsof: util::LsofCommand::default(),
+            error_budget: ErrorBudget::default(),
+            update_interval: DEFAULT_UPDATE_INTERVAL,
+            simulated_state: None,
+            simulate_for: None,
+            simulation_expires: None,
+            demo_started: None,
+            last_successful_read: None,
+            metadata_source: None,
+            state_encryption_key: None,
+            last_read_result: None,
+            #[cfg(test)]
+            test_fixture: None,
+            #[cfg(test)]
+            test_running: None,
+        }
+    }
+
+    /// Check which data sources are available without reading them.
+    ///
+    /// The subscription tier comes from `api_client::fetch_user_profile`,
+    /// so this makes at most one API call an hour (none when the API is
+    /// disabled or suspended).
+    #[must_use]
+    pub fn check_health(&self) -> HealthReport {
+        HealthReport {
+            brainfm_running: self.is_running(),
+            leveldb_found: self
+                .app_support_path
+                .join("Local Storage")
+                .join("leveldb")
+                .is_dir(),
+            cache_found: self
+                .app_support_path
+                .join("Cache")
+                .join("Cache_Data")
+                .is_dir(),
+            memory_cache_tracks: self.memory_cache.len(),
+            cache_statistics: self.cache_statistics(),
+            last_api_result: self.diagnostics.last_api_result.clone(),
+            suspended_sources: self.error_budget.suspended_sources(),
+            subscription_tier: self
+                .source_enabled(DataSource::Api)
+                .then(|| api_client::fetch_user_profile(&self.app_support_path))
+                .and_then(|profile| profile.ok().flatten())
+                .map(|profile| profile.subscription_tier),
+            rust_version: Self::declared_rust_version(),
+            confidence_score: self.last_state.as_ref().map(|state| state.confidence_score),
+        }
+    }
+
+    /// The `rust-version` (MSRV) declared in the manifest this binary was
+    /// built from, for bug reports; `None` if it declares none. This is not
+    /// the compiler that built it.
+    #[must_use]
+    pub fn declared_rust_version() -> Option<String> {
+        Some(env!("CARGO_PKG_RUST_VERSION"))
+            .filter(|version| !version.is_empty())
+            .map(str::to_string)
+    }
+
+    /// Statistics of the in-memory API cache
+    #[must_use]
+    pub fn cache_statistics(&self) -> api_cache_reader::CacheStatistics {
+        self.memory_cache.statistics()
+    }
+
+    /// The Brain.fm data directory this reader reads from
+    #[must_use]
+    pub fn app_support_path(&self) -> &Path {
+        &self.app_support_path
+    }
+
+    /// The Brain.fm API version the reader calls: the newest found in the
+    /// app's cache so far, `api_client::DEFAULT_API_VERSION` until then
+    #[must_use]
+    pub fn detected_api_version(&self) -> u32 {
+        self.api_version
+    }
+
+    /// Restrict which data sources `read_state()` consults
+    pub fn set_scan_mode(&mut self, scan_mode: ScanMode) {
+        self.scan_mode = scan_mode;
+    }
+
+    /// Limit how long the API client waits out a rate limit before retrying
+    pub fn set_max_retry_after(&mut self, max_retry_after: Duration) {
+        self.max_retry_after = max_retry_after;
+    }
+
+    /// Set the `lsof` binary and arguments used to find open cache files
+    pub fn set_lsof_command(&mut self, lsof: util::LsofCommand) {
+        self.lsof = lsof;
+    }
+
+    /// Replace the error budget, e.g. with limits from the config
+    pub fn set_error_budget(&mut self, error_budget: ErrorBudget) {
+        self.error_budget = error_budget;
+    }
+
+    /// Set how long `poll_until` waits between reads
+    pub fn set_update_interval(&mut self, update_interval: Duration) {
+        self.update_interval = update_interval;
+    }
+
+    /// Apply the reader settings from `config`: scan mode, `Retry-After`
+    /// limit, `lsof` command, error budget limits, update interval and state
+    /// file encryption.
+    ///
+    /// Takes effect from the next `read_state()`; failure counts in the
+    /// error budget are kept.
+    pub fn apply_config(&mut self, config: &config::Config) {
+        self.set_scan_mode(config.scan_mode);
+        self.set_max_retry_after(Duration::from_secs(config.max_retry_after_secs));
+        self.set_lsof_command(util::LsofCommand::from_config(config));
+        self.error_budget.max_failures = config.max_source_failures;
+        self.error_budget.suspension = Duration::from_secs(config.source_suspension_secs);
+        self.set_update_interval(Duration::from_secs(config.update_interval_secs));
+        // Missing key: encryption fails on save rather than writing plain text
+        self.state_encryption_key = config
+            .encrypt_state_file
+            .then(|| config.state_encryption_key.clone().unwrap_or_default());
+    }
+
+    /// Make `read_state()` return `state` without reading any data source,
+    /// for `simulate_for` (see `set_simulate_for`) or until
+    /// `clear_simulation()`. Useful for demos and for testing outputs
+    /// without a Brain.fm session.
+    pub fn simulate_state(&mut self, state: BrainFmState) {
+        self.simulation_expires = self.simulate_for.map(|duration| Instant::now() + duration);
+        self.simulated_state = Some(state);
+    }
+
+    /// Set how long later `simulate_state()` calls last (`None`: until cleared)
+    pub fn set_simulate_for(&mut self, simulate_for: Option<Duration>) {
+        self.simulate_for = simulate_for;
+    }
+
+    /// End a `simulate_state()` simulation and resume normal reading
+    pub fn clear_simulation(&mut self) {
+        self.simulated_state = None;
+        self.simulation_expires = None;
+    }
+
+    /// Cycle through `demo_state()` instead of reading Brain.fm
+    /// (`Config::demo_mode`). A `simulate_state()` state still wins.
+    pub fn set_demo_mode(&mut self, enabled: bool) {
+        self.demo_started = enabled.then(Instant::now);
+    }
+
+    /// The simulated or demo state `read_state()` should return, if any,
+    /// ending the simulation once it expires
+    fn active_simulation(&mut self) -> Option<BrainFmState> {
+        if self
+            .simulation_expires
+            .is_some_and(|expires| Instant::now() >= expires)
+        {
+            debug!("Simulated state expired, resuming normal reads");
+            self.clear_simulation();
+        }
+        self.simulated_state.clone().or_else(|| {
+            self.demo_started
+                .map(|started| demo_state(started.elapsed()))
+        })
+    }
+
+    /// Wait until `predicate` holds for the current state; see
+    /// `BrainFmReaderTrait::poll_until`
+    pub fn poll_until<F>(&mut self, predicate: F, timeout: Duration) -> Result<BrainFmState>
+    where
+        F: Fn(&BrainFmState) -> bool,
+    {
+        BrainFmReaderTrait::poll_until(self, predicate, timeout)
+    }
+
+    /// Whether `read_state()` should read `source` this cycle: enabled by
+    /// the scan mode and not suspended by the error budget
+    fn source_enabled(&self, source: DataSource) -> bool {
+        self.scan_mode.uses(source) && self.error_budget.allows(source)
+    }
+
+    /// Check if Brain.fm is running
+    #[must_use]
+    pub fn is_running(&self) -> bool {
+        #[cfg(test)]
+        if let Some(running) = self.test_running {
+            return running;
+        }
+        platform::is_brainfm_running()
+    }
+
+    /// Read from `fixture_dir` (see `test_fixture::TestFixtureBuilder`)
+    /// instead of the real data directory, with Brain.fm running and
+    /// `lsof` replaced by its `lsof.txt`. The directory is kept until the
+    /// reader is dropped.
+    #[cfg(test)]
+    pub fn set_test_fixture(&mut self, fixture_dir: tempfile::TempDir) {
+        self.app_support_path = fixture_dir.path().to_path_buf();
+        self.lsof = util::LsofCommand {
+            path: PathBuf::from("cat"),
+            args: vec![fixture_dir
+                .path()
+                .join(test_fixture::LSOF_OUTPUT_FILE)
+                .to_string_lossy()
+                .into_owned()],
+        };
+        self.test_fixture = Some(fixture_dir);
+        self.set_test_running(true);
+    }
+
+    /// Make `is_running()` return `running` without looking for the process
+    #[cfg(test)]
+    pub fn set_test_running(&mut self, running: bool) {
+        self.test_running = Some(running);
+    }
+
+    /// Read current state using all available methods.
+    ///
+    /// Priority order:
+    /// 1. LevelDB — baseline data (mode, ADHD mode), may be stale
+    /// 2. Cache Reader — real-time audio URL detection via `lsof`
+    /// 3. Direct API — called on track change or periodic refresh for fresh metadata
+    /// 4. Memory Cache + Disk cache — fallback when API is unavailable
+    /// 5. MediaRemote — macOS Now Playing fallback when `lsof` detection fails
+    ///
+    /// The merged result is normalized with `BrainFmState::sanitize()`, and
+    /// scored by where its track metadata came from (`MetadataSource`).
+    /// While a state is simulated (`simulate_state()`, demo mode) it is
+    /// returned as is instead. The result is logged field by field at debug
+    /// level (`util::log_state`).
+    pub fn read_state(&mut self) -> Result<BrainFmState> {
+        let state = if let Some(state) = self.active_simulation() {
+            state
+        } else {
+            let mut state = self.read_layered_state();
+            state.last_successful_read = self.last_successful_read;
+            state.confidence_score = self.metadata_source.map_or(0.0, MetadataSource::confidence);
+            state.sanitize();
+            self.record_play(&mut state);
+            state
+        };
+        debug!("Final assembled state:");
+        util::log_state(&state, log::Level::Debug);
+        self.last_state = Some(state.clone());
+        self.last_read_result = Some((Instant::now(), state.clone()));
+        Ok(state)
+    }
+
+    /// Like `read_state()`, but return the previous result as is if it is
+    /// less than `max_age` old.
+    ///
+    /// For callers that need an answer in well under a millisecond and can
+    /// live with slightly stale data, e.g. a shell prompt.
+    pub fn read_state_cached(&mut self, max_age: Duration) -> Result<BrainFmState> {
+        if let Some((read_at, state)) = &self.last_read_result {
+            if read_at.elapsed() < max_age {
+                return Ok(state.clone());
+            }
+        }
+        self.read_state()
+    }
+
+    /// Add the current track to `track_history` when a new one starts
+    /// playing, and set `state.play_count` from it.
+    ///
+    /// Pausing and resuming the same track is still one play.
+    fn record_play(&mut self, state: &mut BrainFmState) {
+        let Some(track) = state.track_name.as_ref() else {
+            return;
+        };
+        if state.is_playing && self.track_history.back() != Some(track) {
+            self.track_history.push_back(track.clone());
+            if self.track_history.len() > MAX_TRACK_HISTORY {
+                self.track_history.pop_front();
+            }
+        }
+        let plays = self.track_history.iter().filter(|t| *t == track).count();
+        state.play_count = u32::try_from(plays).ok().filter(|&plays| plays > 0);
+    }
+
+    /// Persist the last read state and the in-memory API cache.
+    ///
+    /// The state is written to `path`; the cache goes to `api_cache.json` in
+    /// the same directory. Does nothing if no state has been read yet.
+    pub fn save_state(&self, path: &Path) -> Result<()> {
+        let Some(ref state) = self.last_state else {
+            return Ok(());
+        };
+        state.to_json_file_with(path, self.state_encryption_key.as_deref())?;
+        self.save_memory_cache(path)
+    }
+
+    /// Save just the in-memory API cache next to the state file at
+    /// `state_path`, where `restore_state()` picks it up. It is encrypted
+    /// with the state file's key, since its tracks say as much.
+    ///
+    /// Unlike `save_state()`, this does not need a state to have been read.
+    pub fn save_memory_cache(&self, state_path: &Path) -> Result<()> {
+        util::write_json_file_with(
+            &memory_cache_path(state_path),
+            &self.memory_cache,
+            self.state_encryption_key.as_deref(),
+        )
+    }
+
+    /// Export the in-memory API cache to `path`, e.g. to seed another
+    /// machine with `import_cache()`.
+    pub fn export_memory_cache(&self, path: &Path) -> Result<()> {
+        self.memory_cache.save(path)
+    }
+
+    /// Merge a cache exported by `export_memory_cache()` into the in-memory
+    /// cache. Tracks already cached here are kept as they are.
+    pub fn import_cache(&mut self, path: &Path) -> Result<()> {
+        let imported = api_cache_reader::ApiCacheData::load(path)?;
+        self.memory_cache
+            .merge_with_strategy(&imported, api_cache_reader::MergeStrategy::KeepExisting);
+        info!(
+            "Imported {} tracks from {}; memory cache: {}",
+            imported.len(),
+            path.display(),
+            self.memory_cache.statistics()
+        );
+        Ok(())
+    }
+
+    /// Restore reader state saved by `save_state()`, e.g. after a crash.
+    ///
+    /// The saved track counts as already fetched from the API, so the first
+    /// `read_state()` after a restart does not repeat the call for it. A
+    /// missing or unreadable cache file is ignored.
+    pub fn restore_state(&mut self, path: &Path) -> Result<()> {
+        let state = BrainFmState::from_json_file_with(path, self.state_encryption_key.as_deref())?;
+
+        match util::read_json_file_with(
+            &memory_cache_path(path),
+            self.state_encryption_key.as_deref(),
+        ) {
+            Ok(cache) => self.memory_cache = cache,
+            Err(e) => debug!("No saved API cache restored: {e:#}"),
+        }
+        self.last_api_track.clone_from(&state.track_name);
+        self.api_refresh_counter = 0;
+        debug!(
+            "Restored state for {:?} ({} cached tracks)",
+            state.track_name,
+            self.memory_cache.len()
+        );
+        self.last_state = Some(state);
+
+        Ok(())
+    }
+
+    /// Merge all data sources into a state (see `read_state()` for the order).
+    fn read_layered_state(&mut self) -> BrainFmState {
+        self.diagnostics.field_sources.clear();
+        self.metadata_source = None;
+
+        // Check if app is running
+        if !self.is_running() {
+            self.last_successful_read = Some(SystemTime::now());
+            return BrainFmState::new();
+        }
+        self.metadata_source = Some(MetadataSource::LevelDb);
+
+        // 1. LevelDB (baseline data, may be stale)
+        let mut state = self.read_leveldb_layer();
+
+        // 2. Fast path: complete metadata for the current track in memory
+        if let Some(fast_state) = self.read_fast_path(&state) {
+            return fast_state;
+        }
+        if self.scan_mode == ScanMode::FastPathOnly {
+            return self.read_fast_path_only(state);
+        }
+
+        // 3. Full path: read disk cache + lsof (needed for first detection or incomplete data)
+        let mut combined_cache = self.read_disk_cache_layer();
+
+        // 4. Cache reader — detect what's currently playing via lsof
+        let cache_state = self.read_lsof_layer(&mut combined_cache);
+
+        // 5. Determine if playing — lsof is primary, MediaRemote is fallback
+        let Some((current_track_key, detection_source)) = self.detect_playing(&cache_state) else {
+            self.record_sources("cache reader (lsof)", &cache_state);
+            return Self::merge_state(state, cache_state);
+        };
+
+        // 6. Call the Direct API when the track changed or metadata is missing
+        let has_complete_metadata = cache_state.track_name.is_some()
+            && cache_state.neural_effect.is_some()
+            && cache_state.image_url.is_some();
+        let fresh_api = self.read_api_layer(
+            current_track_key.as_ref(),
+            has_complete_metadata,
+            detection_source,
+            &mut combined_cache,
+        );
+
+        // 7. Enrich track data depending on detection source
+        let miss = if detection_source == "lsof" {
+            // Re-run cache reader with (potentially) API-enriched combined cache
+            if let Ok(enriched_state) = cache_reader::read_state(
+                &self.app_support_path,
+                Some(&mut combined_cache),
+                &self.lsof,
+            ) {
+                self.record_sources("cache reader (lsof + API cache)", &enriched_state);
+                state = Self::merge_state(state, enriched_state);
+            } else {
+                self.record_sources("cache reader (lsof)", &cache_state);
+                state = Self::merge_state(state, cache_state);
+            }
+            MetadataSource::Filename
+        } else {
+            state = self.enrich_from_media_remote(state, current_track_key, &mut combined_cache);
+            MetadataSource::MediaRemote
+        };
+        self.metadata_source = Some(self.classify_metadata(
+            state.track_name.as_deref(),
+            fresh_api.as_ref(),
+            &combined_cache,
+            miss,
+        ));
+
+        state
+    }
+
+    /// Layer 1: the `LevelDB` state, or an empty one when `LevelDB` is
+    /// disabled or unreadable
+    fn read_leveldb_layer(&mut self) -> BrainFmState {
+        let state = BrainFmState::new();
+        if !self.source_enabled(DataSource::LevelDb) {
+            return state;
+        }
+        let result = self.read_from_leveldb();
+        self.error_budget.record(DataSource::LevelDb, &result);
+        let Ok(leveldb_state) = result else {
+            return state;
+        };
+        self.diagnostics.last_leveldb_read = Some(SystemTime::now());
+        self.last_successful_read = self.diagnostics.last_leveldb_read;
+        self.record_sources("LevelDB", &leveldb_state);
+        Self::merge_state(state, leveldb_state)
+    }
+
+    /// Layer 2: the final state when `MediaRemote` and the memory cache
+    /// answer on their own, skipping disk cache parsing and `lsof`: the
+    /// same track still playing with complete cached metadata, or still
+    /// paused. `None` when the other layers are needed.
+    fn read_fast_path(&mut self, state: &BrainFmState) -> Option<BrainFmState> {
+        if self.memory_cache.is_empty() || !self.scan_mode.uses(DataSource::MediaRemote) {
+            return None;
+        }
+        let mr_state = media_remote_reader::read_state()?;
+        self.last_successful_read = Some(SystemTime::now());
+        let track_changed = mr_state.track_name != self.last_api_track;
+
+        if !mr_state.is_playing {
+            // MediaRemote says not playing, same track context — quick not-playing
+            return (!track_changed && self.last_api_track.is_some()).then(|| {
+                debug!("Fast path: not playing");
+                state.clone()
+            });
+        }
+
+        let title = mr_state.track_name?;
+        let metadata = self.memory_cache.lookup_by_name(&title)?;
+        let has_complete = metadata.neural_effect.is_some() && metadata.image_url.is_some();
+        if track_changed || !has_complete {
+            return None;
+        }
+
+        debug!("Fast path: '{title}' fully cached in memory, skipping disk I/O");
+        let enriched = BrainFmState::from_track_metadata(metadata);
+        self.record_sources("memory cache", &enriched);
+        self.diagnostics
+            .field_sources
+            .insert("is_playing", "MediaRemote");
+        self.metadata_source = Some(MetadataSource::MemoryCache);
+        Some(Self::merge_state(state.clone(), enriched))
+    }
+
+    /// `ScanMode::FastPathOnly`: `MediaRemote` enriched from whatever the
+    /// memory cache holds, with no disk I/O
+    fn read_fast_path_only(&mut self, state: BrainFmState) -> BrainFmState {
+        let Some(mr_state) = media_remote_reader::read_state() else {
+            return state;
+        };
+        self.last_successful_read = Some(SystemTime::now());
+        if !mr_state.is_playing {
+            return state;
+        }
+        let mut memory_cache = std::mem::take(&mut self.memory_cache);
+        let state = self.enrich_from_media_remote(state, mr_state.track_name, &mut memory_cache);
+        self.memory_cache = memory_cache;
+        self.metadata_source = Some(self.classify_metadata(
+            state.track_name.as_deref(),
+            None,
+            &self.memory_cache,
+            MetadataSource::MediaRemote,
+        ));
+        state
+    }
+
+    /// Layer 3: the memory cache merged with the API responses cached on
+    /// disk, when the disk cache is enabled
+    fn read_disk_cache_layer(&mut self) -> api_cache_reader::ApiCacheData {
+        let mut combined_cache = self.memory_cache.clone();
+
+        if self.source_enabled(DataSource::DiskCache) {
+            let result = api_cache_reader::read_api_cache(&self.app_support_path);
+            self.error_budget.record(DataSource::DiskCache, &result);
+            if let Ok(disk_cache) = result {
+                self.diagnostics.disk_cache_len = Some(disk_cache.len());
+                if let Some(version) = disk_cache.api_version() {
+                    if version != self.api_version {
+                        info!("Brain.fm API version {version} detected in the cache");
+                        self.api_version = version;
+                    }
+                }
+                // Disk entries may be older than fresh API data held in memory
+                combined_cache.merge_with_strategy(
+                    &disk_cache,
+                    api_cache_reader::MergeStrategy::MostComplete,
+                );
+            }
+        }
+
+        if !combined_cache.is_empty() {
+            debug!(
+                "Combined cache: {} tracks available (Memory: {}, Total unique: {})",
+                combined_cache.len(),
+                self.memory_cache.len(),
+                combined_cache.len()
+            );
+        }
+        combined_cache
+    }
+
+    /// Layer 4: what the audio files Brain.fm has open say is playing, or
+    /// an empty state when `lsof` is disabled or fails
+    fn read_lsof_layer(
+        &mut self,
+        combined_cache: &mut api_cache_reader::ApiCacheData,
+    ) -> BrainFmState {
+        if !self.source_enabled(DataSource::Lsof) {
+            return BrainFmState::new();
+        }
+        let result =
+            cache_reader::read_state(&self.app_support_path, Some(combined_cache), &self.lsof);
+        self.error_budget.record(DataSource::Lsof, &result);
+        match result {
+            Ok(s) => {
+                self.last_successful_read = Some(SystemTime::now());
+                self.diagnostics.last_lsof_result = Some(match s.track_name {
+                    Some(ref track) if s.is_playing => format!("playing '{track}'"),
+                    _ if s.is_playing => "playing (unknown track)".to_string(),
+                    _ => "no open audio cache files".to_string(),
+                });
+                s
+            }
+            Err(e) => {
+                debug!("Cache reader error: {e}");
+                self.diagnostics.last_lsof_result = Some(format!("error: {e}"));
+                BrainFmState::new()
+            }
+        }
+    }
+
+    /// Layer 5: the playing track and the source that detected it (`lsof`
+    /// first, then `MediaRemote`), or `None` when nothing is playing
+    fn detect_playing(
+        &mut self,
+        cache_state: &BrainFmState,
+    ) -> Option<(Option<String>, &'static str)> {
+        if cache_state.is_playing {
+            return Some((cache_state.track_name.clone(), "lsof"));
+        }
+        let mr_state = self
+            .scan_mode
+            .uses(DataSource::MediaRemote)
+            .then(media_remote_reader::read_state)
+            .flatten()?;
+        self.last_successful_read = Some(SystemTime::now());
+        mr_state.is_playing.then(|| {
+            debug!("MediaRemote: Brain.fm is playing (lsof missed it)");
+            (mr_state.track_name, "MediaRemote")
+        })
+    }
+
+    /// Layer 6: call the Direct API when `should_call_api()` says so, adding
+    /// what it returns to the memory cache and `combined_cache`. Returns the
+    /// tracks fetched this cycle, if any.
+    fn read_api_layer(
+        &mut self,
+        current_track_key: Option<&String>,
+        has_complete_metadata: bool,
+        detection_source: &str,
+        combined_cache: &mut api_cache_reader::ApiCacheData,
+    ) -> Option<api_cache_reader::ApiCacheData> {
+        self.api_refresh_counter += 1;
+        if !self.source_enabled(DataSource::Api)
+            || !self.should_call_api(current_track_key, has_complete_metadata)
+        {
+            return None;
+        }
+
+        if current_track_key == self.last_api_track.as_ref() {
+            debug!(
+                "Incomplete metadata, periodic API refresh (cycle {}) [detected by {detection_source}]",
+                self.api_refresh_counter
+            );
+        } else {
+            debug!(
+                "Track changed ({:?} → {current_track_key:?}), calling API for fresh metadata [detected by {detection_source}]",
+                self.last_api_track
+            );
+        }
+
+        let result = api_client::fetch_recent_tracks(
+            &self.app_support_path,
+            self.api_version,
+            self.max_retry_after,
+        );
+        self.error_budget.record(DataSource::Api, &result);
+        match result {
+            Ok(Some(api_data)) if !api_data.is_empty() => {
+                debug!("Direct API: {} tracks loaded", api_data.len());
+                self.diagnostics.last_api_result = Some(format!("ok ({} tracks)", api_data.len()));
+
+                // Update memory cache with fresh data
+                self.memory_cache.merge(&api_data);
+                info!("Memory cache: {}", self.memory_cache.statistics());
+                combined_cache.merge(&api_data);
+                self.api_refresh_counter = 0;
+                self.last_api_track = current_track_key.cloned();
+                return Some(api_data);
+            }
+            Ok(Some(_)) => {
+                debug!("API returned empty result");
+                self.diagnostics.last_api_result = Some("empty result".to_string());
+            }
+            Ok(None) if api_client::is_rate_limited() => {
+                warn!("API rate limited, using cached data");
+                self.diagnostics.last_api_result = Some("rate limited".to_string());
+            }
+            Ok(None) => {
+                warn!("API unavailable (token expired or not found), using cached data");
+                self.diagnostics.last_api_result =
+                    Some("unavailable (token expired or not found)".to_string());
+            }
+            Err(e) => {
+                warn!("API error: {e}, using cached data");
+                self.diagnostics.last_api_result = Some(format!("error: {e}"));
+            }
+        }
+        None
+    }
+
+    /// Where the metadata of the playing `track` came from: the API data
+    /// fetched this cycle (`fresh_api`), a complete memory cache entry, or
+    /// `cache` (memory and disk caches combined). `miss` is where the title
+    /// itself came from when no cache knows it.
+    fn classify_metadata(
+        &self,
+        track: Option<&str>,
+        fresh_api: Option<&api_cache_reader::ApiCacheData>,
+        cache: &api_cache_reader::ApiCacheData,
+        miss: MetadataSource,
+    ) -> MetadataSource {
+        let Some(track) = track else {
+            return MetadataSource::LevelDb;
+        };
+        if fresh_api.is_some_and(|api| api.peek_by_name(track).is_some()) {
+            MetadataSource::DirectApi
+        } else if self
+            .memory_cache
+            .peek_by_name(track)
+            .is_some_and(|metadata| {
+                metadata.neural_effect.is_some() && metadata.image_url.is_some()
+            })
+        {
+            MetadataSource::MemoryCache
+        } else if cache.peek_by_name(track).is_some() {
+            MetadataSource::DiskCache
+        } else {
+            miss
+        }
+    }
+
+    /// Mark `state` as playing (detected by `MediaRemote`) and enrich the
+    /// track `title` via a cache lookup, falling back to the raw title.
+    fn enrich_from_media_remote(
+        &mut self,
+        mut state: BrainFmState,
+        title: Option<String>,
+        cache: &mut api_cache_reader::ApiCacheData,
+    ) -> BrainFmState {
+        state.is_playing = true;
+        self.diagnostics
+            .field_sources
+            .insert("is_playing", "MediaRemote");
+
+        let Some(title) = title else {
+            return state;
+        };
+        if let Some(metadata) = cache.lookup_by_name(&title) {
+            debug!("MediaRemote: enriched '{title}' from cache/API");
+            let enriched = BrainFmState::from_track_metadata(metadata);
+            self.record_sources("API cache", &enriched);
+            self.diagnostics
+                .field_sources
+                .insert("is_playing", "MediaRemote");
+            Self::merge_state(state, enriched)
+        } else {
+            debug!("MediaRemote: no cache/API match for '{title}', using raw title");
+            state.track_name = Some(title);
+            self.diagnostics
+                .field_sources
+                .insert("track", "MediaRemote");
+            state
+        }
+    }
+
+    /// Whether the Direct API should be called for `current_track`:
+    /// - ALWAYS on track change (new song needs fresh metadata)
+    /// - Periodically every N cycles ONLY if metadata is incomplete
+    fn should_call_api(&self, current_track: Option<&String>, has_complete_metadata: bool) -> bool {
+        let track_changed = current_track != self.last_api_track.as_ref();
+        let periodic_refresh =
+            !has_complete_metadata && self.api_refresh_counter >= API_REFRESH_INTERVAL;
+        track_changed || periodic_refresh
+    }
+
+    /// Produce a multi-line report explaining how `state` was assembled.
+    ///
+    /// Lists every populated field with the data source that provided it
+    /// (as recorded during the last `read_state()` call, `unknown` otherwise),
+    /// followed by the reader's internal bookkeeping. Intended for bug reports.
+    #[must_use]
+    pub fn explain_state(&self, state: &BrainFmState) -> String {
+        let vars = state.template_vars();
+        let mut out = String::from("Brain.fm state explanation\n");
+
+        out.push_str("  Fields:\n");
+        for &field in EXPLAIN_FIELDS {
+            if let Some(value) = vars.get(field) {
+                let source = self
+                    .diagnostics
+                    .field_sources
+                    .get(field)
+                    .copied()
+                    .unwrap_or("unknown");
+                let _ = writeln!(out, "    {field:<14} {value}  [{source}]");
+            }
+        }
+
+        let leveldb_read = self.diagnostics.last_leveldb_read.map_or_else(
+            || "never".to_string(),
+            |t| match t.elapsed() {
+                Ok(ago) => format!("{}s ago", ago.as_secs()),
+                Err(_) => "just now".to_string(),
+            },
+        );
+        let disk_cache = self
+            .diagnostics
+            .disk_cache_len
+            .map_or_else(|| "not read".to_string(), |n| format!("{n} tracks"));
+
+        out.push_str("  Reader:\n");
+        let _ = writeln!(out, "    Scan mode:           {:?}", self.scan_mode);
+        let _ = writeln!(out, "    LevelDB last read:   {leveldb_read}");
+        let _ = writeln!(out, "    Disk cache:          {disk_cache}");
+        let _ = writeln!(
+            out,
+            "    Memory cache:        {}",
+            self.memory_cache.statistics()
+        );
+        let _ = writeln!(
+            out,
+            "    Last API call:       {}",
+            self.diagnostics
+                .last_api_result
+                .as_deref()
+                .unwrap_or("none")
+        );
+        let _ = writeln!(
+            out,
+            "    Last lsof result:    {}",
+            self.diagnostics
+                .last_lsof_result
+                .as_deref()
+                .unwrap_or("none")
+        );
+        let _ = writeln!(
+            out,
+            "    API refresh counter: {}/{}",
+            self.api_refresh_counter, API_REFRESH_INTERVAL
+        );
+
+        out
+    }
+
+    /// Record `source` as the provider of every populated field in `layer`.
+    ///
+    /// Later calls overwrite earlier ones, mirroring `merge_state`'s
+    /// overlay-wins semantics.
+    fn record_sources(&mut self, source: &'static str, layer: &BrainFmState) {
+        for field in layer.template_vars().into_keys() {
+            if field != "is_playing" || layer.is_playing {
+                self.diagnostics.field_sources.insert(field, source);
+            }
+        }
+    }
+
+    /// Read from LevelDB local storage
+    fn read_from_leveldb(&self) -> Result<BrainFmState> {
+        leveldb_reader::read_state(&self.app_support_path)
+    }
+
+    /// Merge two states, preferring non-None values from the overlay state.
+    ///
+    /// For `is_playing`: overlay always wins (cache reader is authoritative for play/pause).
+    fn merge_state(base: BrainFmState, overlay: BrainFmState) -> BrainFmState {
+        BrainFmState {
+            mode: overlay.mode.or(base.mode),
+            is_playing: overlay.is_playing,
+            track_name: overlay.track_name.or(base.track_name),
+            neural_effect: overlay.neural_effect.or(base.neural_effect),
+            genre: overlay.genre.or(base.genre),
+            activity: overlay.activity.or(base.activity),
+            image_url: overlay.image_url.or(base.image_url),
+            bpm: overlay.bpm.or(base.bpm),
+            moods: if overlay.moods.is_empty() {
+                base.moods
+            } else {
+                overlay.moods
+            },
+            instruments: if overlay.instruments.is_empty() {
+                base.instruments
+            } else {
+                overlay.instruments
+            },
+            session_state: overlay.session_state.or(base.session_state),
+            session_time: overlay.session_time.or(base.session_time),
+            session_elapsed_secs: overlay.session_elapsed_secs.or(base.session_elapsed_secs),
+            infinite_play: overlay.infinite_play || base.infinite_play,
+            adhd_mode: overlay.adhd_mode || base.adhd_mode,
+            play_count: overlay.play_count.or(base.play_count),
+            last_successful_read: overlay.last_successful_read.or(base.last_successful_read),
+            confidence_score: overlay.confidence_score.max(base.confidence_score),
+            session_goal_type: overlay.session_goal_type.or(base.session_goal_type),
+            session_goal_secs: overlay.session_goal_secs.or(base.session_goal_secs),
+            session_goal_progress_secs: overlay
+                .session_goal_progress_secs
+                .or(base.session_goal_progress_secs),
+            is_guided_meditation: overlay.is_guided_meditation || base.is_guided_meditation,
+        }
+    }
+}
+
+impl BrainFmReaderTrait for BrainFmReader {
+    fn read_state(&mut self) -> Result<BrainFmState> {
+        BrainFmReader::read_state(self)
+    }
+
+    fn is_running(&self) -> bool {
+        BrainFmReader::is_running(self)
+    }
+
+    fn check_health(&self) -> HealthReport {
+        BrainFmReader::check_health(self)
+    }
+
+    fn save_state(&self, path: &Path) -> Result<()> {
+        BrainFmReader::save_state(self, path)
+    }
+
+    fn update_interval(&self) -> Duration {
+        self.update_interval
+    }
+
+    fn apply_config(&mut self, config: &config::Config) {
+        BrainFmReader::apply_config(self, config);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presence_string_formats_elapsed_secs() {
+        let mut state = BrainFmState {
+            mode: Some("Focus".into()),
+            session_elapsed_secs: Some(3725.4),
+            ..Default::default()
+        };
+        assert_eq!(state.to_presence_string(), "Focus [1:02:05]");
+
+        // A pre-formatted session_time takes precedence
+        state.session_time = Some("0:10".into());
+        assert_eq!(state.to_presence_string(), "Focus [0:10]");
+    }
+
+    #[test]
+    fn test_error_budget_suspends_and_restores_source() {
+        let mut budget = ErrorBudget::default();
+        let start = Instant::now();
+        for cycle in 0..10 {
+            assert!(
+                budget.allows_at(DataSource::LevelDb, start),
+                "cycle {cycle}"
+            );
+            budget.record_failure_at(DataSource::LevelDb, start);
+        }
+
+        // 11th cycle: skipped, other sources unaffected
+        let eleventh = start + Duration::from_secs(5);
+        assert!(!budget.allows_at(DataSource::LevelDb, eleventh));
+        assert!(budget.allows_at(DataSource::Api, eleventh));
+        assert_eq!(budget.suspended_sources(), [DataSource::LevelDb]);
+
+        // Tried again after 60s; success resets the count
+        let later = start + DEFAULT_SOURCE_SUSPENSION;
+        assert!(budget.allows_at(DataSource::LevelDb, later));
+        budget.record_success(DataSource::LevelDb);
+        assert!(budget.failures.is_empty());
+        assert!(budget.suspended_sources().is_empty());
+
+        budget.record_failure_at(DataSource::LevelDb, later);
+        assert!(budget.allows_at(DataSource::LevelDb, later));
+    }
+
+    #[test]
+    fn test_state_debouncer_holds_false_pause() {
+        let config = config::Config::default();
+        let playing = |track: &str| BrainFmState {
+            is_playing: true,
+            mode: Some("Focus".into()),
+            track_name: Some(track.into()),
+            ..Default::default()
+        };
+        let paused = BrainFmState::new();
+        let mut debouncer = StateDebouncer::default();
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        // Nothing to hold before the first playing state
+        assert_eq!(debouncer.debounce_at(paused.clone(), start), paused);
+
+        // Three paused reads between tracks: the presence is never cleared
+        assert_eq!(
+            debouncer.debounce_at(playing("Blooming"), at(1)),
+            playing("Blooming")
+        );
+        for secs in 2..=4 {
+            let shown = debouncer.debounce_at(paused.clone(), at(secs));
+            assert_eq!(shown, playing("Blooming"), "{secs}s");
+            assert!(!shown.to_discord_activity_json(0, &config).is_null());
+        }
+        assert_eq!(
+            debouncer.debounce_at(playing("Tidal"), at(5)),
+            playing("Tidal")
+        );
+
+        // A real pause is passed on once it outlasts the window
+        assert_eq!(
+            debouncer.debounce_at(paused.clone(), at(6)),
+            playing("Tidal")
+        );
+        assert_eq!(debouncer.debounce_at(paused.clone(), at(9)), paused);
+        assert!(paused.to_discord_activity_json(0, &config).is_null());
+        assert_eq!(debouncer.debounce_at(paused.clone(), at(10)), paused);
+
+        let mut passthrough = StateDebouncer::new(Duration::ZERO);
+        passthrough.debounce_at(playing("Tidal"), at(0));
+        assert_eq!(passthrough.debounce_at(paused.clone(), at(0)), paused);
+    }
+
+    #[test]
+    fn test_check_health_reports_suspended_sources() {
+        let mut reader = test_reader();
+        reader.set_error_budget(ErrorBudget::new(1, Duration::from_secs(60)));
+        assert!(reader.check_health().suspended_sources.is_empty());
+
+        reader
+            .error_budget
+            .record(DataSource::Lsof, &Err::<(), _>("lsof not found"));
+        assert!(!reader.source_enabled(DataSource::Lsof));
+        assert_eq!(reader.check_health().suspended_sources, [DataSource::Lsof]);
+    }
+
+    #[test]
+    fn test_brainfm_mode_parse_and_display() {
+        for mode in BrainFmMode::KNOWN {
+            let name = mode.to_string();
+            assert_eq!(BrainFmMode::parse(&name), mode);
+            assert_eq!(
+                BrainFmMode::parse(&name.to_lowercase().replace(' ', "_")),
+                mode
+            );
+        }
+        assert_eq!(BrainFmMode::parse("DEEP-WORK"), BrainFmMode::DeepWork);
+        assert_eq!(
+            BrainFmMode::parse("Creativity"),
+            BrainFmMode::Unknown("Creativity".into())
+        );
+        assert_eq!(
+            BrainFmMode::Unknown("Creativity".into()).to_string(),
+            "Creativity"
+        );
+
+        let state = BrainFmState {
+            mode: Some("light sleep".into()),
+            ..Default::default()
+        };
+        assert_eq!(state.parsed_mode(), Some(BrainFmMode::LightSleep));
+        assert_eq!(BrainFmState::new().parsed_mode(), None);
+    }
+
+    #[test]
+    fn test_brainfm_mode_categories() {
+        use BrainFmMode::*;
+        let expected = [
+            (Focus, "focus"),
+            (DeepWork, "focus"),
+            (LightWork, "focus"),
+            (Motivation, "focus"),
+            (Sleep, "sleep"),
+            (DeepSleep, "sleep"),
+            (LightSleep, "sleep"),
+            (Relax, "relax"),
+            (Recharge, "relax"),
+            (Chill, "relax"),
+            (Meditate, "meditate"),
+            (Unguided, "meditate"),
+            (Guided, "meditate"),
+            (Unknown("Creativity".into()), "none"),
+        ];
+        assert_eq!(expected.len(), BrainFmMode::KNOWN.len() + 1);
+        for (mode, category) in expected {
+            let flags = [
+                (mode.is_focus_category(), "focus"),
+                (mode.is_sleep_category(), "sleep"),
+                (mode.is_relax_category(), "relax"),
+                (mode.is_meditate_category(), "meditate"),
+            ];
+            for (flag, name) in flags {
+                assert_eq!(flag, name == category, "{mode} in {name}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_json_round_trip_snake_and_camel_case() {
+        let state = BrainFmState {
+            mode: Some("Focus".into()),
+            is_playing: true,
+            track_name: Some("Blooming".into()),
+            session_elapsed_secs: Some(61.5),
+            moods: vec!["Calm".into()],
+            adhd_mode: true,
+            ..Default::default()
+        };
+
+        let snake = serde_json::to_value(&state).unwrap();
+        assert_eq!(snake["track_name"], "Blooming");
+        assert!(snake.get("trackName").is_none());
+        assert_eq!(
+            serde_json::from_value::<BrainFmState>(snake).unwrap(),
+            state
+        );
+
+        let camel = state.to_camel_json();
+        assert_eq!(camel["trackName"], "Blooming");
+        assert_eq!(camel["isPlaying"], true);
+        assert_eq!(camel["sessionElapsedSecs"], 61.5);
+        assert_eq!(camel["adhdMode"], true);
+        assert!(camel.get("track_name").is_none());
+        let parsed: BrainFmStateJson = serde_json::from_value(camel).unwrap();
+        assert_eq!(BrainFmState::from(parsed), state);
+    }
+
+    /// Check `activity` against Discord's documented activity object: field
+    /// types, string lengths (2-128), at most two buttons and the
+    /// Listening activity type
+    fn assert_discord_activity_schema(activity: &serde_json::Value) {
+        let object = activity.as_object().expect("activity is an object");
+        for key in object.keys() {
+            assert!(
+                [
+                    "state",
+                    "details",
+                    "timestamps",
+                    "assets",
+                    "party",
+                    "buttons",
+                    "type"
+                ]
+                .contains(&key.as_str()),
+                "unexpected key {key}"
+            );
+        }
+        let text = |value: &serde_json::Value, max: usize| {
+            let text = value.as_str().expect("string field");
+            assert!((2..=max).contains(&text.chars().count()), "{text:?}");
+        };
+        text(&activity["state"], 128);
+        text(&activity["details"], 128);
+        assert!(activity["timestamps"]["start"].is_i64());
+        for key in ["large_image", "large_text", "small_image", "small_text"] {
+            text(&activity["assets"][key], 256);
+        }
+        assert_eq!(activity["type"], 2);
+        if let Some(size) = activity.get("party").map(|party| &party["size"]) {
+            let size = size.as_array().expect("party size is an array");
+            assert_eq!(size.len(), 2);
+            assert!(size[0].as_i64().unwrap() <= size[1].as_i64().unwrap());
+        }
+        if let Some(buttons) = activity.get("buttons") {
+            let buttons = buttons.as_array().expect("buttons is an array");
+            assert!((1..=2).contains(&buttons.len()));
+            for button in buttons {
+                text(&button["label"], 32);
+                text(&button["url"], 512);
+            }
+        }
+    }
+
+    #[test]
+    fn test_meditation_type() {
+        let meditation = |mode: &str, activity: Option<&str>, guided: bool| BrainFmState {
+            mode: Some(mode.into()),
+            activity: activity.map(Into::into),
+            is_guided_meditation: guided,
+            ..Default::default()
+        };
+        assert_eq!(
+            meditation("Meditate", Some("Guided Meditation"), true).meditation_type(),
+            Some(MeditationType::Guided)
+        );
+        assert_eq!(
+            meditation("Unguided Meditation", Some("Unguided Meditation"), false).meditation_type(),
+            Some(MeditationType::Unguided)
+        );
+        assert_eq!(
+            meditation("Meditate", None, false).meditation_type(),
+            Some(MeditationType::Unknown)
+        );
+        assert_eq!(meditation("Deep Work", None, false).meditation_type(), None);
+        assert_eq!(
+            meditation("Sleep", Some("Guided Sleep"), false).meditation_type(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_guided_meditation_discord_details() {
+        let config = config::Config::default();
+        let mut state = BrainFmState {
+            mode: Some("Guided Meditation".into()),
+            activity: Some("Guided Meditation".into()),
+            track_name: Some("Morning Calm with Ana".into()),
+            is_playing: true,
+            is_guided_meditation: true,
+            ..Default::default()
+        };
+        let activity = state.to_discord_activity_json(0, &config);
+        assert_eq!(
+            activity["details"],
+            "Guided Meditation — Morning Calm with Ana"
+        );
+
+        state.is_guided_meditation = false;
+        let activity = state.to_discord_activity_json(0, &config);
+        assert_eq!(activity["details"], "Morning Calm with Ana");
+    }
+
+    #[test]
+    fn test_to_discord_activity_json_matches_schema() {
+        let config = config::Config::default();
+        let state = BrainFmState {
+            mode: Some("Sleep".into()),
+            activity: Some("Deep Sleep".into()),
+            track_name: Some("Blooming".into()),
+            genre: Some("Atmospheric".into()),
+            neural_effect: Some("High Neural Effect".into()),
+            play_count: Some(3),
+            is_playing: true,
+            ..Default::default()
+        };
+        let activity = state.to_discord_activity_json(1_700_000_000, &config);
+
+        assert_discord_activity_schema(&activity);
+        assert_eq!(activity["state"], "Sleep");
+        assert_eq!(activity["details"], "Blooming");
+        assert_eq!(activity["timestamps"]["start"], 1_700_000_000);
+        assert_eq!(
+            activity["assets"]["large_image"],
+            "https://cdn.brain.fm/images/sleep/sleep_mental_state_bg_small_aura.webp"
+        );
+        assert_eq!(activity["assets"]["large_text"], "High Neural Effect");
+        assert_eq!(activity["party"]["size"], serde_json::json!([3, 3]));
+        assert_eq!(activity["buttons"][0]["label"], "Open in Browser");
+
+        // Unknown fields fall back to placeholders; no mode means no button
+        let bare = BrainFmState {
+            is_playing: true,
+            ..Default::default()
+        };
+        let activity = bare.to_discord_activity_json(0, &config);
+        assert_discord_activity_schema(&activity);
+        assert_eq!(activity["details"], "Brain.fm");
+        assert!(activity.get("buttons").is_none() && activity.get("party").is_none());
+        assert!(activity["timestamps"].get("end").is_none());
+
+        // A session goal ends the elapsed time at the goal
+        let with_goal = BrainFmState {
+            session_goal_secs: Some(2700),
+            session_goal_progress_secs: Some(1340),
+            ..state
+        };
+        let activity = with_goal.to_discord_activity_json(1_700_000_000, &config);
+        assert_discord_activity_schema(&activity);
+        assert_eq!(activity["timestamps"]["end"], 1_700_002_700);
+
+        assert!(BrainFmState::new()
+            .to_discord_activity_json(0, &config)
+            .is_null());
+    }
+
+    #[test]
+    fn test_validate_image_url_falls_back_to_mode_artwork() {
+        let mut server = mockito::Server::new();
+        let broken = server
+            .mock("HEAD", "/photo-broken")
+            .match_query(mockito::Matcher::Any)
+            .with_status(500)
+            .expect(1) // cached afterwards
+            .create();
+        let working = server
+            .mock("HEAD", "/photo-ok")
+            .match_query(mockito::Matcher::Any)
+            .expect(1)
+            .create();
+
+        let state = |path: &str| BrainFmState {
+            mode: Some("Relax".into()),
+            image_url: Some(format!("{}{path}?w=640", server.url())),
+            ..Default::default()
+        };
+        let fallback = "https://cdn.brain.fm/images/relax/relax_mental_state_bg_small_aura.webp";
+
+        let broken_state = state("/photo-broken");
+        assert_eq!(broken_state.validate_image_url().as_deref(), Some(fallback));
+        assert_eq!(broken_state.validate_image_url().as_deref(), Some(fallback));
+        let ok_state = state("/photo-ok");
+        assert_eq!(ok_state.validate_image_url(), ok_state.image_url);
+        assert_eq!(BrainFmState::new().validate_image_url(), None);
+        broken.assert();
+        working.assert();
+    }
+
+    #[test]
+    fn test_checked_image_url_checks_in_background() {
+        let mut server = mockito::Server::new();
+        let broken = server
+            .mock("HEAD", "/photo-slow")
+            .with_status(404)
+            .expect(1)
+            .create();
+        let state = BrainFmState {
+            mode: Some("Sleep".into()),
+            image_url: Some(format!("{}/photo-slow", server.url())),
+            ..Default::default()
+        };
+
+        // Unchecked: passed through while the check runs
+        assert_eq!(state.checked_image_url(), state.image_url);
+        let fallback = "https://cdn.brain.fm/images/sleep/sleep_mental_state_bg_small_aura.webp";
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while state.checked_image_url().as_deref() != Some(fallback) {
+            assert!(Instant::now() < deadline, "background check never finished");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        broken.assert();
+        assert_eq!(BrainFmState::new().checked_image_url(), None);
+    }
+
+    #[test]
+    fn test_image_url_checks_evict_expired_and_oldest() {
+        let mut checks = ImageUrlChecks::default();
+        let expired = Instant::now().checked_sub(IMAGE_CHECK_TTL).unwrap();
+        checks
+            .results
+            .insert("https://img/expired".into(), (true, expired));
+        checks.insert("https://img/0", true);
+        assert_eq!(checks.results.len(), 1);
+        checks.results.get_mut("https://img/0").unwrap().1 -= Duration::from_secs(1);
+        assert_eq!(checks.reachable("https://img/expired"), None);
+
+        for i in 1..=IMAGE_CHECK_CAPACITY {
+            checks.insert(&format!("https://img/{i}"), false);
+        }
+        assert_eq!(checks.results.len(), IMAGE_CHECK_CAPACITY);
+        assert_eq!(checks.reachable("https://img/0"), None);
+        assert_eq!(
+            checks.reachable(&format!("https://img/{IMAGE_CHECK_CAPACITY}")),
+            Some(false)
+        );
+    }
+
+    #[cfg(feature = "tokio-async")]
+    #[tokio::test]
+    async fn test_validate_and_cache_image_url_async() {
+        let mut server = mockito::Server::new_async().await;
+        let broken = server
+            .mock("HEAD", "/photo-500")
+            .with_status(500)
+            .create_async()
+            .await;
+        let state = BrainFmState {
+            mode: Some("Sleep".into()),
+            image_url: Some(format!("{}/photo-500", server.url())),
+            ..Default::default()
+        };
+        assert_eq!(
+            state.validate_and_cache_image_url().await,
+            Some(state.mode_image_url())
+        );
+        assert!(state.mode_image_url().contains("/sleep/"));
+        broken.assert_async().await;
+    }
+
+    #[test]
+    fn test_to_i3blocks_json() {
+        let state = BrainFmState {
+            mode: Some("Deep Work".into()),
+            track_name: Some("Nothing Remains".into()),
+            is_playing: true,
+            ..Default::default()
+        };
+        let block: serde_json::Value = serde_json::from_str(&state.to_i3blocks_json()).unwrap();
+        assert_eq!(
+            block,
+            serde_json::json!({
+                "full_text": "🧠 Deep Work — Nothing Remains",
+                "short_text": "🧠 Deep Work",
+                "color": "#00D0FF",
+                "urgent": false,
+            })
+        );
+
+        let paused = BrainFmState {
+            is_playing: false,
+            ..state
+        };
+        let block: serde_json::Value = serde_json::from_str(&paused.to_i3blocks_json()).unwrap();
+        assert_eq!(block["full_text"], "🧠 Brain.fm");
+        assert_eq!(block["color"], "#888888");
+    }
+
+    #[test]
+    fn test_raycast_outputs_per_mode() {
+        for (mode, script, menu_bar) in [
+            (
+                "Focus",
+                "🧠 Focus — Nothing Remains • Electronic",
+                "🧠 Focus | Nothing Remains • Electronic • High Neural Effect",
+            ),
+            (
+                "Deep Work",
+                "🧠 Deep Work — Nothing Remains • Electronic",
+                "🧠 Deep Work | Nothing Remains • Electronic • High Neural Effect",
+            ),
+            (
+                "Sleep",
+                "🌙 Sleep — Nothing Remains • Electronic",
+                "🌙 Sleep | Nothing Remains • Electronic • High Neural Effect",
+            ),
+            (
+                "Relax",
+                "🌿 Relax — Nothing Remains • Electronic",
+                "🌿 Relax | Nothing Remains • Electronic • High Neural Effect",
+            ),
+            (
+                "Meditate",
+                "🧘 Meditate — Nothing Remains • Electronic",
+                "🧘 Meditate | Nothing Remains • Electronic • High Neural Effect",
+            ),
+            (
+                "Jogging",
+                "🎵 Jogging — Nothing Remains • Electronic",
+                "🎵 Jogging | Nothing Remains • Electronic • High Neural Effect",
+            ),
+        ] {
+            let state = BrainFmState {
+                mode: Some(mode.into()),
+                track_name: Some("Nothing Remains".into()),
+                genre: Some("Electronic".into()),
+                neural_effect: Some("High Neural Effect".into()),
+                is_playing: true,
+                ..Default::default()
+            };
+            assert_eq!(state.to_raycast_script_output(), script);
+            assert_eq!(state.to_raycast_menu_bar_output(), menu_bar);
+        }
+
+        let no_track = BrainFmState {
+            mode: Some("Sleep".into()),
+            is_playing: true,
+            ..Default::default()
+        };
+        assert_eq!(no_track.to_raycast_script_output(), "🌙 Sleep");
+        assert_eq!(no_track.to_raycast_menu_bar_output(), "🌙 Sleep | Playing");
+
+        let paused = BrainFmState::new();
+        assert_eq!(paused.to_raycast_script_output(), "⏸️ Brain.fm");
+        assert_eq!(
+            paused.to_raycast_menu_bar_output(),
+            "⏸️ Brain.fm | Not playing"
+        );
+    }
+
+    /// The parts of the JSON:API schema (<https://jsonapi.org/schema>) a
+    /// single-resource success document has to satisfy
+    fn jsonapi_success_schema() -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "required": ["data"],
+            "properties": {
+                "data": { "$ref": "#/definitions/resource" },
+                "meta": { "type": "object" },
+                "jsonapi": { "type": "object" },
+                "links": { "type": "object" },
+                "included": { "type": "array" },
+            },
+            "additionalProperties": false,
+            "definitions": {
+                "resource": {
+                    "type": "object",
+                    "required": ["type", "id"],
+                    "properties": {
+                        "type": { "type": "string" },
+                        "id": { "type": "string" },
+                        "attributes": { "$ref": "#/definitions/attributes" },
+                        "relationships": { "type": "object" },
+                        "links": { "type": "object" },
+                        "meta": { "type": "object" },
+                    },
+                    "additionalProperties": false,
+                },
+                "attributes": {
+                    "type": "object",
+                    "patternProperties": {
+                        "^[a-zA-Z0-9](?:[-\\w]*[a-zA-Z0-9])?$": {},
+                    },
+                    "not": {
+                        "anyOf": [
+                            { "required": ["relationships"] },
+                            { "required": ["links"] },
+                            { "required": ["id"] },
+                            { "required": ["type"] },
+                        ],
+                    },
+                    "additionalProperties": false,
+                },
+            },
+        })
+    }
+
+    #[test]
+    fn test_aggregate_states_picks_primary() {
+        let deep_work = BrainFmState {
+            is_playing: true,
+            mode: Some("Focus".into()),
+            activity: Some("Deep Work".into()),
+            track_name: Some("Nothing Remains".into()),
+            confidence_score: 0.5,
+            ..Default::default()
+        };
+        let sleep = BrainFmState {
+            is_playing: true,
+            mode: Some("Sleep".into()),
+            track_name: Some("Tidal".into()),
+            confidence_score: 1.0,
+            ..Default::default()
+        };
+        // More confident than Deep Work, but paused: 0.9 * 1 < 0.5 * 2
+        let paused = BrainFmState {
+            mode: Some("Relax".into()),
+            confidence_score: 0.9,
+            ..Default::default()
+        };
+
+        let aggregated = aggregate_states(&[deep_work.clone(), paused.clone(), sleep.clone()]);
+        assert_eq!(aggregated.primary, sleep);
+        assert_eq!(aggregated.all_playing, [deep_work.clone(), sleep.clone()]);
+        assert_eq!(aggregated.total_active_users, 2);
+        assert_eq!(aggregated.to_presence_string(), "2 users focusing");
+        assert_eq!(
+            aggregated.to_details_string().as_deref(),
+            Some("Deep Work · Sleep")
+        );
+        let activity =
+            aggregated.to_discord_activity_json(1_700_000_000, &config::Config::default());
+        assert_eq!(activity["state"], "2 users focusing");
+        assert_eq!(activity["details"], "Tidal");
+
+        // Playing beats a more confident paused state; ties keep the first
+        let aggregated = aggregate_states(&[paused.clone(), deep_work.clone()]);
+        assert_eq!(aggregated.primary, deep_work);
+        assert_eq!(aggregated.total_active_users, 1);
+        assert_eq!(
+            aggregated.to_presence_string(),
+            deep_work.to_presence_string()
+        );
+        assert_eq!(
+            aggregated.to_discord_activity_json(0, &config::Config::default())["state"],
+            "Focus"
+        );
+        let twin = BrainFmState {
+            track_name: Some("Blooming".into()),
+            ..deep_work.clone()
+        };
+        assert_eq!(
+            aggregate_states(&[deep_work.clone(), twin]).primary,
+            deep_work
+        );
+
+        let nobody = aggregate_states(&[]);
+        assert_eq!(nobody.total_active_users, 0);
+        assert!(!nobody.primary.is_playing);
+    }
+
+    #[test]
+    fn test_to_jsonapi_document_matches_schema() {
+        let validator = jsonschema::validator_for(&jsonapi_success_schema()).unwrap();
+        let state = BrainFmState {
+            mode: Some("Focus".into()),
+            activity: Some("Deep Work".into()),
+            track_name: Some("Nothing Remains".into()),
+            is_playing: true,
+            confidence_score: 0.9,
+            ..Default::default()
+        };
+
+        for state in [state, BrainFmState::new()] {
+            let document = state.to_jsonapi_document();
+            let errors: Vec<String> = validator
+                .iter_errors(&document)
+                .map(|e| e.to_string())
+                .collect();
+            assert!(errors.is_empty(), "{errors:?}");
+            assert_eq!(document["data"]["type"], "brain-fm-state");
+            assert_eq!(document["data"]["id"], "current");
+            assert!(document["meta"]["generatedAt"]
+                .as_str()
+                .is_some_and(|time| time.ends_with('Z')));
+        }
+
+        let document = BrainFmState {
+            track_name: Some("Nothing Remains".into()),
+            confidence_score: 0.9,
+            ..Default::default()
+        }
+        .to_jsonapi_document();
+        let attributes = &document["data"]["attributes"];
+        assert_eq!(attributes["trackName"], "Nothing Remains");
+        assert_eq!(attributes["isPlaying"], false);
+        assert!((document["meta"]["confidence"].as_f64().unwrap() - 0.9).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_git_hook_is_valid_shell() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("prepare-commit-msg");
+        std::fs::write(&path, BrainFmState::to_git_prepare_commit_msg_hook()).unwrap();
+        let status = std::process::Command::new("sh")
+            .arg("-n")
+            .arg(&path)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_git_hook_appends_footer() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let hook = dir.join("prepare-commit-msg");
+        std::fs::write(&hook, BrainFmState::to_git_prepare_commit_msg_hook()).unwrap();
+        // Stands in for `brainfm-presence --once --json`
+        let fake_binary = dir.join("brainfm-presence");
+        let run_hook = |state: &BrainFmState, source: &str| {
+            std::fs::write(
+                &fake_binary,
+                format!(
+                    "#!/bin/sh\ncat <<'EOF'\n{}\nEOF\n",
+                    serde_json::to_string(state).unwrap()
+                ),
+            )
+            .unwrap();
+            std::fs::set_permissions(&fake_binary, std::fs::Permissions::from_mode(0o755)).unwrap();
+            let message = dir.join("COMMIT_EDITMSG");
+            std::fs::write(&message, "Fix the thing\n").unwrap();
+            let status = std::process::Command::new("sh")
+                .arg(&hook)
+                .arg(&message)
+                .arg(source)
+                .env("BRAINFM_PRESENCE", &fake_binary)
+                .status()
+                .unwrap();
+            assert!(status.success());
+            std::fs::read_to_string(&message).unwrap()
+        };
+
+        let playing = BrainFmState {
+            mode: Some("Focus".into()),
+            activity: Some("Deep Work".into()),
+            genre: Some("Piano".into()),
+            is_playing: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            run_hook(&playing, "message"),
+            "Fix the thing\n\n[Brain.fm: Deep Work — Piano]\n"
+        );
+        let without_genre = BrainFmState {
+            activity: None,
+            genre: None,
+            ..playing.clone()
+        };
+        assert_eq!(
+            run_hook(&without_genre, ""),
+            "Fix the thing\n\n[Brain.fm: Focus]\n"
+        );
+        assert_eq!(run_hook(&playing, "merge"), "Fix the thing\n");
+        assert_eq!(run_hook(&BrainFmState::new(), ""), "Fix the thing\n");
+    }
+
+    #[test]
+    fn test_to_polybar_format_per_mode() {
+        for (mode, expected) in [
+            (
+                "Deep Work",
+                "%{F#00D0FF}🧠 Deep Work — Nothing Remains%{F-}",
+            ),
+            ("Sleep", "%{F#6B5ECD}🧠 Sleep — Nothing Remains%{F-}"),
+            ("Relax", "%{F#3DDC84}🧠 Relax — Nothing Remains%{F-}"),
+            ("Meditate", "%{F#FF9500}🧠 Meditate — Nothing Remains%{F-}"),
+        ] {
+            let state = BrainFmState {
+                mode: Some(mode.into()),
+                track_name: Some("Nothing Remains".into()),
+                is_playing: true,
+                ..Default::default()
+            };
+            assert_eq!(state.to_polybar_format(None), expected);
+        }
+        assert_eq!(
+            BrainFmState::new().to_polybar_format(None),
+            "%{F#888888}🧠 Brain.fm%{F-}"
+        );
+    }
+
+    #[test]
+    fn test_to_polybar_format_click_action() {
+        let state = BrainFmState {
+            mode: Some("Focus".into()),
+            is_playing: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            state.to_polybar_format(Some("brainfm-presence open")),
+            "%{A:brainfm-presence open:}%{F#00D0FF}🧠 Focus%{F-}%{A}"
+        );
+        assert_eq!(
+            state.to_polybar_format(Some("xdg-open https://my.brain.fm")),
+            "%{A:xdg-open https\\://my.brain.fm:}%{F#00D0FF}🧠 Focus%{F-}%{A}"
+        );
+    }
+
+    #[test]
+    fn test_editor_status_lines() {
+        let state = BrainFmState {
+            mode: Some("Deep Work".into()),
+            track_name: Some("Nothing Remains".into()),
+            is_playing: true,
+            ..Default::default()
+        };
+        assert_eq!(state.to_helix_status_item(), "🧠 Deep Work");
+        assert_eq!(BrainFmState::new().to_helix_status_item(), "🧠 Brain.fm");
+        assert_eq!(
+            state.to_kakoune_modeline(),
+            "set-option global modelinefmt \"%val{bufname} {{context_info}} {{mode_info}} - BrainFM: %opt{brainfm_mode}\""
+        );
+    }
+
+    #[test]
+    fn test_to_focus_filter_payload() {
+        let state = BrainFmState {
+            mode: Some("Focus".into()),
+            activity: Some("Deep Work".into()),
+            track_name: Some("Nothing Remains".into()),
+            is_playing: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            state.to_focus_filter_payload(),
+            serde_json::json!({
+                "focusMode": "Deep-Work",
+                "shortcut": "BrainFM-Deep-Work",
+                "isFocus": true,
+                "isPlaying": true,
+                "mode": "Focus",
+                "track": "Nothing Remains",
+            })
+        );
+
+        let paused = BrainFmState {
+            is_playing: false,
+            ..state
+        };
+        assert_eq!(paused.to_focus_filter_payload()["isFocus"], false);
+
+        let sleep = BrainFmState {
+            mode: Some("Sleep".into()),
+            is_playing: true,
+            ..Default::default()
+        };
+        let payload = sleep.to_focus_filter_payload();
+        assert_eq!(payload["shortcut"], "BrainFM-Sleep");
+        assert_eq!(payload["isFocus"], false);
+        assert!(BrainFmState::new().to_focus_filter_payload()["shortcut"].is_null());
+    }
+
+    #[test]
+    fn test_i3blocks_color_per_mode() {
+        for (mode, color) in [
+            ("Focus", "#00D0FF"),
+            ("Light Work", "#00D0FF"),
+            ("Sleep", "#6B5ECD"),
+            ("Relax", "#3DDC84"),
+            ("Recharge", "#3DDC84"),
+            ("Meditate", "#FF9500"),
+            ("Jogging", "#888888"),
+        ] {
+            let state = BrainFmState {
+                mode: Some(mode.into()),
+                is_playing: true,
+                ..Default::default()
+            };
+            let block: serde_json::Value = serde_json::from_str(&state.to_i3blocks_json()).unwrap();
+            assert_eq!(block["color"], color, "{mode}");
+        }
+        let idle: serde_json::Value =
+            serde_json::from_str(&BrainFmState::new().to_i3blocks_json()).unwrap();
+        assert_eq!(idle["color"], "#888888");
+    }
+
+    #[test]
+    fn test_to_stream_deck_json() {
+        let state = BrainFmState {
+            mode: Some("Focus".into()),
+            activity: Some("Deep Work".into()),
+            is_playing: true,
+            ..Default::default()
+        };
+        let json = state.to_stream_deck_json();
+        assert_eq!(json["title"], "🧠\nDeep Work");
+        assert_eq!(json["state"], 0);
+        assert!(json["image"]
+            .as_str()
+            .unwrap()
+            .starts_with("data:image/png;base64,iVBORw0KGgo"));
+
+        let paused = BrainFmState {
+            is_playing: false,
+            ..state
+        };
+        assert_eq!(paused.to_stream_deck_json()["title"], "🧠\nBrain.fm");
+        assert_eq!(paused.to_stream_deck_json()["state"], 1);
+    }
+
+    #[test]
+    fn test_to_plasma_dataengine_json() {
+        let state = BrainFmState {
+            mode: Some("Focus".into()),
+            track_name: Some("Blooming".into()),
+            genre: Some("Piano".into()),
+            neural_effect: Some("High Neural Effect".into()),
+            is_playing: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            state.to_plasma_dataengine_json(),
+            serde_json::json!({
+                "sources": {
+                    "BrainFM": {
+                        "mode": "Focus",
+                        "track": "Blooming",
+                        "isPlaying": true,
+                        "genre": "Piano",
+                        "nel": 0.75,
+                    }
+                }
+            })
+        );
+
+        let idle = BrainFmState::new().to_plasma_dataengine_json();
+        assert_eq!(idle["sources"]["BrainFM"]["isPlaying"], false);
+        assert!(idle["sources"]["BrainFM"]["nel"].is_null());
+    }
+
+    #[test]
+    fn test_home_assistant_payloads() {
+        let state = BrainFmState {
+            mode: Some("Focus".into()),
+            activity: Some("Deep Work".into()),
+            track_name: Some("Nothing Remains".into()),
+            is_playing: true,
+            ..Default::default()
+        };
+        let config = state.to_ha_discovery_payload("brainfm_mac");
+        assert_eq!(config["state_topic"], HA_STATE_TOPIC);
+        assert_eq!(config["value_template"], "{{value_json.mode}}");
+        assert_eq!(config["unique_id"], "brainfm_mac_mode");
+        assert_eq!(config["device"]["identifiers"][0], "brainfm_mac");
+
+        let payload: serde_json::Value =
+            serde_json::from_str(&state.to_ha_state_payload()).unwrap();
+        assert_eq!(payload["mode"], "Focus");
+        assert_eq!(payload["track"], "Nothing Remains");
+        assert_eq!(payload["genre"], serde_json::Value::Null);
+
+        let paused: serde_json::Value =
+            serde_json::from_str(&BrainFmState::new().to_ha_state_payload()).unwrap();
+        assert_eq!(paused["mode"], "Idle");
+        assert_eq!(paused["is_playing"], false);
+    }
+
+    /// Parse the `<key>`/value pairs of the track `<dict>` in `xml`
+    fn itunes_track_fields(xml: &str) -> HashMap<String, String> {
+        use quick_xml::events::Event;
+
+        let mut reader = quick_xml::Reader::from_str(xml);
+        let (mut fields, mut key, mut text) = (HashMap::new(), None, String::new());
+        loop {
+            match reader.read_event().unwrap() {
+                Event::Start(_) => text.clear(),
+                Event::Text(t) => text.push_str(&t.unescape().unwrap()),
+                Event::End(end) => match end.name().as_ref() {
+                    b"key" => key = Some(text.clone()),
+                    b"string" | b"integer" => {
+                        fields.insert(key.take().unwrap(), text.clone());
+                    }
+                    _ => {}
+                },
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        fields
+    }
+
+    #[test]
+    fn test_to_itunes_xml_entry() {
+        let state = BrainFmState {
+            mode: Some("Deep Work".into()),
+            track_name: Some("Rock & <Roll>".into()),
+            genre: Some("Electronic".into()),
+            bpm: Some(120),
+            is_playing: true,
+            ..Default::default()
+        };
+        let fields = itunes_track_fields(&state.to_itunes_xml_entry());
+        assert_eq!(fields["Name"], "Rock & <Roll>");
+        assert_eq!(fields["Artist"], "Brain.fm");
+        assert_eq!(fields["Album"], "Deep Work");
+        assert_eq!(fields["Composer"], "Electronic");
+        assert_eq!(fields["BPM"], "120");
+
+        // Unknown fields are left out
+        let fields = itunes_track_fields(&BrainFmState::new().to_itunes_xml_entry());
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields["Artist"], "Brain.fm");
+    }
+
+    #[test]
+    fn test_to_itunes_xml_document_wraps_entry() {
+        let state = BrainFmState {
+            mode: Some("Focus".into()),
+            track_name: Some("Blooming".into()),
+            ..Default::default()
+        };
+        let document = state.to_itunes_xml_document();
+        assert!(document.starts_with("<?xml"));
+        assert!(document.contains(&state.to_itunes_xml_entry()));
+        let fields = itunes_track_fields(&document);
+        assert_eq!(fields["Name"], "Blooming");
+        assert_eq!(fields["Album"], "Focus");
+        assert_eq!(fields["Major Version"], "1");
+    }
+
+    #[test]
+    fn test_to_json_ld_music_event() {
+        let state = BrainFmState {
+            mode: Some("Focus".into()),
+            track_name: Some("Blooming".into()),
+            genre: Some("Piano".into()),
+            neural_effect: Some("High Neural Effect".into()),
+            session_elapsed_secs: Some(90.0),
+            last_successful_read: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_767_225_690)),
+            is_playing: true,
+            ..Default::default()
+        };
+        let event = state.to_json_ld();
+
+        assert_eq!(event["@context"], "https://schema.org");
+        assert_eq!(event["@type"], "MusicEvent");
+        assert_eq!(event["name"], "Blooming");
+        assert_eq!(event["performer"]["@type"], "MusicGroup");
+        assert_eq!(event["performer"]["name"], "Brain.fm");
+        assert_eq!(event["genre"], "Piano");
+        assert_eq!(event["description"], "High Neural Effect");
+        assert_eq!(event["offers"]["category"], "Focus");
+        assert_eq!(event["startDate"], "2026-01-01T00:00:00Z");
+        for key in ["name", "genre", "description", "startDate"] {
+            assert!(event[key].is_string(), "{key} should be a string");
+        }
+
+        // Unknown fields are left out, but the event is still named
+        let event = BrainFmState::new().to_json_ld();
+        assert_eq!(event["name"], "Brain.fm");
+        for key in ["genre", "description", "offers", "startDate"] {
+            assert!(event.get(key).is_none(), "{key} should be absent");
+        }
+    }
+
+    #[test]
+    fn test_to_applescript_record() {
+        let state = BrainFmState {
+            mode: Some("Deep Work".into()),
+            track_name: Some(r#"Say "Hi" \ Bye"#.into()),
+            neural_effect: Some("High Neural Effect".into()),
+            is_playing: true,
+            session_time: Some("1:23:45".into()),
+            ..Default::default()
+        };
+        let record = state.to_applescript_record();
+
+        let field_re =
+            regex::Regex::new(r#"(\w+):("(?:[^"\\]|\\.)*"|missing value|[\d.]+|true|false)"#)
+                .unwrap();
+        let fields: HashMap<_, _> = field_re
+            .captures_iter(&record)
+            .map(|caps| (caps[1].to_string(), caps[2].to_string()))
+            .collect();
+        assert!(record.starts_with('{') && record.ends_with('}'));
+        assert_eq!(fields["mode"], r#""Deep Work""#);
+        assert_eq!(fields["track"], r#""Say \"Hi\" \\ Bye""#);
+        assert_eq!(fields["genre"], "missing value");
+        assert_eq!(fields["nelLevel"], "0.75");
+        assert_eq!(fields["isPlaying"], "true");
+        assert_eq!(fields["sessionTime"], r#""1:23:45""#);
+        assert_eq!(fields.len(), 6);
+    }
+
+    #[test]
+    fn test_data_staleness_secs() {
+        let state = BrainFmState {
+            last_successful_read: Some(SystemTime::now() - Duration::from_secs(90)),
+            ..Default::default()
+        };
+        let staleness = state.data_staleness_secs().unwrap();
+        assert!((90.0..91.0).contains(&staleness), "{staleness}");
+
+        assert_eq!(BrainFmState::new().data_staleness_secs(), None);
+        let future = BrainFmState {
+            last_successful_read: Some(SystemTime::now() + Duration::from_secs(60)),
+            ..Default::default()
+        };
+        assert_eq!(future.data_staleness_secs(), None);
+    }
+
+    #[test]
+    fn test_is_empty() {
+        let state = BrainFmState::new();
+        assert!(state.is_empty());
+        assert_eq!(state.to_presence_string(), "Brain.fm");
+        assert_eq!(state.to_details_string(), None);
+
+        for state in [
+            BrainFmState {
+                adhd_mode: true,
+                ..Default::default()
+            },
+            BrainFmState {
+                moods: vec!["Calm".into()],
+                ..Default::default()
+            },
+            BrainFmState {
+                session_elapsed_secs: Some(0.0),
+                ..Default::default()
+            },
+        ] {
+            assert!(!state.is_empty(), "{state:?}");
+        }
+    }
+
+    #[test]
+    fn test_sanitize_mode_variants() {
+        for raw in ["deep_work", "  Deep Work ", "DEEP-WORK"] {
+            let mut state = BrainFmState {
+                mode: Some(raw.into()),
+                ..Default::default()
+            };
+            state.sanitize();
+            assert_eq!(state.mode.as_deref(), Some("Deep Work"), "input {raw:?}");
+        }
+
+        // Unknown modes are only trimmed
+        let mut state = BrainFmState {
+            mode: Some(" Custom ".into()),
+            ..Default::default()
+        };
+        state.sanitize();
+        assert_eq!(state.mode.as_deref(), Some("Custom"));
+    }
+
+    #[test]
+    fn test_sanitize_genre_and_neural_effect() {
+        let mut state = BrainFmState {
+            genre: Some("lofi".into()),
+            neural_effect: Some("high neural effect".into()),
+            ..Default::default()
+        };
+        state.sanitize();
+        assert_eq!(state.genre.as_deref(), Some("Lofi"));
+        assert_eq!(state.neural_effect.as_deref(), Some("High Neural Effect"));
+    }
+
+    #[test]
+    fn test_sanitize_trims_strings_and_tags() {
+        let mut state = BrainFmState {
+            track_name: Some("  Nothing Remains\n".into()),
+            session_time: Some(" 1:23 ".into()),
+            moods: vec![" Calm ".into(), "  ".into()],
+            instruments: vec!["Piano".into()],
+            ..Default::default()
+        };
+        state.sanitize();
+        assert_eq!(state.track_name.as_deref(), Some("Nothing Remains"));
+        assert_eq!(state.session_time.as_deref(), Some("1:23"));
+        assert_eq!(state.moods, vec!["Calm".to_string()]);
+        assert_eq!(state.instruments, vec!["Piano".to_string()]);
+    }
+
+    #[test]
+    fn test_sanitize_empty_mode_is_not_active() {
+        let mut state = BrainFmState {
+            is_playing: true,
+            mode: Some("   ".into()),
+            ..Default::default()
+        };
+        assert!(state.is_active());
+        state.sanitize();
+        assert_eq!(state.mode, None);
+        assert!(!state.is_active());
+    }
+
+    #[test]
+    fn test_default_html_overlay_is_valid_html() {
+        let state = BrainFmState {
+            is_playing: true,
+            mode: Some("Deep Work".into()),
+            track_name: Some("Rock & <Roll>".into()),
+            genre: Some("Piano".into()),
+            neural_effect: Some("High Neural Effect".into()),
+            image_url: Some("https://cdn.brain.fm/art/it's.png".into()),
+            ..Default::default()
+        };
+
+        let html = scraper::Html::parse_document(&state.to_html_overlay(None));
+        assert!(html.errors.is_empty(), "parse errors: {:?}", html.errors);
+
+        let text = |selector: &str| {
+            let selector = scraper::Selector::parse(selector).unwrap();
+            html.select(&selector)
+                .next()
+                .unwrap()
+                .text()
+                .collect::<String>()
+        };
+        assert_eq!(text(".mode"), "Deep Work");
+        assert_eq!(text(".track"), "Rock & <Roll>");
+        assert_eq!(text(".genre"), "Piano");
+        assert_eq!(text(".nel"), "High Neural Effect");
+
+        let art = scraper::Selector::parse(".art").unwrap();
+        let style = html.select(&art).next().unwrap().attr("style").unwrap();
+        assert!(style.contains("url('https://cdn.brain.fm/art/it%27s.png')"));
+    }
+
+    #[test]
+    fn test_html_overlay_unknown_fields_are_blank() {
+        let html = BrainFmState::default().to_html_overlay(None);
+        for placeholder in ["{mode}", "{track}", "{genre}", "{nel}", "{image_url}"] {
+            assert!(!html.contains(placeholder), "{placeholder} left in overlay");
+        }
+    }
+
+    #[test]
+    fn test_html_overlay_custom_template() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("overlay.html");
+        std::fs::write(
+            &path,
+            "<p>{mode}|{track}|{genre}|{nel}|{image_url}|{bpm}</p>",
+        )
+        .unwrap();
+        let state = BrainFmState {
+            mode: Some("Focus".into()),
+            track_name: Some("Nothing Remains".into()),
+            bpm: Some(90),
+            ..Default::default()
+        };
+
+        let html = state.to_html_overlay(Some(&path));
+
+        assert_eq!(html, "<p>Focus|Nothing Remains||||90</p>");
+
+        // A missing template falls back to the built-in one
+        let fallback = state.to_html_overlay(Some(Path::new("/nonexistent/overlay.html")));
+        assert!(fallback.starts_with("<!DOCTYPE html>"));
+    }
+
+    /// Flatten a decoded OSC bundle into `(address, argument)` pairs
+    fn osc_messages(packet: rosc::OscPacket) -> Vec<(String, rosc::OscType)> {
+        let rosc::OscPacket::Bundle(bundle) = packet else {
+            panic!("expected an OSC bundle");
+        };
+        bundle
+            .content
+            .into_iter()
+            .map(|packet| match packet {
+                rosc::OscPacket::Message(mut msg) => (msg.addr, msg.args.remove(0)),
+                rosc::OscPacket::Bundle(_) => panic!("unexpected nested bundle"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_osc_bundle_over_udp() {
+        use rosc::OscType;
+        use std::net::UdpSocket;
+
+        let state = BrainFmState {
+            is_playing: true,
+            mode: Some("Deep Work".into()),
+            track_name: Some("Evolving Harmony".into()),
+            genre: Some("Piano".into()),
+            neural_effect: Some("High Neural Effect".into()),
+            bpm: Some(120),
+            session_time: Some("1:05".into()),
+            ..Default::default()
+        };
+
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        sender
+            .send_to(&state.to_osc_bundle(), receiver.local_addr().unwrap())
+            .unwrap();
+
+        let mut buf = [0u8; rosc::decoder::MTU];
+        let len = receiver.recv(&mut buf).unwrap();
+        let (_, packet) = rosc::decoder::decode_udp(&buf[..len]).unwrap();
+
+        assert_eq!(
+            osc_messages(packet),
+            vec![
+                ("/brainfm/playing".to_string(), OscType::Bool(true)),
+                (
+                    "/brainfm/mode".to_string(),
+                    OscType::String("Deep Work".into())
+                ),
+                (
+                    "/brainfm/track".to_string(),
+                    OscType::String("Evolving Harmony".into())
+                ),
+                (
+                    "/brainfm/genre".to_string(),
+                    OscType::String("Piano".into())
+                ),
+                ("/brainfm/nel".to_string(), OscType::Float(0.75)),
+                ("/brainfm/bpm".to_string(), OscType::Int(120)),
+                ("/brainfm/session_time".to_string(), OscType::Float(65.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_osc_bundle_omits_unknown_fields() {
+        let state = BrainFmState::default();
+        let (_, packet) = rosc::decoder::decode_udp(&state.to_osc_bundle()).unwrap();
+        assert_eq!(
+            osc_messages(packet),
+            vec![("/brainfm/playing".to_string(), rosc::OscType::Bool(false))]
+        );
+    }
+
+    fn test_reader() -> BrainFmReader {
+        BrainFmReader {
+            app_support_path: PathBuf::new(),
+            memory_cache: api_cache_reader::ApiCacheData::new(),
+            api_refresh_counter: 0,
+            last_api_track: None,
+            diagnostics: ReadDiagnostics::default(),
+            last_state: None,
+            scan_mode: ScanMode::default(),
+            max_retry_after: Duration::from_secs(api_client::DEFAULT_MAX_RETRY_AFTER_SECS),
+            api_version: api_client::DEFAULT_API_VERSION,
+            track_history: VecDeque::new(),
+            lsof: util::LsofCommand::default(),
+            error_budget: ErrorBudget::default(),
+            update_interval: DEFAULT_UPDATE_INTERVAL,
+            simulated_state: None,
+            simulate_for: None,
+            simulation_expires: None,
+            demo_started: None,
+            last_successful_read: None,
+            metadata_source: None,
+            state_encryption_key: None,
+            last_read_result: None,
+            test_fixture: None,
+            test_running: None,
+        }
+    }
+
+    /// A reader of `fixture`
+    fn fixture_reader(fixture: test_fixture::TestFixtureBuilder) -> BrainFmReader {
+        let mut reader = test_reader();
+        reader.set_test_fixture(fixture.build());
+        reader
+    }
+
+    #[test]
+    fn test_read_state_from_fixture() {
+        let state = fixture_reader(test_fixture::TestFixtureBuilder::focus_playing())
+            .read_state()
+            .unwrap();
+        assert!(state.is_playing);
+        assert_eq!(state.track_name.as_deref(), Some("Nothing Remains"));
+        assert_eq!(state.genre.as_deref(), Some("Piano"));
+        assert_eq!(state.activity.as_deref(), Some("Deep Work"));
+        assert_eq!(state.neural_effect.as_deref(), Some("High Neural Effect"));
+        assert!(state.adhd_mode);
+
+        // The same metadata from a gzipped response
+        let state = fixture_reader(
+            test_fixture::TestFixtureBuilder::new()
+                .with_api_cache_json(test_fixture::SERVINGS_RECENT_JSON)
+                .with_cache_entry("https://api.brain.fm/v3/users/abc/servings/recent", true)
+                .with_cache_entry(test_fixture::NOTHING_REMAINS_URL, false),
+        )
+        .read_state()
+        .unwrap();
+        assert_eq!(state.track_name.as_deref(), Some("Nothing Remains"));
+        assert_eq!(state.bpm, Some(120));
+
+        let state = fixture_reader(test_fixture::TestFixtureBuilder::focus_paused())
+            .read_state()
+            .unwrap();
+        assert!(!state.is_playing);
+        assert_eq!(state.track_name, None);
+        assert!(state.adhd_mode);
+
+        let mut reader = fixture_reader(test_fixture::TestFixtureBuilder::focus_playing());
+        reader.set_test_running(false);
+        let state = reader.read_state().unwrap();
+        assert!(!state.is_playing && state.mode.is_none() && !state.adhd_mode);
+    }
+
+    #[test]
+    fn test_read_state_cached_within_max_age() {
+        let mut reader = test_reader();
+        let state_for = |track: &str| BrainFmState {
+            is_playing: true,
+            track_name: Some(track.into()),
+            ..Default::default()
+        };
+        reader.simulate_state(state_for("Blooming"));
+        let first = reader.read_state_cached(Duration::from_secs(60)).unwrap();
+        assert_eq!(first.track_name.as_deref(), Some("Blooming"));
+
+        // Within max_age: the cached result, not the new simulated state
+        reader.simulate_state(state_for("Nothing Remains"));
+        let cached = reader.read_state_cached(Duration::from_secs(60)).unwrap();
+        assert_eq!(cached, first);
+
+        // Stale: read again
+        let fresh = reader.read_state_cached(Duration::ZERO).unwrap();
+        assert_eq!(fresh.track_name.as_deref(), Some("Nothing Remains"));
+        let cached = reader.read_state_cached(Duration::from_secs(60)).unwrap();
+        assert_eq!(cached.track_name.as_deref(), Some("Nothing Remains"));
+    }
+
+    #[test]
+    fn test_read_state_logs_final_state() {
+        let mut reader = test_reader();
+        reader.simulate_state(BrainFmState {
+            is_playing: true,
+            mode: Some("Focus".into()),
+            track_name: Some("Blooming".into()),
+            ..Default::default()
+        });
+
+        let logs = util::capture_logs(|| {
+            reader.read_state().unwrap();
+        });
+        let start = logs
+            .iter()
+            .position(|line| line == "Final assembled state:")
+            .expect("no final state in the log");
+        let fields = &logs[start + 1..];
+        for expected in [
+            r#"mode: "Focus" field=mode value="Focus" present=true"#,
+            r#"track_name: "Blooming" field=track_name value="Blooming" present=true"#,
+            "is_playing: true field=is_playing value=true present=true",
+            "genre: None field=genre value=null present=false",
+        ] {
+            assert!(
+                fields.iter().any(|line| line == expected),
+                "{expected} not in {fields:#?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_simulate_state_bypasses_sources() {
+        let mut reader = test_reader();
+        let state = BrainFmState {
+            is_playing: true,
+            mode: Some("Focus".into()),
+            track_name: Some("Nothing Remains".into()),
+            ..Default::default()
+        };
+        reader.simulate_state(state.clone());
+        assert_eq!(reader.read_state().unwrap(), state);
+        assert_eq!(reader.read_state().unwrap(), state);
+
+        reader.set_simulate_for(Some(Duration::ZERO));
+        reader.simulate_state(state);
+        assert_eq!(reader.active_simulation(), None);
+        assert!(reader.simulated_state.is_none());
+    }
+
+    /// Cache holding `name`, complete (neural effect and image) or not
+    fn cache_with(name: &str, complete: bool) -> api_cache_reader::ApiCacheData {
+        let mut cache = api_cache_reader::ApiCacheData::new();
+        cache.insert(
+            name.to_lowercase(),
+            api_cache_reader::TrackMetadata {
+                name: name.to_string(),
+                track_id: None,
+                genre: Some("Electronic".into()),
+                neural_effect: complete.then(|| "High Neural Effect".into()),
+                neural_effect_level: None,
+                mental_state: Some("Focus".into()),
+                activity: None,
+                is_guided_meditation: false,
+                image_url: complete.then(|| "https://images.unsplash.com/1".into()),
+                bpm: None,
+                moods: vec![],
+                instruments: vec![],
+                loaded_at: SystemTime::now(),
+            },
+        );
+        cache
+    }
+
+    #[test]
+    fn test_classify_metadata_paths() {
+        use MetadataSource::{DirectApi, DiskCache, Filename, LevelDb, MediaRemote, MemoryCache};
+        let empty = api_cache_reader::ApiCacheData::new();
+        let disk = cache_with("Blooming", true);
+        let mut reader = test_reader();
+        let classify = |reader: &BrainFmReader, fresh_api, cache, miss| {
+            reader.classify_metadata(Some("Blooming"), fresh_api, cache, miss)
+        };
+
+        // Cache misses: the title is only as good as where it came from
+        assert_eq!(classify(&reader, None, &empty, Filename), Filename);
+        assert_eq!(classify(&reader, None, &empty, MediaRemote), MediaRemote);
+        assert_eq!(
+            reader.classify_metadata(None, None, &disk, Filename),
+            LevelDb
+        );
+        // Disk cache hit
+        assert_eq!(classify(&reader, None, &disk, Filename), DiskCache);
+
+        // Memory cache: only complete entries count as such
+        reader.memory_cache = cache_with("blooming", false);
+        assert_eq!(classify(&reader, None, &disk, MediaRemote), DiskCache);
+        reader.memory_cache = cache_with("Blooming", true);
+        assert_eq!(classify(&reader, None, &disk, MediaRemote), MemoryCache);
+
+        // Fetched from the API this cycle
+        assert_eq!(classify(&reader, Some(&disk), &disk, Filename), DirectApi);
+        assert_eq!(
+            classify(&reader, Some(&cache_with("Other", true)), &disk, Filename),
+            MemoryCache
+        );
+
+        let scores: Vec<f32> = [
+            DirectApi,
+            MemoryCache,
+            DiskCache,
+            MediaRemote,
+            Filename,
+            LevelDb,
+        ]
+        .into_iter()
+        .map(MetadataSource::confidence)
+        .collect();
+        assert_eq!(scores, [1.0, 0.9, 0.7, 0.5, 0.4, 0.3]);
+    }
+
+    #[test]
+    fn test_confidence_score_merging_and_health() {
+        let merged = BrainFmReader::merge_state(
+            BrainFmState {
+                confidence_score: 0.3,
+                ..Default::default()
+            },
+            BrainFmState {
+                confidence_score: 0.0,
+                ..Default::default()
+            },
+        );
+        assert!((merged.confidence_score - 0.3).abs() < f32::EPSILON);
+        assert!((demo_state(Duration::ZERO).confidence_score - 1.0).abs() < f32::EPSILON);
+
+        let mut reader = test_reader();
+        assert_eq!(reader.check_health().confidence_score, None);
+        reader.last_state = Some(BrainFmState {
+            confidence_score: 0.7,
+            ..Default::default()
+        });
+        assert_eq!(reader.check_health().confidence_score, Some(0.7));
+    }
+
+    #[test]
+    fn test_demo_state_cycle() {
+        let step = |n: u64| demo_state(DEMO_STEP_DURATION * u32::try_from(n).unwrap());
+        let modes: Vec<_> = (0..6).map(|n| step(n).mode).collect();
+        assert_eq!(
+            modes,
+            [
+                Some("Focus".into()),
+                Some("Sleep".into()),
+                Some("Relax".into()),
+                Some("Meditate".into()),
+                None,
+                Some("Focus".into()),
+            ]
+        );
+        assert!(step(0).is_playing);
+        assert!(!step(4).is_playing);
+
+        let mut reader = test_reader();
+        reader.set_demo_mode(true);
+        assert_eq!(reader.read_state().unwrap().mode.as_deref(), Some("Focus"));
+
+        let mut demo = BrainFmReader::demo();
+        assert_eq!(demo.read_state().unwrap().mode.as_deref(), Some("Focus"));
+    }
+
+    fn instruments_config() -> config::Config {
+        config::Config {
+            show_instruments_in_presence: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_details_with_instruments_capped() {
+        let state = BrainFmState {
+            track_name: Some("Nothing Remains".into()),
+            genre: Some("Piano".into()),
+            neural_effect: Some("High Neural Effect".into()),
+            instruments: vec![
+                "Acoustic Piano".into(),
+                "Electronic Percussion".into(),
+                "Synth Pad".into(),
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            state.to_details_string_with(&instruments_config()).unwrap(),
+            "Nothing Remains • Piano • High Neural Effect • Acoustic Piano & Electronic Percussion"
+        );
+
+        let one = config::Config {
+            max_instruments: 1,
+            ..instruments_config()
+        };
+        assert_eq!(
+            state.to_details_string_with(&one).unwrap(),
+            "Nothing Remains • Piano • High Neural Effect • Acoustic Piano"
+        );
+
+        // Disabled by default
+        assert_eq!(
+            state.to_details_string_with(&config::Config::default()),
+            state.to_details_string()
+        );
+    }
+
+    #[test]
+    fn test_details_without_instruments_unchanged() {
+        let state = BrainFmState {
+            track_name: Some("Nothing Remains".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            state.to_details_string_with(&instruments_config()).unwrap(),
+            "Nothing Remains"
+        );
+    }
+
+    #[test]
+    fn test_details_truncated_to_discord_limit() {
+        let state = BrainFmState {
+            track_name: Some("A".repeat(120)),
+            instruments: vec!["Acoustic Piano".into()],
+            ..Default::default()
+        };
+        let details = state.to_details_string_with(&instruments_config()).unwrap();
+        assert_eq!(details.chars().count(), 128);
+        assert!(details.ends_with("..."));
+
+        let exact = BrainFmState {
+            track_name: Some("B".repeat(128)),
+            ..Default::default()
+        };
+        let details = exact.to_details_string_with(&instruments_config()).unwrap();
+        assert_eq!(details, "B".repeat(128));
+    }
+
+    #[test]
+    fn test_check_health_missing_data_dir() {
+        let report = test_reader().check_health();
+        assert!(!report.leveldb_found);
+        assert!(!report.cache_found);
+        assert_eq!(report.memory_cache_tracks, 0);
+        assert_eq!(report.cache_statistics.total_tracks, 0);
+        assert!(!report.is_healthy());
+        assert_eq!(
+            report.rust_version.as_deref(),
+            Some(env!("CARGO_PKG_RUST_VERSION"))
+        );
+    }
+
+    #[test]
+    fn test_scan_mode_sources() {
+        use DataSource::{Api, DiskCache, LevelDb, Lsof, MediaRemote};
+        let all = [LevelDb, MediaRemote, DiskCache, Lsof, Api];
+        let enabled = |mode: ScanMode| {
+            all.into_iter()
+                .filter(|&source| mode.uses(source))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(enabled(ScanMode::Full), all);
+        assert_eq!(enabled(ScanMode::FastPathOnly), [LevelDb, MediaRemote]);
+        assert_eq!(enabled(ScanMode::ApiOnly), [MediaRemote, Api]);
+        assert_eq!(
+            enabled(ScanMode::LevelDbAndCache),
+            [LevelDb, DiskCache, Lsof]
+        );
+        assert_eq!(
+            enabled(ScanMode::NoApi),
+            [LevelDb, MediaRemote, DiskCache, Lsof]
+        );
+    }
+
+    #[test]
+    fn test_scan_mode_skips_disabled_sources() {
+        use DataSource::{DiskCache, LevelDb, Lsof};
+        for mode in [
+            ScanMode::Full,
+            ScanMode::FastPathOnly,
+            ScanMode::ApiOnly,
+            ScanMode::LevelDbAndCache,
+            ScanMode::NoApi,
+        ] {
+            let mut reader = fixture_reader(test_fixture::TestFixtureBuilder::focus_playing());
+            reader.set_scan_mode(mode);
+            let state = reader.read_state().unwrap();
+
+            let diagnostics = &reader.diagnostics;
+            assert_eq!(
+                diagnostics.last_leveldb_read.is_some(),
+                mode.uses(LevelDb),
+                "{mode:?}"
+            );
+            assert_eq!(
+                diagnostics.disk_cache_len.is_some(),
+                mode.uses(DiskCache),
+                "{mode:?}"
+            );
+            assert_eq!(
+                diagnostics.last_lsof_result.is_some(),
+                mode.uses(Lsof),
+                "{mode:?}"
+            );
+            // The track comes from the audio file `lsof` finds open
+            assert_eq!(state.track_name.is_some(), mode.uses(Lsof), "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn test_scan_mode_from_config() {
+        let config: config::Config = toml::from_str(r#"scan_mode = "no_api""#).unwrap();
+        assert_eq!(config.scan_mode, ScanMode::NoApi);
+        assert_eq!(config::Config::default().scan_mode, ScanMode::Full);
+    }
+
+    #[test]
+    fn test_restore_state_after_restart_skips_api_call() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_path = dir.path().join("last_state.json");
+        let track = "Nothing Remains".to_string();
+
+        // First run: API was called for the track, then the state was saved
+        let mut before = test_reader();
+        before.last_api_track = Some(track.clone());
+        before.memory_cache = serde_json::from_value(serde_json::json!({
+            "tracks": [["Nothing_Remains.mp3", {
+                "name": track, "genre": "Piano", "neural_effect": null,
+                "neural_effect_level": null, "mental_state": "Focus", "activity": null,
+                "image_url": null, "bpm": 120, "moods": [], "instruments": [],
+                "loaded_at": { "secs_since_epoch": 0, "nanos_since_epoch": 0 }
+            }]]
+        }))
+        .unwrap();
+        before.last_state = Some(BrainFmState {
+            is_playing: true,
+            track_name: Some(track.clone()),
+            ..Default::default()
+        });
+        before.save_state(&state_path).unwrap();
+
+        // A fresh reader would call the API for the same track again
+        let fresh = test_reader();
+        assert!(fresh.should_call_api(Some(&track), true));
+
+        // After restoring, the same track does not trigger a duplicate call
+        let mut after = test_reader();
+        after.restore_state(&state_path).unwrap();
+        assert_eq!(after.last_api_track.as_ref(), Some(&track));
+        assert_eq!(after.api_refresh_counter, 0);
+        assert_eq!(
+            after.memory_cache.lookup_by_name(&track).unwrap().bpm,
+            Some(120)
+        );
+        assert!(!after.should_call_api(Some(&track), true));
+        assert!(after.should_call_api(Some(&"Other".to_string()), true));
+    }
+
+    #[test]
+    fn test_memory_cache_is_encrypted_with_state_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_path = dir.path().join("last_state.json");
+        let key = "correct horse battery staple 123";
+
+        let mut before = test_reader();
+        before.state_encryption_key = Some(key.to_string());
+        before.memory_cache = serde_json::from_value(serde_json::json!({
+            "tracks": [["Nothing_Remains.mp3", {
+                "name": "Nothing Remains", "genre": "Piano", "neural_effect": null,
+                "neural_effect_level": null, "mental_state": "Focus", "activity": null,
+                "image_url": null, "bpm": 120, "moods": [], "instruments": [],
+                "loaded_at": { "secs_since_epoch": 0, "nanos_since_epoch": 0 }
+            }]]
+        }))
+        .unwrap();
+        before.last_state = Some(BrainFmState::default());
+        before.save_state(&state_path).unwrap();
+
+        let contents = std::fs::read(memory_cache_path(&state_path)).unwrap();
+        assert!(util::is_encrypted(&contents));
+        assert!(!String::from_utf8_lossy(&contents).contains("Nothing Remains"));
+
+        let mut after = test_reader();
+        after.state_encryption_key = Some(key.to_string());
+        after.restore_state(&state_path).unwrap();
+        assert!(after
+            .memory_cache
+            .lookup_by_name("Nothing Remains")
+            .is_some());
+    }
+
+    #[test]
+    fn test_export_import_memory_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("api_cache.json");
+        let tracks: Vec<_> = (1..=5)
+            .map(|i| {
+                serde_json::json!([format!("Track_{i}.mp3"), {
+                    "name": format!("Track {i}"), "genre": "Piano", "neural_effect": null,
+                    "neural_effect_level": null, "mental_state": "Focus", "activity": null,
+                    "image_url": null, "bpm": null, "moods": [], "instruments": [],
+                    "loaded_at": { "secs_since_epoch": 0, "nanos_since_epoch": 0 }
+                }])
+            })
+            .collect();
+        let mut desktop = test_reader();
+        desktop.memory_cache =
+            serde_json::from_value(serde_json::json!({ "tracks": tracks })).unwrap();
+        desktop.export_memory_cache(&path).unwrap();
+
+        let mut laptop = test_reader();
+        laptop.import_cache(&path).unwrap();
+
+        assert_eq!(laptop.cache_statistics().total_tracks, 5);
+        for i in 1..=5 {
+            let name = format!("Track {i}");
+            assert!(
+                laptop.memory_cache.lookup_by_name(&name).is_some(),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_play_count_from_track_history() {
+        let mut reader = test_reader();
+        let mut play = |track: &str, is_playing: bool| {
+            let mut state = BrainFmState {
+                track_name: Some(track.into()),
+                is_playing,
+                ..Default::default()
+            };
+            reader.record_play(&mut state);
+            state
+        };
+
+        assert_eq!(play("Blooming", true).play_count, Some(1));
+        // Polling and pause/resume don't start a new play
+        assert_eq!(play("Blooming", true).play_count, Some(1));
+        assert_eq!(play("Blooming", false).play_count, Some(1));
+        assert_eq!(play("Blooming", true).play_count, Some(1));
+        assert_eq!(play("Nothing Remains", true).play_count, Some(1));
+        assert_eq!(play("Blooming", true).play_count, Some(2));
+        assert_eq!(play("Tidal", true).play_count, Some(1));
+        let third = play("Blooming", true);
+        assert_eq!(third.play_count, Some(3));
+        assert_eq!(third.to_play_count_string().as_deref(), Some("3rd time"));
+        assert_eq!(play("Unplayed", false).play_count, None);
+    }
+
+    #[test]
+    fn test_play_count_string_ordinals() {
+        let with_count = |count| BrainFmState {
+            play_count: Some(count),
+            ..Default::default()
+        };
+        assert_eq!(with_count(1).to_play_count_string(), None);
+        assert_eq!(with_count(2).to_play_count_string().unwrap(), "2nd time");
+        assert_eq!(with_count(11).to_play_count_string().unwrap(), "11th time");
+        assert_eq!(with_count(21).to_play_count_string().unwrap(), "21st time");
+        assert_eq!(BrainFmState::new().to_play_count_string(), None);
+    }
+
+    #[test]
+    fn test_save_state_without_read_is_noop() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("never-written").join("last_state.json");
+        test_reader().save_state(&path).unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_encrypted_state_file_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("encrypted").join("last_state.json");
+        let key = "correct horse battery staple 123";
+        let state = BrainFmState {
+            is_playing: true,
+            mode: Some("Focus".into()),
+            track_name: Some("Nothing Remains".into()),
+            ..Default::default()
+        };
+
+        state.to_json_file_with(&path, Some(key)).unwrap();
+        let contents = std::fs::read(&path).unwrap();
+        assert!(util::is_encrypted(&contents));
+        assert!(!String::from_utf8_lossy(&contents).contains("Nothing Remains"));
+        assert_eq!(
+            BrainFmState::from_json_file_with(&path, Some(key)).unwrap(),
+            state
+        );
+        let err = BrainFmState::from_json_file(&path).unwrap_err();
+        assert!(
+            format!("{err:#}").contains("state_encryption_key"),
+            "{err:#}"
+        );
+
+        // Plain files written before encryption was turned on still load
+        state.to_json_file(&path).unwrap();
+        assert_eq!(
+            BrainFmState::from_json_file_with(&path, Some(key)).unwrap(),
+            state
+        );
+    }
+
+    #[test]
+    fn test_explain_state_source_annotations() {
+        let mut reader = test_reader();
+        let leveldb = BrainFmState {
+            mode: Some("Deep Work".into()),
+            adhd_mode: true,
+            ..Default::default()
+        };
+        let cache = BrainFmState {
+            is_playing: true,
+            track_name: Some("Nothing Remains".into()),
+            genre: Some("Piano".into()),
+            ..Default::default()
+        };
+        reader.record_sources("LevelDB", &leveldb);
+        reader.record_sources("cache reader (lsof)", &cache);
+        reader.diagnostics.last_api_result = Some("ok (12 tracks)".into());
+        let state = BrainFmReader::merge_state(leveldb, cache);
+
+        let report = reader.explain_state(&state);
+        assert!(report.contains("Deep Work  [LevelDB]"));
+        assert!(report.contains("Nothing Remains  [cache reader (lsof)]"));
+        assert!(report.contains("Piano  [cache reader (lsof)]"));
+        assert!(report.contains("true  [cache reader (lsof)]"));
+        assert!(report.contains("Last API call:       ok (12 tracks)"));
+        assert!(report.contains("Disk cache:          not read"));
+    }
+
+    #[test]
+    fn test_explain_state_unknown_source() {
+        let reader = test_reader();
+        let state = BrainFmState {
+            genre: Some("Lofi".into()),
+            ..Default::default()
+        };
+        let report = reader.explain_state(&state);
+        assert!(report.contains("Lofi  [unknown]"));
+        assert!(!report.contains("track "));
+    }
+
+    #[test]
     fn test_merge_state_option_overlay_wins() {
         let base = BrainFmState {
             mode: Some("Focus".into()),
@@ -446,6 +5176,27 @@ mod tests {
         assert!(merged.infinite_play); // base false || overlay true
     }
 
+    #[test]
+    fn test_template_vars_populated_fields_only() {
+        let state = BrainFmState {
+            mode: Some("Deep Work".into()),
+            track_name: Some("Nothing Remains".into()),
+            bpm: Some(90),
+            moods: vec!["Calm".into(), "Chill".into()],
+            instruments: vec!["Acoustic Piano".into(), "Strings".into()],
+            ..Default::default()
+        };
+        let vars = state.template_vars();
+        assert_eq!(vars["mode"], "Deep Work");
+        assert_eq!(vars["track"], "Nothing Remains");
+        assert_eq!(vars["bpm"], "90");
+        assert_eq!(vars["moods"], "Calm, Chill");
+        assert_eq!(vars["instruments"], "Acoustic Piano");
+        assert_eq!(vars["is_playing"], "false");
+        assert!(!vars.contains_key("genre"));
+        assert!(!vars.contains_key("session_time"));
+    }
+
     #[test]
     fn test_merge_state_both_none() {
         let base = BrainFmState::new();
@@ -455,3 +5206,141 @@ mod tests {
         assert!(merged.track_name.is_none());
     }
 }
+
+#[cfg(test)]
+mod proptests {
+    use super::*;
+    use proptest::prelude::*;
+    use proptest::sample::select;
+
+    const NEURAL_EFFECTS: &[&str] = &[
+        "Low Neural Effect",
+        "Medium Neural Effect",
+        "High Neural Effect",
+    ];
+
+    /// Free text of up to 100 chars, or `None`
+    fn arb_text() -> impl Strategy<Value = Option<String>> {
+        proptest::option::of(".{0,100}")
+    }
+
+    /// One of `options` as an owned string, or `None`
+    fn arb_choice(options: Vec<&'static str>) -> impl Strategy<Value = Option<String>> {
+        proptest::option::of(select(options).prop_map(str::to_string))
+    }
+
+    /// States shaped like the ones Brain.fm produces: known modes, genres
+    /// and neural effect levels, with free text elsewhere
+    fn arb_state() -> impl Strategy<Value = BrainFmState> {
+        let modes: Vec<&'static str> = vec![
+            "Focus",
+            "Deep Work",
+            "Light Work",
+            "Motivation",
+            "Sleep",
+            "Deep Sleep",
+            "Relax",
+            "Recharge",
+            "Chill",
+            "Meditate",
+            "Unguided",
+        ];
+        let tags = || proptest::collection::vec(".{0,100}", 0..5);
+        (
+            (
+                arb_choice(modes),
+                any::<bool>(),
+                arb_text(),
+                arb_choice(NEURAL_EFFECTS.to_vec()),
+                arb_choice(util::KNOWN_GENRES.to_vec()),
+                arb_text(),
+            ),
+            (
+                arb_text(),
+                proptest::option::of(any::<u32>()),
+                tags(),
+                tags(),
+            ),
+            (
+                arb_text(),
+                arb_text(),
+                proptest::option::of(0.0f64..1e7),
+                any::<bool>(),
+                any::<bool>(),
+                proptest::option::of(any::<u32>()),
+            ),
+        )
+            .prop_map(
+                |(
+                    (mode, is_playing, track_name, neural_effect, genre, activity),
+                    (image_url, bpm, moods, instruments),
+                    (
+                        session_state,
+                        session_time,
+                        session_elapsed_secs,
+                        infinite_play,
+                        adhd_mode,
+                        play_count,
+                    ),
+                )| BrainFmState {
+                    mode,
+                    is_playing,
+                    track_name,
+                    neural_effect,
+                    genre,
+                    activity,
+                    image_url,
+                    bpm,
+                    moods,
+                    instruments,
+                    session_state,
+                    session_time,
+                    session_elapsed_secs,
+                    infinite_play,
+                    adhd_mode,
+                    play_count,
+                    last_successful_read: None,
+                    confidence_score: 0.0,
+                    session_goal_type: None,
+                    session_goal_secs: None,
+                    session_goal_progress_secs: None,
+                    is_guided_meditation: false,
+                },
+            )
+    }
+
+    proptest! {
+        #[test]
+        fn prop_merge_state_prefers_overlay(base in arb_state(), overlay in arb_state()) {
+            let merged = BrainFmReader::merge_state(base.clone(), overlay.clone());
+            prop_assert_eq!(merged.is_playing, overlay.is_playing);
+            prop_assert_eq!(merged.mode, overlay.mode.or(base.mode));
+            prop_assert_eq!(merged.track_name, overlay.track_name.or(base.track_name));
+        }
+
+        #[test]
+        fn prop_presence_strings_never_panic(state in arb_state()) {
+            prop_assert!(!state.to_presence_string().is_empty());
+            let _ = state.to_details_string();
+        }
+
+        #[test]
+        fn prop_sanitize_leaves_no_blank_fields(mut state in arb_state()) {
+            state.sanitize();
+            for field in [
+                &state.mode,
+                &state.track_name,
+                &state.neural_effect,
+                &state.genre,
+                &state.activity,
+                &state.image_url,
+                &state.session_state,
+                &state.session_time,
+            ] {
+                prop_assert!(field.as_ref().is_none_or(|value| !value.trim().is_empty()));
+            }
+            prop_assert!(state.moods.iter().chain(&state.instruments).all(|tag| !tag.is_empty()));
+            let _ = state.to_presence_string();
+        }
+    }
+}
@@ -0,0 +1,299 @@
+//! Log output for the binaries
+//!
+//! `RotatingJsonFile` writes log records to a file as JSON lines, rotating
+//! it at a size limit, and `TeeLogger` sends each record to a console
+//! logger (e.g. `env_logger`) and such a file. Set up from
+//! `Config::log_file`, `Config::log_max_size_bytes` and `Config::log_level`,
+//! so a service keeps its logs when the journal rotates.
+
+use anyhow::{Context, Result};
+use log::{LevelFilter, Log, Metadata, Record};
+use std::fs::{File, OpenOptions};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+/// Default `Config::log_max_size_bytes`: 10 MB
+pub const DEFAULT_LOG_MAX_SIZE_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Rotated files kept besides the current one: `<log_file>.1` (newest) to
+/// `<log_file>.3`
+pub const MAX_ROTATED_LOG_FILES: usize = 3;
+
+/// A log file of JSON lines,
+/// `{"time":"2025-01-01T12:00:00.000Z","level":"INFO","module":"brainfm_presence","message":"…"}`,
+/// renamed to `<path>.1` when the next record would take it past
+/// `max_size_bytes`. Older files move up to `<path>.2` and `<path>.3`;
+/// the oldest is deleted.
+#[derive(Debug)]
+pub struct RotatingJsonFile {
+    path: PathBuf,
+    max_size_bytes: u64,
+    file: File,
+    /// Bytes in `file`
+    size: u64,
+}
+
+impl RotatingJsonFile {
+    /// Append to `path`, creating it and its directory if needed
+    pub fn open(path: &Path, max_size_bytes: u64) -> Result<Self> {
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let file = Self::open_file(path)?;
+        let size = file.metadata().map_or(0, |metadata| metadata.len());
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_size_bytes,
+            file,
+            size,
+        })
+    }
+
+    fn open_file(path: &Path) -> Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open log file {}", path.display()))
+    }
+
+    /// Append `record` as a JSON line, rotating first if it doesn't fit
+    pub fn write_record(&mut self, record: &Record) -> Result<()> {
+        let mut line = json_line(record);
+        line.push('\n');
+        if self.size > 0 && self.size + line.len() as u64 > self.max_size_bytes {
+            self.rotate()?;
+        }
+        self.file
+            .write_all(line.as_bytes())
+            .with_context(|| format!("Failed to write log file {}", self.path.display()))?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// `<path>.<n>`
+    fn rotated_path(&self, n: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{n}"));
+        PathBuf::from(path)
+    }
+
+    /// Shift `<path>.1`, `<path>.2`, … up by one (dropping the oldest),
+    /// move the current file to `<path>.1` and start a fresh one
+    fn rotate(&mut self) -> Result<()> {
+        let _ = std::fs::remove_file(self.rotated_path(MAX_ROTATED_LOG_FILES));
+        for n in (1..MAX_ROTATED_LOG_FILES).rev() {
+            let from = self.rotated_path(n);
+            if from.exists() {
+                std::fs::rename(&from, self.rotated_path(n + 1))
+                    .with_context(|| format!("Failed to rotate {}", from.display()))?;
+            }
+        }
+        std::fs::rename(&self.path, self.rotated_path(1))
+            .with_context(|| format!("Failed to rotate {}", self.path.display()))?;
+        self.file = Self::open_file(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+/// `record` as a JSON object with `time` (UTC), `level`, `module` and
+/// `message`
+fn json_line(record: &Record) -> String {
+    serde_json::json!({
+        "time": chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        "level": record.level().as_str(),
+        "module": record.module_path().unwrap_or_else(|| record.target()),
+        "message": record.args().to_string(),
+    })
+    .to_string()
+}
+
+/// Logs to `console` as it filters, and records up to `file_level` to a
+/// `RotatingJsonFile`. File errors are reported on stderr, as there is
+/// nowhere else to log them.
+pub struct TeeLogger<L> {
+    console: L,
+    console_level: LevelFilter,
+    file: Mutex<RotatingJsonFile>,
+    file_level: LevelFilter,
+}
+
+impl<L: Log> TeeLogger<L> {
+    /// `console_level` is the most verbose level `console` lets through,
+    /// e.g. `env_logger::Logger::filter()`
+    pub fn new(
+        console: L,
+        console_level: LevelFilter,
+        file: RotatingJsonFile,
+        file_level: LevelFilter,
+    ) -> Self {
+        Self {
+            console,
+            console_level,
+            file: Mutex::new(file),
+            file_level,
+        }
+    }
+
+    /// The level to pass to `log::set_max_level`
+    pub fn max_level(&self) -> LevelFilter {
+        self.console_level.max(self.file_level)
+    }
+}
+
+impl<L: Log> Log for TeeLogger<L> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.file_level || self.console.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.console.enabled(record.metadata()) {
+            self.console.log(record);
+        }
+        if record.level() <= self.file_level {
+            let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Err(e) = file.write_record(record) {
+                eprintln!("{e:#}");
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.console.flush();
+        let _ = self
+            .file
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .file
+            .flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Level;
+
+    fn write(file: &mut RotatingJsonFile, level: Level, message: &str) {
+        file.write_record(
+            &Record::builder()
+                .level(level)
+                .target("brainfm_presence")
+                .module_path(Some("brainfm_presence::logging"))
+                .args(format_args!("{message}"))
+                .build(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_json_lines_parse() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("presence.log");
+        let mut file = RotatingJsonFile::open(&path, DEFAULT_LOG_MAX_SIZE_BYTES).unwrap();
+        write(
+            &mut file,
+            Level::Info,
+            "🧠 Brain.fm Discord Rich Presence starting...",
+        );
+        write(
+            &mut file,
+            Level::Warn,
+            "Failed to \"quote\"\nand break lines",
+        );
+
+        let lines: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["level"], "INFO");
+        assert_eq!(lines[0]["module"], "brainfm_presence::logging");
+        assert_eq!(
+            lines[0]["message"],
+            "🧠 Brain.fm Discord Rich Presence starting..."
+        );
+        assert!(lines[0]["time"].as_str().unwrap().ends_with('Z'));
+        assert_eq!(lines[1]["level"], "WARN");
+        assert_eq!(lines[1]["message"], "Failed to \"quote\"\nand break lines");
+    }
+
+    #[test]
+    fn test_rotates_at_size_limit_and_keeps_three_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("presence.log");
+        let max_size = 2000;
+        let mut file = RotatingJsonFile::open(&path, max_size).unwrap();
+        for i in 0..100 {
+            write(&mut file, Level::Info, &format!("Line {i:03}"));
+        }
+
+        let rotated: Vec<PathBuf> = (1..=MAX_ROTATED_LOG_FILES)
+            .map(|n| file.rotated_path(n))
+            .collect();
+        assert!(rotated.iter().all(|path| path.exists()));
+        assert!(!file.rotated_path(MAX_ROTATED_LOG_FILES + 1).exists());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 4);
+
+        // Newest records in the current file, then .1, .2, .3; none split
+        // across files, none over the limit
+        let mut messages = Vec::new();
+        for path in rotated.iter().rev().chain([&path]) {
+            let contents = std::fs::read_to_string(path).unwrap();
+            assert!(contents.len() as u64 <= max_size, "{}", path.display());
+            for line in contents.lines() {
+                let line: serde_json::Value = serde_json::from_str(line).unwrap();
+                messages.push(line["message"].as_str().unwrap().to_string());
+            }
+        }
+        assert_eq!(messages.last().unwrap(), "Line 099");
+        let first: usize = messages[0]["Line ".len()..].parse().unwrap();
+        let expected: Vec<String> = (first..100).map(|i| format!("Line {i:03}")).collect();
+        assert_eq!(messages, expected);
+        assert!(first > 0, "the oldest records were dropped");
+
+        // Reopening appends to the current file
+        let size = std::fs::metadata(&path).unwrap().len();
+        let mut file = RotatingJsonFile::open(&path, max_size).unwrap();
+        assert_eq!(file.size, size);
+        write(&mut file, Level::Info, "Reopened");
+        assert!(std::fs::read_to_string(&path).unwrap().contains("Reopened"));
+    }
+
+    #[test]
+    fn test_tee_logger_filters_file_by_level() {
+        struct NoConsole;
+        impl Log for NoConsole {
+            fn enabled(&self, _: &Metadata) -> bool {
+                false
+            }
+            fn log(&self, _: &Record) {}
+            fn flush(&self) {}
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("presence.log");
+        let file = RotatingJsonFile::open(&path, DEFAULT_LOG_MAX_SIZE_BYTES).unwrap();
+        let logger = TeeLogger::new(NoConsole, LevelFilter::Off, file, LevelFilter::Info);
+        assert_eq!(logger.max_level(), LevelFilter::Info);
+        for (level, message) in [(Level::Debug, "hidden"), (Level::Info, "shown")] {
+            logger.log(
+                &Record::builder()
+                    .level(level)
+                    .args(format_args!("{message}"))
+                    .build(),
+            );
+        }
+        logger.flush();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("shown") && !contents.contains("hidden"));
+    }
+}
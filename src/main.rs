@@ -4,15 +4,32 @@
 //! and displays it for potential Discord Rich Presence integration.
 
 use anyhow::Result;
-use brainfm_presence::util::truncate;
+use brainfm_presence::config::Config;
+use brainfm_presence::platform::BrainFmInstallType;
+use brainfm_presence::util::{truncate, LsofCommand};
 use brainfm_presence::{BrainFmReader, BrainFmState};
+use std::path::PathBuf;
+use std::time::Duration;
 
 fn main() -> Result<()> {
+    let explain = std::env::args().any(|arg| arg == "--explain");
+    let install_type = if std::env::args().any(|arg| arg == "--pwa") {
+        BrainFmInstallType::ChromePWA
+    } else {
+        BrainFmInstallType::Unknown
+    };
+    let data_dir = std::env::args()
+        .skip_while(|arg| arg != "--windows-pwa-path")
+        .nth(1)
+        .map(PathBuf::from);
+
     println!("🧠 Brain.fm Presence Reader - PoC");
     println!("==================================\n");
 
     // Create reader
-    let mut reader = match BrainFmReader::new() {
+    let config = Config::load();
+    let lsof = LsofCommand::from_config(&config);
+    let mut reader = match create_reader(install_type, data_dir, &lsof) {
         Ok(r) => r,
         Err(e) => {
             eprintln!("❌ Error: {e}");
@@ -44,6 +61,10 @@ fn main() -> Result<()> {
             if let Some(details) = state.to_details_string() {
                 println!("   Details: {details}");
             }
+
+            if explain {
+                println!("\n🔎 {}", reader.explain_state(&state));
+            }
         }
         Err(e) => {
             eprintln!("❌ Error reading state: {e}");
@@ -56,33 +77,25 @@ fn main() -> Result<()> {
 
     // LevelDB reader
     println!("📂 LevelDB Reader:");
-    match brainfm_presence::leveldb_reader::read_state(
-        &dirs::home_dir()
-            .expect("home directory not found")
-            .join("Library/Application Support/Brain.fm"),
-    ) {
+    let app_path = reader.app_support_path().to_path_buf();
+    match brainfm_presence::leveldb_reader::read_state(&app_path) {
         Ok(state) => print_state_compact(&state, "   "),
         Err(e) => println!("   ❌ Error: {e}"),
     }
 
     // Cache reader (standalone, without API cache enrichment)
     println!("\n💾 Cache Reader (standalone):");
-    match brainfm_presence::cache_reader::read_state(
-        &dirs::home_dir()
-            .expect("home directory not found")
-            .join("Library/Application Support/Brain.fm"),
-        None,
-    ) {
+    match brainfm_presence::cache_reader::read_state(&app_path, None, &lsof) {
         Ok(state) => print_state_compact(&state, "   "),
         Err(e) => println!("   ❌ Error: {e}"),
     }
 
     // Direct API client
     println!("\n🔑 Direct API Client:");
-    let app_path = dirs::home_dir()
-        .expect("home directory not found")
-        .join("Library/Application Support/Brain.fm");
-    match brainfm_presence::api_client::fetch_recent_tracks(&app_path) {
+    let max_retry_after = Duration::from_secs(config.max_retry_after_secs);
+    let api_version = reader.detected_api_version();
+    match brainfm_presence::api_client::fetch_recent_tracks(&app_path, api_version, max_retry_after)
+    {
         Ok(Some(data)) => {
             println!("   ✅ Fetched {} tracks from live API", data.len());
         }
@@ -126,6 +139,20 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+/// Create a reader for the data directory chosen on the command line
+fn create_reader(
+    install_type: BrainFmInstallType,
+    data_dir: Option<PathBuf>,
+    lsof: &LsofCommand,
+) -> Result<BrainFmReader> {
+    let mut reader = match data_dir {
+        Some(data_dir) => BrainFmReader::with_data_dir(data_dir)?,
+        None => BrainFmReader::with_install_type(install_type)?,
+    };
+    reader.set_lsof_command(lsof.clone());
+    Ok(reader)
+}
+
 fn print_state(state: &BrainFmState) {
     println!("┌─────────────────────────────────────┐");
     println!("│ 🧠 Brain.fm Current State           │");
@@ -150,7 +177,7 @@ fn print_state(state: &BrainFmState) {
         println!("│ Session:       {session_state:20} │");
     }
 
-    if let Some(ref time) = state.session_time {
+    if let Some(time) = state.session_time_display() {
         println!("│ Time:          {time:20} │");
     }
 
@@ -194,7 +221,7 @@ fn print_state_compact(state: &BrainFmState, prefix: &str) {
     if state.is_playing {
         fields.push("Playing: Yes".to_string());
     }
-    if let Some(ref time) = state.session_time {
+    if let Some(time) = state.session_time_display() {
         fields.push(format!("Time: {time}"));
     }
     if state.adhd_mode {
@@ -1,7 +1,8 @@
 //! MediaRemote reader for Brain.fm
 //!
-//! Uses macOS MediaRemote framework (via `mediaremote-rs`) to detect
-//! whether Brain.fm is currently playing audio. This provides a reliable
+//! Uses the macOS MediaRemote framework (via `mediaremote-rs`), or the
+//! Windows System Media Transport Controls, to detect whether Brain.fm is
+//! currently playing audio. This provides a reliable
 //! `is_playing` signal that doesn't depend on `lsof` cache file detection,
 //! which can fail during long playback sessions.
 //!
@@ -12,13 +13,20 @@
 //! The `mediaremote-rs` crate handles macOS 15.4+ restrictions automatically
 //! via a dual-process Perl adapter architecture.
 //!
+//! On Windows, Electron reports the same metadata to SMTC (the media
+//! overlay), read through `GlobalSystemMediaTransportControlsSessionManager`.
+//!
 //! # Bundle ID
 //!
-//! Brain.fm's Electron app registers as `com.electron.brain.fm`.
+//! Brain.fm's Electron app registers as `com.electron.brain.fm`. Windows
+//! identifies sessions by `AppUserModelID` instead, which differs between the
+//! desktop app and a PWA, so any ID containing "brain" matches there.
 
+#[cfg(any(target_os = "macos", target_os = "windows"))]
 use log::debug;
 
 /// Brain.fm's macOS bundle identifier
+#[cfg(target_os = "macos")]
 const BRAINFM_BUNDLE_ID: &str = "com.electron.brain.fm";
 
 /// Simplified state from MediaRemote, filtered for Brain.fm
@@ -27,7 +35,7 @@ pub struct MediaRemoteState {
     /// Whether Brain.fm is actively playing audio
     pub is_playing: bool,
 
-    /// Track title as reported by Brain.fm to macOS (e.g., "Nocturne")
+    /// Track title as reported by Brain.fm to the OS (e.g., "Nocturne")
     pub track_name: Option<String>,
 
     /// Elapsed playback time in seconds
@@ -42,6 +50,7 @@ pub struct MediaRemoteState {
 /// Returns `Some(state)` if Brain.fm is the current Now Playing app,
 /// `None` if MediaRemote is inaccessible or another app is playing.
 #[cfg(target_os = "macos")]
+#[must_use]
 pub fn read_state() -> Option<MediaRemoteState> {
     let info = mediaremote_rs::get_now_playing()?;
 
@@ -73,8 +82,100 @@ pub fn read_state() -> Option<MediaRemoteState> {
     })
 }
 
-/// Stub for non-macOS platforms — always returns None.
-#[cfg(not(target_os = "macos"))]
+/// Read Brain.fm playback state from the Windows System Media Transport
+/// Controls.
+///
+/// Returns `Some(state)` if a Brain.fm media session exists, `None` if SMTC
+/// is inaccessible or Brain.fm has no session.
+#[cfg(target_os = "windows")]
+#[must_use]
+pub fn read_state() -> Option<MediaRemoteState> {
+    use windows::Media::Control::{
+        GlobalSystemMediaTransportControlsSessionManager as SessionManager,
+        GlobalSystemMediaTransportControlsSessionPlaybackStatus as PlaybackStatus,
+    };
+
+    let manager = SessionManager::RequestAsync().ok()?.get().ok()?;
+    let session = manager.GetSessions().ok()?.into_iter().find(|session| {
+        session
+            .SourceAppUserModelId()
+            .is_ok_and(|id| is_brainfm_app_id(&id.to_string()))
+    });
+    let Some(session) = session else {
+        debug!("SMTC: no Brain.fm media session");
+        return None;
+    };
+
+    let is_playing = session
+        .GetPlaybackInfo()
+        .and_then(|info| info.PlaybackStatus())
+        .is_ok_and(|status| status == PlaybackStatus::Playing);
+    let track_name = session
+        .TryGetMediaPropertiesAsync()
+        .and_then(|properties| properties.get())
+        .and_then(|properties| properties.Title())
+        .ok()
+        .map(|title| title.to_string())
+        .filter(|title| !title.is_empty());
+
+    let secs = |span: windows::Foundation::TimeSpan| ticks_to_secs(span.Duration);
+    let timeline = session.GetTimelineProperties().ok();
+    let elapsed_secs = timeline
+        .as_ref()
+        .and_then(|timeline| timeline.Position().ok())
+        .map(secs);
+    let duration_secs = timeline
+        .as_ref()
+        .and_then(|timeline| timeline.EndTime().ok())
+        .map(secs)
+        .filter(|&secs| secs > 0.0);
+
+    debug!("SMTC: Brain.fm playing={is_playing}, track={track_name:?}");
+
+    Some(MediaRemoteState {
+        is_playing,
+        track_name,
+        elapsed_secs,
+        duration_secs,
+    })
+}
+
+/// Whether an SMTC `AppUserModelID` belongs to Brain.fm, the desktop app
+/// or a PWA
+#[cfg(any(target_os = "windows", test))]
+fn is_brainfm_app_id(app_id: &str) -> bool {
+    app_id.to_lowercase().contains("brain")
+}
+
+/// Seconds in a `TimeSpan` of 100 ns ticks
+#[cfg(any(target_os = "windows", test))]
+#[allow(clippy::cast_precision_loss)] // playback positions are far below 2^52 ticks
+fn ticks_to_secs(ticks: i64) -> f64 {
+    ticks as f64 / 10_000_000.0
+}
+
+/// Stub for platforms without a now-playing API (neither macOS nor
+/// Windows) — always returns None.
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+#[must_use]
 pub fn read_state() -> Option<MediaRemoteState> {
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_brainfm_app_ids() {
+        assert!(is_brainfm_app_id("com.electron.brain.fm"));
+        assert!(is_brainfm_app_id("Brain.fm.Desktop_abc123!App"));
+        assert!(!is_brainfm_app_id("Spotify.exe"));
+    }
+
+    #[test]
+    fn test_ticks_to_secs() {
+        assert!((ticks_to_secs(15_000_000) - 1.5).abs() < f64::EPSILON);
+        assert!(ticks_to_secs(0).abs() < f64::EPSILON);
+    }
+}
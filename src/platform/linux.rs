@@ -0,0 +1,174 @@
+//! Linux platform implementation
+//!
+//! Brain.fm data is looked for in this order:
+//!
+//! 1. The Electron desktop app: `~/.config/Brain.fm`
+//! 2. The PWA installed from Chrome: the first profile under
+//!    `~/.config/google-chrome` whose Local Storage mentions Brain.fm
+//!    (`Default` first)
+//!
+//! Whether Brain.fm is running is read straight from `/proc`, so no
+//! `pgrep` (procps) is needed, e.g. in containers.
+
+use super::{find_pwa_profile, find_user_data_dirs, Platform};
+use anyhow::Result;
+use log::debug;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Parent of every account's home directory
+const USERS_DIR: &str = "/home";
+
+/// Lowercase names the Brain.fm executable may go by (`brain.fm`,
+/// `Brain.fm-1.2.3.AppImage`, `BrainFM`)
+const PROCESS_NAMES: &[&str] = &["brain.fm", "brainfm"];
+
+/// Prefix of this crate's own executables (`brainfm-presence`,
+/// `brainfm-debug`), which must not count as Brain.fm
+const OWN_EXECUTABLE_PREFIX: &str = "brainfm-";
+
+/// Linux platform implementation
+pub struct LinuxPlatform;
+
+impl Platform for LinuxPlatform {
+    fn get_brainfm_data_dir() -> Result<PathBuf> {
+        if let Some(path) = dirs::config_dir()
+            .map(|dir| dir.join("Brain.fm"))
+            .filter(|path| path.exists())
+        {
+            return Ok(path);
+        }
+
+        if let Some(path) = get_brainfm_pwa_data_dir() {
+            return Ok(path);
+        }
+
+        anyhow::bail!(
+            "Brain.fm data directory not found on Linux. \
+             Looked for the desktop app in ~/.config/Brain.fm \
+             and for the web app in Chrome profiles."
+        )
+    }
+
+    fn get_brainfm_pwa_data_dir() -> Option<PathBuf> {
+        get_brainfm_pwa_data_dir()
+    }
+
+    fn get_all_user_data_dirs() -> Vec<(String, PathBuf)> {
+        find_user_data_dirs(Path::new(USERS_DIR), |home| {
+            Some(home.join(".config").join("Brain.fm")).filter(|path| path.is_dir())
+        })
+    }
+
+    fn is_brainfm_running() -> bool {
+        is_brainfm_running()
+    }
+
+    fn name() -> &'static str {
+        "Linux"
+    }
+}
+
+/// Find the Chrome profile directory holding Brain.fm PWA data
+#[must_use]
+pub fn get_brainfm_pwa_data_dir() -> Option<PathBuf> {
+    find_pwa_profile(&dirs::config_dir()?.join("google-chrome"))
+}
+
+/// Whether a Brain.fm process is running, found by scanning
+/// `/proc/<pid>/cmdline` (see `brainfm_process_in`)
+#[must_use]
+pub fn is_brainfm_running() -> bool {
+    brainfm_process_in(Path::new("/proc"))
+}
+
+/// Whether any process in a `/proc`-style directory at `proc_root` runs
+/// Brain.fm: the file name of its first argument contains "brain.fm" or
+/// "brainfm", case-insensitively, and isn't one of this crate's own
+/// executables.
+///
+/// Stops at the first match. Processes whose `cmdline` can't be read
+/// (another user's under `hidepid`, or gone mid-scan) are skipped.
+#[must_use]
+pub fn brainfm_process_in(proc_root: &Path) -> bool {
+    let Ok(entries) = std::fs::read_dir(proc_root) else {
+        return false;
+    };
+    let own_pid = std::process::id().to_string();
+    entries.filter_map(Result::ok).any(|entry| {
+        let name = entry.file_name();
+        let Some(pid) = name.to_str() else {
+            return false;
+        };
+        if pid == own_pid || !pid.bytes().all(|b| b.is_ascii_digit()) {
+            return false;
+        }
+        match std::fs::read(entry.path().join("cmdline")) {
+            Ok(cmdline) => is_brainfm_cmdline(&cmdline),
+            Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+                debug!("Skipping process {pid}: {e}");
+                false
+            }
+            Err(_) => false,
+        }
+    })
+}
+
+/// Whether a NUL-separated `cmdline` starts with a Brain.fm executable
+fn is_brainfm_cmdline(cmdline: &[u8]) -> bool {
+    let program = cmdline.split(|&b| b == 0).next().unwrap_or_default();
+    let program = String::from_utf8_lossy(program);
+    let Some(name) = Path::new(&*program).file_name() else {
+        return false;
+    };
+    let name = name.to_string_lossy().to_lowercase();
+    !name.starts_with(OWN_EXECUTABLE_PREFIX)
+        && PROCESS_NAMES.iter().any(|process| name.contains(process))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `/proc`-style directory with a process per `(pid, cmdline)`; a
+    /// `None` cmdline leaves the file out
+    fn mock_proc(processes: &[(&str, Option<&[u8]>)]) -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        for (pid, cmdline) in processes {
+            let dir = root.path().join(pid);
+            std::fs::create_dir_all(&dir).unwrap();
+            if let Some(cmdline) = cmdline {
+                std::fs::write(dir.join("cmdline"), cmdline).unwrap();
+            }
+        }
+        root
+    }
+
+    #[test]
+    fn test_brainfm_process_in_mock_proc() {
+        let others: &[(&str, Option<&[u8]>)] = &[
+            ("1", Some(b"/sbin/init\0splash\0")),
+            ("self", Some(b"/opt/Brain.fm/brain.fm\0")),
+            ("17", None),
+            ("23", Some(b"/usr/bin/brainfm-presence\0--once\0")),
+            ("31", Some(b"vim\0notes/brain.fm.md\0")),
+            ("40", Some(b"")),
+        ];
+        assert!(!brainfm_process_in(mock_proc(others).path()));
+
+        for (name, cmdline) in [
+            (
+                "electron",
+                &b"/opt/Brain.fm/brain.fm\0--type=renderer\0"[..],
+            ),
+            ("appimage", b"/home/ada/Apps/Brain.fm-0.9.2.AppImage\0"),
+            ("upper", b"BrainFM\0"),
+        ] {
+            let mut processes = others.to_vec();
+            processes.push(("4242", Some(cmdline)));
+            assert!(brainfm_process_in(mock_proc(&processes).path()), "{name}");
+        }
+
+        assert!(!brainfm_process_in(Path::new("/nonexistent/proc")));
+    }
+}
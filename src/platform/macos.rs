@@ -1,45 +1,516 @@
 //! macOS platform implementation
 //!
 //! Provides macOS-specific functionality for Brain.fm presence detection.
+//!
+//! The desktop app comes as a direct download (Electron) or from the App
+//! Store, which is sandboxed and keeps its data inside its container. On
+//! Apple Silicon the iOS app also runs natively, with its own container;
+//! see `BrainFmInstallVariant`.
+//!
+//! `set_focus_mode` runs a `BrainFM-<mode>` Shortcut, which the user sets up
+//! to turn on a Focus mode; `FocusModeSwitcher` runs `BrainFM-Deep-Work`
+//! whenever Deep Work starts playing.
 
-use super::Platform;
-use crate::util;
+use super::{find_pwa_profile, find_user_data_dirs, BrainFmInstallType, Platform};
+use crate::{util, BrainFmMode, BrainFmState, FOCUS_SHORTCUT_PREFIX};
 use anyhow::{Context, Result};
-use std::path::PathBuf;
-use std::process::Command;
+use log::{debug, info, warn};
+use objc2_app_kit::NSWorkspace;
+use objc2_foundation::{NSString, NSURL};
+use std::path::{Path, PathBuf};
+
+/// Process names of the direct-download (`Brain.fm`) and App Store
+/// (`BrainFM`) apps
+const PROCESS_NAMES: &[&str] = &["Brain.fm", "BrainFM"];
+
+/// Parent of every account's home directory
+const USERS_DIR: &str = "/Users";
+
+/// Bundle identifier of the App Store app, which names its sandbox container
+const APP_STORE_BUNDLE_ID: &str = "fm.brain.BrainFM";
+
+/// Bundle identifier of the iOS app, which names its container when it runs
+/// on Apple Silicon
+const IOS_BUNDLE_ID: &str = "com.brainfm.app";
+
+/// Which build of the Brain.fm desktop app has data on this Mac
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BrainFmInstallVariant {
+    /// Direct download, data in `~/Library/Application Support/Brain.fm`
+    DirectDownload(PathBuf),
+
+    /// App Store, data in the sandbox container
+    /// (`~/Library/Containers/fm.brain.BrainFM/Data/Library/Application Support/Brain.fm`)
+    AppStore(PathBuf),
+
+    /// The iOS app running natively on Apple Silicon, data in its container
+    /// (`~/Library/Containers/com.brainfm.app/Data/Library/Caches`)
+    IosMacNative(PathBuf),
+
+    /// More than one is present; `preferred` is the one written most
+    /// recently and `other` the runner-up (on a tie the direct download,
+    /// then the App Store app)
+    BothFound { preferred: PathBuf, other: PathBuf },
+
+    /// None is present
+    NotFound,
+}
+
+impl BrainFmInstallVariant {
+    /// The data directory to read, if any
+    #[must_use]
+    pub fn data_dir(&self) -> Option<&Path> {
+        match self {
+            Self::DirectDownload(path) | Self::AppStore(path) | Self::IosMacNative(path) => {
+                Some(path)
+            }
+            Self::BothFound { preferred, .. } => Some(preferred),
+            Self::NotFound => None,
+        }
+    }
+}
 
 /// macOS platform implementation
 pub struct MacOSPlatform;
 
+impl MacOSPlatform {
+    /// Detect whether the direct-download, App Store or iOS app (or more
+    /// than one) has data for the current user
+    #[must_use]
+    pub fn detect_install_variant() -> BrainFmInstallVariant {
+        dirs::home_dir().map_or(BrainFmInstallVariant::NotFound, |home| {
+            detect_install_variant(&home)
+        })
+    }
+
+    /// Every app variant with data for the current user, most recently
+    /// written first, for callers that want to choose one themselves
+    #[must_use]
+    pub fn detect_install_variants() -> Vec<BrainFmInstallVariant> {
+        dirs::home_dir().map_or_else(Vec::new, |home| detect_install_variants(&home))
+    }
+}
+
 impl Platform for MacOSPlatform {
     fn get_brainfm_data_dir() -> Result<PathBuf> {
         let home = dirs::home_dir().context("Could not find home directory")?;
-        let path = home
-            .join("Library")
-            .join("Application Support")
-            .join("Brain.fm");
-
-        if !path.exists() {
-            anyhow::bail!(
-                "Brain.fm app support directory not found at {:?}. \
+
+        match detect_install(&home) {
+            Some((install_type, path)) => {
+                debug!(
+                    "Detected Brain.fm install: {install_type:?} at {}",
+                    path.display()
+                );
+                Ok(path)
+            }
+            None => anyhow::bail!(
+                "Brain.fm app support directory not found at {}, {} or {}. \
                  Make sure Brain.fm is installed and has been run at least once.",
-                path
-            );
+                electron_data_dir(&home).display(),
+                app_store_data_dir(&home).display(),
+                ios_data_dir(&home).display()
+            ),
         }
+    }
+
+    fn get_brainfm_pwa_data_dir() -> Option<PathBuf> {
+        get_brainfm_pwa_data_dir()
+    }
 
-        Ok(path)
+    fn get_all_user_data_dirs() -> Vec<(String, PathBuf)> {
+        find_user_data_dirs(Path::new(USERS_DIR), |home| {
+            detect_install(home).map(|(_, path)| path)
+        })
     }
 
     fn is_brainfm_running() -> bool {
-        util::run_command_with_timeout(
-            Command::new("pgrep").args(["-x", "Brain.fm"]),
-            util::DEFAULT_COMMAND_TIMEOUT,
-        )
-        .map(|output| output.status.success())
-        .unwrap_or(false)
+        PROCESS_NAMES
+            .iter()
+            .any(|name| util::find_process_pids(name).is_ok_and(|pids| !pids.is_empty()))
     }
 
     fn name() -> &'static str {
         "macOS"
     }
 }
+
+/// Find the Chrome profile directory holding Brain.fm PWA data.
+///
+/// The profile has the same `Local Storage/leveldb` and `Cache/Cache_Data`
+/// layout as the Electron app's directory, so the readers work unchanged.
+#[must_use]
+pub fn get_brainfm_pwa_data_dir() -> Option<PathBuf> {
+    find_pwa_profile(&chrome_user_data_dir(&dirs::home_dir()?))
+}
+
+/// The data directory of the iOS app running natively on Apple Silicon
+/// (`~/Library/Containers/com.brainfm.app/Data/Library/Caches`), if present
+#[must_use]
+pub fn get_brainfm_ios_data_dir() -> Option<PathBuf> {
+    Some(ios_data_dir(&dirs::home_dir()?)).filter(|dir| dir.is_dir())
+}
+
+/// Which kind of Brain.fm install is present for the current user
+#[must_use]
+pub fn detect_install_type() -> BrainFmInstallType {
+    dirs::home_dir()
+        .and_then(|home| detect_install(&home))
+        .map_or(BrainFmInstallType::Unknown, |(install_type, _)| {
+            install_type
+        })
+}
+
+/// Detect the install under `home`, preferring the desktop app (either
+/// variant) over the PWA.
+fn detect_install(home: &Path) -> Option<(BrainFmInstallType, PathBuf)> {
+    let variant = detect_install_variant(home);
+    if let Some(path) = variant.data_dir() {
+        debug!("Desktop app variant: {variant:?}");
+        return Some((BrainFmInstallType::Electron, path.to_path_buf()));
+    }
+
+    find_pwa_profile(&chrome_user_data_dir(home))
+        .map(|profile| (BrainFmInstallType::ChromePWA, profile))
+}
+
+/// The Electron app's data directory: `~/Library/Application Support/Brain.fm`
+fn electron_data_dir(home: &Path) -> PathBuf {
+    home.join("Library")
+        .join("Application Support")
+        .join("Brain.fm")
+}
+
+/// The App Store app's data directory, inside its sandbox container
+fn app_store_data_dir(home: &Path) -> PathBuf {
+    home.join("Library")
+        .join("Containers")
+        .join(APP_STORE_BUNDLE_ID)
+        .join("Data")
+        .join("Library")
+        .join("Application Support")
+        .join("Brain.fm")
+}
+
+/// The iOS app's data directory, inside its container
+fn ios_data_dir(home: &Path) -> PathBuf {
+    home.join("Library")
+        .join("Containers")
+        .join(IOS_BUNDLE_ID)
+        .join("Data")
+        .join("Library")
+        .join("Caches")
+}
+
+/// Check which app variants have a data directory under `home`, picking
+/// the most recently written one when there are several
+fn detect_install_variant(home: &Path) -> BrainFmInstallVariant {
+    let mut variants = detect_install_variants(home).into_iter();
+    match (variants.next(), variants.next()) {
+        (Some(preferred), Some(other)) => BrainFmInstallVariant::BothFound {
+            preferred: preferred
+                .data_dir()
+                .map(Path::to_path_buf)
+                .unwrap_or_default(),
+            other: other.data_dir().map(Path::to_path_buf).unwrap_or_default(),
+        },
+        (Some(only), None) => only,
+        _ => BrainFmInstallVariant::NotFound,
+    }
+}
+
+/// Each app variant with a data directory under `home`, most recently
+/// written first; ties keep the order direct download, App Store, iOS
+fn detect_install_variants(home: &Path) -> Vec<BrainFmInstallVariant> {
+    let mut variants: Vec<(Option<std::time::SystemTime>, BrainFmInstallVariant)> = [
+        BrainFmInstallVariant::DirectDownload(electron_data_dir(home)),
+        BrainFmInstallVariant::AppStore(app_store_data_dir(home)),
+        BrainFmInstallVariant::IosMacNative(ios_data_dir(home)),
+    ]
+    .into_iter()
+    .filter(|variant| variant.data_dir().is_some_and(Path::is_dir))
+    .map(|variant| (last_written(&variant), variant))
+    .collect();
+    variants.sort_by(|(a, _), (b, _)| b.cmp(a));
+    variants.into_iter().map(|(_, variant)| variant).collect()
+}
+
+/// When a variant's data was last written, if known: the desktop apps'
+/// Local Storage, or the iOS app's cache directory
+fn last_written(variant: &BrainFmInstallVariant) -> Option<std::time::SystemTime> {
+    let path = match variant {
+        BrainFmInstallVariant::DirectDownload(data_dir)
+        | BrainFmInstallVariant::AppStore(data_dir) => {
+            data_dir.join("Local Storage").join("leveldb")
+        }
+        BrainFmInstallVariant::IosMacNative(data_dir) => data_dir.clone(),
+        _ => return None,
+    };
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Chrome's user data directory, with one subdirectory per profile
+fn chrome_user_data_dir(home: &Path) -> PathBuf {
+    home.join("Library")
+        .join("Application Support")
+        .join("Google")
+        .join("Chrome")
+}
+
+/// Run the `BrainFM-<mode>` Shortcut (e.g. `set_focus_mode("Deep-Work")`)
+/// through its `shortcuts://run-shortcut` link
+pub fn set_focus_mode(mode: &str) -> Result<()> {
+    set_focus_mode_with(mode, open_url)
+}
+
+/// `set_focus_mode`, opening the link with `open_url`
+fn set_focus_mode_with(mode: &str, open_url: impl FnOnce(&str) -> Result<()>) -> Result<()> {
+    open_url(&focus_shortcut_url(mode))
+}
+
+/// `shortcuts://run-shortcut?name=BrainFM-<mode>`
+fn focus_shortcut_url(mode: &str) -> String {
+    format!(
+        "shortcuts://run-shortcut?name={}",
+        util::percent_encode(&format!("{FOCUS_SHORTCUT_PREFIX}{mode}"))
+    )
+}
+
+/// Open `url` with its default handler via `NSWorkspace`
+fn open_url(url: &str) -> Result<()> {
+    let ns_url = NSURL::URLWithString(&NSString::from_str(url))
+        .with_context(|| format!("Invalid URL {url}"))?;
+    anyhow::ensure!(
+        NSWorkspace::sharedWorkspace().openURL(&ns_url),
+        "Failed to open {url}"
+    );
+    Ok(())
+}
+
+/// Runs the `BrainFM-Deep-Work` Shortcut each time Deep Work (as the mode
+/// or the activity) starts playing, when `Config::enable_macos_focus_mode`
+/// is on
+#[derive(Debug, Default)]
+pub struct FocusModeSwitcher {
+    /// Whether the last state was playing Deep Work
+    in_deep_work: bool,
+}
+
+impl FocusModeSwitcher {
+    /// Switch the Focus mode if `state` just entered Deep Work
+    pub fn update(&mut self, state: &BrainFmState) {
+        self.update_with(state, open_url);
+    }
+
+    /// `update`, opening the Shortcut link with `open_url`
+    fn update_with(&mut self, state: &BrainFmState, open_url: impl FnOnce(&str) -> Result<()>) {
+        let in_deep_work = state.is_playing
+            && [&state.mode, &state.activity]
+                .into_iter()
+                .flatten()
+                .any(|mode| BrainFmMode::parse(mode) == BrainFmMode::DeepWork);
+        if in_deep_work && !self.in_deep_work {
+            match set_focus_mode_with("Deep-Work", open_url) {
+                Ok(()) => info!("🎯 Ran the {FOCUS_SHORTCUT_PREFIX}Deep-Work Shortcut"),
+                Err(e) => warn!("Failed to switch the Focus mode: {e:#}"),
+            }
+        }
+        self.in_deep_work = in_deep_work;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create a Chrome profile whose Local Storage contains `content`
+    fn chrome_profile(home: &Path, profile: &str, content: &str) -> PathBuf {
+        let profile = chrome_user_data_dir(home).join(profile);
+        let leveldb = profile.join("Local Storage").join("leveldb");
+        std::fs::create_dir_all(&leveldb).unwrap();
+        std::fs::write(leveldb.join("000003.log"), content).unwrap();
+        profile
+    }
+
+    #[test]
+    fn test_detect_electron_preferred_over_pwa() {
+        let temp = tempfile::tempdir().unwrap();
+        let home = temp.path();
+        std::fs::create_dir_all(electron_data_dir(home)).unwrap();
+        chrome_profile(home, "Default", "_https://my.brain.fm\x00persist:auth");
+
+        let detected = detect_install(home);
+
+        assert_eq!(
+            detected,
+            Some((BrainFmInstallType::Electron, electron_data_dir(home)))
+        );
+    }
+
+    #[test]
+    fn test_detect_pwa_in_named_profile() {
+        let temp = tempfile::tempdir().unwrap();
+        let home = temp.path();
+        chrome_profile(home, "Default", "_https://www.example.com\x00theme");
+        let profile = chrome_profile(home, "Profile 2", "_https://my.brain.fm\x00persist:auth");
+
+        let detected = detect_install(home);
+
+        assert_eq!(detected, Some((BrainFmInstallType::ChromePWA, profile)));
+    }
+
+    /// Create a desktop app data directory whose Local Storage was last
+    /// written `age_secs` ago
+    fn app_data(data_dir: &Path, age_secs: u64) {
+        let leveldb = data_dir.join("Local Storage").join("leveldb");
+        std::fs::create_dir_all(&leveldb).unwrap();
+        let written = std::time::SystemTime::now() - std::time::Duration::from_secs(age_secs);
+        std::fs::File::open(&leveldb)
+            .unwrap()
+            .set_modified(written)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_detect_install_variant() {
+        let temp = tempfile::tempdir().unwrap();
+        let home = temp.path();
+        let direct = electron_data_dir(home);
+        let app_store = app_store_data_dir(home);
+
+        let none = detect_install_variant(home);
+        app_data(&app_store, 60);
+        let app_store_only = detect_install_variant(home);
+        let detected = detect_install(home);
+        app_data(&direct, 3600);
+        let both_app_store_newer = detect_install_variant(home);
+        app_data(&direct, 0);
+        let both_direct_newer = detect_install_variant(home);
+        std::fs::remove_dir_all(&app_store).unwrap();
+        let direct_only = detect_install_variant(home);
+
+        assert_eq!(none, BrainFmInstallVariant::NotFound);
+        assert_eq!(
+            app_store_only,
+            BrainFmInstallVariant::AppStore(app_store.clone())
+        );
+        assert_eq!(
+            detected,
+            Some((BrainFmInstallType::Electron, app_store.clone()))
+        );
+        assert_eq!(
+            both_app_store_newer,
+            BrainFmInstallVariant::BothFound {
+                preferred: app_store.clone(),
+                other: direct.clone(),
+            }
+        );
+        assert_eq!(both_direct_newer.data_dir(), Some(direct.as_path()));
+        assert_eq!(direct_only, BrainFmInstallVariant::DirectDownload(direct));
+    }
+
+    /// Create an iOS app container whose cache directory was last written
+    /// `age_secs` ago
+    fn ios_container(home: &Path, age_secs: u64) -> PathBuf {
+        let caches = ios_data_dir(home);
+        std::fs::create_dir_all(caches.join("com.brainfm.app")).unwrap();
+        std::fs::write(caches.join("com.brainfm.app").join("Cache.db"), "").unwrap();
+        let written = std::time::SystemTime::now() - std::time::Duration::from_secs(age_secs);
+        std::fs::File::open(&caches)
+            .unwrap()
+            .set_modified(written)
+            .unwrap();
+        caches
+    }
+
+    #[test]
+    fn test_detect_ios_mac_native() {
+        let temp = tempfile::tempdir().unwrap();
+        let home = temp.path();
+        let ios = ios_container(home, 60);
+        let ios_only = detect_install_variant(home);
+        let detected = detect_install(home);
+
+        let direct = electron_data_dir(home);
+        app_data(&direct, 3600);
+        let ios_newer = detect_install_variant(home);
+        app_data(&direct, 0);
+        let variants = detect_install_variants(home);
+
+        assert_eq!(ios_only, BrainFmInstallVariant::IosMacNative(ios.clone()));
+        assert_eq!(detected, Some((BrainFmInstallType::Electron, ios.clone())));
+        assert_eq!(
+            ios_newer,
+            BrainFmInstallVariant::BothFound {
+                preferred: ios.clone(),
+                other: direct.clone(),
+            }
+        );
+        assert_eq!(
+            variants,
+            [
+                BrainFmInstallVariant::DirectDownload(direct),
+                BrainFmInstallVariant::IosMacNative(ios),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_detect_nothing_installed() {
+        let temp = tempfile::tempdir().unwrap();
+        let home = temp.path();
+        chrome_profile(home, "Default", "_https://www.example.com\x00theme");
+
+        let detected = detect_install(home);
+
+        assert_eq!(detected, None);
+    }
+
+    #[test]
+    fn test_set_focus_mode_opens_shortcut_url() {
+        let mut opened = None;
+        set_focus_mode_with("Deep-Work", |url| {
+            opened = Some(url.to_string());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(
+            opened.as_deref(),
+            Some("shortcuts://run-shortcut?name=BrainFM-Deep-Work")
+        );
+        assert_eq!(
+            focus_shortcut_url("Light Work"),
+            "shortcuts://run-shortcut?name=BrainFM-Light%20Work"
+        );
+    }
+
+    #[test]
+    fn test_focus_mode_switcher_runs_on_entering_deep_work() {
+        let deep_work = BrainFmState {
+            mode: Some("Focus".into()),
+            activity: Some("Deep Work".into()),
+            is_playing: true,
+            ..Default::default()
+        };
+        let paused = BrainFmState {
+            is_playing: false,
+            ..deep_work.clone()
+        };
+        let mut switcher = FocusModeSwitcher::default();
+        let mut opened = Vec::new();
+        for state in [&deep_work, &deep_work, &paused, &deep_work] {
+            switcher.update_with(state, |url| {
+                opened.push(url.to_string());
+                Ok(())
+            });
+        }
+        assert_eq!(
+            opened,
+            [
+                "shortcuts://run-shortcut?name=BrainFM-Deep-Work",
+                "shortcuts://run-shortcut?name=BrainFM-Deep-Work",
+            ]
+        );
+    }
+}
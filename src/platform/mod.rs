@@ -4,6 +4,9 @@
 //! - Finding Brain.fm data directories
 //! - Detecting if Brain.fm is running
 //! - Loading platform-appropriate icons
+//!
+//! `PathOverrideRegistry` lets users point the reader at a non-standard
+//! install; overrides win over auto-detection.
 
 #[cfg(target_os = "macos")]
 pub mod macos;
@@ -11,14 +14,52 @@ pub mod macos;
 #[cfg(target_os = "windows")]
 pub mod windows;
 
-use anyhow::Result;
-use std::path::PathBuf;
+#[cfg(target_os = "linux")]
+pub mod linux;
+
+use anyhow::{Context, Result};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Entries of the users directory that are not personal accounts
+const SHARED_HOME_NAMES: &[&str] = &["Shared", "Public", "Default", "Default User", "All Users"];
+
+/// Substring of the Brain.fm web origin, found in a browser profile's Local
+/// Storage once the PWA has been used
+const BRAINFM_WEB_ORIGIN: &str = "brain.fm";
+
+/// How Brain.fm is installed, which decides where its data lives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrainFmInstallType {
+    /// The Electron desktop app
+    Electron,
+    /// The progressive web app, whose data lives in a Chrome (or, on
+    /// Windows, Edge) profile
+    ChromePWA,
+    /// No Brain.fm data found
+    Unknown,
+}
 
 /// Platform-specific operations
 pub trait Platform {
     /// Get the Brain.fm application support directory
     fn get_brainfm_data_dir() -> Result<PathBuf>;
 
+    /// Get the Chrome profile directory holding Brain.fm PWA data, if any
+    #[must_use]
+    fn get_brainfm_pwa_data_dir() -> Option<PathBuf> {
+        None
+    }
+
+    /// Brain.fm data directories of every user account on this machine, as
+    /// `(username, path)` pairs sorted by username
+    #[must_use]
+    fn get_all_user_data_dirs() -> Vec<(String, PathBuf)> {
+        Vec::new()
+    }
+
     /// Check if Brain.fm is currently running
     fn is_brainfm_running() -> bool;
 
@@ -33,12 +74,264 @@ pub use macos::MacOSPlatform as CurrentPlatform;
 #[cfg(target_os = "windows")]
 pub use windows::WindowsPlatform as CurrentPlatform;
 
-/// Get the Brain.fm data directory for the current platform
+#[cfg(target_os = "linux")]
+pub use linux::LinuxPlatform as CurrentPlatform;
+
+/// User-chosen Brain.fm data directories, keyed by platform
+/// (`std::env::consts::OS`: `"macos"`, `"windows"`, `"linux"`, …).
+///
+/// Stored in `~/.config/brainfm-presence/paths.toml`:
+///
+/// ```toml
+/// [overrides]
+/// macos = "/Volumes/External/Brain.fm"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PathOverrideRegistry {
+    /// Data directory override per platform
+    #[serde(default)]
+    pub overrides: HashMap<String, PathBuf>,
+}
+
+impl PathOverrideRegistry {
+    /// Default registry location: `~/.config/brainfm-presence/paths.toml`
+    #[must_use]
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::home_dir().map(|home| {
+            home.join(".config")
+                .join("brainfm-presence")
+                .join("paths.toml")
+        })
+    }
+
+    /// Load the registry from the default location; a missing or invalid
+    /// file yields an empty registry
+    #[must_use]
+    pub fn load() -> Self {
+        let Some(path) = Self::default_path().filter(|path| path.exists()) else {
+            return Self::default();
+        };
+        Self::from_file(&path).unwrap_or_else(|e| {
+            log::warn!("Ignoring invalid path overrides: {e:#}");
+            Self::default()
+        })
+    }
+
+    /// Load and parse a registry file from an explicit path
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read path overrides {}", path.display()))?;
+        toml::from_str(&text)
+            .with_context(|| format!("Failed to parse path overrides {}", path.display()))
+    }
+
+    /// Write the registry to `path`, creating its directory
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let text = toml::to_string(self).context("Failed to serialize path overrides")?;
+        crate::util::write_file_atomic(path, text.as_bytes())
+    }
+
+    /// The override for `platform`, if any
+    #[must_use]
+    pub fn get(&self, platform: &str) -> Option<&Path> {
+        self.overrides.get(platform).map(PathBuf::as_path)
+    }
+
+    /// Override the data directory for `platform`
+    pub fn set(&mut self, platform: &str, data_dir: PathBuf) {
+        self.overrides.insert(platform.to_string(), data_dir);
+    }
+
+    /// The data directory for the current platform: the override when one
+    /// is set, otherwise `Platform::get_brainfm_data_dir`
+    pub fn get_brainfm_data_dir(&self) -> Result<PathBuf> {
+        if let Some(data_dir) = self.get(std::env::consts::OS) {
+            debug!("Using data directory override {}", data_dir.display());
+            return Ok(data_dir.to_path_buf());
+        }
+        CurrentPlatform::get_brainfm_data_dir()
+    }
+}
+
+/// Get the Brain.fm data directory for the current platform, honouring
+/// `PathOverrideRegistry` overrides
 pub fn get_brainfm_data_dir() -> Result<PathBuf> {
-    CurrentPlatform::get_brainfm_data_dir()
+    PathOverrideRegistry::load().get_brainfm_data_dir()
+}
+
+/// Every user's Brain.fm data directory on this machine, as `(username,
+/// path)` pairs sorted by username (`/Users/*` on macOS, `C:\Users\*` on
+/// Windows, `/home/*` on Linux). Overrides are ignored.
+///
+/// Reading another account's directory usually needs admin rights.
+#[must_use]
+pub fn discover_all_data_dirs() -> Vec<(String, PathBuf)> {
+    CurrentPlatform::get_all_user_data_dirs()
+}
+
+/// The Brain.fm data directory of the account `username` (for `--user`)
+pub fn get_brainfm_data_dir_for_user(username: &str) -> Result<PathBuf> {
+    let dirs = discover_all_data_dirs();
+    let users: Vec<&str> = dirs.iter().map(|(user, _)| user.as_str()).collect();
+    let users = users.join(", ");
+    dirs.iter()
+        .find(|(user, _)| user == username)
+        .map(|(_, path)| path.clone())
+        .with_context(|| format!("No Brain.fm data found for user {username:?} (found: {users})"))
+}
+
+/// `(username, data directory)` for each home directory under `users_dir`
+/// in which `data_dir` finds Brain.fm data, sorted by username. Hidden and
+/// shared entries (`Shared`, `Public`, …) are skipped.
+pub(crate) fn find_user_data_dirs(
+    users_dir: &Path,
+    data_dir: impl Fn(&Path) -> Option<PathBuf>,
+) -> Vec<(String, PathBuf)> {
+    let Ok(entries) = std::fs::read_dir(users_dir) else {
+        return Vec::new();
+    };
+    let mut dirs: Vec<(String, PathBuf)> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let username = entry.file_name().into_string().ok()?;
+            if username.starts_with('.') || SHARED_HOME_NAMES.contains(&username.as_str()) {
+                return None;
+            }
+            let home = entry.path();
+            if !home.is_dir() {
+                return None;
+            }
+            Some((username, data_dir(&home)?))
+        })
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+/// Every data directory auto-detection finds on this machine, by install
+/// type, ignoring overrides (for `configure detect`)
+#[must_use]
+pub fn detected_data_dirs() -> Vec<(BrainFmInstallType, Option<PathBuf>)> {
+    vec![
+        (
+            BrainFmInstallType::Electron,
+            CurrentPlatform::get_brainfm_data_dir().ok(),
+        ),
+        (
+            BrainFmInstallType::ChromePWA,
+            CurrentPlatform::get_brainfm_pwa_data_dir(),
+        ),
+    ]
+}
+
+/// Get the Brain.fm data directory for a specific install type.
+///
+/// `ChromePWA` only looks in Chrome profiles (the `--pwa` flag); `Electron`
+/// and `Unknown` use `get_brainfm_data_dir`, which prefers an override and
+/// then the desktop app.
+pub fn get_brainfm_data_dir_for(install_type: BrainFmInstallType) -> Result<PathBuf> {
+    match install_type {
+        BrainFmInstallType::ChromePWA => CurrentPlatform::get_brainfm_pwa_data_dir()
+            .context("Brain.fm Chrome PWA data not found in any Chrome profile"),
+        BrainFmInstallType::Electron | BrainFmInstallType::Unknown => get_brainfm_data_dir(),
+    }
 }
 
 /// Check if Brain.fm is running on the current platform
+#[must_use]
 pub fn is_brainfm_running() -> bool {
     CurrentPlatform::is_brainfm_running()
 }
+
+/// Find the first browser profile with Brain.fm data under a Chromium
+/// `User Data` directory, checking `Default` before named profiles
+/// (`Profile 1`, `Profile 2`, …).
+///
+/// The profile has the same `Local Storage/leveldb` and `Cache/Cache_Data`
+/// layout as the Electron app's directory, so the readers work unchanged.
+pub(crate) fn find_pwa_profile(user_data_dir: &Path) -> Option<PathBuf> {
+    let mut named_profiles: Vec<PathBuf> = std::fs::read_dir(user_data_dir)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("Profile "))
+        })
+        .collect();
+    named_profiles.sort();
+
+    std::iter::once(user_data_dir.join("Default"))
+        .chain(named_profiles)
+        .find(|profile| has_brainfm_data(profile))
+}
+
+/// Whether a browser profile's Local Storage mentions the Brain.fm origin
+fn has_brainfm_data(profile: &Path) -> bool {
+    let leveldb = profile.join("Local Storage").join("leveldb");
+    leveldb.is_dir()
+        && crate::util::iter_leveldb_strings(&leveldb)
+            .filter_map(Result::ok)
+            .any(|content| content.contains(BRAINFM_WEB_ORIGIN))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_override_registry_round_trip_and_lookup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("paths.toml");
+        let data_dir = dir.path().join("Brain.fm");
+
+        let mut registry = PathOverrideRegistry::default();
+        registry.set(std::env::consts::OS, data_dir.clone());
+        registry.set("plan9", PathBuf::from("/n/brainfm"));
+        registry.save(&path).unwrap();
+        let loaded = PathOverrideRegistry::from_file(&path).unwrap();
+
+        assert_eq!(loaded, registry);
+        assert_eq!(loaded.get_brainfm_data_dir().unwrap(), data_dir);
+        assert_eq!(loaded.get("plan9"), Some(Path::new("/n/brainfm")));
+    }
+
+    #[test]
+    fn test_find_user_data_dirs_in_mock_users_dir() {
+        let temp = tempfile::tempdir().unwrap();
+        let users = temp.path().to_path_buf();
+        let data_dir = |home: &Path| home.join("Library/Application Support/Brain.fm");
+        for user in ["bob", "alice", "Shared", ".localized"] {
+            std::fs::create_dir_all(data_dir(&users.join(user))).unwrap();
+        }
+        // A user without Brain.fm
+        std::fs::create_dir_all(users.join("carol")).unwrap();
+
+        let found = find_user_data_dirs(&users, |home| Some(data_dir(home)).filter(|p| p.is_dir()));
+        temp.close().unwrap();
+
+        assert_eq!(
+            found,
+            vec![
+                ("alice".to_string(), data_dir(&users.join("alice"))),
+                ("bob".to_string(), data_dir(&users.join("bob"))),
+            ]
+        );
+        assert!(find_user_data_dirs(&users, |home| Some(home.to_path_buf())).is_empty());
+    }
+
+    #[test]
+    fn test_override_registry_parses_toml() {
+        let registry: PathOverrideRegistry =
+            toml::from_str("[overrides]\nmacos = \"/Volumes/External/Brain.fm\"\n").unwrap();
+        assert_eq!(
+            registry.get("macos"),
+            Some(Path::new("/Volumes/External/Brain.fm"))
+        );
+        assert_eq!(registry.get("windows"), None);
+        assert!(toml::from_str::<PathOverrideRegistry>("")
+            .unwrap()
+            .overrides
+            .is_empty());
+    }
+}
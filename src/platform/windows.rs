@@ -1,66 +1,873 @@
-//! Windows platform implementation (stub)
+//! Windows platform implementation
 //!
-//! This module provides stub implementations for Windows.
-//! Full Windows support requires testing with Brain.fm on Windows
-//! to determine the correct data directory paths.
+//! Provides Windows-specific functionality for Brain.fm presence detection.
+//!
+//! Brain.fm data is looked for in this order:
+//!
+//! 1. The Electron desktop app: `%APPDATA%\Brain.fm`, then
+//!    `%LOCALAPPDATA%\Brain.fm`
+//! 2. The PWA installed from Edge: the first profile under
+//!    `%LOCALAPPDATA%\Microsoft\Edge\User Data` whose Local Storage mentions
+//!    Brain.fm (`Default` first). Its cache is in `Default\Cache`.
+//! 3. The PWA installed from Chrome: the same search under
+//!    `%LOCALAPPDATA%\Google\Chrome\User Data`, whose cache is in
+//!    `Default\Cache\Cache_Data`.
+//!
+//! `--windows-pwa-path <path>` skips detection and reads the given profile
+//! directory instead.
+//!
+//! `set_focus_assist` turns Focus Assist on and off, and `FocusAssistSwitcher`
+//! keeps it on while a Focus mode plays (and turns it off when dropped).
 
-use super::Platform;
+use super::{find_pwa_profile, find_user_data_dirs, Platform};
+use crate::BrainFmState;
 use anyhow::Result;
-use std::path::PathBuf;
+#[cfg(target_os = "windows")]
+use log::debug;
+use log::{info, warn};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Parent of every account's profile directory
+const USERS_DIR: &str = r"C:\Users";
+
+/// `HKEY_CURRENT_USER` subkey holding the Focus Assist (Quiet Hours) state
+const QUIET_HOURS_STATE_KEY: &str = r"SOFTWARE\Microsoft\Windows\CurrentVersion\CloudStore\Store\Cache\DefaultAccount\$$windows.data.unifiedtile.quiethoursstate\Current";
+
+/// `REG_BINARY` value under `QUIET_HOURS_STATE_KEY` with the state
+const QUIET_HOURS_STATE_VALUE: &str = "Data";
+
+/// Focus Assist profile for "Priority only"
+const PRIORITY_ONLY_PROFILE: &str = "Microsoft.QuietHoursProfile.PriorityOnly";
+
+/// Focus Assist profile for "Off"
+const UNRESTRICTED_PROFILE: &str = "Microsoft.QuietHoursProfile.Unrestricted";
+
+/// 100 ns intervals from 1601 (the `FILETIME` epoch) to 1970
+const FILETIME_UNIX_EPOCH: u64 = 116_444_736_000_000_000;
 
-/// Windows platform implementation (stub)
+/// The Quiet Hours state found when `set_focus_assist` first turned Focus
+/// Assist on, put back when it turns it off
+static SAVED_QUIET_HOURS_STATE: Mutex<Option<Vec<u8>>> = Mutex::new(None);
+
+/// How long `is_brainfm_running` reuses its check of the browsers' command
+/// lines, which starts PowerShell
+#[cfg(target_os = "windows")]
+const PWA_CHECK_TTL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// The last `is_pwa_running` result, and when it was checked
+#[cfg(target_os = "windows")]
+static PWA_RUNNING: Mutex<Option<(bool, std::time::Instant)>> = Mutex::new(None);
+
+/// Browsers whose command lines are checked for the Brain.fm PWA
+#[cfg(target_os = "windows")]
+const PWA_BROWSER_EXES: [&str; 2] = ["msedge.exe", "chrome.exe"];
+
+/// Windows platform implementation
 pub struct WindowsPlatform;
 
 impl Platform for WindowsPlatform {
     fn get_brainfm_data_dir() -> Result<PathBuf> {
-        // Brain.fm on Windows is likely an Electron app, which typically stores
-        // data in one of these locations:
-        // - %APPDATA%\Brain.fm
-        // - %LOCALAPPDATA%\Brain.fm
-        // - %APPDATA%\brain-fm (lowercase)
-
-        // Try common locations
-        if let Some(appdata) = dirs::data_dir() {
-            let path = appdata.join("Brain.fm");
-            if path.exists() {
-                return Ok(path);
-            }
+        let electron_dirs = [dirs::data_dir(), dirs::data_local_dir()];
+        if let Some(path) = electron_dirs
+            .into_iter()
+            .flatten()
+            .map(|dir| dir.join("Brain.fm"))
+            .find(|path| path.exists())
+        {
+            return Ok(path);
         }
 
-        if let Some(local_appdata) = dirs::data_local_dir() {
-            let path = local_appdata.join("Brain.fm");
-            if path.exists() {
-                return Ok(path);
-            }
+        if let Some(path) = get_brainfm_pwa_data_dir() {
+            return Ok(path);
         }
 
         anyhow::bail!(
             "Brain.fm data directory not found on Windows. \
-             This platform is not yet fully supported. \
-             Please open an issue with your Brain.fm installation path."
+             Looked for the desktop app in %APPDATA%\\Brain.fm and %LOCALAPPDATA%\\Brain.fm, \
+             and for the web app in Edge and Chrome profiles. \
+             Use --windows-pwa-path to point at the browser profile directly."
         )
     }
 
+    fn get_brainfm_pwa_data_dir() -> Option<PathBuf> {
+        get_brainfm_pwa_data_dir()
+    }
+
+    fn get_all_user_data_dirs() -> Vec<(String, PathBuf)> {
+        find_user_data_dirs(Path::new(USERS_DIR), |profile| {
+            Some(profile.join("AppData").join("Roaming").join("Brain.fm"))
+                .filter(|path| path.is_dir())
+        })
+    }
+
     fn is_brainfm_running() -> bool {
-        // On Windows, we would use the Windows API to check for the process
-        // For now, return false as a stub
         #[cfg(target_os = "windows")]
         {
-            use std::process::Command;
-            // Use run_command_with_timeout to prevent indefinite hangs
-            if let Ok(output) = crate::util::run_command_with_timeout(
-                Command::new("tasklist").args(["/FI", "IMAGENAME eq Brain.fm.exe"]),
-                crate::util::DEFAULT_COMMAND_TIMEOUT,
-            ) {
-                let stdout = String::from_utf8_lossy(&output.stdout);
-                return stdout.contains("Brain.fm.exe");
+            if !processes_named(handles::BRAINFM_EXE).is_empty() {
+                return true;
+            }
+            if PWA_BROWSER_EXES
+                .iter()
+                .all(|exe| processes_named(exe).is_empty())
+            {
+                return false;
             }
-        }
 
-        false
+            let mut cached = PWA_RUNNING.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some((running, checked_at)) = *cached {
+                if checked_at.elapsed() < PWA_CHECK_TTL {
+                    return running;
+                }
+            }
+            let running = is_pwa_running();
+            *cached = Some((running, std::time::Instant::now()));
+            running
+        }
+        #[cfg(not(target_os = "windows"))]
+        {
+            false
+        }
     }
 
     fn name() -> &'static str {
         "Windows"
     }
 }
+
+/// Find the Edge or Chrome profile directory holding Brain.fm PWA data,
+/// checking Edge first.
+#[must_use]
+pub fn get_brainfm_pwa_data_dir() -> Option<PathBuf> {
+    find_pwa_data_dir(&dirs::data_local_dir()?)
+}
+
+/// Search the browsers' `User Data` directories under `local_appdata`
+fn find_pwa_data_dir(local_appdata: &Path) -> Option<PathBuf> {
+    browser_user_data_dirs(local_appdata)
+        .iter()
+        .find_map(|user_data| find_pwa_profile(user_data))
+}
+
+/// Edge's and Chrome's user data directories, in detection order
+fn browser_user_data_dirs(local_appdata: &Path) -> [PathBuf; 2] {
+    [
+        local_appdata
+            .join("Microsoft")
+            .join("Edge")
+            .join("User Data"),
+        local_appdata
+            .join("Google")
+            .join("Chrome")
+            .join("User Data"),
+    ]
+}
+
+/// Heuristic for the PWA: whether Edge or Chrome was started as the
+/// Brain.fm app (`msedge.exe --app=https://my.brain.fm`). Reading other
+/// processes' command lines needs WMI, so this runs PowerShell.
+#[cfg(target_os = "windows")]
+fn is_pwa_running() -> bool {
+    use std::process::Command;
+
+    let filter = PWA_BROWSER_EXES
+        .iter()
+        .map(|exe| format!("Name='{exe}'"))
+        .collect::<Vec<_>>()
+        .join(" OR ");
+    crate::util::run_command_with_timeout(
+        Command::new("powershell").args([
+            "-NoProfile",
+            "-Command",
+            &format!(
+                "Get-CimInstance Win32_Process -Filter \"{filter}\" \
+                 | Select-Object -ExpandProperty CommandLine"
+            ),
+        ]),
+        crate::util::DEFAULT_COMMAND_TIMEOUT,
+    )
+    .is_ok_and(|output| launches_brainfm_app(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Whether any line of browser command lines opens Brain.fm as an app
+/// window (`--app=https://my.brain.fm`), not just a tab mentioning it
+#[cfg(any(target_os = "windows", test))]
+fn launches_brainfm_app(command_lines: &str) -> bool {
+    command_lines.lines().any(|line| {
+        line.to_ascii_lowercase()
+            .contains("--app=https://my.brain.fm")
+    })
+}
+
+/// Turn Focus Assist on ("Priority only") or off.
+///
+/// Uses `FocusSessionManager` where Windows supports it (Windows 11), and
+/// otherwise writes the Quiet Hours state to the registry
+/// (`QUIET_HOURS_STATE_KEY`) as the Settings app does. Turning it off then
+/// puts back the state found before it was first turned on, so a user's
+/// own "Alarms only" survives.
+pub fn set_focus_assist(enabled: bool) -> Result<()> {
+    #[cfg(target_os = "windows")]
+    match focus::set_focus_session(enabled) {
+        Ok(true) => return Ok(()),
+        Ok(false) => debug!("Focus sessions not supported, writing the registry instead"),
+        Err(e) => debug!("Focus session failed, writing the registry instead: {e}"),
+    }
+    write_quiet_hours_state(
+        enabled,
+        &mut SAVED_QUIET_HOURS_STATE
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner),
+        filetime_now(),
+        read_registry_binary,
+        write_registry_binary,
+    )
+}
+
+/// Write the Quiet Hours state for `enabled`, stamped `filetime`, through
+/// `write(subkey, value_name, data)`.
+///
+/// Turning it on first keeps the state there (`read(subkey, value_name)`,
+/// "Off" if there is none) in `saved`, unless one is kept already; turning
+/// it off writes that back, restamped, or "Off" when nothing was kept.
+fn write_quiet_hours_state(
+    enabled: bool,
+    saved: &mut Option<Vec<u8>>,
+    filetime: u64,
+    read: impl FnOnce(&str, &str) -> Result<Option<Vec<u8>>>,
+    write: impl FnOnce(&str, &str, &[u8]) -> Result<()>,
+) -> Result<()> {
+    if enabled {
+        if saved.is_none() {
+            *saved = Some(
+                read(QUIET_HOURS_STATE_KEY, QUIET_HOURS_STATE_VALUE)?
+                    .unwrap_or_else(|| quiet_hours_state_data(UNRESTRICTED_PROFILE, filetime)),
+            );
+        }
+        return write(
+            QUIET_HOURS_STATE_KEY,
+            QUIET_HOURS_STATE_VALUE,
+            &quiet_hours_state_data(PRIORITY_ONLY_PROFILE, filetime),
+        );
+    }
+
+    let data = match saved.clone() {
+        Some(mut data) => {
+            // Windows ignores a state older than the one it has
+            if data.len() >= 12 && data[..4] == [0x02, 0x00, 0x00, 0x00] {
+                data[4..12].copy_from_slice(&filetime.to_le_bytes());
+            }
+            data
+        }
+        None => quiet_hours_state_data(UNRESTRICTED_PROFILE, filetime),
+    };
+    write(QUIET_HOURS_STATE_KEY, QUIET_HOURS_STATE_VALUE, &data)?;
+    *saved = None;
+    Ok(())
+}
+
+/// The `CloudStore` blob selecting `profile`: a header with the time it was
+/// written, then the profile name as length-prefixed UTF-16
+fn quiet_hours_state_data(profile: &str, filetime: u64) -> Vec<u8> {
+    let name: Vec<u8> = profile.encode_utf16().flat_map(u16::to_le_bytes).collect();
+    let mut data = vec![0x02, 0x00, 0x00, 0x00];
+    data.extend_from_slice(&filetime.to_le_bytes());
+    data.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x43, 0x42, 0x01, 0x00]);
+    data.extend_from_slice(&[0xC2, 0x0A, 0x01, 0xD2, 0x14]);
+    // Profile names are ASCII and well under 128 characters
+    data.push(u8::try_from(name.len()).unwrap_or(u8::MAX));
+    data.extend_from_slice(&name);
+    data.extend_from_slice(&[0xCA, 0x28, 0xD0, 0x14, 0x02, 0x00, 0x00]);
+    data
+}
+
+/// The current time as a `FILETIME` (100 ns intervals since 1601)
+fn filetime_now() -> u64 {
+    let since_unix = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    FILETIME_UNIX_EPOCH + u64::try_from(since_unix.as_nanos() / 100).unwrap_or(0)
+}
+
+/// The `REG_BINARY` `value_name` under `HKEY_CURRENT_USER\<subkey>`, or
+/// `None` if it doesn't exist
+fn read_registry_binary(subkey: &str, value_name: &str) -> Result<Option<Vec<u8>>> {
+    #[cfg(target_os = "windows")]
+    {
+        use anyhow::Context;
+        use windows::core::PCWSTR;
+        use windows::Win32::Foundation::ERROR_FILE_NOT_FOUND;
+        use windows::Win32::System::Registry::{
+            RegGetValueW, HKEY_CURRENT_USER, RRF_RT_REG_BINARY,
+        };
+
+        let wide = |s: &str| s.encode_utf16().chain([0]).collect::<Vec<u16>>();
+        let (subkey_w, value_name_w) = (wide(subkey), wide(value_name));
+        // SAFETY: both strings are NUL-terminated, and `data` (left out when
+        // empty, to ask for the size) is writable for `*len` bytes.
+        #[allow(unsafe_code)]
+        let get = |data: &mut [u8], len: &mut u32| unsafe {
+            RegGetValueW(
+                HKEY_CURRENT_USER,
+                PCWSTR(subkey_w.as_ptr()),
+                PCWSTR(value_name_w.as_ptr()),
+                RRF_RT_REG_BINARY,
+                None,
+                (!data.is_empty()).then(|| data.as_mut_ptr().cast()),
+                Some(len),
+            )
+        };
+
+        let mut len = 0;
+        let status = get(&mut [], &mut len);
+        if status == ERROR_FILE_NOT_FOUND {
+            return Ok(None);
+        }
+        status
+            .ok()
+            .with_context(|| format!("Failed to read HKCU\\{subkey}"))?;
+        let mut data = vec![0; usize::try_from(len)?];
+        get(&mut data, &mut len)
+            .ok()
+            .with_context(|| format!("Failed to read HKCU\\{subkey}"))?;
+        data.truncate(usize::try_from(len)?);
+        Ok(Some(data))
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        let _ = value_name;
+        anyhow::bail!("No registry to read HKCU\\{subkey} from outside Windows")
+    }
+}
+
+/// Set `value_name` under `HKEY_CURRENT_USER\<subkey>` to `REG_BINARY` `data`,
+/// creating the key if needed
+fn write_registry_binary(subkey: &str, value_name: &str, data: &[u8]) -> Result<()> {
+    #[cfg(target_os = "windows")]
+    {
+        use anyhow::Context;
+        use windows::core::PCWSTR;
+        use windows::Win32::System::Registry::{RegSetKeyValueW, HKEY_CURRENT_USER, REG_BINARY};
+
+        let wide = |s: &str| s.encode_utf16().chain([0]).collect::<Vec<u16>>();
+        let (subkey_w, value_name_w) = (wide(subkey), wide(value_name));
+        let len = u32::try_from(data.len())?;
+        // SAFETY: both strings are NUL-terminated and `data` is readable
+        // for `data.len()` bytes.
+        #[allow(unsafe_code)]
+        let status = unsafe {
+            RegSetKeyValueW(
+                HKEY_CURRENT_USER,
+                PCWSTR(subkey_w.as_ptr()),
+                PCWSTR(value_name_w.as_ptr()),
+                REG_BINARY.0,
+                Some(data.as_ptr().cast()),
+                len,
+            )
+        };
+        status
+            .ok()
+            .with_context(|| format!("Failed to write HKCU\\{subkey}"))
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        let _ = (value_name, data);
+        anyhow::bail!("No registry to write HKCU\\{subkey} to outside Windows")
+    }
+}
+
+/// `FocusSessionManager` (Windows 11's Focus sessions)
+#[cfg(target_os = "windows")]
+mod focus {
+    use windows::UI::Shell::FocusSessionManager;
+
+    /// Start or end a Focus session; `Ok(false)` if Windows doesn't support
+    /// them
+    pub fn set_focus_session(enabled: bool) -> windows::core::Result<bool> {
+        if !FocusSessionManager::IsSupported()? {
+            return Ok(false);
+        }
+        let manager = FocusSessionManager::GetDefault()?;
+        if enabled {
+            manager.TryStartFocusSession()?;
+        } else {
+            manager.DeactivateFocus()?;
+        }
+        Ok(true)
+    }
+}
+
+/// Turns Focus Assist on when a Focus-category mode starts playing and off
+/// once it stops (paused, or another kind of mode) or the switcher is
+/// dropped, when `Config::enable_windows_focus_assist` is on
+#[derive(Debug, Default)]
+pub struct FocusAssistSwitcher {
+    /// Whether Focus Assist was turned on by this switcher
+    enabled: bool,
+}
+
+impl FocusAssistSwitcher {
+    /// Turn Focus Assist on or off if `state` entered or left a Focus mode
+    pub fn update(&mut self, state: &BrainFmState) {
+        self.update_with(state, set_focus_assist);
+    }
+
+    /// `update`, switching Focus Assist with `set_focus_assist`
+    fn update_with(
+        &mut self,
+        state: &BrainFmState,
+        set_focus_assist: impl FnOnce(bool) -> Result<()>,
+    ) {
+        let in_focus = state.is_playing
+            && state
+                .parsed_mode()
+                .is_some_and(|mode| mode.is_focus_category());
+        if in_focus == self.enabled {
+            return;
+        }
+        match set_focus_assist(in_focus) {
+            Ok(()) => {
+                info!(
+                    "🔕 Focus Assist turned {}",
+                    if in_focus { "on" } else { "off" }
+                );
+                self.enabled = in_focus;
+            }
+            Err(e) => warn!("Failed to switch Focus Assist: {e:#}"),
+        }
+    }
+}
+
+impl Drop for FocusAssistSwitcher {
+    /// Focus Assist left on would keep notifications hidden after exit
+    fn drop(&mut self) {
+        self.update(&BrainFmState::new());
+    }
+}
+
+/// Handle enumeration for the Electron app's processes.
+///
+/// Stands in for `lsof -c Brain.fm`: lists the files Brain.fm has open by
+/// walking the system-wide handle table.
+#[cfg(target_os = "windows")]
+#[allow(unsafe_code)]
+mod handles {
+    use anyhow::Result;
+    use std::collections::HashMap;
+    use std::ffi::c_void;
+    use windows::Wdk::Foundation::{
+        NtQueryObject, OBJECT_INFORMATION_CLASS, OBJECT_NAME_INFORMATION,
+    };
+    use windows::Wdk::System::SystemInformation::{
+        NtQuerySystemInformation, SYSTEM_INFORMATION_CLASS,
+    };
+    use windows::Win32::Foundation::{
+        CloseHandle, DuplicateHandle, DUPLICATE_SAME_ACCESS, HANDLE, STATUS_INFO_LENGTH_MISMATCH,
+    };
+    use windows::Win32::Storage::FileSystem::{GetFileType, FILE_TYPE_DISK};
+    use windows::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W,
+        TH32CS_SNAPPROCESS,
+    };
+    use windows::Win32::System::Threading::{GetCurrentProcess, OpenProcess, PROCESS_DUP_HANDLE};
+
+    /// Executable name shared by the Electron app and its helper processes
+    pub(super) const BRAINFM_EXE: &str = "Brain.fm.exe";
+
+    /// Like `SystemHandleInformation`, but with full-width process IDs and
+    /// handle values (the classic class truncates both to 16 bits)
+    const SYSTEM_EXTENDED_HANDLE_INFORMATION: SYSTEM_INFORMATION_CLASS =
+        SYSTEM_INFORMATION_CLASS(64);
+
+    /// `ObjectNameInformation`, not exported by the `windows` crate
+    const OBJECT_NAME_INFORMATION_CLASS: OBJECT_INFORMATION_CLASS = OBJECT_INFORMATION_CLASS(1);
+
+    /// Initial size of the handle table buffer, grown on demand
+    const HANDLE_TABLE_INITIAL_BYTES: usize = 1 << 20;
+
+    /// Room for the longest NT path (32767 UTF-16 units) plus the header
+    const OBJECT_NAME_BUFFER_BYTES: usize = 64 * 1024 + 64;
+
+    /// `SYSTEM_HANDLE_TABLE_ENTRY_INFO_EX` from `ntdef.h`
+    #[repr(C)]
+    #[allow(dead_code)] // Mirrors the kernel layout; only some fields are read
+    struct HandleEntry {
+        object: *mut c_void,
+        unique_process_id: usize,
+        handle_value: usize,
+        granted_access: u32,
+        creator_back_trace_index: u16,
+        object_type_index: u16,
+        handle_attributes: u32,
+        reserved: u32,
+    }
+
+    /// `SYSTEM_HANDLE_INFORMATION_EX`; `handles` is variable-length
+    #[repr(C)]
+    #[allow(dead_code)] // Mirrors the kernel layout; only some fields are read
+    struct HandleTable {
+        number_of_handles: usize,
+        reserved: usize,
+        handles: [HandleEntry; 1],
+    }
+
+    /// Process ID of the main Brain.fm process (the one not started by
+    /// another Brain.fm process), if it is running
+    #[must_use]
+    pub fn get_brainfm_pid() -> Option<u32> {
+        let processes = brainfm_processes();
+        processes
+            .iter()
+            .find(|(_, parent)| !processes.iter().any(|(pid, _)| pid == parent))
+            .map(|(pid, _)| *pid)
+    }
+
+    /// NT paths (`\Device\HarddiskVolume3\…`) of every file any Brain.fm
+    /// process has open.
+    ///
+    /// Chromium opens the disk cache from a helper process rather than the
+    /// main one, so all processes named `Brain.fm.exe` are included.
+    pub fn brainfm_open_files() -> Result<Vec<String>> {
+        let pids: Vec<usize> = brainfm_processes()
+            .into_iter()
+            .map(|(pid, _)| pid as usize)
+            .collect();
+        if pids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let table = query_handle_table()?;
+        // SAFETY: `query_handle_table` returns a buffer filled by the kernel
+        // with a `HandleTable` header followed by `number_of_handles` entries.
+        let entries = unsafe {
+            let header = table.as_ptr().cast::<HandleTable>();
+            let count = (*header).number_of_handles;
+            let available = (table.len() * size_of::<usize>() - 2 * size_of::<usize>())
+                / size_of::<HandleEntry>();
+            let first = std::ptr::addr_of!((*header).handles).cast::<HandleEntry>();
+            std::slice::from_raw_parts(first, count.min(available))
+        };
+
+        let mut processes: HashMap<usize, Option<HANDLE>> = HashMap::new();
+        let mut name_buffer = vec![0usize; OBJECT_NAME_BUFFER_BYTES / size_of::<usize>()];
+        let mut paths = Vec::new();
+
+        for entry in entries {
+            if !pids.contains(&entry.unique_process_id) {
+                continue;
+            }
+            let process = *processes.entry(entry.unique_process_id).or_insert_with(|| {
+                let pid = u32::try_from(entry.unique_process_id).ok()?;
+                // SAFETY: plain Win32 call; failure is an `Err`.
+                unsafe { OpenProcess(PROCESS_DUP_HANDLE, false, pid) }.ok()
+            });
+            let Some(process) = process else {
+                continue;
+            };
+            if let Some(path) = file_path(process, entry.handle_value, &mut name_buffer) {
+                paths.push(path);
+            }
+        }
+
+        for process in processes.into_values().flatten() {
+            // SAFETY: each handle came from a successful `OpenProcess`.
+            let _ = unsafe { CloseHandle(process) };
+        }
+
+        Ok(paths)
+    }
+
+    /// Every `Brain.fm.exe` process as `(pid, parent pid)`
+    fn brainfm_processes() -> Vec<(u32, u32)> {
+        processes_named(BRAINFM_EXE)
+    }
+
+    /// Every process whose executable is `exe_name` (case-insensitive), as
+    /// `(pid, parent pid)`
+    #[must_use]
+    pub fn processes_named(exe_name: &str) -> Vec<(u32, u32)> {
+        let mut processes = Vec::new();
+
+        // SAFETY: the snapshot handle is valid once created and closed at the
+        // end; `entry.dwSize` is set as `Process32FirstW` requires.
+        unsafe {
+            let Ok(snapshot) = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) else {
+                return processes;
+            };
+
+            let mut entry = PROCESSENTRY32W {
+                dwSize: u32::try_from(size_of::<PROCESSENTRY32W>()).unwrap_or(u32::MAX),
+                ..Default::default()
+            };
+            let mut found = Process32FirstW(snapshot, &raw mut entry).is_ok();
+            while found {
+                let exe = &entry.szExeFile;
+                let len = exe.iter().position(|&c| c == 0).unwrap_or(exe.len());
+                if String::from_utf16_lossy(&exe[..len]).eq_ignore_ascii_case(exe_name) {
+                    processes.push((entry.th32ProcessID, entry.th32ParentProcessID));
+                }
+                found = Process32NextW(snapshot, &raw mut entry).is_ok();
+            }
+
+            let _ = CloseHandle(snapshot);
+        }
+
+        processes
+    }
+
+    /// Snapshot of the system-wide handle table, as `usize` words so the
+    /// entries are correctly aligned
+    fn query_handle_table() -> Result<Vec<usize>> {
+        let mut buffer = vec![0usize; HANDLE_TABLE_INITIAL_BYTES / size_of::<usize>()];
+        loop {
+            let mut needed = 0u32;
+            let bytes = u32::try_from(buffer.len() * size_of::<usize>())?;
+            // SAFETY: `buffer` is writable for `bytes` bytes.
+            let status = unsafe {
+                NtQuerySystemInformation(
+                    SYSTEM_EXTENDED_HANDLE_INFORMATION,
+                    buffer.as_mut_ptr().cast(),
+                    bytes,
+                    &raw mut needed,
+                )
+            };
+            if status == STATUS_INFO_LENGTH_MISMATCH {
+                // The table grows between calls, so leave some headroom
+                let words = (needed as usize).max(buffer.len() * size_of::<usize>()) * 2
+                    / size_of::<usize>();
+                buffer = vec![0usize; words];
+                continue;
+            }
+            anyhow::ensure!(
+                status.is_ok(),
+                "NtQuerySystemInformation failed: {:#x}",
+                status.0
+            );
+            return Ok(buffer);
+        }
+    }
+
+    /// NT path of a file handle held by `process`, or `None` for anything
+    /// that is not an on-disk file
+    fn file_path(process: HANDLE, handle_value: usize, buffer: &mut [usize]) -> Option<String> {
+        let mut handle = HANDLE::default();
+
+        // SAFETY: `process` has PROCESS_DUP_HANDLE access and `handle` is
+        // closed before returning. Only disk files are passed to
+        // `NtQueryObject`, which can block forever on some pipes.
+        unsafe {
+            DuplicateHandle(
+                process,
+                HANDLE(handle_value as *mut c_void),
+                GetCurrentProcess(),
+                &raw mut handle,
+                0,
+                false,
+                DUPLICATE_SAME_ACCESS,
+            )
+            .ok()?;
+
+            let mut path = None;
+            if GetFileType(handle) == FILE_TYPE_DISK {
+                let status = NtQueryObject(
+                    handle,
+                    OBJECT_NAME_INFORMATION_CLASS,
+                    Some(buffer.as_mut_ptr().cast()),
+                    u32::try_from(size_of_val(buffer)).unwrap_or(u32::MAX),
+                    None,
+                );
+                if status.is_ok() {
+                    let name = &(*buffer.as_ptr().cast::<OBJECT_NAME_INFORMATION>()).Name;
+                    if !name.Buffer.is_null() {
+                        let units = std::slice::from_raw_parts(
+                            name.Buffer.0,
+                            usize::from(name.Length) / size_of::<u16>(),
+                        );
+                        path = Some(String::from_utf16_lossy(units));
+                    }
+                }
+            }
+
+            let _ = CloseHandle(handle);
+            path
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub use handles::{brainfm_open_files, get_brainfm_pid, processes_named};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create a browser profile whose Local Storage contains `content`
+    fn browser_profile(user_data: &Path, profile: &str, content: &str) -> PathBuf {
+        let profile = user_data.join(profile);
+        let leveldb = profile.join("Local Storage").join("leveldb");
+        std::fs::create_dir_all(&leveldb).unwrap();
+        std::fs::write(leveldb.join("000003.log"), content).unwrap();
+        profile
+    }
+
+    #[test]
+    fn test_pwa_edge_preferred_over_chrome() {
+        let temp = tempfile::tempdir().unwrap();
+        let local = temp.path();
+        let [edge, chrome] = browser_user_data_dirs(local);
+        let edge_profile = browser_profile(&edge, "Default", "_https://my.brain.fm\x00auth");
+        browser_profile(&chrome, "Default", "_https://my.brain.fm\x00auth");
+
+        let detected = find_pwa_data_dir(local);
+
+        assert_eq!(detected, Some(edge_profile));
+    }
+
+    #[test]
+    fn test_pwa_falls_back_to_chrome() {
+        let temp = tempfile::tempdir().unwrap();
+        let local = temp.path();
+        let [edge, chrome] = browser_user_data_dirs(local);
+        browser_profile(&edge, "Default", "_https://www.example.com\x00theme");
+        let chrome_profile = browser_profile(&chrome, "Profile 1", "_https://my.brain.fm\x00auth");
+
+        let detected = find_pwa_data_dir(local);
+
+        assert_eq!(detected, Some(chrome_profile));
+    }
+
+    #[test]
+    fn test_launches_brainfm_app() {
+        assert!(launches_brainfm_app(
+            "C:\\msedge.exe --type=renderer\r\nC:\\msedge.exe --app=https://my.Brain.fm/app\r\n"
+        ));
+        assert!(!launches_brainfm_app(
+            "C:\\chrome.exe --profile-directory=Default\r\n"
+        ));
+        assert!(!launches_brainfm_app(
+            "C:\\chrome.exe https://www.brain.fm/blog\r\n"
+        ));
+    }
+
+    #[test]
+    fn test_focus_assist_registry_write() {
+        let filetime = FILETIME_UNIX_EPOCH;
+        let mut saved = None;
+        for (enabled, profile) in [(true, PRIORITY_ONLY_PROFILE), (false, UNRESTRICTED_PROFILE)] {
+            let mut written = None;
+            write_quiet_hours_state(
+                enabled,
+                &mut saved,
+                filetime,
+                |_, _| Ok(None),
+                |key, value, data| {
+                    written = Some((key.to_string(), value.to_string(), data.to_vec()));
+                    Ok(())
+                },
+            )
+            .unwrap();
+            let (key, value, data) = written.unwrap();
+            assert_eq!(
+                key,
+                r"SOFTWARE\Microsoft\Windows\CurrentVersion\CloudStore\Store\Cache\DefaultAccount\$$windows.data.unifiedtile.quiethoursstate\Current"
+            );
+            assert_eq!(value, "Data");
+            assert_eq!(data[..4], [0x02, 0x00, 0x00, 0x00]);
+            assert_eq!(data[4..12], filetime.to_le_bytes());
+            let name: Vec<u8> = profile.encode_utf16().flat_map(u16::to_le_bytes).collect();
+            assert_eq!(usize::from(data[25]), name.len());
+            assert_eq!(data[26..26 + name.len()], name[..], "{profile}");
+            assert_eq!(data.len(), 26 + name.len() + 7);
+        }
+    }
+
+    #[test]
+    fn test_focus_assist_restores_previous_state() {
+        let alarms_only = quiet_hours_state_data("Microsoft.QuietHoursProfile.AlarmsOnly", 1);
+        let mut saved = None;
+        let mut writes = Vec::new();
+        let mut switch = |enabled: bool, filetime: u64| {
+            write_quiet_hours_state(
+                enabled,
+                &mut saved,
+                filetime,
+                |key, value| {
+                    assert_eq!((key, value), (QUIET_HOURS_STATE_KEY, "Data"));
+                    // Only read before the first switch on; later reads would
+                    // see our own "Priority only"
+                    assert_eq!(filetime, FILETIME_UNIX_EPOCH);
+                    Ok(Some(alarms_only.clone()))
+                },
+                |_, _, data| {
+                    writes.push(data.to_vec());
+                    Ok(())
+                },
+            )
+            .unwrap();
+        };
+        switch(true, FILETIME_UNIX_EPOCH);
+        switch(true, FILETIME_UNIX_EPOCH + 1);
+        switch(false, FILETIME_UNIX_EPOCH + 2);
+
+        let mut restored = alarms_only.clone();
+        restored[4..12].copy_from_slice(&(FILETIME_UNIX_EPOCH + 2).to_le_bytes());
+        assert_eq!(
+            writes,
+            [
+                quiet_hours_state_data(PRIORITY_ONLY_PROFILE, FILETIME_UNIX_EPOCH),
+                quiet_hours_state_data(PRIORITY_ONLY_PROFILE, FILETIME_UNIX_EPOCH + 1),
+                restored,
+            ]
+        );
+        assert_eq!(saved, None);
+
+        // A failed read leaves Focus Assist alone
+        let error = write_quiet_hours_state(
+            true,
+            &mut saved,
+            FILETIME_UNIX_EPOCH,
+            |_, _| anyhow::bail!("access denied"),
+            |_, _, _| panic!("written without the state to restore"),
+        );
+        assert!(error.is_err());
+    }
+
+    #[test]
+    fn test_focus_assist_switcher_follows_focus_modes() {
+        let playing = |mode: &str| BrainFmState {
+            mode: Some(mode.into()),
+            is_playing: true,
+            ..Default::default()
+        };
+        let mut switcher = FocusAssistSwitcher::default();
+        let mut calls = Vec::new();
+        for state in [
+            playing("Deep Work"),
+            playing("Focus"),
+            BrainFmState::new(),
+            BrainFmState::new(),
+            playing("Sleep"),
+            playing("Light Work"),
+            playing("Relax"),
+        ] {
+            switcher.update_with(&state, |enabled| {
+                calls.push(enabled);
+                Ok(())
+            });
+        }
+        assert_eq!(calls, [true, false, true, false]);
+
+        // A failed switch is retried on the next update
+        let mut switcher = FocusAssistSwitcher::default();
+        switcher.update_with(&playing("Focus"), |_| anyhow::bail!("access denied"));
+        let mut retried = false;
+        switcher.update_with(&playing("Focus"), |enabled| {
+            retried = enabled;
+            Ok(())
+        });
+        assert!(retried);
+        // Off again, so dropping the switcher leaves Focus Assist alone
+        switcher.update_with(&BrainFmState::new(), |_| Ok(()));
+    }
+}
@@ -0,0 +1,176 @@
+//! Markdown reports built from playback history
+//!
+//! `to_markdown_report` renders a day of `BrainFmStateHistory` as GitHub
+//! Flavored Markdown, for pasting into a productivity journal.
+
+use crate::history::{local_day, BrainFmStateHistory};
+use crate::util::format_duration;
+use chrono::{DateTime, Local};
+use std::fmt::Write;
+use std::time::{Duration, SystemTime};
+
+/// Render `history` as a Markdown report.
+///
+/// The report covers the local date of the first entry (today when the
+/// history is empty) and contains time per mode, the tracks played and the
+/// longest uninterrupted focus session. Durations are counted up to the
+/// end of that day, or now if the day is not over yet.
+#[must_use]
+pub fn to_markdown_report(history: &BrainFmStateHistory) -> String {
+    to_markdown_report_at(history, SystemTime::now())
+}
+
+fn to_markdown_report_at(history: &BrainFmStateHistory, now: SystemTime) -> String {
+    let date = history.iter().next().map_or(now, |entry| entry.timestamp);
+    let date = DateTime::<Local>::from(date).date_naive();
+    let end = local_day(date).map_or(now, |(_, end_of_day)| end_of_day.min(now));
+
+    let mut report = format!("# Brain.fm report: {date}\n\n");
+
+    report.push_str("## Time by mode\n\n");
+    let mut totals: Vec<_> = history.total_duration_by_mode_at(end).into_iter().collect();
+    if totals.is_empty() {
+        report.push_str("_No listening recorded._\n\n");
+    } else {
+        totals.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        report.push_str("| Mode | Time |\n| --- | ---: |\n");
+        for (mode, duration) in &totals {
+            let _ = writeln!(report, "| {} | {} |", cell(mode), hms(*duration));
+        }
+        let total = totals.iter().map(|(_, duration)| *duration).sum();
+        let _ = writeln!(report, "| **Total** | **{}** |\n", hms(total));
+    }
+
+    report.push_str("## Tracks\n\n");
+    let tracks: Vec<_> = history
+        .iter()
+        .filter(|entry| entry.state.is_playing && entry.state.track_name.is_some())
+        .collect();
+    if tracks.is_empty() {
+        report.push_str("_No tracks recorded._\n\n");
+    } else {
+        report.push_str("| Started | Track | Mode | Activity | Genre | Neural effect |\n");
+        report.push_str("| --- | --- | --- | --- | --- | --- |\n");
+        let mut previous_track = None;
+        for entry in tracks {
+            let state = &entry.state;
+            // Pausing and resuming the same track is one row
+            if previous_track == state.track_name.as_deref() {
+                continue;
+            }
+            previous_track = state.track_name.as_deref();
+            let optional = |value: &Option<String>| value.as_deref().map_or_else(String::new, cell);
+            let _ = writeln!(
+                report,
+                "| {} | {} | {} | {} | {} | {} |",
+                DateTime::<Local>::from(entry.timestamp).format("%H:%M"),
+                optional(&state.track_name),
+                optional(&state.mode),
+                optional(&state.activity),
+                optional(&state.genre),
+                optional(&state.neural_effect),
+            );
+        }
+        report.push('\n');
+    }
+
+    report.push_str("## Peak focus streak\n\n");
+    let streak = history.longest_streak_by(end, |state| {
+        state.is_playing
+            && state
+                .parsed_mode()
+                .is_some_and(|mode| mode.is_focus_category())
+    });
+    if streak.is_zero() {
+        report.push_str("_No focus sessions recorded._\n");
+    } else {
+        let _ = writeln!(
+            report,
+            "Longest uninterrupted focus session: **{}**",
+            hms(streak)
+        );
+    }
+
+    report
+}
+
+/// Format a duration for the report (`H:MM:SS` or `M:SS`)
+fn hms(duration: Duration) -> String {
+    #[allow(clippy::cast_precision_loss)] // whole seconds well below 2^52
+    format_duration(duration.as_secs() as f64)
+}
+
+/// Escape text for a Markdown table cell
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|").replace(['\n', '\r'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BrainFmState;
+    use chrono::NaiveTime;
+
+    fn playing(mode: &str, track: &str, genre: &str) -> BrainFmState {
+        BrainFmState {
+            mode: Some(mode.into()),
+            track_name: Some(track.into()),
+            genre: Some(genre.into()),
+            is_playing: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_markdown_report_sections() {
+        let date = chrono::NaiveDate::from_ymd_opt(2026, 3, 2).unwrap();
+        let start = date
+            .and_time(NaiveTime::MIN)
+            .and_local_timezone(Local)
+            .earliest()
+            .map(SystemTime::from)
+            .unwrap()
+            + Duration::from_secs(9 * 3600);
+        let minutes = |m: u64| start + Duration::from_secs(m * 60);
+
+        let mut csv =
+            "timestamp,is_playing,mode,activity,track_name,genre,neural_effect\n".to_string();
+        for (m, row) in [
+            (0, "true,Focus,Deep Work,Blooming,Piano,High"),
+            (25, "true,Focus,Deep Work,Night | Day,Electronic,High"),
+            (50, "false,Focus,Deep Work,Night | Day,Electronic,High"),
+            (55, "true,Relax,,Waves,Ambient,Low"),
+            (70, "false,,,,,"),
+        ] {
+            let secs = minutes(m)
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            let _ = writeln!(csv, "{secs},{row}");
+        }
+        let (history, _) = BrainFmStateHistory::from_csv(&csv);
+        let report = to_markdown_report_at(&history, minutes(24 * 60));
+
+        assert!(report.starts_with("# Brain.fm report: 2026-03-02\n"));
+        assert!(report
+            .contains("| Mode | Time |\n| --- | ---: |\n| Focus | 50:00 |\n| Relax | 15:00 |\n"));
+        assert!(report.contains("| **Total** | **1:05:00** |"));
+        assert!(report.contains("| Night \\| Day | Focus | Deep Work | Electronic | High |"));
+        assert_eq!(report.matches("| Blooming |").count(), 1);
+        assert!(report.contains("| Waves | Relax |  | Ambient | Low |"));
+        assert!(report.contains("Longest uninterrupted focus session: **50:00**"));
+
+        let empty = to_markdown_report_at(&BrainFmStateHistory::new(), start);
+        assert!(empty.contains("_No listening recorded._"));
+        assert!(empty.contains("_No focus sessions recorded._"));
+    }
+
+    #[test]
+    fn test_markdown_report_for_live_history() {
+        let mut history = BrainFmStateHistory::new();
+        history.push(playing("Focus", "Blooming", "Piano"));
+        let report = to_markdown_report(&history);
+        assert!(report.contains("| Blooming | Focus |  | Piano |  |"));
+        assert!(report.contains("## Peak focus streak"));
+    }
+}
@@ -0,0 +1,177 @@
+//! Fake Brain.fm data directories for hermetic `read_state()` tests
+//!
+//! `TestFixtureBuilder` writes the files each data source reads (the Local
+//! Storage log, `Cache_Data` entries) into a `TempDir`, along with
+//! `lsof.txt`, the `lsof` output listing the audio entries Brain.fm "has
+//! open".
+//! `BrainFmReader::set_test_fixture` points a reader at the result, so no
+//! Brain.fm installation, `pgrep` or `lsof` is needed. Local Storage holds no
+//! auth token, so the Direct API is never called.
+//!
+//! Common scenarios are pre-built from the files in `fixtures/`:
+//! `focus_playing()` and `focus_paused()`. Tests that need a state rather
+//! than files (the integrations') start from `focus_state()`.
+
+use crate::BrainFmState;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Write as _;
+use tempfile::TempDir;
+
+/// Local Storage log with the Deep Work activity and ADHD mode on
+pub const DEEP_WORK_LEVELDB: &str = include_str!("../fixtures/leveldb/deep_work.log");
+
+/// Servings response with one track, "Nothing Remains" (Piano, Deep Work,
+/// High Neural Effect)
+pub const SERVINGS_RECENT_JSON: &str = include_str!("../fixtures/api/servings_recent.json");
+
+/// Audio URL of the track in `SERVINGS_RECENT_JSON`
+pub const NOTHING_REMAINS_URL: &str =
+    "https://audio2.brain.fm/Nothing_Remains_Focus_Deep_Work_Piano_120bpm_HighNEL.mp3";
+
+/// Servings URL added by `build()` when `with_api_cache_json` is used
+/// without a servings entry
+const DEFAULT_SERVINGS_URL: &str = "https://api.brain.fm/v3/users/fixture/servings/recent";
+
+/// File in the fixture that `set_test_fixture` has `lsof` print
+pub const LSOF_OUTPUT_FILE: &str = "lsof.txt";
+
+/// Builds a fake Brain.fm data directory
+#[derive(Debug, Clone, Default)]
+pub struct TestFixtureBuilder {
+    leveldb_content: Option<String>,
+    /// `(url, compressed)` per cache entry, in index order
+    cache_entries: Vec<(String, bool)>,
+    api_cache_json: Option<String>,
+    /// Leave the audio entries out of `lsof.txt`, as Brain.fm releases its
+    /// cache files while paused
+    paused: bool,
+}
+
+impl TestFixtureBuilder {
+    /// An empty data directory
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// "Nothing Remains" playing in Deep Work, with its metadata cached
+    #[must_use]
+    pub fn focus_playing() -> Self {
+        Self::new()
+            .with_leveldb_content(DEEP_WORK_LEVELDB)
+            .with_api_cache_json(SERVINGS_RECENT_JSON)
+            .with_cache_entry(NOTHING_REMAINS_URL, false)
+    }
+
+    /// `focus_playing()`, paused
+    #[must_use]
+    pub fn focus_paused() -> Self {
+        Self {
+            paused: true,
+            ..Self::focus_playing()
+        }
+    }
+
+    /// Write `content` to `Local Storage/leveldb/000003.log`
+    #[must_use]
+    pub fn with_leveldb_content(mut self, content: &str) -> Self {
+        self.leveldb_content = Some(content.to_string());
+        self
+    }
+
+    /// Add a `Cache_Data` entry keyed by `url`. A servings URL gets the
+    /// `with_api_cache_json` body, gzipped when `compressed`; any other URL
+    /// is an audio file, listed in `lsof.txt` unless paused.
+    #[must_use]
+    pub fn with_cache_entry(mut self, url: &str, compressed: bool) -> Self {
+        self.cache_entries.push((url.to_string(), compressed));
+        self
+    }
+
+    /// Body of the servings entries (see `with_cache_entry`)
+    #[must_use]
+    pub fn with_api_cache_json(mut self, json: &str) -> Self {
+        self.api_cache_json = Some(json.to_string());
+        self
+    }
+
+    /// Create the directory
+    ///
+    /// # Panics
+    ///
+    /// If the files can't be written.
+    #[must_use]
+    pub fn build(self) -> TempDir {
+        let dir = tempfile::Builder::new()
+            .prefix("brainfm-fixture-")
+            .tempdir()
+            .unwrap();
+
+        if let Some(content) = &self.leveldb_content {
+            let leveldb = dir.path().join("Local Storage").join("leveldb");
+            fs::create_dir_all(&leveldb).unwrap();
+            fs::write(leveldb.join("000003.log"), content).unwrap();
+        }
+
+        let mut entries = self.cache_entries;
+        if self.api_cache_json.is_some() && !entries.iter().any(|(url, _)| is_servings(url)) {
+            entries.push((DEFAULT_SERVINGS_URL.to_string(), false));
+        }
+
+        let cache_data = dir.path().join("Cache").join("Cache_Data");
+        fs::create_dir_all(&cache_data).unwrap();
+        let mut lsof_output = String::new();
+        for (i, (url, compressed)) in entries.iter().enumerate() {
+            let name = format!("{i:016x}_0");
+            let body = if is_servings(url) {
+                self.api_cache_json.as_deref().unwrap_or(r#"{"result":[]}"#)
+            } else {
+                if !self.paused {
+                    let _ = writeln!(
+                        lsof_output,
+                        "Brain.fm 1073 user 22u REG 259,2 524288 {i} {}",
+                        cache_data.join(&name).display()
+                    );
+                }
+                "ID3"
+            };
+            let mut entry = format!("{url}\n").into_bytes();
+            if *compressed {
+                let mut encoder = GzEncoder::new(entry, Compression::default());
+                encoder.write_all(body.as_bytes()).unwrap();
+                entry = encoder.finish().unwrap();
+            } else {
+                entry.extend_from_slice(body.as_bytes());
+            }
+            fs::write(cache_data.join(name), entry).unwrap();
+        }
+        fs::write(dir.path().join(LSOF_OUTPUT_FILE), lsof_output).unwrap();
+
+        dir
+    }
+}
+
+/// Whether `url` is a servings request, whose response the API cache holds
+fn is_servings(url: &str) -> bool {
+    url.contains("/servings/")
+}
+
+/// "Nothing Remains" (Piano, 120 BPM, High Neural Effect) playing in
+/// Focus · Deep Work
+#[allow(dead_code)] // Only the integrations' tests use it, each behind a feature
+#[must_use]
+pub fn focus_state() -> BrainFmState {
+    BrainFmState {
+        mode: Some("Focus".into()),
+        activity: Some("Deep Work".into()),
+        track_name: Some("Nothing Remains".into()),
+        genre: Some("Piano".into()),
+        neural_effect: Some("High Neural Effect".into()),
+        bpm: Some(120),
+        is_playing: true,
+        ..Default::default()
+    }
+}
@@ -0,0 +1,160 @@
+//! Test doubles for crates that embed `brainfm-presence`
+//!
+//! Enabled with the `test-utils` feature. `MockBrainFmReader` implements
+//! `BrainFmReaderTrait` without needing a Brain.fm installation.
+
+use crate::api_cache_reader::CacheStatistics;
+use crate::{BrainFmReaderTrait, BrainFmState, HealthReport};
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Reader that replays a queue of canned states, one per `read_state()` call.
+///
+/// Once the queue is exhausted `read_state()` returns an error, like a real
+/// reader that can no longer find Brain.fm. `poll_until` does not wait
+/// between reads unless `set_update_interval` is called.
+#[derive(Debug, Clone)]
+pub struct MockBrainFmReader {
+    states: VecDeque<BrainFmState>,
+    running: bool,
+    update_interval: Duration,
+}
+
+impl MockBrainFmReader {
+    /// Create a running mock that returns `states` in order
+    pub fn new(states: impl IntoIterator<Item = BrainFmState>) -> Self {
+        Self {
+            states: states.into_iter().collect(),
+            running: true,
+            update_interval: Duration::ZERO,
+        }
+    }
+
+    /// Queue another state to be returned after the existing ones
+    pub fn push_state(&mut self, state: BrainFmState) {
+        self.states.push_back(state);
+    }
+
+    /// Set what `is_running()` and `check_health()` report
+    pub fn set_running(&mut self, running: bool) {
+        self.running = running;
+    }
+
+    /// Set how long `poll_until` waits between reads
+    pub fn set_update_interval(&mut self, update_interval: Duration) {
+        self.update_interval = update_interval;
+    }
+
+    /// Number of states not yet returned
+    #[must_use]
+    pub fn remaining(&self) -> usize {
+        self.states.len()
+    }
+}
+
+impl BrainFmReaderTrait for MockBrainFmReader {
+    fn read_state(&mut self) -> Result<BrainFmState> {
+        self.states
+            .pop_front()
+            .ok_or_else(|| anyhow::anyhow!("MockBrainFmReader has no more states"))
+    }
+
+    fn is_running(&self) -> bool {
+        self.running
+    }
+
+    fn check_health(&self) -> HealthReport {
+        HealthReport {
+            brainfm_running: self.running,
+            leveldb_found: true,
+            cache_found: true,
+            memory_cache_tracks: 0,
+            cache_statistics: CacheStatistics::default(),
+            last_api_result: None,
+            suspended_sources: Vec::new(),
+            subscription_tier: None,
+            rust_version: None,
+            confidence_score: None,
+        }
+    }
+
+    fn update_interval(&self) -> Duration {
+        self.update_interval
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BrainFmError;
+
+    fn playing_focus() -> BrainFmState {
+        BrainFmState {
+            is_playing: true,
+            mode: Some("Focus".into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_mock_returns_states_in_order() {
+        let playing = BrainFmState {
+            is_playing: true,
+            track_name: Some("Nothing Remains".into()),
+            ..Default::default()
+        };
+        let mut reader = MockBrainFmReader::new([playing, BrainFmState::new()]);
+
+        assert!(reader.read_state().unwrap().is_playing);
+        assert!(!reader.read_state().unwrap().is_playing);
+        assert!(reader.read_state().is_err());
+        assert_eq!(reader.remaining(), 0);
+    }
+
+    #[test]
+    fn test_mock_health_follows_running() {
+        let mut reader = MockBrainFmReader::new([]);
+        assert!(reader.check_health().is_healthy());
+
+        reader.set_running(false);
+        assert!(!reader.is_running());
+        assert!(!reader.check_health().is_healthy());
+    }
+
+    #[test]
+    fn test_poll_until_waits_for_playing() {
+        let mut reader =
+            MockBrainFmReader::new([BrainFmState::new(), BrainFmState::new(), playing_focus()]);
+        let state = reader
+            .poll_until(|state| state.is_playing, Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(state.mode.as_deref(), Some("Focus"));
+        assert_eq!(reader.remaining(), 0);
+    }
+
+    #[test]
+    fn test_poll_until_times_out() {
+        let mut reader = MockBrainFmReader::new(vec![BrainFmState::new(); 100]);
+        reader.set_update_interval(Duration::from_millis(10));
+        let err = reader
+            .poll_until(|state| state.is_playing, Duration::from_millis(50))
+            .unwrap_err();
+        match err.downcast_ref::<BrainFmError>() {
+            Some(BrainFmError::TimeoutWaiting { elapsed }) => {
+                assert!(*elapsed >= Duration::from_millis(50));
+            }
+            _ => panic!("unexpected error: {err:#}"),
+        }
+        assert!(reader.remaining() > 0);
+    }
+
+    #[test]
+    fn test_poll_until_returns_read_errors() {
+        let mut reader = MockBrainFmReader::new([BrainFmState::new()]);
+        let err = reader
+            .poll_until(|state| state.is_playing, Duration::from_secs(5))
+            .unwrap_err();
+        assert!(err.downcast_ref::<BrainFmError>().is_none());
+    }
+}